@@ -14,9 +14,14 @@ fn main() {
                 TokenKind::Name => println!("Name: {}", Latin1Decoded(token.span())),
                 TokenKind::Symbol(sym) => println!("Symbol: {:?}", sym),
                 TokenKind::Comment => { /* ignore */ }
-                TokenKind::String => println!("String: {}", Latin1Decoded(token.span())),
+                TokenKind::String | TokenKind::LongString { .. } | TokenKind::InterpString(_) => {
+                    println!("String: {}", Latin1Decoded(token.span()))
+                }
                 TokenKind::Hex(val) => println!("Hex: 0x{:x}", val),
+                TokenKind::Integer(val) => println!("Integer: {}", val),
                 TokenKind::Number(val) => println!("Number: {}", val),
+                TokenKind::Cdata(val) => println!("Cdata: {:?}", val),
+                TokenKind::Error => println!("Error: {}", Latin1Decoded(token.span())),
                 TokenKind::Eof => break,
             }
         }
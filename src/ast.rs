@@ -0,0 +1,755 @@
+//! # Abstract Syntax Tree
+//!
+//! An owned syntax tree that borrows spans from the input, built by [AstSink].
+//!
+//! This module requires the `alloc` feature.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::mem;
+
+use crate::{BinOp, Cdata, Decoder, Dialect, Error, Parser, Sink, UnOp};
+
+/// A sequence of statements
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk<S> {
+    pub stmts: Vec<Stmt<S>>,
+}
+
+/// `if exp then block {elseif exp then block} [else block] end`
+#[derive(Debug, Clone, PartialEq)]
+pub struct If<S> {
+    /// The `if` and all `elseif` clauses
+    pub clauses: Vec<(Expr<S>, Chunk<S>)>,
+    pub else_block: Option<Chunk<S>>,
+}
+
+/// `funcname ::= Name {'.' Name} [':' Name]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncName<S> {
+    /// The name and all fields
+    pub path: Vec<S>,
+    pub method: Option<S>,
+}
+
+/// `namelist ::= Name {',' Name}`, used while building statements
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameList<S> {
+    pub names: Vec<S>,
+    /// The attribute of each name, only in `local` statements (Lua 5.4)
+    pub attribs: Vec<Option<S>>,
+}
+
+/// `funcbody ::= '(' [parlist] ')' block end`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncBody<S> {
+    pub params: Vec<S>,
+    /// Whether the parameter list ends with `...`
+    pub vararg: bool,
+    pub block: Chunk<S>,
+}
+
+/// `functioncall ::= prefixexp args | prefixexp ':' Name args`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call<S> {
+    pub prefix: Box<Expr<S>>,
+    pub method: Option<S>,
+    pub args: Vec<Expr<S>>,
+}
+
+/// Statements
+///
+/// See: <https://www.lua.org/manual/5.1/manual.html#2.4>
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt<S> {
+    /// `do block end`
+    Do(Chunk<S>),
+    While {
+        cond: Expr<S>,
+        block: Chunk<S>,
+    },
+    Repeat {
+        block: Chunk<S>,
+        cond: Expr<S>,
+    },
+    If(If<S>),
+    NumericFor {
+        name: S,
+        start: Expr<S>,
+        limit: Expr<S>,
+        step: Option<Expr<S>>,
+        block: Chunk<S>,
+    },
+    GenericFor {
+        names: Vec<S>,
+        exprs: Vec<Expr<S>>,
+        block: Chunk<S>,
+    },
+    Function {
+        name: FuncName<S>,
+        body: FuncBody<S>,
+    },
+    LocalFunction {
+        name: S,
+        body: FuncBody<S>,
+    },
+    /// `local namelist ['=' explist]`, `exprs` is empty without `=`
+    Local {
+        names: Vec<S>,
+        /// `<const>` or `<close>` for each name (Lua 5.4)
+        attribs: Vec<Option<S>>,
+        exprs: Vec<Expr<S>>,
+    },
+    Assign {
+        vars: Vec<Var<S>>,
+        exprs: Vec<Expr<S>>,
+    },
+    Call(Call<S>),
+    Return(Vec<Expr<S>>),
+    Break,
+    /// `goto Name` (Lua 5.2)
+    Goto(S),
+    /// `'::' Name '::'` (Lua 5.2)
+    Label(S),
+    /// `continue` (Luau)
+    Continue,
+    /// `var binop'=' exp` (Luau)
+    CompoundAssign {
+        var: Var<S>,
+        op: BinOp,
+        expr: Expr<S>,
+    },
+    /// `['export'] 'type' Name '=' Type` (Luau)
+    TypeAlias {
+        export: bool,
+        name: S,
+        /// The original text of the type
+        ty: S,
+    },
+}
+
+/// Variables, i.e. the targets of assignments
+#[derive(Debug, Clone, PartialEq)]
+pub enum Var<S> {
+    /// `Name`
+    Name(S),
+    /// `prefixexp '[' exp ']'`
+    Index(Box<Expr<S>>, Box<Expr<S>>),
+    /// `prefixexp '.' Name`
+    Field(Box<Expr<S>>, S),
+}
+
+/// Fields of a table constructor
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableField<S> {
+    /// `exp`
+    Item(Expr<S>),
+    /// `Name '=' exp`
+    Named(S, Expr<S>),
+    /// `'[' exp ']' '=' exp`
+    Keyed(Expr<S>, Expr<S>),
+}
+
+/// Expressions
+///
+/// See: <https://www.lua.org/manual/5.1/manual.html#2.5>
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<S> {
+    Nil,
+    Bool(bool),
+    /// The original text and the value
+    Number(S, f64),
+    /// The original text and the value of an integer (Lua 5.3)
+    Integer(S, i64),
+    /// The original text and the value of a number with a suffix (LuaJIT)
+    Cdata(S, Cdata),
+    /// The original text, including delimiters
+    String(S),
+    /// The strings and expressions of an interpolated string, alternating (Luau)
+    Interp(Vec<Expr<S>>),
+    /// `if exp then exp else exp` (Luau)
+    If(Box<Expr<S>>, Box<Expr<S>>, Box<Expr<S>>),
+    /// `...`
+    Vararg,
+    Function(FuncBody<S>),
+    Table(Vec<TableField<S>>),
+    Binary(Box<Expr<S>>, BinOp, Box<Expr<S>>),
+    Unary(UnOp, Box<Expr<S>>),
+    /// `'(' exp ')'`
+    Paren(Box<Expr<S>>),
+    Var(Var<S>),
+    Call(Call<S>),
+}
+
+/// Moves the operands of `expr` to `stack`, leaving `nil` in their place
+fn take_operands<S>(expr: &mut Expr<S>, stack: &mut Vec<Expr<S>>) {
+    let mut take = |expr: &mut Expr<S>| stack.push(mem::replace(expr, Expr::Nil));
+    match expr {
+        Expr::If(cond, then, else_) => {
+            take(cond);
+            take(then);
+            take(else_);
+        }
+        Expr::Binary(lhs, _, rhs) => {
+            take(lhs);
+            take(rhs);
+        }
+        Expr::Unary(_, expr) | Expr::Paren(expr) => take(expr),
+        Expr::Var(Var::Index(prefix, key)) => {
+            take(prefix);
+            take(key);
+        }
+        Expr::Var(Var::Field(prefix, _)) => take(prefix),
+        Expr::Call(call) => {
+            take(&mut call.prefix);
+            call.args.iter_mut().for_each(take);
+        }
+        _ => {}
+    }
+}
+
+/// Left operands and prefixes don't count as syntax levels, so the parser
+/// accepts chains like `a + a + ...` and `f()()...` that are too long to
+/// drop recursively
+impl<S> Drop for Expr<S> {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        take_operands(self, &mut stack);
+        while let Some(mut expr) = stack.pop() {
+            take_operands(&mut expr, &mut stack);
+        }
+    }
+}
+
+impl<S> crate::Chunk<Stmt<S>> for Chunk<S> {
+    fn push_stmt(&mut self, stmt: Stmt<S>) {
+        self.stmts.push(stmt);
+    }
+}
+
+impl<S> crate::If<Expr<S>, Chunk<S>> for If<S> {
+    fn else_if_clause(&mut self, exp: Expr<S>, block: Chunk<S>) {
+        self.clauses.push((exp, block));
+    }
+
+    fn else_clause(&mut self, block: Chunk<S>) {
+        self.else_block = Some(block);
+    }
+}
+
+impl<S> From<If<S>> for Stmt<S> {
+    fn from(value: If<S>) -> Self {
+        Self::If(value)
+    }
+}
+
+impl<S> crate::FuncName<S> for FuncName<S> {
+    fn push_field(&mut self, name: S) {
+        self.path.push(name);
+    }
+
+    fn method(&mut self, name: S) {
+        self.method = Some(name);
+    }
+}
+
+impl<S> crate::ExprList<Expr<S>> for Vec<Expr<S>> {
+    fn push_expr(&mut self, expr: Expr<S>) {
+        self.push(expr);
+    }
+}
+
+impl<S> crate::VarList<Var<S>> for Vec<Var<S>> {
+    fn push_var(&mut self, var: Var<S>) {
+        self.push(var);
+    }
+}
+
+impl<S> crate::NameList<S> for NameList<S> {
+    fn push_name(&mut self, name: S) {
+        self.names.push(name);
+        self.attribs.push(None);
+    }
+
+    fn push_attrib(&mut self, attrib: S) {
+        if let Some(last) = self.attribs.last_mut() {
+            *last = Some(attrib);
+        }
+    }
+}
+
+impl<S> crate::Table<S, Expr<S>> for Vec<TableField<S>> {
+    fn push_item(&mut self, value: Expr<S>) {
+        self.push(TableField::Item(value));
+    }
+
+    fn push_named(&mut self, name: S, value: Expr<S>) {
+        self.push(TableField::Named(name, value));
+    }
+
+    fn push_keyed(&mut self, key: Expr<S>, value: Expr<S>) {
+        self.push(TableField::Keyed(key, value));
+    }
+}
+
+/// A [Sink] that builds the types in this module
+#[derive(Debug, Default, Clone)]
+pub struct AstSink {
+    errors: Vec<Error>,
+}
+
+impl AstSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The errors that the parser recovered from
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+}
+
+impl<S> Sink<S> for AstSink {
+    type If = If<S>;
+    type Var = Var<S>;
+    type VarList = Vec<Var<S>>;
+    type FuncName = FuncName<S>;
+    type Expr = Expr<S>;
+    type ExprList = Vec<Expr<S>>;
+    type NameList = NameList<S>;
+    type Table = Vec<TableField<S>>;
+    type FuncBody = FuncBody<S>;
+    type Call = Call<S>;
+    type Stmt = Stmt<S>;
+    type Chunk = Chunk<S>;
+
+    fn chunk(&mut self) -> Self::Chunk {
+        Chunk { stmts: Vec::new() }
+    }
+
+    fn block(&mut self, chunk: Self::Chunk) -> Self::Stmt {
+        Stmt::Do(chunk)
+    }
+
+    fn while_stmt(&mut self, cond: Self::Expr, block: Self::Chunk) -> Self::Stmt {
+        Stmt::While { cond, block }
+    }
+
+    fn repeat_stmt(&mut self, block: Self::Chunk, cond: Self::Expr) -> Self::Stmt {
+        Stmt::Repeat { block, cond }
+    }
+
+    fn if_stmt(&mut self, exp: Self::Expr, block: Self::Chunk) -> Self::If {
+        If {
+            clauses: alloc::vec![(exp, block)],
+            else_block: None,
+        }
+    }
+
+    fn numeric_for_stmt(
+        &mut self,
+        name: S,
+        start: Self::Expr,
+        limit: Self::Expr,
+        step: Option<Self::Expr>,
+        block: Self::Chunk,
+    ) -> Self::Stmt {
+        Stmt::NumericFor {
+            name,
+            start,
+            limit,
+            step,
+            block,
+        }
+    }
+
+    fn generic_for_stmt(
+        &mut self,
+        names: Self::NameList,
+        exprs: Self::ExprList,
+        block: Self::Chunk,
+    ) -> Self::Stmt {
+        Stmt::GenericFor {
+            names: names.names,
+            exprs,
+            block,
+        }
+    }
+
+    fn function_stmt(&mut self, name: Self::FuncName, body: Self::FuncBody) -> Self::Stmt {
+        Stmt::Function { name, body }
+    }
+
+    fn local_function_stmt(&mut self, name: S, body: Self::FuncBody) -> Self::Stmt {
+        Stmt::LocalFunction { name, body }
+    }
+
+    fn local_stmt(&mut self, names: Self::NameList, exprs: Option<Self::ExprList>) -> Self::Stmt {
+        let exprs = exprs.unwrap_or_default();
+        Stmt::Local {
+            names: names.names,
+            attribs: names.attribs,
+            exprs,
+        }
+    }
+
+    fn assign_stmt(&mut self, vars: Self::VarList, exprs: Self::ExprList) -> Self::Stmt {
+        Stmt::Assign { vars, exprs }
+    }
+
+    fn call_stmt(&mut self, call: Self::Call) -> Self::Stmt {
+        Stmt::Call(call)
+    }
+
+    fn return_stmt(&mut self, exprs: Option<Self::ExprList>) -> Self::Stmt {
+        Stmt::Return(exprs.unwrap_or_default())
+    }
+
+    fn break_stmt(&mut self) -> Self::Stmt {
+        Stmt::Break
+    }
+
+    fn goto_stmt(&mut self, label: S) -> Self::Stmt {
+        Stmt::Goto(label)
+    }
+
+    fn label_stmt(&mut self, name: S) -> Self::Stmt {
+        Stmt::Label(name)
+    }
+
+    fn continue_stmt(&mut self) -> Self::Stmt {
+        Stmt::Continue
+    }
+
+    fn compound_assign_stmt(&mut self, var: Self::Var, op: BinOp, expr: Self::Expr) -> Self::Stmt {
+        Stmt::CompoundAssign { var, op, expr }
+    }
+
+    fn type_alias_stmt(&mut self, export: bool, name: S, ty: S) -> Self::Stmt {
+        Stmt::TypeAlias { export, name, ty }
+    }
+
+    fn var_list(&mut self) -> Self::VarList {
+        Vec::new()
+    }
+
+    fn expr_list(&mut self) -> Self::ExprList {
+        Vec::new()
+    }
+
+    fn name_list(&mut self) -> Self::NameList {
+        NameList {
+            names: Vec::new(),
+            attribs: Vec::new(),
+        }
+    }
+
+    fn func_name(&mut self, name: S) -> Self::FuncName {
+        FuncName {
+            path: alloc::vec![name],
+            method: None,
+        }
+    }
+
+    fn table(&mut self) -> Self::Table {
+        Vec::new()
+    }
+
+    fn func_body(
+        &mut self,
+        params: Self::NameList,
+        vararg: bool,
+        block: Self::Chunk,
+    ) -> Self::FuncBody {
+        FuncBody {
+            params: params.names,
+            vararg,
+            block,
+        }
+    }
+
+    fn name_var(&mut self, name: S) -> Self::Var {
+        Var::Name(name)
+    }
+
+    fn index_var(&mut self, prefix: Self::Expr, key: Self::Expr) -> Self::Var {
+        Var::Index(Box::new(prefix), Box::new(key))
+    }
+
+    fn field_var(&mut self, prefix: Self::Expr, name: S) -> Self::Var {
+        Var::Field(Box::new(prefix), name)
+    }
+
+    fn call(&mut self, prefix: Self::Expr, args: Self::ExprList) -> Self::Call {
+        Call {
+            prefix: Box::new(prefix),
+            method: None,
+            args,
+        }
+    }
+
+    fn method_call(&mut self, prefix: Self::Expr, name: S, args: Self::ExprList) -> Self::Call {
+        Call {
+            prefix: Box::new(prefix),
+            method: Some(name),
+            args,
+        }
+    }
+
+    fn nil_expr(&mut self) -> Self::Expr {
+        Expr::Nil
+    }
+
+    fn bool_expr(&mut self, value: bool) -> Self::Expr {
+        Expr::Bool(value)
+    }
+
+    fn number_expr(&mut self, span: S, value: f64) -> Self::Expr {
+        Expr::Number(span, value)
+    }
+
+    fn integer_expr(&mut self, span: S, value: i64) -> Self::Expr {
+        Expr::Integer(span, value)
+    }
+
+    fn cdata_expr(&mut self, span: S, value: Cdata) -> Self::Expr {
+        Expr::Cdata(span, value)
+    }
+
+    fn string_expr(&mut self, span: S) -> Self::Expr {
+        Expr::String(span)
+    }
+
+    fn interp_string_expr(&mut self, parts: Self::ExprList) -> Self::Expr {
+        Expr::Interp(parts)
+    }
+
+    fn if_expr(&mut self, cond: Self::Expr, then: Self::Expr, else_: Self::Expr) -> Self::Expr {
+        Expr::If(Box::new(cond), Box::new(then), Box::new(else_))
+    }
+
+    fn vararg_expr(&mut self) -> Self::Expr {
+        Expr::Vararg
+    }
+
+    fn function_expr(&mut self, body: Self::FuncBody) -> Self::Expr {
+        Expr::Function(body)
+    }
+
+    fn table_expr(&mut self, table: Self::Table) -> Self::Expr {
+        Expr::Table(table)
+    }
+
+    fn binary_expr(&mut self, lhs: Self::Expr, op: BinOp, rhs: Self::Expr) -> Self::Expr {
+        Expr::Binary(Box::new(lhs), op, Box::new(rhs))
+    }
+
+    fn unary_expr(&mut self, op: UnOp, expr: Self::Expr) -> Self::Expr {
+        Expr::Unary(op, Box::new(expr))
+    }
+
+    fn paren_expr(&mut self, expr: Self::Expr) -> Self::Expr {
+        Expr::Paren(Box::new(expr))
+    }
+
+    fn var_expr(&mut self, var: Self::Var) -> Self::Expr {
+        Expr::Var(var)
+    }
+
+    fn call_expr(&mut self, call: Self::Call) -> Self::Expr {
+        Expr::Call(call)
+    }
+
+    fn error(&mut self, error: Error) {
+        self.errors.push(error);
+    }
+}
+
+/// Parse UTF-8 source text
+pub fn parse_str(text: &str) -> Result<Chunk<&str>, Error> {
+    Parser::new_from_str(text)?.parse_main(&mut AstSink::new())
+}
+
+/// Parse Latin-1 (Windows-1252) source bytes
+pub fn parse_latin1(bytes: &[u8]) -> Result<Chunk<&[u8]>, Error> {
+    Parser::new_from_latin1(bytes)?.parse_main(&mut AstSink::new())
+}
+
+/// Parse the input of any decoder with the syntax of a Lua version and its extensions
+pub fn parse_with<'a, D: Decoder<'a>>(
+    decoder: D,
+    dialect: Dialect,
+) -> Result<Chunk<&'a D::Slice>, Error> {
+    Parser::with_dialect(decoder, dialect)?.parse_main(&mut AstSink::new())
+}
+
+/// Parse the input of any decoder and recover from syntax errors
+///
+/// Returns the statements that could be parsed and all errors.
+pub fn parse_recovering<'a, D: Decoder<'a>>(
+    decoder: D,
+    dialect: Dialect,
+) -> (Chunk<&'a D::Slice>, Vec<Error>) {
+    let mut sink = AstSink::new();
+    let chunk = Parser::recovering(decoder, dialect)
+        .parse_main(&mut sink)
+        .expect("a recovering parser reports all errors to the sink");
+    (chunk, sink.errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, format, vec, vec::Vec};
+
+    use super::{
+        parse_latin1, parse_recovering, parse_str, parse_with, Call, Chunk, Expr, Stmt, TableField,
+        Var,
+    };
+    use crate::{
+        BinOp, Cdata, Dialect, ErrorKind, Expected, Keyword, LuaVersion, Symbol, TokenKind,
+        Utf8Decoder,
+    };
+
+    #[test]
+    fn test_parse_str() {
+        let chunk = parse_str("local t = { x = 1 }\nprint(t.x + 2)").unwrap();
+        let t = || Box::new(Expr::Var(Var::Name("t")));
+        assert_eq!(
+            chunk,
+            Chunk {
+                stmts: vec![
+                    Stmt::Local {
+                        names: vec!["t"],
+                        attribs: vec![None],
+                        exprs: vec![Expr::Table(vec![TableField::Named(
+                            "x",
+                            Expr::Number("1", 1.0)
+                        )])],
+                    },
+                    Stmt::Call(Call {
+                        prefix: Box::new(Expr::Var(Var::Name("print"))),
+                        method: None,
+                        args: vec![Expr::Binary(
+                            Box::new(Expr::Var(Var::Field(t(), "x"))),
+                            BinOp::Add,
+                            Box::new(Expr::Number("2", 2.0)),
+                        )],
+                    }),
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_with() {
+        let decoder = Utf8Decoder::new("local x <close> = 0x10");
+        let chunk = parse_with(decoder, LuaVersion::Lua54.into()).unwrap();
+        assert_eq!(
+            chunk.stmts,
+            vec![Stmt::Local {
+                names: vec!["x"],
+                attribs: vec![Some("close")],
+                exprs: vec![Expr::Integer("0x10", 16)],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_dialects() {
+        let decoder = Utf8Decoder::new("x += if y then 1i else `{z}`");
+        let chunk = parse_with(decoder, Dialect::LUAU);
+        assert!(chunk.is_err(), "Luau has no imaginary numbers");
+
+        let decoder = Utf8Decoder::new("x ..= if y then 'a' else `{z}!`");
+        let chunk = parse_with(decoder, Dialect::LUAU).unwrap();
+        let var = |name| Expr::Var(Var::Name(name));
+        assert_eq!(
+            chunk.stmts,
+            vec![Stmt::CompoundAssign {
+                var: Var::Name("x"),
+                op: BinOp::Concat,
+                expr: Expr::If(
+                    Box::new(var("y")),
+                    Box::new(Expr::String("'a'")),
+                    Box::new(Expr::Interp(vec![
+                        Expr::String("`{"),
+                        var("z"),
+                        Expr::String("}!`"),
+                    ])),
+                ),
+            }]
+        );
+
+        let decoder = Utf8Decoder::new("export type Point = { x: number }");
+        let chunk = parse_with(decoder, Dialect::LUAU).unwrap();
+        assert_eq!(
+            chunk.stmts,
+            vec![Stmt::TypeAlias {
+                export: true,
+                name: "Point",
+                ty: "{ x: number }",
+            }]
+        );
+
+        let decoder = Utf8Decoder::new("return 1ULL, 2i");
+        let chunk = parse_with(decoder, Dialect::LUAJIT).unwrap();
+        assert_eq!(
+            chunk.stmts,
+            vec![Stmt::Return(vec![
+                Expr::Cdata("1ULL", Cdata::UInt64(1)),
+                Expr::Cdata("2i", Cdata::Imaginary(2.0)),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let text = "local x = = 1\nif x then y = end\nz = 'abc\nreturn x end";
+        let (chunk, errors) = parse_recovering(Utf8Decoder::new(text), Dialect::default());
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|e| (e.position().line, e.position().column, e.kind()))
+            .collect();
+        let unexpected = |expected, found| ErrorKind::UnexpectedToken { expected, found };
+        let end = TokenKind::Keyword(Keyword::End);
+        assert_eq!(
+            errors,
+            [
+                (
+                    1,
+                    11,
+                    unexpected(Expected::Expression, TokenKind::Symbol(Symbol::Assign))
+                ),
+                (2, 15, unexpected(Expected::Expression, end)),
+                (3, 5, ErrorKind::UnterminatedString),
+                (
+                    4,
+                    1,
+                    unexpected(Expected::Expression, TokenKind::Keyword(Keyword::Return))
+                ),
+                (4, 10, unexpected(Expected::Eof, end)),
+            ]
+        );
+        assert_eq!(chunk.stmts.len(), 2);
+        assert!(matches!(chunk.stmts[0], Stmt::If(_)));
+        assert_eq!(
+            chunk.stmts[1],
+            Stmt::Return(vec![Expr::Var(Var::Name("x"))])
+        );
+    }
+
+    #[test]
+    fn test_parse_latin1() {
+        let chunk = parse_latin1(b"return '\xe4'").unwrap();
+        let value: &[u8] = b"'\xe4'";
+        assert_eq!(chunk.stmts, vec![Stmt::Return(vec![Expr::String(value)])]);
+        assert!(parse_latin1(b"return return").is_err());
+    }
+
+    #[test]
+    fn test_drop_long_chains() {
+        for (first, next) in [("a", " + a"), ("f()", "()"), ("t", ".x[1]"), ("s", ":m()")] {
+            let input = format!("x = {first}{}", next.repeat(100_000));
+            drop(parse_str(&input).unwrap());
+        }
+    }
+}
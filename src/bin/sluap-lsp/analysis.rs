@@ -0,0 +1,390 @@
+//! The language features, computed from a concrete syntax tree
+
+use std::ops::Range;
+
+use serde_json::{json, Value};
+use sluap::{
+    cst::{Element, Node},
+    resolve::{resolve, DeclKind},
+    Error, NodeKind, Token, TokenKind,
+};
+
+/// The token types of [semantic_tokens], in the order of their indices
+pub const TOKEN_TYPES: [&str; 6] = [
+    "keyword", "variable", "operator", "string", "number", "comment",
+];
+
+/// Converts byte offsets to LSP positions, which count UTF-16 code units
+///
+/// LSP only knows `\n`, `\r\n` and `\r` as line breaks.
+pub struct LineIndex<'a> {
+    text: &'a str,
+    /// The offset of the first byte of every line
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let bytes = text.as_bytes();
+        let mut starts = vec![0];
+        let mut index = 0;
+        while index < bytes.len() {
+            index += match (bytes[index], bytes.get(index + 1)) {
+                (b'\r', Some(b'\n')) => 2,
+                (b'\n' | b'\r', _) => 1,
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            };
+            starts.push(index);
+        }
+        Self { text, starts }
+    }
+
+    fn line(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// The end of a line without its line break
+    fn line_end(&self, line: usize) -> usize {
+        match self.starts.get(line + 1) {
+            Some(&next) => self.text[..next].trim_end_matches(['\r', '\n']).len(),
+            None => self.text.len(),
+        }
+    }
+
+    /// The line and character of an offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        let start = self.starts[line];
+        (line, self.text[start..offset].encode_utf16().count())
+    }
+
+    pub fn position(&self, offset: usize) -> Value {
+        let (line, character) = self.line_col(offset);
+        json!({ "line": line, "character": character })
+    }
+
+    pub fn range(&self, range: Range<usize>) -> Value {
+        json!({ "start": self.position(range.start), "end": self.position(range.end) })
+    }
+
+    /// The offset of a line and character, clamped to the end of the line
+    pub fn offset(&self, line: usize, character: usize) -> usize {
+        let Some(&start) = self.starts.get(line) else {
+            return self.text.len();
+        };
+        let mut units = 0;
+        for (index, c) in self.text[start..self.line_end(line)].char_indices() {
+            if units >= character {
+                return start + index;
+            }
+            units += c.len_utf16();
+        }
+        self.line_end(line)
+    }
+
+    /// Split a range at line breaks
+    fn lines(&self, range: Range<usize>) -> impl Iterator<Item = Range<usize>> + '_ {
+        (self.line(range.start)..=self.line(range.end)).filter_map(move |line| {
+            let start = range.start.max(self.starts[line]);
+            let end = range.end.min(self.line_end(line));
+            (start < end).then_some(start..end)
+        })
+    }
+}
+
+fn span(token: &Token<&str>) -> Range<usize> {
+    token.start().offset..token.end().offset
+}
+
+fn node_span(node: &Node<&str>) -> Option<Range<usize>> {
+    Some(node.start()?.offset..node.end()?.offset)
+}
+
+/// The tokens that are direct children of a node
+fn child_tokens<'n, 'a>(node: &'n Node<&'a str>) -> impl Iterator<Item = &'n Token<&'a str>> {
+    node.children().iter().filter_map(|child| match child {
+        Element::Token(token) => Some(token),
+        Element::Node(_) => None,
+    })
+}
+
+fn names<'n, 'a>(node: &'n Node<&'a str>) -> impl Iterator<Item = &'n Token<&'a str>> {
+    child_tokens(node).filter(|token| token.kind() == TokenKind::Name)
+}
+
+fn find_child<'n, 'a>(node: &'n Node<&'a str>, kind: NodeKind) -> Option<&'n Node<&'a str>> {
+    node.nodes().find(|child| child.kind() == kind)
+}
+
+/// The `Diagnostic`s for the errors of a recovering parse
+pub fn diagnostics(root: &Node<&str>, errors: &[Error], index: &LineIndex) -> Vec<Value> {
+    errors
+        .iter()
+        .map(|error| {
+            // Mark the token at the error, if there is one
+            let start = error.offset();
+            let end = root
+                .significant_tokens()
+                .find(|token| token.start().offset == start)
+                .map_or(start, |token| token.end().offset);
+            json!({
+                "range": index.range(start..end),
+                "severity": 1,
+                "source": "sluap",
+                "message": error.kind().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn symbol(name: &str, kind: u32, range: Value, selection: Value, children: Vec<Value>) -> Value {
+    json!({
+        "name": name,
+        "kind": kind,
+        "range": range,
+        "selectionRange": selection,
+        "children": children,
+    })
+}
+
+/// The `DocumentSymbol`s for the functions and local variables in a node
+///
+/// The symbols in a function body are children of the function.
+pub fn symbols(node: &Node<&str>, index: &LineIndex) -> Vec<Value> {
+    const METHOD: u32 = 6;
+    const FUNCTION: u32 = 12;
+    const VARIABLE: u32 = 13;
+
+    let mut symbols = Vec::new();
+    for child in node.nodes() {
+        let Some(range) = node_span(child) else {
+            continue;
+        };
+        match child.kind() {
+            NodeKind::Function => {
+                let (Some(name), Some(body)) = (
+                    find_child(child, NodeKind::FuncName),
+                    find_child(child, NodeKind::FuncBody),
+                ) else {
+                    continue;
+                };
+                let text: String = name.significant_tokens().map(Token::span).collect();
+                let kind = match text.contains(':') {
+                    true => METHOD,
+                    false => FUNCTION,
+                };
+                let selection = node_span(name).unwrap_or(range.clone());
+                symbols.push(symbol(
+                    &text,
+                    kind,
+                    index.range(range),
+                    index.range(selection),
+                    self::symbols(body, index),
+                ));
+            }
+            NodeKind::LocalFunction => {
+                let (Some(name), Some(body)) =
+                    (names(child).next(), find_child(child, NodeKind::FuncBody))
+                else {
+                    continue;
+                };
+                symbols.push(symbol(
+                    name.span(),
+                    FUNCTION,
+                    index.range(range),
+                    index.range(span(name)),
+                    self::symbols(body, index),
+                ));
+            }
+            NodeKind::Local => {
+                for name in find_child(child, NodeKind::NameList)
+                    .into_iter()
+                    .flat_map(names)
+                {
+                    let selection = index.range(span(name));
+                    symbols.push(symbol(
+                        name.span(),
+                        VARIABLE,
+                        index.range(range.clone()),
+                        selection,
+                        Vec::new(),
+                    ));
+                }
+                // Functions in the expressions
+                symbols.extend(self::symbols(child, index));
+            }
+            _ => symbols.extend(self::symbols(child, index)),
+        }
+    }
+    symbols
+}
+
+/// The `FoldingRange`s for blocks, tables and long comments and strings
+pub fn folding_ranges(root: &Node<&str>, index: &LineIndex) -> Vec<Value> {
+    let mut ranges = Vec::new();
+    fold(root, index, &mut ranges);
+    ranges
+}
+
+fn fold(node: &Node<&str>, index: &LineIndex, ranges: &mut Vec<Value>) {
+    for element in node.children() {
+        match element {
+            Element::Node(child) => {
+                if let Some(range) = node_span(child) {
+                    let start = index.line(range.start);
+                    let end = index.line(range.end);
+                    // Keep the line of the closing token visible
+                    let end = match child.kind() {
+                        NodeKind::ElseIf | NodeKind::Else => Some(end),
+                        NodeKind::Do
+                        | NodeKind::While
+                        | NodeKind::Repeat
+                        | NodeKind::If
+                        | NodeKind::NumericFor
+                        | NodeKind::GenericFor
+                        | NodeKind::FuncBody
+                        | NodeKind::Table => end.checked_sub(1),
+                        _ => None,
+                    };
+                    if let Some(end) = end.filter(|&end| end > start) {
+                        ranges.push(json!({ "startLine": start, "endLine": end }));
+                    }
+                }
+                fold(child, index, ranges);
+            }
+            Element::Token(token) => {
+                let start = index.line(token.start().offset);
+                let end = index.line(token.end().offset);
+                match token.kind() {
+                    TokenKind::Comment if end > start => ranges.push(json!({
+                        "startLine": start,
+                        "endLine": end,
+                        "kind": "comment",
+                    })),
+                    TokenKind::LongString { .. } if end > start => {
+                        ranges.push(json!({ "startLine": start, "endLine": end }))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// The index of a token kind in [TOKEN_TYPES]
+fn token_type(kind: TokenKind) -> Option<u32> {
+    match kind {
+        TokenKind::Keyword(_) => Some(0),
+        TokenKind::Name => Some(1),
+        TokenKind::Symbol(_) => Some(2),
+        TokenKind::String | TokenKind::LongString { .. } | TokenKind::InterpString(_) => Some(3),
+        TokenKind::Hex(_) | TokenKind::Integer(_) | TokenKind::Number(_) | TokenKind::Cdata(_) => {
+            Some(4)
+        }
+        TokenKind::Comment => Some(5),
+        TokenKind::Whitespace | TokenKind::Error | TokenKind::Eof => None,
+    }
+}
+
+/// The data of `SemanticTokens`, with tokens that span lines split at the
+/// line breaks
+pub fn semantic_tokens(root: &Node<&str>, index: &LineIndex) -> Vec<u32> {
+    let mut data = Vec::new();
+    let (mut prev_line, mut prev_start) = (0, 0);
+    for token in root.tokens() {
+        let Some(ty) = token_type(token.kind()) else {
+            continue;
+        };
+        for range in index.lines(span(token)) {
+            let (line, start) = index.line_col(range.start);
+            let length = index.text[range].encode_utf16().count();
+            if line != prev_line {
+                prev_start = 0;
+            }
+            data.extend([line - prev_line, start - prev_start, length, ty as usize, 0]);
+            (prev_line, prev_start) = (line, start);
+        }
+    }
+    data.into_iter().map(|value| value as u32).collect()
+}
+
+/// Links the names that refer to local variables to their declarations
+///
+/// Every declaration is linked to itself. Global names are not linked.
+pub fn definitions(root: &Node<&str>) -> Vec<(Range<usize>, Range<usize>)> {
+    let resolution = resolve(root);
+    let declarations = &resolution.declarations;
+    let names = declarations
+        .iter()
+        .filter(|declaration| declaration.kind != DeclKind::SelfParam)
+        .map(|declaration| (span(&declaration.token), span(&declaration.token)));
+    let references = resolution.references.iter().filter_map(|reference| {
+        let declaration = &declarations[reference.binding.declaration()?];
+        Some((span(&reference.token), span(&declaration.token)))
+    });
+    names.chain(references).collect()
+}
+
+/// The declaration of the local variable at an offset
+pub fn definition(links: &[(Range<usize>, Range<usize>)], offset: usize) -> Option<Range<usize>> {
+    links
+        .iter()
+        .find(|(name, _)| name.start <= offset && offset <= name.end)
+        .map(|(_, declaration)| declaration.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use sluap::cst;
+
+    use super::{definition, definitions, semantic_tokens, LineIndex};
+
+    #[test]
+    fn test_line_index() {
+        let text = "a = 'é😀'\r\nb\rc";
+        let index = LineIndex::new(text);
+        assert_eq!(index.line_col(text.find('\'').unwrap() + 7), (0, 8));
+        assert_eq!(index.line_col(text.find('b').unwrap()), (1, 0));
+        assert_eq!(index.line_col(text.len()), (2, 1));
+        assert_eq!(index.offset(0, 6), text.find('😀').unwrap());
+        assert_eq!(index.offset(0, 99), text.find('\r').unwrap());
+        assert_eq!(index.offset(2, 0), text.find('c').unwrap());
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let text = "x = [[a\nbc]] --y";
+        let root = cst::parse_str(text).unwrap();
+        let data = semantic_tokens(&root, &LineIndex::new(text));
+        #[rustfmt::skip]
+        assert_eq!(data, [
+            0, 0, 1, 1, 0,
+            0, 2, 1, 2, 0,
+            0, 2, 3, 3, 0,
+            1, 0, 4, 3, 0,
+            0, 5, 3, 5, 0,
+        ]);
+    }
+
+    #[test]
+    fn test_definitions() {
+        let text = "local x = 1\n\
+            local x = x\n\
+            for i = 1, x do repeat local j = i until j end\n\
+            function f(x) return i, x end";
+        let root = cst::parse_str(text).unwrap();
+        let links = definitions(&root);
+        let find = |pattern: &str| text.find(pattern).unwrap();
+        let at = |pattern: &str, delta: usize| definition(&links, find(pattern) + delta);
+        // The value of `local x = x` is the outer `x`
+        assert_eq!(at("= x", 2), Some(find("x = 1")..find(" = 1")));
+        assert_eq!(at("1, x do", 3), Some(find("x = x")..find(" = x")));
+        assert_eq!(at("= i until", 2), Some(find("i = 1")..find(" = 1, x")));
+        assert_eq!(at("until j", 6), Some(find("j = i")..find(" = i")));
+        assert_eq!(at("return i", 7), None);
+        assert_eq!(at("i, x end", 3), Some(find("x) return")..find(") return")));
+    }
+}
@@ -0,0 +1,367 @@
+//! The `sluap-lsp` language server, enabled by the `lsp` feature
+//!
+//! Speaks the Language Server Protocol over standard input and output.
+
+mod analysis;
+mod rpc;
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    process::ExitCode,
+};
+
+use serde_json::{json, Value};
+use sluap::{cst, Dialect, LuaVersion, Utf8Decoder};
+
+use analysis::LineIndex;
+
+const USAGE: &str = "\
+USAGE: sluap-lsp [--lua VERSION] [--luajit | --luau]
+
+Runs a language server on standard input and output.
+
+LANGUAGE:
+    --lua VERSION           5.1, 5.2, 5.3 or 5.4 (default: 5.1)
+    --luajit                Enable the extensions of LuaJIT (default version: 5.2)
+    --luau                  Enable the extensions of Luau";
+
+/// A JSON-RPC error code and message
+type Failure = (i64, String);
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+fn invalid_params(message: &str) -> Failure {
+    (INVALID_PARAMS, message.to_string())
+}
+
+struct Server {
+    dialect: Dialect,
+    /// The text of the open documents by URI
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+impl Server {
+    fn new(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// The URI and text of the document in `params`
+    fn document<'a>(&'a self, params: &'a Value) -> Result<(&'a str, &'a str), Failure> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| invalid_params("missing textDocument"))?;
+        let text = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| invalid_params("unknown document"))?;
+        Ok((uri, text))
+    }
+
+    /// The `textDocument/publishDiagnostics` notification for a document
+    fn diagnostics(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let (root, errors) = cst::parse_recovering(Utf8Decoder::new(text), self.dialect);
+        let diagnostics = analysis::diagnostics(&root, &errors, &LineIndex::new(text));
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, Failure> {
+        if self.shutdown {
+            return Err((INVALID_REQUEST, "the server is shut down".to_string()));
+        }
+        match method {
+            "initialize" => {
+                return Ok(json!({
+                    "capabilities": {
+                        "textDocumentSync": 1,
+                        "documentSymbolProvider": true,
+                        "foldingRangeProvider": true,
+                        "definitionProvider": true,
+                        "semanticTokensProvider": {
+                            "legend": { "tokenTypes": analysis::TOKEN_TYPES, "tokenModifiers": [] },
+                            "full": true,
+                        },
+                    },
+                    "serverInfo": { "name": "sluap-lsp", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                return Ok(Value::Null);
+            }
+            "textDocument/documentSymbol"
+            | "textDocument/foldingRange"
+            | "textDocument/semanticTokens/full"
+            | "textDocument/definition" => {}
+            _ => return Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        }
+
+        let (uri, text) = self.document(params)?;
+        let (root, _) = cst::parse_recovering(Utf8Decoder::new(text), self.dialect);
+        let index = LineIndex::new(text);
+        match method {
+            "textDocument/documentSymbol" => Ok(json!(analysis::symbols(&root, &index))),
+            "textDocument/foldingRange" => Ok(json!(analysis::folding_ranges(&root, &index))),
+            "textDocument/semanticTokens/full" => {
+                Ok(json!({ "data": analysis::semantic_tokens(&root, &index) }))
+            }
+            "textDocument/definition" => {
+                let position = &params["position"];
+                let (Some(line), Some(character)) =
+                    (position["line"].as_u64(), position["character"].as_u64())
+                else {
+                    return Err(invalid_params("missing position"));
+                };
+                let offset = index.offset(line as usize, character as usize);
+                let links = analysis::definitions(&root);
+                Ok(match analysis::definition(&links, offset) {
+                    Some(range) => json!({ "uri": uri, "range": index.range(range) }),
+                    None => Value::Null,
+                })
+            }
+            _ => unreachable!("unknown methods are rejected above"),
+        }
+    }
+
+    /// Handle a notification, returning the notification to send back
+    fn notification(&mut self, method: &str, params: &Value) -> Option<Value> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str()?;
+                self.documents.insert(uri.to_string(), text.to_string());
+            }
+            // With full sync, the last change has the entire text
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array()?;
+                let text = changes.last()?["text"].as_str()?;
+                self.documents.insert(uri.to_string(), text.to_string());
+            }
+            // Clear the diagnostics of the document
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+            }
+            _ => return None,
+        }
+        Some(self.diagnostics(uri))
+    }
+
+    /// Handle a message, returning the message to send back
+    fn handle(&mut self, message: &Value) -> Option<Value> {
+        let method = message["method"].as_str();
+        let params = &message["params"];
+        match (message.get("id"), method) {
+            (Some(id), Some(method)) => Some(match self.request(method, params) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": code, "message": message },
+                }),
+            }),
+            (None, Some(method)) => self.notification(method, params),
+            // Responses to requests, which this server doesn't send
+            (_, None) => None,
+        }
+    }
+}
+
+/// Serve messages until `exit` or the end of the input
+///
+/// Returns whether the client shut the server down before it exited.
+fn run<R: BufRead, W: Write>(server: &mut Server, mut input: R, mut output: W) -> io::Result<bool> {
+    while let Some(content) = rpc::read(&mut input)? {
+        let reply = match serde_json::from_slice::<Value>(&content) {
+            Ok(message) if message["method"] == "exit" => return Ok(server.shutdown),
+            Ok(message) => server.handle(&message),
+            Err(e) => Some(json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": PARSE_ERROR, "message": e.to_string() },
+            })),
+        };
+        if let Some(reply) = reply {
+            rpc::write(&mut output, &reply)?;
+        }
+    }
+    Ok(false)
+}
+
+fn usage(message: &str) -> ExitCode {
+    eprintln!("sluap-lsp: {}\n\n{}", message, USAGE);
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let mut version = None;
+    let mut luajit = false;
+    let mut luau = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            "--luajit" => luajit = true,
+            "--luau" => luau = true,
+            // Editors commonly pass this to every server
+            "--stdio" => {}
+            "--lua" => {
+                let Some(value) = args.next() else {
+                    return usage("missing value for --lua");
+                };
+                match LuaVersion::parse(&value) {
+                    Some(v) => version = Some(v),
+                    None => return usage(&format!("invalid value {:?} for --lua", value)),
+                }
+            }
+            _ => return usage(&format!("unknown option {}", arg)),
+        }
+    }
+
+    let default = if luajit {
+        Dialect::LUAJIT.version
+    } else {
+        LuaVersion::Lua51
+    };
+    let dialect = Dialect {
+        version: version.unwrap_or(default),
+        luajit,
+        luau,
+    };
+
+    let mut server = Server::new(dialect);
+    match run(&mut server, io::stdin().lock(), io::stdout().lock()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("sluap-lsp: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use sluap::Dialect;
+
+    use super::{rpc, run, Server};
+
+    /// Run a session and return the messages from the server
+    fn session(messages: &[Value]) -> (bool, Vec<Value>) {
+        let mut input = Vec::new();
+        for message in messages {
+            rpc::write(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        let mut server = Server::new(Dialect::default());
+        let clean = run(&mut server, &input[..], &mut output).unwrap();
+        let mut reader = &output[..];
+        let mut replies = Vec::new();
+        while let Some(content) = rpc::read(&mut reader).unwrap() {
+            replies.push(serde_json::from_slice(&content).unwrap());
+        }
+        (clean, replies)
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": params })
+    }
+
+    #[test]
+    fn test_session() {
+        let uri = "file:///main.lua";
+        let doc = json!({ "textDocument": { "uri": uri } });
+        let text = "local x = 1\nfunction t.f(a)\n  return a + x\nend\nx = = 2\n";
+        let (clean, replies) = session(&[
+            request(1, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            notification(
+                "textDocument/didOpen",
+                json!({ "textDocument": { "uri": uri, "languageId": "lua", "version": 1, "text": text } }),
+            ),
+            request(2, "textDocument/documentSymbol", doc.clone()),
+            request(
+                3,
+                "textDocument/definition",
+                json!({ "textDocument": { "uri": uri }, "position": { "line": 2, "character": 13 } }),
+            ),
+            request(4, "textDocument/foldingRange", doc.clone()),
+            request(5, "textDocument/semanticTokens/full", doc.clone()),
+            request(6, "textDocument/hover", doc),
+            // Unknown methods are reported before the missing textDocument
+            request(7, "workspace/symbol", json!({ "query": "x" })),
+            request(8, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+        ]);
+        assert!(clean);
+        assert_eq!(replies.len(), 9);
+        assert_eq!(
+            replies[0]["result"]["capabilities"]["definitionProvider"],
+            true
+        );
+
+        let diagnostics = &replies[1]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({ "start": { "line": 4, "character": 4 }, "end": { "line": 4, "character": 5 } })
+        );
+
+        let symbols = replies[2]["result"].as_array().unwrap();
+        let names: Vec<_> = symbols
+            .iter()
+            .map(|s| s["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["x", "t.f"]);
+        assert_eq!(symbols[1]["kind"], 12);
+
+        assert_eq!(
+            replies[3]["result"],
+            json!({ "uri": uri, "range": {
+                "start": { "line": 0, "character": 6 },
+                "end": { "line": 0, "character": 7 },
+            } })
+        );
+        assert_eq!(
+            replies[4]["result"],
+            json!([{ "startLine": 1, "endLine": 2 }])
+        );
+        // `local`, `x`, `=` and `1` on the first line
+        let data = replies[5]["result"]["data"].as_array().unwrap();
+        assert_eq!(
+            data[..20],
+            [0, 0, 5, 0, 0, 0, 6, 1, 1, 0, 0, 2, 1, 2, 0, 0, 2, 1, 4, 0]
+        );
+        assert_eq!(replies[6]["error"]["code"], -32601);
+        assert_eq!(replies[7]["error"]["code"], -32601);
+        assert_eq!(replies[8]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_exit_without_shutdown() {
+        let (clean, replies) = session(&[notification("exit", Value::Null)]);
+        assert!(!clean);
+        assert!(replies.is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+//! JSON-RPC messages with `Content-Length` headers, as in the base protocol of LSP
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read the content of the next message, or `None` at the end of the input
+pub fn read<R: BufRead>(input: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return match length {
+                None => Ok(None),
+                Some(_) => Err(invalid("unexpected end of headers")),
+            };
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let value = value.trim().parse();
+                length = Some(value.map_err(|_| invalid("invalid Content-Length"))?);
+            }
+        }
+    }
+    let length = length.ok_or_else(|| invalid("missing Content-Length"))?;
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+    Ok(Some(content))
+}
+
+/// Write a message with its header
+pub fn write<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
@@ -0,0 +1,524 @@
+//! The `sluap` command-line tool, enabled by the `cli` feature
+
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+use sluap::{
+    ast,
+    bytecode::{self, Header, Proto},
+    cst,
+    format::{self, Indent, Options, QuoteStyle},
+    lint::{self, Lint},
+    Decoder, Dialect, Error, Latin1Decoded, Latin1Decoder, Lexer, LuaVersion, Position, TokenKind,
+    Utf8Decoder,
+};
+
+const USAGE: &str = "\
+USAGE: sluap [--utf8 | --latin1] [--lua VERSION] [--luajit | --luau] <COMMAND> [OPTIONS] FILE...
+
+Reads standard input if FILE is `-`.
+
+COMMANDS:
+    lex [--json] FILE       Print the tokens of a file with their positions
+    parse FILE              Print the syntax tree of a file
+    check FILE...           Report all syntax errors of files
+    lint [OPTIONS] FILE...  Report syntax errors and likely mistakes in files
+    fmt [OPTIONS] FILE...   Print formatted files
+    compile [OPTIONS] FILE  Compile a file to Lua 5.1 bytecode
+    list FILE               Print the bytecode of a source or precompiled file
+
+FORMAT OPTIONS:
+    --write                 Rewrite the files instead of printing them
+    --check                 Only report files that are not formatted
+    --indent N              Indent with N spaces (default: 4)
+    --tabs                  Indent with tabs
+    --quotes STYLE          double, single or preserve (default: double)
+    --width N               Maximum line width (default: 80)
+
+COMPILE OPTIONS:
+    --output FILE           Write to FILE instead of luac.out, `-` for standard output
+    --strip                 Leave out the debug information
+
+LINT OPTIONS:
+    --allow LINT            Don't report a lint, e.g. unused-param
+    --global NAME           Allow reading and assigning a global
+
+LANGUAGE:
+    --lua VERSION           5.1, 5.2, 5.3 or 5.4 (default: 5.1)
+    --luajit                Enable the extensions of LuaJIT (default version: 5.2)
+    --luau                  Enable the extensions of Luau
+
+ENCODING:
+    --utf8                  Read files as UTF-8
+    --latin1                Read files as Latin-1 (Windows-1252)
+                            By default, files that are not valid UTF-8 are Latin-1";
+
+/// Something that stops a command, already reported to stderr
+struct Failed;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Encoding {
+    Auto,
+    Utf8,
+    Latin1,
+}
+
+/// The contents of an input file
+enum Source<'a> {
+    Utf8(&'a str),
+    Latin1(&'a [u8]),
+}
+
+impl<'a> Source<'a> {
+    fn new(bytes: &'a [u8], encoding: Encoding, path: &str) -> Result<Self, Failed> {
+        match (encoding, std::str::from_utf8(bytes)) {
+            (Encoding::Latin1, _) => Ok(Self::Latin1(bytes)),
+            (_, Ok(text)) => Ok(Self::Utf8(text)),
+            (Encoding::Auto, Err(_)) => Ok(Self::Latin1(bytes)),
+            (Encoding::Utf8, Err(e)) => {
+                eprintln!("{}: invalid UTF-8: {}", path, e);
+                Err(Failed)
+            }
+        }
+    }
+}
+
+/// Spans that can be printed
+trait Text {
+    fn text(&self) -> Cow<'_, str>;
+}
+
+impl Text for str {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl Text for [u8] {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Owned(Latin1Decoded(self).to_string())
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, Failed> {
+    let result = if path == "-" {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+    } else {
+        std::fs::read(path)
+    };
+    result.map_err(|e| io_error(path, e))
+}
+
+/// The length of a first line that starts with `#`, e.g. `#!/usr/bin/lua`
+///
+/// Lua skips this line, but not its newline, which keeps the line numbers.
+fn shebang(bytes: &[u8]) -> usize {
+    match bytes.first() {
+        Some(b'#') => bytes
+            .iter()
+            .position(|&b| b == b'\n')
+            .unwrap_or(bytes.len()),
+        _ => 0,
+    }
+}
+
+fn io_error(path: &str, error: io::Error) -> Failed {
+    eprintln!("{}: {}", path, error);
+    Failed
+}
+
+fn report(path: &str, error: Error) -> Failed {
+    eprintln!("{}:{}", path, error);
+    Failed
+}
+
+fn json_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn json_position(out: &mut String, pos: Position) {
+    write!(
+        out,
+        "{{\"offset\":{},\"line\":{},\"column\":{}}}",
+        pos.offset, pos.line, pos.column
+    )
+    .unwrap();
+}
+
+fn kind_name(kind: TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Whitespace => "Whitespace",
+        TokenKind::Keyword(_) => "Keyword",
+        TokenKind::Name => "Name",
+        TokenKind::Symbol(_) => "Symbol",
+        TokenKind::Comment => "Comment",
+        TokenKind::String => "String",
+        TokenKind::LongString { .. } => "LongString",
+        TokenKind::Hex(_) => "Hex",
+        TokenKind::Integer(_) => "Integer",
+        TokenKind::Number(_) => "Number",
+        TokenKind::Cdata(_) => "Cdata",
+        TokenKind::InterpString(_) => "InterpString",
+        TokenKind::Error => "Error",
+        TokenKind::Eof => "Eof",
+    }
+}
+
+fn lex<'a, D: Decoder<'a> + 'a>(
+    decoder: D,
+    dialect: Dialect,
+    path: &str,
+    json: bool,
+) -> Result<(), Failed>
+where
+    D::Slice: Text,
+{
+    let mut lexer = Lexer::with_dialect(decoder, dialect);
+    let mut out = String::new();
+    if json {
+        out.push('[');
+    }
+    loop {
+        let token = lexer.token().map_err(|e| report(path, e))?;
+        if token.kind() == TokenKind::Eof {
+            break;
+        }
+        let text = token.span().text();
+        if json {
+            if out.len() > 1 {
+                out.push(',');
+            }
+            out.push_str("\n  {\"kind\":");
+            json_string(&mut out, kind_name(token.kind()));
+            out.push_str(",\"text\":");
+            json_string(&mut out, &text);
+            out.push_str(",\"start\":");
+            json_position(&mut out, token.start());
+            out.push_str(",\"end\":");
+            json_position(&mut out, token.end());
+            out.push('}');
+        } else {
+            writeln!(
+                out,
+                "{}-{} {:?} {:?}",
+                token.start(),
+                token.end(),
+                token.kind(),
+                text
+            )
+            .unwrap();
+        }
+    }
+    if json {
+        out.push_str("\n]\n");
+    }
+    print!("{}", out);
+    Ok(())
+}
+
+fn parse(source: Source, dialect: Dialect, path: &str) -> Result<(), Failed> {
+    let decoded;
+    let text = match source {
+        Source::Utf8(text) => text,
+        // Print the spans as text instead of byte arrays, but report
+        // errors with the byte positions of the original file
+        Source::Latin1(bytes) => {
+            ast::parse_with(Latin1Decoder::new(bytes), dialect).map_err(|e| report(path, e))?;
+            decoded = Latin1Decoded(bytes).to_string();
+            &decoded
+        }
+    };
+    let chunk = ast::parse_with(Utf8Decoder::new(text), dialect).map_err(|e| report(path, e))?;
+    println!("{:#?}", chunk);
+    Ok(())
+}
+
+/// Reports all syntax errors of a file
+fn check(source: Source, dialect: Dialect, path: &str) -> Result<(), Failed> {
+    let errors = match source {
+        Source::Utf8(text) => ast::parse_recovering(Utf8Decoder::new(text), dialect).1,
+        Source::Latin1(bytes) => ast::parse_recovering(Latin1Decoder::new(bytes), dialect).1,
+    };
+    for &error in &errors {
+        report(path, error);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Failed)
+    }
+}
+
+/// Reports all syntax errors and lints of a file
+fn lint(source: Source, path: &str, config: &lint::Config) -> Result<(), Failed> {
+    let (errors, diagnostics) = match source {
+        Source::Utf8(text) => {
+            let (root, errors) = cst::parse_recovering(Utf8Decoder::new(text), config.dialect);
+            (errors, lint::lint(&root, config))
+        }
+        Source::Latin1(bytes) => {
+            let (root, errors) = cst::parse_recovering(Latin1Decoder::new(bytes), config.dialect);
+            (errors, lint::lint(&root, config))
+        }
+    };
+    for &error in &errors {
+        report(path, error);
+    }
+    for diagnostic in &diagnostics {
+        eprintln!("{}:{}", path, diagnostic);
+    }
+    if errors.is_empty() && diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(Failed)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum FmtMode {
+    Print,
+    Write,
+    Check,
+}
+
+fn fmt(
+    source: Source,
+    bytes: &[u8],
+    dialect: Dialect,
+    path: &str,
+    options: &Options,
+    mode: FmtMode,
+) -> Result<(), Failed> {
+    let body = match source {
+        Source::Utf8(text) => cst::parse_with(Utf8Decoder::new(text), dialect)
+            .map(|root| format::format_node(&root, options)),
+        Source::Latin1(bytes) => cst::parse_with(Latin1Decoder::new(bytes), dialect)
+            .map(|root| format::format_node(&root, options)),
+    }
+    .map_err(|e| report(path, e))?;
+    let mut formatted = bytes[..shebang(bytes)].to_vec();
+    if !formatted.is_empty() {
+        formatted.push(b'\n');
+    }
+    formatted.extend(body);
+    match mode {
+        FmtMode::Check if formatted != bytes => {
+            eprintln!("{}: not formatted", path);
+            Err(Failed)
+        }
+        FmtMode::Check => Ok(()),
+        FmtMode::Write if formatted == bytes => Ok(()),
+        FmtMode::Write if path != "-" => {
+            std::fs::write(path, formatted).map_err(|e| io_error(path, e))
+        }
+        _ => io::stdout()
+            .write_all(&formatted)
+            .map_err(|e| io_error(path, e)),
+    }
+}
+
+/// Compiles a file to the prototype of its main function
+fn compile_source(source: Source, dialect: Dialect, path: &str) -> Result<Proto, Failed> {
+    let name = if path == "-" {
+        String::from("=stdin")
+    } else {
+        format!("@{}", path)
+    };
+    match source {
+        Source::Utf8(text) => {
+            let chunk =
+                ast::parse_with(Utf8Decoder::new(text), dialect).map_err(|e| report(path, e))?;
+            bytecode::compile(&chunk, Utf8Decoder::new(text), dialect, &name)
+        }
+        Source::Latin1(bytes) => {
+            let chunk =
+                ast::parse_with(Latin1Decoder::new(bytes), dialect).map_err(|e| report(path, e))?;
+            bytecode::compile(&chunk, Latin1Decoder::new(bytes), dialect, &name)
+        }
+    }
+    .map_err(|e| {
+        eprintln!("{}:{}", path, e);
+        Failed
+    })
+}
+
+/// Compiles a file to a binary chunk like `luac`
+fn compile(
+    source: Source,
+    dialect: Dialect,
+    path: &str,
+    output: &str,
+    strip: bool,
+) -> Result<(), Failed> {
+    let bytes = compile_source(source, dialect, path)?.dump(&Header::default(), strip);
+    if output == "-" {
+        io::stdout()
+            .write_all(&bytes)
+            .map_err(|e| io_error(output, e))
+    } else {
+        std::fs::write(output, bytes).map_err(|e| io_error(output, e))
+    }
+}
+
+/// Prints the bytecode of a binary chunk, or of a source file like `luac -l -l`
+fn list(bytes: &[u8], encoding: Encoding, dialect: Dialect, path: &str) -> Result<(), Failed> {
+    let proto = if bytes.starts_with(Header::SIGNATURE) {
+        let (_, proto) = bytecode::load(bytes).map_err(|e| {
+            eprintln!("{}: {}", path, e);
+            Failed
+        })?;
+        proto
+    } else {
+        compile_source(Source::new(bytes, encoding, path)?, dialect, path)?
+    };
+    print!("{}", proto.listing(true));
+    Ok(())
+}
+
+fn usage(message: &str) -> ExitCode {
+    eprintln!("sluap: {}\n\n{}", message, USAGE);
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let mut encoding = Encoding::Auto;
+    let mut version = None;
+    let mut luajit = false;
+    let mut luau = false;
+    let mut command = None;
+    let mut json = false;
+    let mut mode = FmtMode::Print;
+    let mut options = Options::default();
+    let mut allowed = Vec::new();
+    let mut globals = Vec::new();
+    let mut output = String::from("luac.out");
+    let mut strip = false;
+    let mut files = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            "--utf8" => encoding = Encoding::Utf8,
+            "--latin1" => encoding = Encoding::Latin1,
+            "--json" => json = true,
+            "--write" => mode = FmtMode::Write,
+            "--check" => mode = FmtMode::Check,
+            "--tabs" => options.indent = Indent::Tabs,
+            "--strip" => strip = true,
+            "--luajit" => luajit = true,
+            "--luau" => luau = true,
+            "--indent" | "--width" | "--quotes" | "--lua" | "--allow" | "--global" | "--output" => {
+                let Some(value) = args.next() else {
+                    return usage(&format!("missing value for {}", arg));
+                };
+                match (arg.as_str(), value.as_str()) {
+                    ("--lua", v) if LuaVersion::parse(v).is_some() => {
+                        version = LuaVersion::parse(v)
+                    }
+                    ("--quotes", "double") => options.quote_style = QuoteStyle::Double,
+                    ("--quotes", "single") => options.quote_style = QuoteStyle::Single,
+                    ("--quotes", "preserve") => options.quote_style = QuoteStyle::Preserve,
+                    ("--indent", n) if n.parse::<usize>().is_ok() => {
+                        options.indent = Indent::Spaces(n.parse().unwrap())
+                    }
+                    ("--width", n) if n.parse::<usize>().is_ok() => {
+                        options.line_width = n.parse().unwrap()
+                    }
+                    ("--allow", name) if Lint::parse(name).is_some() => {
+                        allowed.extend(Lint::parse(name))
+                    }
+                    ("--global", _) => globals.push(value),
+                    ("--output", _) => output = value,
+                    _ => return usage(&format!("invalid value {:?} for {}", value, arg)),
+                }
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                return usage(&format!("unknown option {}", flag))
+            }
+            _ if command.is_none() => command = Some(arg),
+            _ => files.push(arg),
+        }
+    }
+
+    let command = match command.as_deref() {
+        Some(command @ ("lex" | "parse" | "check" | "lint" | "fmt" | "compile" | "list")) => {
+            command
+        }
+        Some(other) => return usage(&format!("unknown command {:?}", other)),
+        None => return usage("missing command"),
+    };
+    match (command, files.len()) {
+        (_, 0) => return usage("missing FILE"),
+        ("lex" | "parse" | "compile" | "list", 1) => {}
+        ("lex" | "parse" | "compile" | "list", _) => {
+            return usage(&format!("{} takes a single FILE", command))
+        }
+        _ => {}
+    }
+
+    let default = if luajit {
+        Dialect::LUAJIT.version
+    } else {
+        LuaVersion::Lua51
+    };
+    let dialect = Dialect {
+        version: version.unwrap_or(default),
+        luajit,
+        luau,
+    };
+    let globals: Vec<_> = globals.iter().map(String::as_str).collect();
+    let config = lint::Config {
+        dialect,
+        globals: &globals,
+        disabled: &allowed,
+    };
+
+    let mut failed = false;
+    for path in &files {
+        let result = read(path).and_then(|bytes| {
+            let lua = &bytes[shebang(&bytes)..];
+            if command == "list" {
+                return list(lua, encoding, dialect, path);
+            }
+            let source = Source::new(lua, encoding, path)?;
+            match (command, source) {
+                ("lex", Source::Utf8(text)) => lex(Utf8Decoder::new(text), dialect, path, json),
+                ("lex", Source::Latin1(bytes)) => {
+                    lex(Latin1Decoder::new(bytes), dialect, path, json)
+                }
+                ("parse", source) => parse(source, dialect, path),
+                ("check", source) => check(source, dialect, path),
+                ("lint", source) => lint(source, path, &config),
+                ("compile", source) => compile(source, dialect, path, &output, strip),
+                (_, source) => fmt(source, &bytes, dialect, path, &options, mode),
+            }
+        });
+        failed |= result.is_err();
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,2416 @@
+//! Code generation, as `lparser.c` and `lcode.c` of Lua 5.1
+//!
+//! The functions keep the names and the order of operations of the C code,
+//! so that the registers, constants and jumps are the same as those of `luac`.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{fmt, mem};
+
+use super::{Constant, Instruction, LocalVar, OpCode, Proto};
+use crate::{
+    ast::{Call, Chunk, Expr, FuncBody, Stmt, TableField, Var},
+    encoding::ByteLen,
+    BinOp, Decoder, Dialect, Keyword, Lexer, Symbol, TokenKind, UnOp, Unescape,
+};
+
+/// The end of a jump list
+const NO_JUMP: usize = usize::MAX;
+/// The `A` of a `TESTSET` whose value isn't needed
+const NO_REG: u32 = Instruction::MAX_A;
+/// The largest constant index in an RK argument
+const MAX_INDEX_RK: u32 = Instruction::CONSTANT - 1;
+/// `LUA_MULTRET`
+const MULTRET: i32 = -1;
+const MAX_STACK: u32 = 250;
+const MAX_VARS: usize = 200;
+const MAX_UPVALUES: usize = 60;
+const FIELDS_PER_FLUSH: usize = 50;
+
+/// The reasons why a chunk can't be compiled
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompileErrorKind {
+    /// Syntax of a later Lua version or another dialect, e.g. `goto`
+    Unsupported(&'static str),
+    /// `break` outside a loop
+    NoLoopToBreak,
+    /// `...` in a function without `...` in its parameters
+    VarargOutsideVararg,
+    /// More than 200 local variables in a function
+    TooManyLocals,
+    /// More than 60 upvalues in a function
+    TooManyUpvalues,
+    /// A function that needs more than 250 registers
+    TooComplex,
+    /// More than 262143 constants in a function
+    TooManyConstants,
+    /// A jump over more than 131071 instructions
+    TooLong,
+}
+
+impl fmt::Display for CompileErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(what) => write!(f, "{} not supported by Lua 5.1", what),
+            Self::NoLoopToBreak => f.write_str("no loop to break"),
+            Self::VarargOutsideVararg => f.write_str("cannot use '...' outside a vararg function"),
+            Self::TooManyLocals => f.write_str("too many local variables"),
+            Self::TooManyUpvalues => f.write_str("too many upvalues"),
+            Self::TooComplex => f.write_str("function or expression too complex"),
+            Self::TooManyConstants => f.write_str("constant table overflow"),
+            Self::TooLong => f.write_str("control structure too long"),
+        }
+    }
+}
+
+/// An error of the compiler
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    kind: CompileErrorKind,
+    line: u32,
+}
+
+impl CompileError {
+    pub fn kind(&self) -> CompileErrorKind {
+        self.kind
+    }
+
+    /// The line of the last token before the error
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.kind)
+    }
+}
+
+/// `expkind` of `lparser.h`, with the `info` and `aux` that each kind uses
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    /// No value, e.g. an empty argument list
+    Void,
+    Nil,
+    True,
+    False,
+    /// A constant index
+    K(u32),
+    /// A number that may still be folded
+    Number(f64),
+    /// A register of a local variable
+    Local(u32),
+    Upvalue(u32),
+    /// The constant index of the name
+    Global(u32),
+    /// The register of the table and the RK of the key
+    Indexed(u32, u32),
+    /// The pc of the jump after a test
+    Jump(usize),
+    /// The pc of an instruction whose `A` can be set to any register
+    Relocable(usize),
+    /// A register with the value
+    NonReloc(u32),
+    /// The pc of an open call
+    Call(usize),
+    /// The pc of an open `VARARG`
+    Vararg(usize),
+}
+
+/// `expdesc`: how to get the value of an expression, and the jump lists
+/// that exit it when it is true (`t`) and false (`f`)
+#[derive(Debug, Copy, Clone)]
+struct Exp {
+    kind: Kind,
+    t: usize,
+    f: usize,
+}
+
+impl Exp {
+    fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            t: NO_JUMP,
+            f: NO_JUMP,
+        }
+    }
+
+    fn has_jumps(&self) -> bool {
+        self.t != self.f
+    }
+
+    fn has_multret(&self) -> bool {
+        matches!(self.kind, Kind::Call(_) | Kind::Vararg(_))
+    }
+
+    fn is_numeral(&self) -> bool {
+        matches!(self.kind, Kind::Number(_)) && self.t == NO_JUMP && self.f == NO_JUMP
+    }
+
+    /// The register of a discharged expression
+    fn reg(&self) -> u32 {
+        match self.kind {
+            Kind::NonReloc(reg) => reg,
+            kind => unreachable!("{:?} is not in a register", kind),
+        }
+    }
+}
+
+/// Where a function finds an upvalue when it is created
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Upvalue {
+    /// A local variable of the enclosing function
+    Local(u32),
+    /// An upvalue of the enclosing function
+    Upvalue(u32),
+}
+
+/// The result of resolving a name
+enum Resolved {
+    Global,
+    Local(u32),
+    Upvalue(u32),
+}
+
+/// `BlockCnt`
+struct Block {
+    /// The jumps of the `break` statements
+    break_list: usize,
+    /// The number of active locals outside the block
+    nactvar: usize,
+    /// Whether some local of the block is an upvalue
+    upval: bool,
+    /// Whether the block is a loop
+    breakable: bool,
+}
+
+/// `FuncState`
+struct Func {
+    proto: Proto,
+    strings: BTreeMap<Vec<u8>, u32>,
+    /// Numbers by their bits, with `-0` as `0` like the keys of a Lua table
+    numbers: BTreeMap<u64, u32>,
+    /// The constants `nil`, `false` and `true`
+    singletons: [Option<u32>; 3],
+    blocks: Vec<Block>,
+    /// The pc of the last jump target
+    last_target: Option<usize>,
+    /// The jumps to the next instruction
+    jpc: usize,
+    free_reg: u32,
+    nactvar: usize,
+    /// The indices in `proto.locals` of the active and declared locals
+    actvar: Vec<usize>,
+    upvalues: Vec<Upvalue>,
+}
+
+/// A token that isn't whitespace or a comment
+#[derive(Copy, Clone)]
+struct Lexed {
+    kind: TokenKind,
+    offset: usize,
+    /// The line of its end
+    line: u32,
+}
+
+struct Compiler {
+    /// The address of the source, to find the tokens of spans
+    start: usize,
+    tokens: Vec<Lexed>,
+    /// The number of tokens that `lparser.c` has read at this point
+    next: usize,
+    /// `lastline`: the line of the last read token
+    line: u32,
+    /// Whether `luaX_lookahead` has read the token after the next one, which
+    /// moves `lastline` to its line
+    lookahead: bool,
+    funcs: Vec<Func>,
+    /// The first limit that was exceeded, which doesn't stop the compiler
+    error: Option<CompileError>,
+}
+
+/// Compiles a chunk to the prototype of its main function
+///
+/// The chunk must be parsed from the input of `decoder` with `dialect`. The
+/// tokens of the input give the lines of the debug information, which are
+/// those of the last token that `luac` has read for each instruction.
+/// `name` is the chunk name, e.g. `@file.lua` or `=stdin`.
+pub fn compile<'a, D: Decoder<'a> + 'a>(
+    chunk: &Chunk<&'a D::Slice>,
+    decoder: D,
+    dialect: Dialect,
+    name: &str,
+) -> Result<Proto, CompileError> {
+    let mut c = Compiler::new(decoder, dialect);
+    c.open_func(Some(name.as_bytes().to_vec()), 0);
+    c.fs().proto.is_vararg = Proto::VARARG_ISVARARG;
+    c.chunk(chunk)?;
+    let (proto, _) = c.close_func();
+    match c.error {
+        Some(error) => Err(error),
+        None => Ok(proto),
+    }
+}
+
+/// `luaO_int2fb`: a size as a "floating point byte" `eeeeexxx`
+fn int_to_fb(mut x: usize) -> u32 {
+    let mut e = 0;
+    while x >= 16 {
+        x = (x + 1) >> 1;
+        e += 1;
+    }
+    if x < 8 {
+        x as u32
+    } else {
+        ((e + 1) << 3) | (x as u32 - 8)
+    }
+}
+
+/// `floor`, which is not in `core`
+fn floor(x: f64) -> f64 {
+    if x.is_nan() || x.abs() >= 4503599627370496.0 {
+        // Integral or infinite
+        return x;
+    }
+    let truncated = x as i64 as f64;
+    if truncated > x {
+        truncated - 1.0
+    } else {
+        truncated
+    }
+}
+
+/// `pow` for the exponents that can be folded exactly without `libm`
+fn pow(base: f64, exponent: f64) -> Option<f64> {
+    const EXACT: f64 = 9007199254740992.0;
+    if floor(base) != base || !(0.0..=64.0).contains(&exponent) || floor(exponent) != exponent {
+        return None;
+    }
+    let mut result = 1.0;
+    for _ in 0..exponent as u32 {
+        result *= base;
+        if result.abs() > EXACT {
+            return None;
+        }
+    }
+    Some(result)
+}
+
+impl Compiler {
+    fn new<'a, D: Decoder<'a> + 'a>(decoder: D, dialect: Dialect) -> Self {
+        let start = decoder.as_bytes().as_ptr() as usize;
+        let mut lexer = Lexer::with_dialect(decoder, dialect);
+        let mut tokens = Vec::new();
+        // The parser has read the same tokens without an error
+        while let Ok(token) = lexer.token() {
+            match token.kind() {
+                TokenKind::Eof => break,
+                TokenKind::Whitespace | TokenKind::Comment => {}
+                kind => tokens.push(Lexed {
+                    kind,
+                    offset: token.start().offset,
+                    line: token.end().line as u32,
+                }),
+            }
+        }
+        Self {
+            start,
+            tokens,
+            next: 0,
+            line: 1,
+            lookahead: false,
+            funcs: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn fs(&mut self) -> &mut Func {
+        self.funcs.last_mut().expect("a function is open")
+    }
+
+    fn fail(&self, kind: CompileErrorKind) -> CompileError {
+        CompileError {
+            kind,
+            line: self.line,
+        }
+    }
+
+    /// Records an exceeded limit and goes on, with wrong but harmless code
+    fn limit(&mut self, kind: CompileErrorKind) {
+        if self.error.is_none() {
+            self.error = Some(self.fail(kind));
+        }
+    }
+
+    // Reading tokens, as `llex.c`
+
+    /// `luaX_next`: reads the next token
+    fn read(&mut self) {
+        if let Some(token) = self.tokens.get(self.next) {
+            self.next += 1;
+            self.line = match mem::take(&mut self.lookahead) {
+                true => self.tokens.get(self.next).unwrap_or(token).line,
+                false => token.line,
+            };
+        }
+    }
+
+    /// Reads the tokens up to the one that a span starts
+    fn mark<T: ?Sized + ByteLen>(&mut self, span: &T) {
+        let offset = (span.as_bytes().as_ptr() as usize).wrapping_sub(self.start);
+        let index = self.tokens.partition_point(|t| t.offset < offset);
+        if index < self.tokens.len() && index >= self.next {
+            self.next = index;
+            self.read();
+        }
+    }
+
+    /// `checknext`: reads the tokens up to one of a kind
+    fn skip(&mut self, kind: TokenKind) {
+        while let Some(token) = self.tokens.get(self.next) {
+            let found = token.kind == kind;
+            self.read();
+            if found {
+                break;
+            }
+        }
+    }
+
+    fn skip_keyword(&mut self, keyword: Keyword) {
+        self.skip(TokenKind::Keyword(keyword));
+    }
+
+    fn skip_symbol(&mut self, symbol: Symbol) {
+        self.skip(TokenKind::Symbol(symbol));
+    }
+
+    /// `testnext`: reads the next token if it is the symbol
+    fn eat(&mut self, symbol: Symbol) -> bool {
+        let found = self.peek() == Some(TokenKind::Symbol(symbol));
+        if found {
+            self.read();
+        }
+        found
+    }
+
+    fn peek(&self) -> Option<TokenKind> {
+        self.tokens.get(self.next).map(|t| t.kind)
+    }
+
+    /// `linenumber`: the line of the next token
+    fn peek_line(&self) -> u32 {
+        self.tokens.get(self.next).map_or(self.line, |t| t.line)
+    }
+
+    // Code generation, as `lcode.c`
+
+    fn pc(&mut self) -> usize {
+        self.fs().proto.code.len()
+    }
+
+    fn code(&mut self, instruction: Instruction) -> usize {
+        self.discharge_jpc();
+        let line = self.line;
+        let proto = &mut self.fs().proto;
+        proto.code.push(instruction);
+        proto.line_info.push(line);
+        proto.code.len() - 1
+    }
+
+    fn code_abc(&mut self, op: OpCode, a: u32, b: u32, c: u32) -> usize {
+        self.code(Instruction::abc(op, a, b, c))
+    }
+
+    fn code_abx(&mut self, op: OpCode, a: u32, bx: u32) -> usize {
+        self.code(Instruction::abx(op, a, bx))
+    }
+
+    fn code_asbx(&mut self, op: OpCode, a: u32, sbx: i32) -> usize {
+        self.code(Instruction::asbx(op, a, sbx))
+    }
+
+    fn fix_line(&mut self, line: u32) {
+        if let Some(last) = self.fs().proto.line_info.last_mut() {
+            *last = line;
+        }
+    }
+
+    fn nil(&mut self, from: u32, n: u32) {
+        let fs = self.fs();
+        let pc = fs.proto.code.len();
+        // No jumps to the current position?
+        if fs.last_target < Some(pc) {
+            if pc == 0 {
+                // The registers of a new function are already nil
+                if from as usize >= fs.nactvar {
+                    return;
+                }
+            } else {
+                let previous = &mut fs.proto.code[pc - 1];
+                if previous.opcode() == Some(OpCode::LoadNil) {
+                    let (pfrom, pto) = (previous.a(), previous.b());
+                    if pfrom <= from && from <= pto + 1 {
+                        if from + n - 1 > pto {
+                            previous.set_b(from + n - 1);
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+        self.code_abc(OpCode::LoadNil, from, from + n - 1, 0);
+    }
+
+    fn jump(&mut self) -> usize {
+        let jpc = mem::replace(&mut self.fs().jpc, NO_JUMP);
+        let mut j = self.code_asbx(OpCode::Jmp, 0, -1);
+        self.concat(&mut j, jpc);
+        j
+    }
+
+    fn ret(&mut self, first: u32, nret: i32) {
+        self.code_abc(OpCode::Return, first, (nret + 1) as u32, 0);
+    }
+
+    fn cond_jump(&mut self, op: OpCode, a: u32, b: u32, c: u32) -> usize {
+        self.code_abc(op, a, b, c);
+        self.jump()
+    }
+
+    fn fix_jump(&mut self, pc: usize, dest: usize) {
+        let offset = dest as i64 - (pc as i64 + 1);
+        if offset.abs() > i64::from(Instruction::MAX_SBX) {
+            self.limit(CompileErrorKind::TooLong);
+            return;
+        }
+        self.fs().proto.code[pc].set_sbx(offset as i32);
+    }
+
+    /// Marks the next instruction as a jump target
+    fn get_label(&mut self) -> usize {
+        let fs = self.fs();
+        let pc = fs.proto.code.len();
+        fs.last_target = Some(pc);
+        pc
+    }
+
+    /// The next jump of a list
+    fn get_jump(&mut self, pc: usize) -> usize {
+        match self.fs().proto.code[pc].sbx() {
+            -1 => NO_JUMP,
+            offset => (pc as i64 + 1 + i64::from(offset)) as usize,
+        }
+    }
+
+    /// The test that controls a jump, or the jump itself
+    fn jump_control(&mut self, pc: usize) -> usize {
+        let code = &self.fs().proto.code;
+        if pc >= 1 && code[pc - 1].opcode().is_some_and(OpCode::is_test) {
+            pc - 1
+        } else {
+            pc
+        }
+    }
+
+    /// Whether a jump list has jumps that don't produce a value
+    fn need_value(&mut self, mut list: usize) -> bool {
+        while list != NO_JUMP {
+            let control = self.jump_control(list);
+            if self.fs().proto.code[control].opcode() != Some(OpCode::TestSet) {
+                return true;
+            }
+            list = self.get_jump(list);
+        }
+        false
+    }
+
+    /// Sets the destination register of a `TESTSET`, or turns it into a `TEST`
+    fn patch_test_reg(&mut self, node: usize, reg: u32) -> bool {
+        let control = self.jump_control(node);
+        let i = &mut self.fs().proto.code[control];
+        if i.opcode() != Some(OpCode::TestSet) {
+            return false;
+        }
+        if reg != NO_REG && reg != i.b() {
+            i.set_a(reg);
+        } else {
+            *i = Instruction::abc(OpCode::Test, i.b(), 0, i.c());
+        }
+        true
+    }
+
+    fn remove_values(&mut self, mut list: usize) {
+        while list != NO_JUMP {
+            self.patch_test_reg(list, NO_REG);
+            list = self.get_jump(list);
+        }
+    }
+
+    fn patch_list_aux(&mut self, mut list: usize, vtarget: usize, reg: u32, dtarget: usize) {
+        while list != NO_JUMP {
+            let next = self.get_jump(list);
+            if self.patch_test_reg(list, reg) {
+                self.fix_jump(list, vtarget);
+            } else {
+                self.fix_jump(list, dtarget);
+            }
+            list = next;
+        }
+    }
+
+    fn discharge_jpc(&mut self) {
+        let pc = self.pc();
+        let jpc = mem::replace(&mut self.fs().jpc, NO_JUMP);
+        self.patch_list_aux(jpc, pc, NO_REG, pc);
+    }
+
+    fn patch_list(&mut self, list: usize, target: usize) {
+        if target == self.pc() {
+            self.patch_to_here(list);
+        } else {
+            self.patch_list_aux(list, target, NO_REG, target);
+        }
+    }
+
+    fn patch_to_here(&mut self, list: usize) {
+        self.get_label();
+        let mut jpc = self.fs().jpc;
+        self.concat(&mut jpc, list);
+        self.fs().jpc = jpc;
+    }
+
+    fn concat(&mut self, l1: &mut usize, l2: usize) {
+        if l2 == NO_JUMP {
+            return;
+        }
+        if *l1 == NO_JUMP {
+            *l1 = l2;
+            return;
+        }
+        let mut list = *l1;
+        loop {
+            let next = self.get_jump(list);
+            if next == NO_JUMP {
+                break;
+            }
+            list = next;
+        }
+        self.fix_jump(list, l2);
+    }
+
+    fn check_stack(&mut self, n: u32) {
+        let new_stack = self.fs().free_reg + n;
+        if new_stack > u32::from(self.fs().proto.max_stack_size) {
+            if new_stack >= MAX_STACK {
+                self.limit(CompileErrorKind::TooComplex);
+            }
+            self.fs().proto.max_stack_size = new_stack.min(MAX_STACK) as u8;
+        }
+    }
+
+    fn reserve_regs(&mut self, n: u32) {
+        self.check_stack(n);
+        self.fs().free_reg += n;
+    }
+
+    fn free_reg(&mut self, reg: u32) {
+        let fs = self.fs();
+        if reg & Instruction::CONSTANT == 0 && reg as usize >= fs.nactvar {
+            fs.free_reg = fs.free_reg.saturating_sub(1);
+        }
+    }
+
+    fn free_exp(&mut self, e: &Exp) {
+        if let Kind::NonReloc(reg) = e.kind {
+            self.free_reg(reg);
+        }
+    }
+
+    fn push_constant(&mut self, constant: Constant) -> u32 {
+        let constants = &mut self.fs().proto.constants;
+        constants.push(constant);
+        let index = constants.len() as u32 - 1;
+        if index > Instruction::MAX_BX {
+            self.limit(CompileErrorKind::TooManyConstants);
+        }
+        index
+    }
+
+    fn string_k(&mut self, s: &[u8]) -> u32 {
+        if let Some(&index) = self.fs().strings.get(s) {
+            return index;
+        }
+        let index = self.push_constant(Constant::String(s.to_vec()));
+        self.fs().strings.insert(s.to_vec(), index);
+        index
+    }
+
+    fn number_k(&mut self, n: f64) -> u32 {
+        let key = if n == 0.0 { 0 } else { n.to_bits() };
+        if let Some(&index) = self.fs().numbers.get(&key) {
+            return index;
+        }
+        let index = self.push_constant(Constant::Number(n));
+        self.fs().numbers.insert(key, index);
+        index
+    }
+
+    fn singleton_k(&mut self, slot: usize, constant: Constant) -> u32 {
+        if let Some(index) = self.fs().singletons[slot] {
+            return index;
+        }
+        let index = self.push_constant(constant);
+        self.fs().singletons[slot] = Some(index);
+        index
+    }
+
+    fn nil_k(&mut self) -> u32 {
+        self.singleton_k(0, Constant::Nil)
+    }
+
+    fn bool_k(&mut self, b: bool) -> u32 {
+        self.singleton_k(1 + usize::from(b), Constant::Bool(b))
+    }
+
+    fn set_returns(&mut self, e: &mut Exp, nresults: i32) {
+        match e.kind {
+            Kind::Call(pc) => self.fs().proto.code[pc].set_c((nresults + 1) as u32),
+            Kind::Vararg(pc) => {
+                let fs = self.fs();
+                let free = fs.free_reg;
+                let i = &mut fs.proto.code[pc];
+                i.set_b((nresults + 1) as u32);
+                i.set_a(free);
+                self.reserve_regs(1);
+            }
+            _ => {}
+        }
+    }
+
+    fn set_one_ret(&mut self, e: &mut Exp) {
+        match e.kind {
+            Kind::Call(pc) => e.kind = Kind::NonReloc(self.fs().proto.code[pc].a()),
+            Kind::Vararg(pc) => {
+                self.fs().proto.code[pc].set_b(2);
+                e.kind = Kind::Relocable(pc);
+            }
+            _ => {}
+        }
+    }
+
+    fn discharge_vars(&mut self, e: &mut Exp) {
+        match e.kind {
+            Kind::Local(reg) => e.kind = Kind::NonReloc(reg),
+            Kind::Upvalue(index) => {
+                e.kind = Kind::Relocable(self.code_abc(OpCode::GetUpval, 0, index, 0));
+            }
+            Kind::Global(name) => {
+                e.kind = Kind::Relocable(self.code_abx(OpCode::GetGlobal, 0, name));
+            }
+            Kind::Indexed(table, key) => {
+                self.free_reg(key);
+                self.free_reg(table);
+                e.kind = Kind::Relocable(self.code_abc(OpCode::GetTable, 0, table, key));
+            }
+            Kind::Call(_) | Kind::Vararg(_) => self.set_one_ret(e),
+            _ => {}
+        }
+    }
+
+    fn code_label(&mut self, a: u32, b: u32, jump: u32) -> usize {
+        self.get_label();
+        self.code_abc(OpCode::LoadBool, a, b, jump)
+    }
+
+    fn discharge_to_reg(&mut self, e: &mut Exp, reg: u32) {
+        self.discharge_vars(e);
+        match e.kind {
+            Kind::Nil => self.nil(reg, 1),
+            Kind::False | Kind::True => {
+                let b = u32::from(e.kind == Kind::True);
+                self.code_abc(OpCode::LoadBool, reg, b, 0);
+            }
+            Kind::K(k) => {
+                self.code_abx(OpCode::LoadK, reg, k);
+            }
+            Kind::Number(n) => {
+                let k = self.number_k(n);
+                self.code_abx(OpCode::LoadK, reg, k);
+            }
+            Kind::Relocable(pc) => self.fs().proto.code[pc].set_a(reg),
+            Kind::NonReloc(from) => {
+                if from != reg {
+                    self.code_abc(OpCode::Move, reg, from, 0);
+                }
+            }
+            _ => return,
+        }
+        e.kind = Kind::NonReloc(reg);
+    }
+
+    fn discharge_to_any_reg(&mut self, e: &mut Exp) {
+        if !matches!(e.kind, Kind::NonReloc(_)) {
+            self.reserve_regs(1);
+            let reg = self.fs().free_reg - 1;
+            self.discharge_to_reg(e, reg);
+        }
+    }
+
+    fn exp_to_reg(&mut self, e: &mut Exp, reg: u32) {
+        self.discharge_to_reg(e, reg);
+        if let Kind::Jump(pc) = e.kind {
+            self.concat(&mut e.t, pc);
+        }
+        if e.has_jumps() {
+            // Positions of the `LOADBOOL`s for tests that don't set a value
+            let mut p_f = NO_JUMP;
+            let mut p_t = NO_JUMP;
+            if self.need_value(e.t) || self.need_value(e.f) {
+                let fj = if matches!(e.kind, Kind::Jump(_)) {
+                    NO_JUMP
+                } else {
+                    self.jump()
+                };
+                p_f = self.code_label(reg, 0, 1);
+                p_t = self.code_label(reg, 1, 0);
+                self.patch_to_here(fj);
+            }
+            let end = self.get_label();
+            self.patch_list_aux(e.f, end, reg, p_f);
+            self.patch_list_aux(e.t, end, reg, p_t);
+        }
+        e.t = NO_JUMP;
+        e.f = NO_JUMP;
+        e.kind = Kind::NonReloc(reg);
+    }
+
+    fn exp_to_next_reg(&mut self, e: &mut Exp) {
+        self.discharge_vars(e);
+        self.free_exp(e);
+        self.reserve_regs(1);
+        let reg = self.fs().free_reg - 1;
+        self.exp_to_reg(e, reg);
+    }
+
+    fn exp_to_any_reg(&mut self, e: &mut Exp) -> u32 {
+        self.discharge_vars(e);
+        if let Kind::NonReloc(reg) = e.kind {
+            if !e.has_jumps() {
+                return reg;
+            }
+            // Not a local?
+            if reg as usize >= self.fs().nactvar {
+                self.exp_to_reg(e, reg);
+                return reg;
+            }
+        }
+        self.exp_to_next_reg(e);
+        e.reg()
+    }
+
+    fn exp_to_val(&mut self, e: &mut Exp) {
+        if e.has_jumps() {
+            self.exp_to_any_reg(e);
+        } else {
+            self.discharge_vars(e);
+        }
+    }
+
+    fn exp_to_rk(&mut self, e: &mut Exp) -> u32 {
+        self.exp_to_val(e);
+        match e.kind {
+            Kind::Number(_) | Kind::True | Kind::False | Kind::Nil
+                if self.fs().proto.constants.len() <= MAX_INDEX_RK as usize =>
+            {
+                let k = match e.kind {
+                    Kind::Nil => self.nil_k(),
+                    Kind::Number(n) => self.number_k(n),
+                    kind => self.bool_k(kind == Kind::True),
+                };
+                e.kind = Kind::K(k);
+                return k | Instruction::CONSTANT;
+            }
+            Kind::K(k) if k <= MAX_INDEX_RK => return k | Instruction::CONSTANT,
+            _ => {}
+        }
+        self.exp_to_any_reg(e)
+    }
+
+    fn store_var(&mut self, var: &Exp, ex: &mut Exp) {
+        match var.kind {
+            Kind::Local(reg) => {
+                self.free_exp(ex);
+                self.exp_to_reg(ex, reg);
+                return;
+            }
+            Kind::Upvalue(index) => {
+                let e = self.exp_to_any_reg(ex);
+                self.code_abc(OpCode::SetUpval, e, index, 0);
+            }
+            Kind::Global(name) => {
+                let e = self.exp_to_any_reg(ex);
+                self.code_abx(OpCode::SetGlobal, e, name);
+            }
+            Kind::Indexed(table, key) => {
+                let e = self.exp_to_rk(ex);
+                self.code_abc(OpCode::SetTable, table, key, e);
+            }
+            kind => unreachable!("{:?} is not a variable", kind),
+        }
+        self.free_exp(ex);
+    }
+
+    /// `luaK_self`: `e:key`
+    fn method_self(&mut self, e: &mut Exp, key: &mut Exp) {
+        let object = self.exp_to_any_reg(e);
+        self.free_exp(e);
+        let func = self.fs().free_reg;
+        self.reserve_regs(2);
+        let key_rk = self.exp_to_rk(key);
+        self.code_abc(OpCode::SelfOp, func, object, key_rk);
+        self.free_exp(key);
+        e.kind = Kind::NonReloc(func);
+    }
+
+    fn invert_jump(&mut self, e: &Exp) {
+        if let Kind::Jump(pc) = e.kind {
+            let control = self.jump_control(pc);
+            let i = &mut self.fs().proto.code[control];
+            i.set_a(u32::from(i.a() == 0));
+        }
+    }
+
+    fn jump_on_cond(&mut self, e: &mut Exp, cond: bool) -> usize {
+        if let Kind::Relocable(pc) = e.kind {
+            let i = self.fs().proto.code[pc];
+            if i.opcode() == Some(OpCode::Not) {
+                // Remove the `NOT` and test its operand instead
+                let proto = &mut self.fs().proto;
+                proto.code.pop();
+                proto.line_info.pop();
+                return self.cond_jump(OpCode::Test, i.b(), 0, u32::from(!cond));
+            }
+        }
+        self.discharge_to_any_reg(e);
+        self.free_exp(e);
+        self.cond_jump(OpCode::TestSet, NO_REG, e.reg(), u32::from(cond))
+    }
+
+    fn go_if_true(&mut self, e: &mut Exp) {
+        self.discharge_vars(e);
+        let pc = match e.kind {
+            Kind::K(_) | Kind::Number(_) | Kind::True => NO_JUMP,
+            Kind::False => self.jump(),
+            Kind::Jump(pc) => {
+                self.invert_jump(e);
+                pc
+            }
+            _ => self.jump_on_cond(e, false),
+        };
+        self.concat(&mut e.f, pc);
+        self.patch_to_here(e.t);
+        e.t = NO_JUMP;
+    }
+
+    fn go_if_false(&mut self, e: &mut Exp) {
+        self.discharge_vars(e);
+        let pc = match e.kind {
+            Kind::Nil | Kind::False => NO_JUMP,
+            Kind::True => self.jump(),
+            Kind::Jump(pc) => pc,
+            _ => self.jump_on_cond(e, true),
+        };
+        self.concat(&mut e.t, pc);
+        self.patch_to_here(e.f);
+        e.f = NO_JUMP;
+    }
+
+    fn code_not(&mut self, e: &mut Exp) {
+        self.discharge_vars(e);
+        match e.kind {
+            Kind::Nil | Kind::False => e.kind = Kind::True,
+            Kind::K(_) | Kind::Number(_) | Kind::True => e.kind = Kind::False,
+            Kind::Jump(_) => self.invert_jump(e),
+            Kind::Relocable(_) | Kind::NonReloc(_) => {
+                self.discharge_to_any_reg(e);
+                self.free_exp(e);
+                e.kind = Kind::Relocable(self.code_abc(OpCode::Not, 0, e.reg(), 0));
+            }
+            kind => unreachable!("{:?} has no value", kind),
+        }
+        mem::swap(&mut e.t, &mut e.f);
+        self.remove_values(e.f);
+        self.remove_values(e.t);
+    }
+
+    fn indexed(&mut self, t: &mut Exp, k: &mut Exp) {
+        let key = self.exp_to_rk(k);
+        t.kind = Kind::Indexed(t.reg(), key);
+    }
+
+    fn const_folding(op: OpCode, e1: &mut Exp, e2: &Exp) -> bool {
+        let (Kind::Number(v1), Kind::Number(v2)) = (e1.kind, e2.kind) else {
+            return false;
+        };
+        if !e1.is_numeral() || !e2.is_numeral() {
+            return false;
+        }
+        let r = match op {
+            OpCode::Add => v1 + v2,
+            OpCode::Sub => v1 - v2,
+            OpCode::Mul => v1 * v2,
+            OpCode::Div if v2 != 0.0 => v1 / v2,
+            OpCode::Mod if v2 != 0.0 => v1 - floor(v1 / v2) * v2,
+            OpCode::Pow => match pow(v1, v2) {
+                Some(r) => r,
+                None => return false,
+            },
+            OpCode::Unm => -v1,
+            _ => return false,
+        };
+        if r.is_nan() {
+            return false;
+        }
+        e1.kind = Kind::Number(r);
+        true
+    }
+
+    fn code_arith(&mut self, op: OpCode, e1: &mut Exp, e2: &mut Exp) {
+        if Self::const_folding(op, e1, e2) {
+            return;
+        }
+        let o2 = if op != OpCode::Unm && op != OpCode::Len {
+            self.exp_to_rk(e2)
+        } else {
+            0
+        };
+        let o1 = self.exp_to_rk(e1);
+        if o1 > o2 {
+            self.free_exp(e1);
+            self.free_exp(e2);
+        } else {
+            self.free_exp(e2);
+            self.free_exp(e1);
+        }
+        e1.kind = Kind::Relocable(self.code_abc(op, 0, o1, o2));
+    }
+
+    fn code_comp(&mut self, op: OpCode, mut cond: bool, e1: &mut Exp, e2: &mut Exp) {
+        let mut o1 = self.exp_to_rk(e1);
+        let mut o2 = self.exp_to_rk(e2);
+        self.free_exp(e2);
+        self.free_exp(e1);
+        if !cond && op != OpCode::Eq {
+            // Exchange the operands to replace `>` and `>=` by `<` and `<=`
+            mem::swap(&mut o1, &mut o2);
+            cond = true;
+        }
+        e1.kind = Kind::Jump(self.cond_jump(op, u32::from(cond), o1, o2));
+    }
+
+    fn prefix(&mut self, op: UnOp, e: &mut Exp) {
+        let mut e2 = Exp::new(Kind::Number(0.0));
+        match op {
+            UnOp::Neg => {
+                if !e.is_numeral() {
+                    self.exp_to_any_reg(e);
+                }
+                self.code_arith(OpCode::Unm, e, &mut e2);
+            }
+            UnOp::Not => self.code_not(e),
+            UnOp::Len => {
+                self.exp_to_any_reg(e);
+                self.code_arith(OpCode::Len, e, &mut e2);
+            }
+            UnOp::BNot => unreachable!("bitwise operators are rejected before"),
+        }
+    }
+
+    fn infix(&mut self, op: BinOp, v: &mut Exp) {
+        match op {
+            BinOp::And => self.go_if_true(v),
+            BinOp::Or => self.go_if_false(v),
+            BinOp::Concat => self.exp_to_next_reg(v),
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod | BinOp::Pow => {
+                if !v.is_numeral() {
+                    self.exp_to_rk(v);
+                }
+            }
+            _ => {
+                self.exp_to_rk(v);
+            }
+        }
+    }
+
+    fn posfix(&mut self, op: BinOp, e1: &mut Exp, e2: &mut Exp) {
+        match op {
+            BinOp::And => {
+                self.discharge_vars(e2);
+                self.concat(&mut e2.f, e1.f);
+                *e1 = *e2;
+            }
+            BinOp::Or => {
+                self.discharge_vars(e2);
+                self.concat(&mut e2.t, e1.t);
+                *e1 = *e2;
+            }
+            BinOp::Concat => {
+                self.exp_to_val(e2);
+                match e2.kind {
+                    Kind::Relocable(pc)
+                        if self.fs().proto.code[pc].opcode() == Some(OpCode::Concat) =>
+                    {
+                        // Extend the `CONCAT` of the right operand
+                        self.free_exp(e1);
+                        let first = e1.reg();
+                        self.fs().proto.code[pc].set_b(first);
+                        e1.kind = Kind::Relocable(pc);
+                    }
+                    _ => {
+                        self.exp_to_next_reg(e2);
+                        self.code_arith(OpCode::Concat, e1, e2);
+                    }
+                }
+            }
+            BinOp::Add => self.code_arith(OpCode::Add, e1, e2),
+            BinOp::Sub => self.code_arith(OpCode::Sub, e1, e2),
+            BinOp::Mul => self.code_arith(OpCode::Mul, e1, e2),
+            BinOp::Div => self.code_arith(OpCode::Div, e1, e2),
+            BinOp::Mod => self.code_arith(OpCode::Mod, e1, e2),
+            BinOp::Pow => self.code_arith(OpCode::Pow, e1, e2),
+            BinOp::Eq => self.code_comp(OpCode::Eq, true, e1, e2),
+            BinOp::NotEq => self.code_comp(OpCode::Eq, false, e1, e2),
+            BinOp::Lt => self.code_comp(OpCode::Lt, true, e1, e2),
+            BinOp::LtEq => self.code_comp(OpCode::Le, true, e1, e2),
+            BinOp::Gt => self.code_comp(OpCode::Lt, false, e1, e2),
+            BinOp::GtEq => self.code_comp(OpCode::Le, false, e1, e2),
+            _ => unreachable!("{:?} is rejected before", op),
+        }
+    }
+
+    fn set_list(&mut self, base: u32, nelems: usize, to_store: i32) {
+        let c = (nelems - 1) / FIELDS_PER_FLUSH + 1;
+        let b = if to_store == MULTRET {
+            0
+        } else {
+            to_store as u32
+        };
+        if c <= Instruction::MAX_C as usize {
+            self.code_abc(OpCode::SetList, base, b, c as u32);
+        } else {
+            // The block number follows in the next instruction
+            self.code_abc(OpCode::SetList, base, b, 0);
+            self.code(Instruction(c as u32));
+        }
+        self.fs().free_reg = base + 1;
+    }
+
+    // Functions, blocks and variables, as `lparser.c`
+
+    fn open_func(&mut self, source: Option<Vec<u8>>, line: u32) {
+        self.funcs.push(Func {
+            proto: Proto::new(source, line),
+            strings: BTreeMap::new(),
+            numbers: BTreeMap::new(),
+            singletons: [None; 3],
+            blocks: Vec::new(),
+            last_target: None,
+            jpc: NO_JUMP,
+            free_reg: 0,
+            nactvar: 0,
+            actvar: Vec::new(),
+            upvalues: Vec::new(),
+        });
+    }
+
+    fn close_func(&mut self) -> (Proto, Vec<Upvalue>) {
+        self.remove_locals(0);
+        self.ret(0, 0);
+        let fs = self.funcs.pop().expect("a function is open");
+        let mut proto = fs.proto;
+        proto.num_upvalues = proto.upvalues.len().min(usize::from(u8::MAX)) as u8;
+        (proto, fs.upvalues)
+    }
+
+    /// `new_localvar`: declares the `n`th of the next locals
+    fn new_local(&mut self, name: &[u8], n: usize) {
+        if self.fs().nactvar + n + 1 > MAX_VARS {
+            self.limit(CompileErrorKind::TooManyLocals);
+        }
+        let fs = self.fs();
+        fs.proto.locals.push(LocalVar {
+            name: name.to_vec(),
+            start_pc: 0,
+            end_pc: 0,
+        });
+        fs.actvar.truncate(fs.nactvar + n);
+        fs.actvar.push(fs.proto.locals.len() - 1);
+    }
+
+    /// `adjustlocalvars`: activates the next locals
+    fn adjust_locals(&mut self, nvars: usize) {
+        let fs = self.fs();
+        let pc = fs.proto.code.len() as u32;
+        fs.nactvar += nvars;
+        for &index in &fs.actvar[fs.nactvar - nvars..fs.nactvar] {
+            fs.proto.locals[index].start_pc = pc;
+        }
+    }
+
+    fn remove_locals(&mut self, to_level: usize) {
+        let fs = self.fs();
+        let pc = fs.proto.code.len() as u32;
+        while fs.nactvar > to_level {
+            fs.nactvar -= 1;
+            fs.proto.locals[fs.actvar[fs.nactvar]].end_pc = pc;
+        }
+    }
+
+    fn enter_block(&mut self, breakable: bool) {
+        let fs = self.fs();
+        let nactvar = fs.nactvar;
+        fs.blocks.push(Block {
+            break_list: NO_JUMP,
+            nactvar,
+            upval: false,
+            breakable,
+        });
+    }
+
+    fn leave_block(&mut self) {
+        let block = self.fs().blocks.pop().expect("a block is open");
+        self.remove_locals(block.nactvar);
+        if block.upval {
+            self.code_abc(OpCode::Close, block.nactvar as u32, 0, 0);
+        }
+        let fs = self.fs();
+        fs.free_reg = fs.nactvar as u32;
+        self.patch_to_here(block.break_list);
+    }
+
+    /// `singlevaraux`: finds a name in a function and the enclosing ones
+    fn resolve(&mut self, level: usize, name: &[u8], base: bool) -> Resolved {
+        let fs = &mut self.funcs[level];
+        let found = (0..fs.nactvar)
+            .rev()
+            .find(|&i| fs.proto.locals[fs.actvar[i]].name == name);
+        if let Some(v) = found {
+            if !base {
+                // The block of the local must close its upvalue
+                if let Some(block) = fs.blocks.iter_mut().rev().find(|b| b.nactvar <= v) {
+                    block.upval = true;
+                }
+            }
+            return Resolved::Local(v as u32);
+        }
+        if level == 0 {
+            return Resolved::Global;
+        }
+        let upvalue = match self.resolve(level - 1, name, false) {
+            Resolved::Global => return Resolved::Global,
+            Resolved::Local(reg) => Upvalue::Local(reg),
+            Resolved::Upvalue(index) => Upvalue::Upvalue(index),
+        };
+        let fs = &mut self.funcs[level];
+        if let Some(index) = fs.upvalues.iter().position(|&u| u == upvalue) {
+            return Resolved::Upvalue(index as u32);
+        }
+        fs.upvalues.push(upvalue);
+        fs.proto.upvalues.push(name.to_vec());
+        let index = fs.upvalues.len() - 1;
+        if index >= MAX_UPVALUES {
+            self.limit(CompileErrorKind::TooManyUpvalues);
+        }
+        Resolved::Upvalue(index as u32)
+    }
+
+    fn single_var<T: ?Sized + ByteLen>(&mut self, name: &T) -> Exp {
+        self.mark(name);
+        let name = name.as_bytes();
+        let level = self.funcs.len() - 1;
+        Exp::new(match self.resolve(level, name, true) {
+            Resolved::Global => Kind::Global(self.string_k(name)),
+            Resolved::Local(reg) => Kind::Local(reg),
+            Resolved::Upvalue(index) => Kind::Upvalue(index),
+        })
+    }
+
+    /// `field`: `v.name`
+    fn field<T: ?Sized + ByteLen>(&mut self, v: &mut Exp, name: &T) {
+        self.exp_to_any_reg(v);
+        // The `.` or `:`
+        self.read();
+        self.mark(name);
+        let mut key = Exp::new(Kind::K(self.string_k(name.as_bytes())));
+        self.indexed(v, &mut key);
+    }
+
+    fn adjust_assign(&mut self, nvars: usize, nexps: usize, e: &mut Exp) {
+        let mut extra = nvars as i32 - nexps as i32;
+        if e.has_multret() {
+            // The call provides the missing values
+            extra = (extra + 1).max(0);
+            self.set_returns(e, extra);
+            if extra > 1 {
+                self.reserve_regs(extra as u32 - 1);
+            }
+        } else {
+            if e.kind != Kind::Void {
+                self.exp_to_next_reg(e);
+            }
+            if extra > 0 {
+                let reg = self.fs().free_reg;
+                self.reserve_regs(extra as u32);
+                self.nil(reg, extra as u32);
+            }
+        }
+    }
+
+    /// `explist1`: all values but the last one in the next registers
+    fn expr_list<T: ?Sized + ByteLen>(&mut self, exprs: &[Expr<&T>]) -> Result<Exp, CompileError> {
+        let Some((first, rest)) = exprs.split_first() else {
+            return Ok(Exp::new(Kind::Void));
+        };
+        let mut e = self.expr(first)?;
+        for expr in rest {
+            self.skip_symbol(Symbol::Comma);
+            self.exp_to_next_reg(&mut e);
+            e = self.expr(expr)?;
+        }
+        Ok(e)
+    }
+
+    // Expressions
+
+    fn expr<T: ?Sized + ByteLen>(&mut self, expr: &Expr<&T>) -> Result<Exp, CompileError> {
+        let kind = match expr {
+            Expr::Nil => {
+                self.read();
+                Kind::Nil
+            }
+            Expr::Bool(value) => {
+                self.read();
+                match value {
+                    true => Kind::True,
+                    false => Kind::False,
+                }
+            }
+            Expr::Number(span, n) => {
+                self.mark(*span);
+                Kind::Number(*n)
+            }
+            Expr::Integer(span, _) | Expr::Cdata(span, _) => {
+                self.mark(*span);
+                return Err(self.fail(CompileErrorKind::Unsupported("integers")));
+            }
+            Expr::String(span) => {
+                self.mark(*span);
+                let value: Vec<u8> = Unescape::new(span.as_bytes())
+                    .map(Iterator::collect)
+                    .unwrap_or_default();
+                Kind::K(self.string_k(&value))
+            }
+            Expr::Interp(_) => {
+                return Err(self.fail(CompileErrorKind::Unsupported("string interpolation")))
+            }
+            Expr::If(..) => {
+                return Err(self.fail(CompileErrorKind::Unsupported("if-then-else expressions")))
+            }
+            Expr::Vararg => {
+                let proto = &mut self.fs().proto;
+                if proto.is_vararg == 0 {
+                    return Err(self.fail(CompileErrorKind::VarargOutsideVararg));
+                }
+                proto.is_vararg &= !Proto::VARARG_NEEDSARG;
+                // Before the `...` is read
+                let pc = self.code_abc(OpCode::Vararg, 0, 1, 0);
+                self.read();
+                Kind::Vararg(pc)
+            }
+            Expr::Function(body) => {
+                self.read();
+                let line = self.peek_line();
+                return self.body(body, false, line);
+            }
+            Expr::Table(fields) => return self.constructor(fields),
+            Expr::Binary(lhs, op, rhs) => {
+                if matches!(
+                    op,
+                    BinOp::IDiv | BinOp::BAnd | BinOp::BOr | BinOp::BXor | BinOp::Shl | BinOp::Shr
+                ) {
+                    return Err(self.fail(CompileErrorKind::Unsupported("integer operators")));
+                }
+                let mut v = self.expr(lhs)?;
+                self.read();
+                self.infix(*op, &mut v);
+                let mut v2 = self.expr(rhs)?;
+                self.posfix(*op, &mut v, &mut v2);
+                return Ok(v);
+            }
+            Expr::Unary(op, operand) => {
+                if *op == UnOp::BNot {
+                    return Err(self.fail(CompileErrorKind::Unsupported("integer operators")));
+                }
+                self.read();
+                let mut v = self.expr(operand)?;
+                self.prefix(*op, &mut v);
+                return Ok(v);
+            }
+            Expr::Paren(inner) => {
+                // Truncates calls and `...` to one value
+                self.read();
+                let mut v = self.expr(inner)?;
+                self.skip_symbol(Symbol::ParenR);
+                self.discharge_vars(&mut v);
+                return Ok(v);
+            }
+            Expr::Var(var) => return self.var(var),
+            Expr::Call(call) => return self.call(call),
+        };
+        Ok(Exp::new(kind))
+    }
+
+    fn var<T: ?Sized + ByteLen>(&mut self, var: &Var<&T>) -> Result<Exp, CompileError> {
+        Ok(match var {
+            Var::Name(name) => self.single_var(*name),
+            Var::Index(prefix, key) => {
+                let mut v = self.expr(prefix)?;
+                self.exp_to_any_reg(&mut v);
+                self.read();
+                let mut k = self.expr(key)?;
+                self.exp_to_val(&mut k);
+                self.skip_symbol(Symbol::BracketR);
+                self.indexed(&mut v, &mut k);
+                v
+            }
+            Var::Field(prefix, name) => {
+                let mut v = self.expr(prefix)?;
+                self.field(&mut v, *name);
+                v
+            }
+        })
+    }
+
+    /// `primaryexp` with `funcargs`
+    fn call<T: ?Sized + ByteLen>(&mut self, call: &Call<&T>) -> Result<Exp, CompileError> {
+        let mut f = self.expr(&call.prefix)?;
+        match call.method {
+            Some(name) => {
+                self.read();
+                self.mark(name);
+                let mut key = Exp::new(Kind::K(self.string_k(name.as_bytes())));
+                self.method_self(&mut f, &mut key);
+            }
+            None => self.exp_to_next_reg(&mut f),
+        }
+        // The line of the `(`, string or table
+        let line = self.peek_line();
+        let paren = self.eat(Symbol::ParenL);
+        let mut args = self.expr_list(&call.args)?;
+        self.set_returns(&mut args, MULTRET);
+        if paren {
+            self.skip_symbol(Symbol::ParenR);
+        }
+        let base = f.reg();
+        let nparams = if args.has_multret() {
+            MULTRET
+        } else {
+            if args.kind != Kind::Void {
+                self.exp_to_next_reg(&mut args);
+            }
+            (self.fs().free_reg - (base + 1)) as i32
+        };
+        let pc = self.code_abc(OpCode::Call, base, (nparams + 1) as u32, 2);
+        self.fix_line(line);
+        // The call leaves one result, unless changed
+        self.fs().free_reg = base + 1;
+        Ok(Exp::new(Kind::Call(pc)))
+    }
+
+    fn constructor<T: ?Sized + ByteLen>(
+        &mut self,
+        fields: &[TableField<&T>],
+    ) -> Result<Exp, CompileError> {
+        let pc = self.code_abc(OpCode::NewTable, 0, 0, 0);
+        let mut t = Exp::new(Kind::Relocable(pc));
+        self.exp_to_next_reg(&mut t);
+        self.read();
+        let table = t.reg();
+        // The array and hash items, and the pending list items
+        let (mut na, mut nh, mut to_store) = (0, 0, 0);
+        // The last list item
+        let mut v = Exp::new(Kind::Void);
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 && !self.eat(Symbol::Comma) {
+                self.eat(Symbol::Semicolon);
+            }
+            if v.kind != Kind::Void {
+                self.exp_to_next_reg(&mut v);
+                v = Exp::new(Kind::Void);
+                if to_store == FIELDS_PER_FLUSH {
+                    self.set_list(table, na, to_store as i32);
+                    to_store = 0;
+                }
+            }
+            let reg = self.fs().free_reg;
+            // A name is either a key or starts an item
+            self.lookahead = self.peek() == Some(TokenKind::Name);
+            let (mut key, value) = match field {
+                TableField::Item(value) => {
+                    v = self.expr(value)?;
+                    na += 1;
+                    to_store += 1;
+                    continue;
+                }
+                TableField::Named(name, value) => {
+                    self.mark(*name);
+                    (Exp::new(Kind::K(self.string_k(name.as_bytes()))), value)
+                }
+                TableField::Keyed(key, value) => {
+                    self.read();
+                    let mut key = self.expr(key)?;
+                    self.exp_to_val(&mut key);
+                    self.skip_symbol(Symbol::BracketR);
+                    (key, value)
+                }
+            };
+            nh += 1;
+            self.skip_symbol(Symbol::Assign);
+            let key = self.exp_to_rk(&mut key);
+            let mut value = self.expr(value)?;
+            let value = self.exp_to_rk(&mut value);
+            self.code_abc(OpCode::SetTable, table, key, value);
+            self.fs().free_reg = reg;
+        }
+        self.skip_symbol(Symbol::BraceR);
+        if to_store != 0 {
+            if v.has_multret() {
+                self.set_returns(&mut v, MULTRET);
+                self.set_list(table, na, MULTRET);
+                // The number of values is unknown
+                na -= 1;
+            } else {
+                if v.kind != Kind::Void {
+                    self.exp_to_next_reg(&mut v);
+                }
+                self.set_list(table, na, to_store as i32);
+            }
+        }
+        let i = &mut self.fs().proto.code[pc];
+        i.set_b(int_to_fb(na));
+        i.set_c(int_to_fb(nh));
+        Ok(t)
+    }
+
+    /// `body`: compiles a function and creates its closure
+    fn body<T: ?Sized + ByteLen>(
+        &mut self,
+        body: &FuncBody<&T>,
+        needself: bool,
+        line: u32,
+    ) -> Result<Exp, CompileError> {
+        self.open_func(None, line);
+        if needself {
+            self.new_local(b"self", 0);
+            self.adjust_locals(1);
+        }
+        let mut nparams = 0;
+        for param in &body.params {
+            self.mark(*param);
+            self.new_local(param.as_bytes(), nparams);
+            nparams += 1;
+        }
+        self.skip_symbol(Symbol::ParenR);
+        if body.vararg {
+            // Lua 5.0 compatibility: the extra arguments are also in `arg`
+            self.new_local(b"arg", nparams);
+            nparams += 1;
+            self.fs().proto.is_vararg =
+                Proto::VARARG_HASARG | Proto::VARARG_NEEDSARG | Proto::VARARG_ISVARARG;
+        }
+        self.adjust_locals(nparams);
+        let fs = self.fs();
+        let hasarg = usize::from(fs.proto.is_vararg & Proto::VARARG_HASARG);
+        fs.proto.num_params = (fs.nactvar - hasarg) as u8;
+        let nactvar = fs.nactvar as u32;
+        self.reserve_regs(nactvar);
+        self.chunk(&body.block)?;
+        self.fs().proto.last_line_defined = self.peek_line();
+        self.skip_keyword(Keyword::End);
+        let (proto, upvalues) = self.close_func();
+
+        let protos = &mut self.fs().proto.protos;
+        protos.push(proto);
+        let index = protos.len() as u32 - 1;
+        let pc = self.code_abx(OpCode::Closure, 0, index);
+        // Pseudo-instructions that tell the VM where to find the upvalues
+        for upvalue in upvalues {
+            match upvalue {
+                Upvalue::Local(reg) => self.code_abc(OpCode::Move, 0, reg, 0),
+                Upvalue::Upvalue(index) => self.code_abc(OpCode::GetUpval, 0, index, 0),
+            };
+        }
+        Ok(Exp::new(Kind::Relocable(pc)))
+    }
+
+    // Statements
+
+    fn chunk<T: ?Sized + ByteLen>(&mut self, chunk: &Chunk<&T>) -> Result<(), CompileError> {
+        for stmt in &chunk.stmts {
+            self.statement(stmt)?;
+            while self.eat(Symbol::Semicolon) {}
+            let fs = self.fs();
+            fs.free_reg = fs.nactvar as u32;
+        }
+        Ok(())
+    }
+
+    fn block<T: ?Sized + ByteLen>(&mut self, chunk: &Chunk<&T>) -> Result<(), CompileError> {
+        self.enter_block(false);
+        self.chunk(chunk)?;
+        self.leave_block();
+        Ok(())
+    }
+
+    /// Returns the jumps taken when the condition is false
+    fn cond<T: ?Sized + ByteLen>(&mut self, expr: &Expr<&T>) -> Result<usize, CompileError> {
+        let mut v = self.expr(expr)?;
+        // `nil` is false in conditions
+        if v.kind == Kind::Nil {
+            v.kind = Kind::False;
+        }
+        self.go_if_true(&mut v);
+        Ok(v.f)
+    }
+
+    fn statement<T: ?Sized + ByteLen>(&mut self, stmt: &Stmt<&T>) -> Result<(), CompileError> {
+        // The line of the first token
+        let line = self.peek_line();
+        match stmt {
+            Stmt::Do(block) => {
+                self.read();
+                self.block(block)?;
+                self.skip_keyword(Keyword::End);
+                Ok(())
+            }
+            Stmt::While { cond, block } => self.while_stat(cond, block),
+            Stmt::Repeat { block, cond } => self.repeat_stat(block, cond),
+            Stmt::If(stat) => {
+                let mut escape = NO_JUMP;
+                let mut flist = NO_JUMP;
+                for (i, (cond, block)) in stat.clauses.iter().enumerate() {
+                    if i > 0 {
+                        let j = self.jump();
+                        self.concat(&mut escape, j);
+                        self.patch_to_here(flist);
+                    }
+                    // The `if` or `elseif`
+                    self.read();
+                    flist = self.cond(cond)?;
+                    self.skip_keyword(Keyword::Then);
+                    self.block(block)?;
+                }
+                match &stat.else_block {
+                    Some(block) => {
+                        let j = self.jump();
+                        self.concat(&mut escape, j);
+                        self.patch_to_here(flist);
+                        self.read();
+                        self.block(block)?;
+                    }
+                    None => self.concat(&mut escape, flist),
+                }
+                self.skip_keyword(Keyword::End);
+                self.patch_to_here(escape);
+                Ok(())
+            }
+            Stmt::NumericFor {
+                name,
+                start,
+                limit,
+                step,
+                block,
+            } => {
+                self.enter_block(true);
+                let base = self.fs().free_reg;
+                self.mark(*name);
+                self.new_local(b"(for index)", 0);
+                self.new_local(b"(for limit)", 1);
+                self.new_local(b"(for step)", 2);
+                self.new_local(name.as_bytes(), 3);
+                for expr in [Some(start), Some(limit), step.as_ref()]
+                    .into_iter()
+                    .flatten()
+                {
+                    // The `=` or `,`
+                    self.read();
+                    let mut e = self.expr(expr)?;
+                    self.exp_to_next_reg(&mut e);
+                }
+                if step.is_none() {
+                    let k = self.number_k(1.0);
+                    let reg = self.fs().free_reg;
+                    self.code_abx(OpCode::LoadK, reg, k);
+                    self.reserve_regs(1);
+                }
+                self.for_body(base, line, 1, true, block)?;
+                self.skip_keyword(Keyword::End);
+                self.leave_block();
+                Ok(())
+            }
+            Stmt::GenericFor {
+                names,
+                exprs,
+                block,
+            } => {
+                self.enter_block(true);
+                let base = self.fs().free_reg;
+                self.new_local(b"(for generator)", 0);
+                self.new_local(b"(for state)", 1);
+                self.new_local(b"(for control)", 2);
+                for (i, name) in names.iter().enumerate() {
+                    self.mark(*name);
+                    self.new_local(name.as_bytes(), 3 + i);
+                }
+                self.skip_keyword(Keyword::In);
+                let line = self.peek_line();
+                let mut e = self.expr_list(exprs)?;
+                self.adjust_assign(3, exprs.len(), &mut e);
+                // Space to call the generator
+                self.check_stack(3);
+                self.for_body(base, line, names.len(), false, block)?;
+                self.skip_keyword(Keyword::End);
+                self.leave_block();
+                Ok(())
+            }
+            Stmt::Function { name, body } => {
+                let mut v = self.single_var(name.path[0]);
+                for field in &name.path[1..] {
+                    self.field(&mut v, *field);
+                }
+                if let Some(method) = name.method {
+                    self.field(&mut v, method);
+                }
+                let mut b = self.body(body, name.method.is_some(), line)?;
+                self.store_var(&v, &mut b);
+                // The definition happens in the first line
+                self.fix_line(line);
+                Ok(())
+            }
+            Stmt::LocalFunction { name, body } => {
+                self.mark(*name);
+                self.new_local(name.as_bytes(), 0);
+                let v = Exp::new(Kind::Local(self.fs().free_reg));
+                self.reserve_regs(1);
+                self.adjust_locals(1);
+                let line = self.peek_line();
+                let mut b = self.body(body, false, line)?;
+                self.store_var(&v, &mut b);
+                // The debug information only sees the variable after this point
+                let fs = self.fs();
+                let pc = fs.proto.code.len() as u32;
+                fs.proto.locals[fs.actvar[fs.nactvar - 1]].start_pc = pc;
+                Ok(())
+            }
+            Stmt::Local {
+                names,
+                attribs,
+                exprs,
+            } => {
+                if let Some(attrib) = attribs.iter().flatten().next() {
+                    self.mark(*attrib);
+                    return Err(self.fail(CompileErrorKind::Unsupported("attributes")));
+                }
+                for (i, name) in names.iter().enumerate() {
+                    self.mark(*name);
+                    self.new_local(name.as_bytes(), i);
+                }
+                if !exprs.is_empty() {
+                    self.skip_symbol(Symbol::Assign);
+                }
+                let mut e = self.expr_list(exprs)?;
+                self.adjust_assign(names.len(), exprs.len(), &mut e);
+                self.adjust_locals(names.len());
+                Ok(())
+            }
+            Stmt::Assign { vars, exprs } => self.assignment(vars, exprs),
+            Stmt::Call(call) => {
+                if let Kind::Call(pc) = self.call(call)?.kind {
+                    // A call statement uses no results
+                    self.fs().proto.code[pc].set_c(1);
+                }
+                Ok(())
+            }
+            Stmt::Return(exprs) => {
+                self.read();
+                self.return_stat(exprs)
+            }
+            Stmt::Break => {
+                self.read();
+                self.break_stat()
+            }
+            Stmt::Goto(label) | Stmt::Label(label) => {
+                self.mark(*label);
+                Err(self.fail(CompileErrorKind::Unsupported("goto")))
+            }
+            Stmt::Continue => Err(self.fail(CompileErrorKind::Unsupported("continue"))),
+            Stmt::CompoundAssign { .. } => {
+                Err(self.fail(CompileErrorKind::Unsupported("compound assignments")))
+            }
+            Stmt::TypeAlias { name, .. } => {
+                self.mark(*name);
+                Err(self.fail(CompileErrorKind::Unsupported("type aliases")))
+            }
+        }
+    }
+
+    fn while_stat<T: ?Sized + ByteLen>(
+        &mut self,
+        cond: &Expr<&T>,
+        block: &Chunk<&T>,
+    ) -> Result<(), CompileError> {
+        self.read();
+        let init = self.get_label();
+        let exit = self.cond(cond)?;
+        self.enter_block(true);
+        self.skip_keyword(Keyword::Do);
+        self.block(block)?;
+        let j = self.jump();
+        self.patch_list(j, init);
+        self.skip_keyword(Keyword::End);
+        self.leave_block();
+        // False conditions finish the loop
+        self.patch_to_here(exit);
+        Ok(())
+    }
+
+    fn repeat_stat<T: ?Sized + ByteLen>(
+        &mut self,
+        block: &Chunk<&T>,
+        cond: &Expr<&T>,
+    ) -> Result<(), CompileError> {
+        let init = self.get_label();
+        // The loop block and the scope block
+        self.enter_block(true);
+        self.enter_block(false);
+        self.read();
+        self.chunk(block)?;
+        self.skip_keyword(Keyword::Until);
+        // The condition is inside the scope
+        let exit = self.cond(cond)?;
+        if !self.fs().blocks.last().is_some_and(|b| b.upval) {
+            self.leave_block();
+            self.patch_list(exit, init);
+        } else {
+            // Close the upvalues of each iteration before repeating
+            self.break_stat()?;
+            self.patch_to_here(exit);
+            self.leave_block();
+            let j = self.jump();
+            self.patch_list(j, init);
+        }
+        self.leave_block();
+        Ok(())
+    }
+
+    fn for_body<T: ?Sized + ByteLen>(
+        &mut self,
+        base: u32,
+        line: u32,
+        nvars: usize,
+        numeric: bool,
+        block: &Chunk<&T>,
+    ) -> Result<(), CompileError> {
+        // The control variables
+        self.adjust_locals(3);
+        self.skip_keyword(Keyword::Do);
+        let prep = if numeric {
+            self.code_asbx(OpCode::ForPrep, base, -1)
+        } else {
+            self.jump()
+        };
+        // The scope of the declared variables
+        self.enter_block(false);
+        self.adjust_locals(nvars);
+        self.reserve_regs(nvars as u32);
+        self.block(block)?;
+        self.leave_block();
+        self.patch_to_here(prep);
+        let end = if numeric {
+            self.code_asbx(OpCode::ForLoop, base, -1)
+        } else {
+            self.code_abc(OpCode::TForLoop, base, 0, nvars as u32)
+        };
+        // Pretend that the loop instruction starts the loop
+        self.fix_line(line);
+        let back = if numeric { end } else { self.jump() };
+        self.patch_list(back, prep + 1);
+        Ok(())
+    }
+
+    fn assignment<T: ?Sized + ByteLen>(
+        &mut self,
+        vars: &[Var<&T>],
+        exprs: &[Expr<&T>],
+    ) -> Result<(), CompileError> {
+        let mut targets: Vec<Exp> = Vec::with_capacity(vars.len());
+        for (i, var) in vars.iter().enumerate() {
+            if i > 0 {
+                self.skip_symbol(Symbol::Comma);
+            }
+            let v = self.var(var)?;
+            if let Kind::Local(local) = v.kind {
+                self.check_conflict(&mut targets, local);
+            }
+            targets.push(v);
+        }
+        self.skip_symbol(Symbol::Assign);
+        let mut e = self.expr_list(exprs)?;
+        let (nvars, nexps) = (vars.len(), exprs.len());
+        // The targets are assigned in reverse order, the last one may
+        // take the value directly
+        let mut rest = nvars;
+        if nexps != nvars {
+            self.adjust_assign(nvars, nexps, &mut e);
+            if nexps > nvars {
+                // Remove the extra values
+                self.fs().free_reg -= (nexps - nvars) as u32;
+            }
+        } else {
+            self.set_one_ret(&mut e);
+            rest -= 1;
+            self.store_var(&targets[rest], &mut e);
+        }
+        for target in targets[..rest].iter().rev() {
+            let mut e = Exp::new(Kind::NonReloc(self.fs().free_reg - 1));
+            self.store_var(target, &mut e);
+        }
+        Ok(())
+    }
+
+    /// Copies a local that an earlier target of an assignment uses as table
+    /// or key, since the local is assigned first
+    fn check_conflict(&mut self, targets: &mut [Exp], local: u32) {
+        let extra = self.fs().free_reg;
+        let mut conflict = false;
+        for target in targets {
+            if let Kind::Indexed(table, key) = &mut target.kind {
+                if *table == local {
+                    conflict = true;
+                    *table = extra;
+                }
+                if *key == local {
+                    conflict = true;
+                    *key = extra;
+                }
+            }
+        }
+        if conflict {
+            self.code_abc(OpCode::Move, extra, local, 0);
+            self.reserve_regs(1);
+        }
+    }
+
+    fn return_stat<T: ?Sized + ByteLen>(&mut self, exprs: &[Expr<&T>]) -> Result<(), CompileError> {
+        let (first, nret) = if exprs.is_empty() {
+            (0, 0)
+        } else {
+            let mut e = self.expr_list(exprs)?;
+            let nactvar = self.fs().nactvar as u32;
+            if e.has_multret() {
+                self.set_returns(&mut e, MULTRET);
+                if let (Kind::Call(pc), 1) = (e.kind, exprs.len()) {
+                    self.fs().proto.code[pc].set_opcode(OpCode::TailCall);
+                }
+                (nactvar, MULTRET)
+            } else if exprs.len() == 1 {
+                (self.exp_to_any_reg(&mut e), 1)
+            } else {
+                // The values must be in consecutive registers
+                self.exp_to_next_reg(&mut e);
+                (nactvar, exprs.len() as i32)
+            }
+        };
+        self.ret(first, nret);
+        Ok(())
+    }
+
+    fn break_stat(&mut self) -> Result<(), CompileError> {
+        let fs = self.fs();
+        let mut upval = false;
+        let mut found = None;
+        for (i, block) in fs.blocks.iter().enumerate().rev() {
+            if block.breakable {
+                found = Some(i);
+                break;
+            }
+            upval |= block.upval;
+        }
+        let Some(index) = found else {
+            return Err(self.fail(CompileErrorKind::NoLoopToBreak));
+        };
+        if upval {
+            let nactvar = fs.blocks[index].nactvar as u32;
+            self.code_abc(OpCode::Close, nactvar, 0, 0);
+        }
+        let j = self.jump();
+        let mut list = self.fs().blocks[index].break_list;
+        self.concat(&mut list, j);
+        self.fs().blocks[index].break_list = list;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use alloc::{format, string::String, vec, vec::Vec};
+
+    use super::{compile, CompileErrorKind};
+    use crate::{
+        ast,
+        bytecode::{Constant, Header, OpMode, Proto},
+        Dialect, LuaVersion, Utf8Decoder,
+    };
+
+    fn proto(source: &str) -> Proto {
+        let chunk = ast::parse_str(source).unwrap();
+        compile(
+            &chunk,
+            Utf8Decoder::new(source),
+            Dialect::default(),
+            "=test",
+        )
+        .unwrap()
+    }
+
+    /// The instructions with their raw arguments
+    fn listing(proto: &Proto) -> Vec<String> {
+        proto
+            .code
+            .iter()
+            .map(|&i| {
+                let op = i.opcode().unwrap();
+                match op.mode() {
+                    OpMode::ABC => format!("{} {} {} {}", op, i.a(), i.b(), i.c()),
+                    OpMode::ABx => format!("{} {} {}", op, i.a(), i.bx()),
+                    OpMode::AsBx => format!("{} {} {}", op, i.a(), i.sbx()),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_expressions() {
+        let main = proto("print(\"hello\")");
+        assert_eq!(
+            listing(&main),
+            ["GETGLOBAL 0 0", "LOADK 1 1", "CALL 0 2 1", "RETURN 0 1 0"]
+        );
+        assert_eq!(
+            main.constants,
+            [
+                Constant::String(b"print".to_vec()),
+                Constant::String(b"hello".to_vec())
+            ]
+        );
+        assert_eq!(main.max_stack_size, 2);
+        assert_eq!(main.is_vararg, Proto::VARARG_ISVARARG);
+
+        let main = proto("local a, b = 1; b = a + 2; return a .. b .. '!'");
+        assert_eq!(
+            listing(&main),
+            [
+                "LOADK 0 0",
+                "LOADNIL 1 1 0",
+                "ADD 1 0 257",
+                "MOVE 2 0 0",
+                "MOVE 3 1 0",
+                "LOADK 4 2",
+                "CONCAT 2 2 4",
+                "RETURN 2 2 0",
+                "RETURN 0 1 0"
+            ]
+        );
+        assert_eq!(main.max_stack_size, 5);
+        assert_eq!(main.locals.len(), 2);
+        assert_eq!((main.locals[0].start_pc, main.locals[0].end_pc), (2, 8));
+
+        let main = proto("local a = b and c or d");
+        assert_eq!(
+            listing(&main),
+            [
+                "GETGLOBAL 0 0",
+                "TEST 0 0 0",
+                "JMP 0 3",
+                "GETGLOBAL 0 1",
+                "TEST 0 0 1",
+                "JMP 0 1",
+                "GETGLOBAL 0 2",
+                "RETURN 0 1 0"
+            ]
+        );
+
+        // Folded constants, but no division by zero
+        let main = proto("local x, y = 2 * 3 + -4, 1 / 0");
+        assert_eq!(
+            listing(&main),
+            ["LOADK 0 0", "DIV 1 258 257", "RETURN 0 1 0"]
+        );
+        assert_eq!(
+            main.constants,
+            [
+                Constant::Number(2.0),
+                Constant::Number(0.0),
+                Constant::Number(1.0)
+            ]
+        );
+
+        let main = proto("local t = {1, 2, f(), x = 3}");
+        assert_eq!(
+            listing(&main),
+            [
+                "NEWTABLE 0 3 1",
+                "LOADK 1 0",
+                "LOADK 2 1",
+                "GETGLOBAL 3 2",
+                "CALL 3 1 2",
+                "SETTABLE 0 259 260",
+                "SETLIST 0 3 1",
+                "RETURN 0 1 0"
+            ]
+        );
+        let main = proto("local t = {1, 2, f()}");
+        assert_eq!(
+            listing(&main),
+            [
+                "NEWTABLE 0 2 0",
+                "LOADK 1 0",
+                "LOADK 2 1",
+                "GETGLOBAL 3 2",
+                "CALL 3 1 0",
+                "SETLIST 0 0 1",
+                "RETURN 0 1 0"
+            ]
+        );
+
+        let main = proto("return obj:method(1)");
+        assert_eq!(
+            listing(&main),
+            [
+                "GETGLOBAL 0 0",
+                "SELF 0 0 257",
+                "LOADK 2 2",
+                "TAILCALL 0 3 0",
+                "RETURN 0 0 0",
+                "RETURN 0 1 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statements() {
+        let main = proto("local x = 0 while x < 10 do x = x + 1 end");
+        assert_eq!(
+            listing(&main),
+            [
+                "LOADK 0 0",
+                "LT 0 0 257",
+                "JMP 0 2",
+                "ADD 0 0 258",
+                "JMP 0 -4",
+                "RETURN 0 1 0"
+            ]
+        );
+
+        let main = proto("for i = 1, 10 do print(i) end");
+        assert_eq!(
+            listing(&main),
+            [
+                "LOADK 0 0",
+                "LOADK 1 1",
+                "LOADK 2 0",
+                "FORPREP 0 3",
+                "GETGLOBAL 4 2",
+                "MOVE 5 3 0",
+                "CALL 4 2 1",
+                "FORLOOP 0 -4",
+                "RETURN 0 1 0"
+            ]
+        );
+        let names: Vec<_> = main.locals.iter().map(|l| l.name.as_slice()).collect();
+        assert_eq!(
+            names,
+            [&b"(for index)"[..], b"(for limit)", b"(for step)", b"i"]
+        );
+
+        let main = proto("for k, v in pairs(t) do if v then break end end");
+        assert_eq!(
+            listing(&main),
+            [
+                "GETGLOBAL 0 0",
+                "GETGLOBAL 1 1",
+                "CALL 0 2 4",
+                "JMP 0 3",
+                "TEST 4 0 0",
+                "JMP 0 1",
+                "JMP 0 2",
+                "TFORLOOP 0 0 2",
+                "JMP 0 -5",
+                "RETURN 0 1 0"
+            ]
+        );
+
+        let main = proto("if a then x = 1 elseif b then x = 2 else x = 3 end");
+        assert_eq!(
+            listing(&main),
+            [
+                "GETGLOBAL 0 0",
+                "TEST 0 0 0",
+                "JMP 0 3",
+                "LOADK 0 2",
+                "SETGLOBAL 0 1",
+                "JMP 0 8",
+                "GETGLOBAL 0 3",
+                "TEST 0 0 0",
+                "JMP 0 3",
+                "LOADK 0 4",
+                "SETGLOBAL 0 1",
+                "JMP 0 2",
+                "LOADK 0 5",
+                "SETGLOBAL 0 1",
+                "RETURN 0 1 0"
+            ]
+        );
+
+        // The local `a` is copied before it is assigned
+        let main = proto("local a, t = 1, {} t[a], a = 2, 3");
+        assert_eq!(
+            listing(&main),
+            [
+                "LOADK 0 0",
+                "NEWTABLE 1 0 0",
+                "MOVE 2 0 0",
+                "LOADK 3 1",
+                "LOADK 0 2",
+                "SETTABLE 1 2 3",
+                "RETURN 0 1 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let main = proto("local n = 0\nfunction inc()\n  n = n + 1\n  return n\nend");
+        assert_eq!(
+            listing(&main),
+            [
+                "LOADK 0 0",
+                "CLOSURE 1 0",
+                "MOVE 0 0 0",
+                "SETGLOBAL 1 1",
+                "RETURN 0 1 0"
+            ]
+        );
+        // The closure is created after the body, only the assignment is moved
+        assert_eq!(main.line_info, [1, 5, 5, 2, 5]);
+        let inc = &main.protos[0];
+        assert_eq!(
+            listing(inc),
+            [
+                "GETUPVAL 0 0 0",
+                "ADD 0 0 256",
+                "SETUPVAL 0 0 0",
+                "GETUPVAL 0 0 0",
+                "RETURN 0 2 0",
+                "RETURN 0 1 0"
+            ]
+        );
+        assert_eq!(inc.upvalues, [b"n".to_vec()]);
+        assert_eq!((inc.num_upvalues, inc.num_params), (1, 0));
+        assert_eq!((inc.line_defined, inc.last_line_defined), (2, 5));
+        assert_eq!(inc.line_info, [3, 3, 3, 4, 4, 5]);
+
+        let main = proto("function t.a.b:m(x, ...) local y = select('#', ...) end");
+        let m = &main.protos[0];
+        assert_eq!(m.num_params, 2);
+        assert_eq!(m.is_vararg, Proto::VARARG_HASARG | Proto::VARARG_ISVARARG);
+        let names: Vec<_> = m.locals.iter().map(|l| l.name.as_slice()).collect();
+        assert_eq!(names, [&b"self"[..], b"x", b"arg", b"y"]);
+        assert_eq!(
+            listing(m),
+            [
+                "GETGLOBAL 3 0",
+                "LOADK 4 1",
+                "VARARG 5 0 0",
+                "CALL 3 0 2",
+                "RETURN 0 1 0"
+            ]
+        );
+        assert_eq!(
+            listing(&main),
+            [
+                "GETGLOBAL 0 0",
+                "GETTABLE 0 0 257",
+                "GETTABLE 0 0 258",
+                "CLOSURE 1 0",
+                "SETTABLE 0 259 1",
+                "RETURN 0 1 0"
+            ]
+        );
+
+        // The closures of each iteration get their own `x`
+        let main = proto("repeat local x = f() g(function() return x end) until x");
+        assert_eq!(
+            listing(&main),
+            [
+                "GETGLOBAL 0 0",
+                "CALL 0 1 2",
+                "GETGLOBAL 1 1",
+                "CLOSURE 2 0",
+                "MOVE 0 0 0",
+                "CALL 1 2 1",
+                "TEST 0 0 0",
+                "JMP 0 2",
+                "CLOSE 0 0 0",
+                "JMP 0 2",
+                "CLOSE 0 0 0",
+                "JMP 0 -12",
+                "RETURN 0 1 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        // Each instruction has the line of the last token that was read, the
+        // calls and loops have the lines where they start
+        let main = proto("local t = {\n  1, 2,\n}\nfor i = 1, #t do\n  print(\n    t[i])\nend");
+        assert_eq!(
+            listing(&main),
+            [
+                "NEWTABLE 0 2 0",
+                "LOADK 1 0",
+                "LOADK 2 1",
+                "SETLIST 0 2 1",
+                "LOADK 1 0",
+                "LEN 2 0 0",
+                "LOADK 3 0",
+                "FORPREP 1 3",
+                "GETGLOBAL 5 2",
+                "GETTABLE 6 0 4",
+                "CALL 5 2 1",
+                "FORLOOP 1 -4",
+                "RETURN 0 1 0"
+            ]
+        );
+        assert_eq!(main.line_info, [1, 2, 3, 3, 4, 4, 4, 4, 5, 6, 5, 4, 7]);
+
+        let main = proto(
+            "local function f(t)
+              for k, v in
+                pairs(t) do
+                if v then
+                  return k
+                else
+                  break
+                end
+              end
+            end",
+        );
+        assert_eq!(main.line_info, [10, 10]);
+        let f = &main.protos[0];
+        assert_eq!((f.line_defined, f.last_line_defined), (1, 10));
+        assert_eq!(
+            listing(f),
+            [
+                "GETGLOBAL 1 0",
+                "MOVE 2 0 0",
+                "CALL 1 2 4",
+                "JMP 0 5",
+                "TEST 5 0 0",
+                // The `break` also takes the false jump out of the loop
+                "JMP 0 5",
+                "RETURN 4 2 0",
+                "JMP 0 1",
+                "JMP 0 2",
+                "TFORLOOP 1 0 2",
+                "JMP 0 -7",
+                "RETURN 0 1 0"
+            ]
+        );
+        assert_eq!(f.line_info, [3, 3, 3, 3, 4, 4, 5, 5, 7, 3, 8, 10]);
+    }
+
+    #[test]
+    fn test_dump() {
+        let bytes = proto("").dump(&Header::default(), false);
+        let mut expected = vec![0x1b, b'L', b'u', b'a', 0x51, 0, 1, 4, 8, 4, 8, 0];
+        expected.extend_from_slice(&[6, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(b"=test\0");
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2]);
+        // One `RETURN 0 1`, no constants and no functions
+        expected.extend_from_slice(&[1, 0, 0, 0, 0x1e, 0, 0x80, 0]);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        // The line of the instruction, no locals and no upvalues
+        expected.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes, expected);
+
+        let header = Header {
+            little_endian: false,
+            size_t_size: 4,
+            ..Header::default()
+        };
+        let bytes = proto("return 0.5").dump(&header, true);
+        assert_eq!(bytes[6], 0);
+        // No source name
+        assert_eq!(bytes[12..16], [0, 0, 0, 0]);
+        // One constant, 0.5 as a big-endian double
+        let mut expected = vec![0, 0, 0, 1, 3, 0x3f, 0xe0, 0, 0, 0, 0, 0, 0];
+        // No functions, lines, locals and upvalues
+        expected.extend_from_slice(&[0; 16]);
+        assert_eq!(bytes[bytes.len() - expected.len()..], expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str, version| {
+            let dialect = Dialect::from(version);
+            let chunk = ast::parse_with(Utf8Decoder::new(source), dialect).unwrap();
+            compile(&chunk, Utf8Decoder::new(source), dialect, "=test").unwrap_err()
+        };
+        let e = error("local x\n\ngoto done", LuaVersion::Lua52);
+        assert_eq!(e.kind(), CompileErrorKind::Unsupported("goto"));
+        assert_eq!(e.line(), 3);
+        let e = error("function f()\n  return ...\nend", LuaVersion::Lua51);
+        assert_eq!(e.kind(), CompileErrorKind::VarargOutsideVararg);
+        assert_eq!(
+            format!("{}", e),
+            "2: cannot use '...' outside a vararg function"
+        );
+        let e = error("do break end", LuaVersion::Lua51);
+        assert_eq!(e.kind(), CompileErrorKind::NoLoopToBreak);
+        let e = error("return 1 // 2", LuaVersion::Lua53);
+        assert_eq!(e.kind(), CompileErrorKind::Unsupported("integer operators"));
+        let e = error("return 1", LuaVersion::Lua53);
+        assert_eq!(e.kind(), CompileErrorKind::Unsupported("integers"));
+
+        let mut source = String::from("local x = {");
+        for i in 0..300 {
+            source.push_str(&format!("{}, ", i));
+        }
+        source.push_str("} local y = x[1] + 1e100");
+        let main = proto(&source);
+        assert!(listing(&main).contains(&String::from("SETLIST 0 50 6")));
+
+        let mut source = String::from("local a0");
+        for i in 1..200 {
+            source.push_str(&format!(", a{}", i));
+        }
+        source.push_str(" local b");
+        let chunk = ast::parse_str(&source).unwrap();
+        let decoder = Utf8Decoder::new(&source);
+        let e = compile(&chunk, decoder, Dialect::default(), "=test").unwrap_err();
+        assert_eq!(e.kind(), CompileErrorKind::TooManyLocals);
+    }
+}
@@ -0,0 +1,153 @@
+//! Writing binary chunks, as `ldump.c`
+
+use alloc::vec::Vec;
+
+use super::{Constant, Header, Proto};
+
+struct Dumper<'h> {
+    out: Vec<u8>,
+    header: &'h Header,
+    strip: bool,
+}
+
+impl Dumper<'_> {
+    /// Writes the low `size` bytes of a two's complement number
+    fn int_of_size(&mut self, value: i64, size: u8) {
+        let start = self.out.len();
+        for i in 0..u32::from(size) {
+            self.out.push(match value.checked_shr(8 * i) {
+                Some(shifted) => shifted as u8,
+                None if value < 0 => 0xff,
+                None => 0,
+            });
+        }
+        if !self.header.little_endian {
+            self.out[start..].reverse();
+        }
+    }
+
+    fn int(&mut self, value: u32) {
+        self.int_of_size(i64::from(value), self.header.int_size);
+    }
+
+    fn size(&mut self, value: usize) {
+        self.int_of_size(value as i64, self.header.size_t_size);
+    }
+
+    fn count(&mut self, len: usize) {
+        self.int_of_size(len as i64, self.header.int_size);
+    }
+
+    fn number(&mut self, value: f64) {
+        match (self.header.integral, self.header.number_size) {
+            (true, size) => self.int_of_size(value as i64, size),
+            (false, 4) => self.int_of_size(i64::from((value as f32).to_bits()), 4),
+            (false, size) => self.int_of_size(value.to_bits() as i64, size),
+        }
+    }
+
+    fn string(&mut self, value: Option<&[u8]>) {
+        match value {
+            Some(bytes) => {
+                self.size(bytes.len() + 1);
+                self.out.extend_from_slice(bytes);
+                self.out.push(0);
+            }
+            None => self.size(0),
+        }
+    }
+
+    fn header(&mut self) {
+        let header = *self.header;
+        self.out.extend_from_slice(Header::SIGNATURE);
+        self.out.extend_from_slice(&[
+            Header::VERSION,
+            0,
+            u8::from(header.little_endian),
+            header.int_size,
+            header.size_t_size,
+            header.instruction_size,
+            header.number_size,
+            u8::from(header.integral),
+        ]);
+    }
+
+    fn function(&mut self, proto: &Proto, parent_source: Option<&[u8]>) {
+        let source = proto.source.as_deref();
+        if self.strip || source == parent_source {
+            self.string(None);
+        } else {
+            self.string(source);
+        }
+        self.int(proto.line_defined);
+        self.int(proto.last_line_defined);
+        self.out.extend_from_slice(&[
+            proto.num_upvalues,
+            proto.num_params,
+            proto.is_vararg,
+            proto.max_stack_size,
+        ]);
+
+        self.count(proto.code.len());
+        for instruction in &proto.code {
+            self.int_of_size(i64::from(instruction.0), self.header.instruction_size);
+        }
+
+        self.count(proto.constants.len());
+        for constant in &proto.constants {
+            match constant {
+                Constant::Nil => self.out.push(0),
+                Constant::Bool(b) => self.out.extend_from_slice(&[1, u8::from(*b)]),
+                Constant::Number(n) => {
+                    self.out.push(3);
+                    self.number(*n);
+                }
+                Constant::String(s) => {
+                    self.out.push(4);
+                    self.string(Some(s));
+                }
+            }
+        }
+        self.count(proto.protos.len());
+        for child in &proto.protos {
+            self.function(child, source.or(parent_source));
+        }
+
+        if self.strip {
+            self.count(0);
+            self.count(0);
+            self.count(0);
+            return;
+        }
+        self.count(proto.line_info.len());
+        for &line in &proto.line_info {
+            self.int(line);
+        }
+        self.count(proto.locals.len());
+        for local in &proto.locals {
+            self.string(Some(&local.name));
+            self.int(local.start_pc);
+            self.int(local.end_pc);
+        }
+        self.count(proto.upvalues.len());
+        for name in &proto.upvalues {
+            self.string(Some(name));
+        }
+    }
+}
+
+impl Proto {
+    /// Writes a binary chunk of the function, like `luac`
+    ///
+    /// With `strip`, the chunk has no debug information, like `luac -s`.
+    pub fn dump(&self, header: &Header, strip: bool) -> Vec<u8> {
+        let mut dumper = Dumper {
+            out: Vec::new(),
+            header,
+            strip,
+        };
+        dumper.header();
+        dumper.function(self, None);
+        dumper.out
+    }
+}
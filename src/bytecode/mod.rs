@@ -0,0 +1,442 @@
+//! # Lua 5.1 Bytecode
+//!
+//! The function prototypes of the Lua 5.1 virtual machine, a [compile]r from
+//! the [ast](crate::ast) that follows `lparser.c` and `lcode.c`, the binary
+//! chunk format of `luac` that stock Lua 5.1 loads with `loadfile`, and the
+//! [listings](Proto::listing) of `luac -l -l`.
+//!
+//! See: <https://www.lua.org/source/5.1/lopcodes.h.html>
+//!
+//! This module requires the `alloc` feature.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+mod compile;
+pub use compile::{compile, CompileError, CompileErrorKind};
+mod dump;
+mod print;
+pub use print::Listing;
+mod undump;
+pub use undump::{load, LoadError, LoadErrorKind};
+
+/// The operations of the virtual machine, in the order of `lopcodes.h`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum OpCode {
+    Move,
+    LoadK,
+    LoadBool,
+    LoadNil,
+    GetUpval,
+    GetGlobal,
+    GetTable,
+    SetGlobal,
+    SetUpval,
+    SetTable,
+    NewTable,
+    SelfOp,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Unm,
+    Not,
+    Len,
+    Concat,
+    Jmp,
+    Eq,
+    Lt,
+    Le,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    ForLoop,
+    ForPrep,
+    TForLoop,
+    SetList,
+    Close,
+    Closure,
+    Vararg,
+}
+
+/// The layout of the arguments of an instruction
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OpMode {
+    /// `A`, `B` and `C`
+    ABC,
+    /// `A` and an unsigned `Bx`
+    ABx,
+    /// `A` and a signed `sBx`
+    AsBx,
+}
+
+/// How an operation uses its `B` or `C` argument, as `OpArgMask`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OpArgMode {
+    /// Not used
+    N,
+    /// Used as a number or count
+    U,
+    /// A register or a jump offset
+    R,
+    /// A constant, or a register or constant with [Instruction::CONSTANT]
+    K,
+}
+
+impl OpCode {
+    /// All operations, indexed by their number
+    pub const ALL: [Self; 38] = [
+        Self::Move,
+        Self::LoadK,
+        Self::LoadBool,
+        Self::LoadNil,
+        Self::GetUpval,
+        Self::GetGlobal,
+        Self::GetTable,
+        Self::SetGlobal,
+        Self::SetUpval,
+        Self::SetTable,
+        Self::NewTable,
+        Self::SelfOp,
+        Self::Add,
+        Self::Sub,
+        Self::Mul,
+        Self::Div,
+        Self::Mod,
+        Self::Pow,
+        Self::Unm,
+        Self::Not,
+        Self::Len,
+        Self::Concat,
+        Self::Jmp,
+        Self::Eq,
+        Self::Lt,
+        Self::Le,
+        Self::Test,
+        Self::TestSet,
+        Self::Call,
+        Self::TailCall,
+        Self::Return,
+        Self::ForLoop,
+        Self::ForPrep,
+        Self::TForLoop,
+        Self::SetList,
+        Self::Close,
+        Self::Closure,
+        Self::Vararg,
+    ];
+
+    /// Returns the operation with a number
+    pub fn from_u8(op: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(op)).copied()
+    }
+
+    /// Returns the name in listings of `luac -l`, e.g. `GETGLOBAL`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Move => "MOVE",
+            Self::LoadK => "LOADK",
+            Self::LoadBool => "LOADBOOL",
+            Self::LoadNil => "LOADNIL",
+            Self::GetUpval => "GETUPVAL",
+            Self::GetGlobal => "GETGLOBAL",
+            Self::GetTable => "GETTABLE",
+            Self::SetGlobal => "SETGLOBAL",
+            Self::SetUpval => "SETUPVAL",
+            Self::SetTable => "SETTABLE",
+            Self::NewTable => "NEWTABLE",
+            Self::SelfOp => "SELF",
+            Self::Add => "ADD",
+            Self::Sub => "SUB",
+            Self::Mul => "MUL",
+            Self::Div => "DIV",
+            Self::Mod => "MOD",
+            Self::Pow => "POW",
+            Self::Unm => "UNM",
+            Self::Not => "NOT",
+            Self::Len => "LEN",
+            Self::Concat => "CONCAT",
+            Self::Jmp => "JMP",
+            Self::Eq => "EQ",
+            Self::Lt => "LT",
+            Self::Le => "LE",
+            Self::Test => "TEST",
+            Self::TestSet => "TESTSET",
+            Self::Call => "CALL",
+            Self::TailCall => "TAILCALL",
+            Self::Return => "RETURN",
+            Self::ForLoop => "FORLOOP",
+            Self::ForPrep => "FORPREP",
+            Self::TForLoop => "TFORLOOP",
+            Self::SetList => "SETLIST",
+            Self::Close => "CLOSE",
+            Self::Closure => "CLOSURE",
+            Self::Vararg => "VARARG",
+        }
+    }
+
+    pub fn mode(self) -> OpMode {
+        match self {
+            Self::LoadK | Self::GetGlobal | Self::SetGlobal | Self::Closure => OpMode::ABx,
+            Self::Jmp | Self::ForLoop | Self::ForPrep => OpMode::AsBx,
+            _ => OpMode::ABC,
+        }
+    }
+
+    /// The use of `B`, or of `Bx` in [OpMode::ABx]
+    pub fn b_mode(self) -> OpArgMode {
+        match self {
+            Self::LoadK
+            | Self::GetGlobal
+            | Self::SetGlobal
+            | Self::SetTable
+            | Self::Add
+            | Self::Sub
+            | Self::Mul
+            | Self::Div
+            | Self::Mod
+            | Self::Pow
+            | Self::Eq
+            | Self::Lt
+            | Self::Le => OpArgMode::K,
+            Self::LoadBool
+            | Self::GetUpval
+            | Self::SetUpval
+            | Self::NewTable
+            | Self::Call
+            | Self::TailCall
+            | Self::Return
+            | Self::SetList
+            | Self::Closure
+            | Self::Vararg => OpArgMode::U,
+            Self::TForLoop | Self::Close => OpArgMode::N,
+            _ => OpArgMode::R,
+        }
+    }
+
+    /// The use of `C`
+    pub fn c_mode(self) -> OpArgMode {
+        match self {
+            Self::GetTable
+            | Self::SetTable
+            | Self::SelfOp
+            | Self::Add
+            | Self::Sub
+            | Self::Mul
+            | Self::Div
+            | Self::Mod
+            | Self::Pow
+            | Self::Eq
+            | Self::Lt
+            | Self::Le => OpArgMode::K,
+            Self::LoadBool
+            | Self::NewTable
+            | Self::Test
+            | Self::TestSet
+            | Self::Call
+            | Self::TailCall
+            | Self::TForLoop
+            | Self::SetList => OpArgMode::U,
+            Self::Concat => OpArgMode::R,
+            _ => OpArgMode::N,
+        }
+    }
+
+    /// Whether the operation is a test that skips the next instruction,
+    /// which is always a `JMP`
+    pub fn is_test(self) -> bool {
+        matches!(
+            self,
+            Self::Eq | Self::Lt | Self::Le | Self::Test | Self::TestSet | Self::TForLoop
+        )
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An instruction: 6 bits of operation, then `A`, `C` and `B` or `Bx`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Instruction(pub u32);
+
+impl Instruction {
+    pub const MAX_A: u32 = (1 << 8) - 1;
+    pub const MAX_B: u32 = (1 << 9) - 1;
+    pub const MAX_C: u32 = (1 << 9) - 1;
+    pub const MAX_BX: u32 = (1 << 18) - 1;
+    pub const MAX_SBX: i32 = (Self::MAX_BX >> 1) as i32;
+    /// The bit of `B` or `C` that selects a constant instead of a register
+    pub const CONSTANT: u32 = 1 << 8;
+
+    pub fn abc(op: OpCode, a: u32, b: u32, c: u32) -> Self {
+        Self(op as u32 | (a & Self::MAX_A) << 6 | (c & Self::MAX_C) << 14 | (b & Self::MAX_B) << 23)
+    }
+
+    pub fn abx(op: OpCode, a: u32, bx: u32) -> Self {
+        Self(op as u32 | (a & Self::MAX_A) << 6 | (bx & Self::MAX_BX) << 14)
+    }
+
+    pub fn asbx(op: OpCode, a: u32, sbx: i32) -> Self {
+        Self::abx(op, a, (sbx + Self::MAX_SBX) as u32)
+    }
+
+    /// Returns the operation, if the number is valid
+    pub fn opcode(self) -> Option<OpCode> {
+        OpCode::from_u8((self.0 & 0x3f) as u8)
+    }
+
+    pub fn a(self) -> u32 {
+        self.0 >> 6 & Self::MAX_A
+    }
+
+    pub fn b(self) -> u32 {
+        self.0 >> 23
+    }
+
+    pub fn c(self) -> u32 {
+        self.0 >> 14 & Self::MAX_C
+    }
+
+    pub fn bx(self) -> u32 {
+        self.0 >> 14
+    }
+
+    pub fn sbx(self) -> i32 {
+        self.bx() as i32 - Self::MAX_SBX
+    }
+
+    fn set_opcode(&mut self, op: OpCode) {
+        self.0 = self.0 & !0x3f | op as u32;
+    }
+
+    fn set_a(&mut self, a: u32) {
+        self.0 = self.0 & !(Self::MAX_A << 6) | (a & Self::MAX_A) << 6;
+    }
+
+    fn set_b(&mut self, b: u32) {
+        self.0 = self.0 & !(Self::MAX_B << 23) | (b & Self::MAX_B) << 23;
+    }
+
+    fn set_c(&mut self, c: u32) {
+        self.0 = self.0 & !(Self::MAX_C << 14) | (c & Self::MAX_C) << 14;
+    }
+
+    fn set_sbx(&mut self, sbx: i32) {
+        let bx = (sbx + Self::MAX_SBX) as u32 & Self::MAX_BX;
+        self.0 = self.0 & !(Self::MAX_BX << 14) | bx << 14;
+    }
+}
+
+/// A constant of a function
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Vec<u8>),
+}
+
+/// The debug information of a local variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalVar {
+    pub name: Vec<u8>,
+    /// The first instruction where the variable is active
+    pub start_pc: u32,
+    /// The first instruction where the variable is dead
+    pub end_pc: u32,
+}
+
+/// A compiled function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proto {
+    /// The chunk name, e.g. `@file.lua`, only in the main function
+    pub source: Option<Vec<u8>>,
+    pub line_defined: u32,
+    pub last_line_defined: u32,
+    pub num_upvalues: u8,
+    /// The number of fixed parameters, including `self`
+    pub num_params: u8,
+    /// The `VARARG_*` flags
+    pub is_vararg: u8,
+    pub max_stack_size: u8,
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Constant>,
+    pub protos: Vec<Proto>,
+    /// The source line of each instruction
+    pub line_info: Vec<u32>,
+    pub locals: Vec<LocalVar>,
+    /// The names of the upvalues
+    pub upvalues: Vec<Vec<u8>>,
+}
+
+impl Proto {
+    /// The function has an `arg` table for its extra arguments (Lua 5.0)
+    pub const VARARG_HASARG: u8 = 1;
+    /// The function has `...` in its parameter list
+    pub const VARARG_ISVARARG: u8 = 2;
+    /// The function never uses `...`, so it needs the `arg` table
+    pub const VARARG_NEEDSARG: u8 = 4;
+
+    pub(crate) fn new(source: Option<Vec<u8>>, line_defined: u32) -> Self {
+        Self {
+            source,
+            line_defined,
+            last_line_defined: 0,
+            num_upvalues: 0,
+            num_params: 0,
+            is_vararg: 0,
+            max_stack_size: 2,
+            code: Vec::new(),
+            constants: Vec::new(),
+            protos: Vec::new(),
+            line_info: Vec::new(),
+            locals: Vec::new(),
+            upvalues: Vec::new(),
+        }
+    }
+}
+
+/// The header of a binary chunk, which describes the platform that wrote it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub little_endian: bool,
+    /// The size of a C `int` in bytes
+    pub int_size: u8,
+    /// The size of a C `size_t` in bytes
+    pub size_t_size: u8,
+    /// The size of an instruction in bytes, always 4
+    pub instruction_size: u8,
+    /// The size of `lua_Number` in bytes
+    pub number_size: u8,
+    /// Whether `lua_Number` is an integer type
+    pub integral: bool,
+}
+
+impl Header {
+    /// The start of every binary chunk
+    pub const SIGNATURE: &'static [u8] = b"\x1bLua";
+    /// The version byte of Lua 5.1
+    pub const VERSION: u8 = 0x51;
+}
+
+/// The header of a stock Lua 5.1 on 64-bit x86 or ARM
+impl Default for Header {
+    fn default() -> Self {
+        Self {
+            little_endian: true,
+            int_size: 4,
+            size_t_size: 8,
+            instruction_size: 4,
+            number_size: 8,
+            integral: false,
+        }
+    }
+}
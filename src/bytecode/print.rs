@@ -0,0 +1,431 @@
+//! Listings of functions, as `print.c` of `luac`
+
+use alloc::{format, vec::Vec};
+use core::fmt;
+
+use super::{Constant, Header, Instruction, OpArgMode, OpCode, OpMode, Proto};
+use crate::Keyword;
+
+/// The listing of a function and its nested functions, like `luac -l`
+///
+/// Functions are named `F0`, `F1` and so on in the order of the listing,
+/// where `luac` shows their addresses.
+#[derive(Debug, Copy, Clone)]
+pub struct Listing<'p> {
+    proto: &'p Proto,
+    full: bool,
+}
+
+impl Proto {
+    /// Lists the instructions, and with `full` the constants, locals and
+    /// upvalues like `luac -l -l`
+    pub fn listing(&self, full: bool) -> Listing<'_> {
+        Listing { proto: self, full }
+    }
+}
+
+/// The number of functions in a tree
+fn count(proto: &Proto) -> usize {
+    1 + proto.protos.iter().map(count).sum::<usize>()
+}
+
+/// Writes `%.14g` of C, the `LUA_NUMBER_FMT` of Lua 5.1
+fn write_number(f: &mut fmt::Formatter<'_>, n: f64) -> fmt::Result {
+    fn trim(digits: &str) -> &str {
+        match digits.contains('.') {
+            true => digits.trim_end_matches('0').trim_end_matches('.'),
+            false => digits,
+        }
+    }
+
+    if n.is_nan() {
+        return f.write_str(if n.is_sign_negative() { "-nan" } else { "nan" });
+    } else if n.is_infinite() {
+        return f.write_str(if n > 0.0 { "inf" } else { "-inf" });
+    }
+    let scientific = format!("{:.13e}", n);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    if (-4..14).contains(&exponent) {
+        let fixed = format!("{:.*}", (13 - exponent) as usize, n);
+        f.write_str(trim(&fixed))
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        write!(f, "{}e{}{:02}", trim(mantissa), sign, exponent.abs())
+    }
+}
+
+/// Writes bytes with the escapes of `luac`, which are not all valid in Lua
+fn write_escaped(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    for &b in bytes {
+        match b {
+            b'"' => f.write_str("\\\"")?,
+            b'\\' => f.write_str("\\\\")?,
+            0x07 => f.write_str("\\a")?,
+            0x08 => f.write_str("\\b")?,
+            0x0c => f.write_str("\\f")?,
+            b'\n' => f.write_str("\\n")?,
+            b'\r' => f.write_str("\\r")?,
+            b'\t' => f.write_str("\\t")?,
+            0x0b => f.write_str("\\v")?,
+            b' '..=b'~' => write!(f, "{}", b as char)?,
+            _ => write!(f, "\\{:03}", b)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_constant(f: &mut fmt::Formatter<'_>, constant: Option<&Constant>) -> fmt::Result {
+    match constant {
+        Some(Constant::Nil) => write!(f, "{}", Keyword::Nil),
+        Some(Constant::Bool(true)) => write!(f, "{}", Keyword::True),
+        Some(Constant::Bool(false)) => write!(f, "{}", Keyword::False),
+        Some(Constant::Number(n)) => write_number(f, *n),
+        Some(Constant::String(s)) => {
+            f.write_str("\"")?;
+            write_escaped(f, s)?;
+            f.write_str("\"")
+        }
+        None => f.write_str("?"),
+    }
+}
+
+/// Writes a name, or a string constant without quotes
+fn write_name(f: &mut fmt::Formatter<'_>, name: Option<&[u8]>) -> fmt::Result {
+    match name {
+        Some(name) => write_escaped(f, name),
+        None => f.write_str("?"),
+    }
+}
+
+/// The `s` of plurals
+fn s(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+impl Listing<'_> {
+    fn function(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        proto: &Proto,
+        id: usize,
+        source: &[u8],
+    ) -> fmt::Result {
+        let source = proto.source.as_deref().unwrap_or(source);
+        let mut next = id + 1;
+        let mut children = Vec::with_capacity(proto.protos.len());
+        for child in &proto.protos {
+            children.push(next);
+            next += count(child);
+        }
+
+        if id > 0 {
+            f.write_str("\n")?;
+        }
+        self.header(f, proto, id, source)?;
+        self.code(f, proto, &children)?;
+        if self.full {
+            let constants = &proto.constants;
+            writeln!(f, "constants ({}) for F{}:", constants.len(), id)?;
+            for (i, constant) in constants.iter().enumerate() {
+                write!(f, "\t{}\t", i + 1)?;
+                write_constant(f, Some(constant))?;
+                f.write_str("\n")?;
+            }
+            writeln!(f, "locals ({}) for F{}:", proto.locals.len(), id)?;
+            for (i, local) in proto.locals.iter().enumerate() {
+                write!(f, "\t{}\t", i)?;
+                write_name(f, Some(&local.name))?;
+                writeln!(f, "\t{}\t{}", local.start_pc + 1, local.end_pc + 1)?;
+            }
+            writeln!(f, "upvalues ({}) for F{}:", proto.upvalues.len(), id)?;
+            for (i, name) in proto.upvalues.iter().enumerate() {
+                write!(f, "\t{}\t", i)?;
+                write_name(f, Some(name))?;
+                f.write_str("\n")?;
+            }
+        }
+        for (child, &child_id) in proto.protos.iter().zip(&children) {
+            self.function(f, child, child_id, source)?;
+        }
+        Ok(())
+    }
+
+    fn header(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        proto: &Proto,
+        id: usize,
+        source: &[u8],
+    ) -> fmt::Result {
+        let kind = if proto.line_defined == 0 {
+            "main"
+        } else {
+            Keyword::Function.as_str()
+        };
+        write!(f, "{} <", kind)?;
+        match source.split_first() {
+            Some((b'@' | b'=', name)) => write_escaped(f, name)?,
+            Some((&b, _)) if b == Header::SIGNATURE[0] => f.write_str("(bstring)")?,
+            _ => f.write_str("(string)")?,
+        }
+        let code = proto.code.len();
+        writeln!(
+            f,
+            ":{},{}> ({} instruction{}, {} bytes at F{})",
+            proto.line_defined,
+            proto.last_line_defined,
+            code,
+            s(code),
+            code * 4,
+            id
+        )?;
+        let params = usize::from(proto.num_params);
+        let slots = usize::from(proto.max_stack_size);
+        let upvalues = usize::from(proto.num_upvalues);
+        write!(
+            f,
+            "{}{} param{}, {} slot{}, {} upvalue{}, ",
+            params,
+            if proto.is_vararg != 0 { "+" } else { "" },
+            s(params),
+            slots,
+            s(slots),
+            upvalues,
+            s(upvalues)
+        )?;
+        let (locals, constants, functions) = (
+            proto.locals.len(),
+            proto.constants.len(),
+            proto.protos.len(),
+        );
+        writeln!(
+            f,
+            "{} local{}, {} constant{}, {} function{}",
+            locals,
+            s(locals),
+            constants,
+            s(constants),
+            functions,
+            s(functions)
+        )
+    }
+
+    fn code(&self, f: &mut fmt::Formatter<'_>, proto: &Proto, children: &[usize]) -> fmt::Result {
+        let constant = |index: u32| proto.constants.get(index as usize);
+        let rk = |arg: u32| match arg & Instruction::CONSTANT {
+            0 => i64::from(arg),
+            _ => -1 - i64::from(arg & !Instruction::CONSTANT),
+        };
+        let mut pc = 0;
+        while let Some(&i) = proto.code.get(pc) {
+            write!(f, "\t{}\t", pc + 1)?;
+            match proto.line_info.get(pc) {
+                Some(&line) if line > 0 => write!(f, "[{}]\t", line)?,
+                _ => f.write_str("[-]\t")?,
+            }
+            let Some(op) = i.opcode() else {
+                writeln!(f, "?\t{:#010x}", i.0)?;
+                pc += 1;
+                continue;
+            };
+            write!(f, "{:<9}\t", op.as_str())?;
+            let (a, b, c, bx, sbx) = (i.a(), i.b(), i.c(), i.bx(), i.sbx());
+            match op.mode() {
+                OpMode::ABC => {
+                    write!(f, "{}", a)?;
+                    if op.b_mode() != OpArgMode::N {
+                        write!(f, " {}", rk(b))?;
+                    }
+                    if op.c_mode() != OpArgMode::N {
+                        write!(f, " {}", rk(c))?;
+                    }
+                }
+                OpMode::ABx if op.b_mode() == OpArgMode::K => {
+                    write!(f, "{} {}", a, -1 - i64::from(bx))?
+                }
+                OpMode::ABx => write!(f, "{} {}", a, bx)?,
+                OpMode::AsBx if op == OpCode::Jmp => write!(f, "{}", sbx)?,
+                OpMode::AsBx => write!(f, "{} {}", a, sbx)?,
+            }
+
+            let is_k = |arg: u32| arg & Instruction::CONSTANT != 0;
+            let k = |arg: u32| constant(arg & !Instruction::CONSTANT);
+            match op {
+                OpCode::LoadK => {
+                    f.write_str("\t; ")?;
+                    write_constant(f, constant(bx))?;
+                }
+                OpCode::GetUpval | OpCode::SetUpval => {
+                    f.write_str("\t; ")?;
+                    match proto.upvalues.is_empty() {
+                        true => f.write_str("-")?,
+                        false => write_name(f, proto.upvalues.get(b as usize).map(|n| &n[..]))?,
+                    }
+                }
+                OpCode::GetGlobal | OpCode::SetGlobal => {
+                    f.write_str("\t; ")?;
+                    match constant(bx) {
+                        Some(Constant::String(name)) => write_name(f, Some(name))?,
+                        other => write_constant(f, other)?,
+                    }
+                }
+                OpCode::GetTable | OpCode::SelfOp if is_k(c) => {
+                    f.write_str("\t; ")?;
+                    write_constant(f, k(c))?;
+                }
+                OpCode::SetTable
+                | OpCode::Add
+                | OpCode::Sub
+                | OpCode::Mul
+                | OpCode::Div
+                | OpCode::Mod
+                | OpCode::Pow
+                | OpCode::Eq
+                | OpCode::Lt
+                | OpCode::Le
+                    if is_k(b) || is_k(c) =>
+                {
+                    f.write_str("\t; ")?;
+                    match is_k(b) {
+                        true => write_constant(f, k(b))?,
+                        false => f.write_str("-")?,
+                    }
+                    f.write_str(" ")?;
+                    match is_k(c) {
+                        true => write_constant(f, k(c))?,
+                        false => f.write_str("-")?,
+                    }
+                }
+                OpCode::Jmp | OpCode::ForLoop | OpCode::ForPrep => {
+                    write!(f, "\t; to {}", pc as i64 + i64::from(sbx) + 2)?
+                }
+                OpCode::Closure => match children.get(bx as usize) {
+                    Some(id) => write!(f, "\t; F{}", id)?,
+                    None => f.write_str("\t; ?")?,
+                },
+                // A `C` of 0 means the block number is the next word
+                OpCode::SetList if c == 0 => {
+                    pc += 1;
+                    match proto.code.get(pc) {
+                        Some(word) => write!(f, "\t; {}", word.0)?,
+                        None => f.write_str("\t; ?")?,
+                    }
+                }
+                OpCode::SetList => write!(f, "\t; {}", c)?,
+                _ => {}
+            }
+            f.write_str("\n")?;
+            pc += 1;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // A stripped chunk has no source, which Lua names `=?`
+        let source = self.proto.source.as_deref().unwrap_or(b"=?");
+        self.function(f, self.proto, 0, source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use alloc::{format, string::String, vec};
+
+    use crate::{
+        ast,
+        bytecode::{compile, Constant, Instruction, OpCode, Proto},
+        Dialect, Utf8Decoder,
+    };
+
+    #[test]
+    fn test_listing() {
+        let source = "local s = 'hi'\nlocal function f(...) return s end\nf.x = f.y + 1";
+        let chunk = ast::parse_str(source).unwrap();
+        let main = compile(
+            &chunk,
+            Utf8Decoder::new(source),
+            Dialect::default(),
+            "=test",
+        )
+        .unwrap();
+        let expected = "\
+main <test:0,0> (7 instructions, 28 bytes at F0)
+0+ params, 3 slots, 0 upvalues, 2 locals, 4 constants, 1 function
+\t1\t[1]\tLOADK    \t0 -1\t; \"hi\"
+\t2\t[2]\tCLOSURE  \t1 0\t; F1
+\t3\t[2]\tMOVE     \t0 0
+\t4\t[3]\tGETTABLE \t2 1 -3\t; \"y\"
+\t5\t[3]\tADD      \t2 2 -4\t; - 1
+\t6\t[3]\tSETTABLE \t1 -2 2\t; \"x\" -
+\t7\t[3]\tRETURN   \t0 1
+constants (4) for F0:
+\t1\t\"hi\"
+\t2\t\"x\"
+\t3\t\"y\"
+\t4\t1
+locals (2) for F0:
+\t0\ts\t2\t7
+\t1\tf\t4\t7
+upvalues (0) for F0:
+
+function <test:2,2> (3 instructions, 12 bytes at F1)
+0+ params, 2 slots, 1 upvalue, 1 local, 0 constants, 0 functions
+\t1\t[2]\tGETUPVAL \t1 0\t; s
+\t2\t[2]\tRETURN   \t1 2
+\t3\t[2]\tRETURN   \t0 1
+constants (0) for F1:
+locals (1) for F1:
+\t0\targ\t1\t3
+upvalues (1) for F1:
+\t0\ts
+";
+        assert_eq!(format!("{}", main.listing(true)), expected);
+        let short = format!("{}", main.listing(false));
+        assert!(short.starts_with("main <test:0,0>"));
+        assert!(!short.contains("constants ("));
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut proto = Proto::new(None, 0);
+        proto.constants = vec![
+            Constant::Nil,
+            Constant::Bool(false),
+            Constant::Number(0.1),
+            Constant::Number(100.0),
+            Constant::Number(-0.0),
+            Constant::Number(1e15),
+            Constant::Number(9007199254740992.0),
+            Constant::Number(0.0001),
+            Constant::Number(1e-5),
+            Constant::Number(f64::INFINITY),
+            Constant::String(b"\"\\\x07\x1b\xe9".to_vec()),
+        ];
+        proto.code = vec![
+            Instruction::abc(OpCode::SetList, 0, 1, 0),
+            Instruction(70000),
+            Instruction::abc(OpCode::Eq, 1, Instruction::CONSTANT | 1, 0),
+        ];
+        let listing = format!("{}", proto.listing(true));
+        let lines: std::vec::Vec<_> = listing.lines().collect();
+        assert_eq!(lines[0], "main <?:0,0> (3 instructions, 12 bytes at F0)");
+        assert_eq!(lines[2], "\t1\t[-]\tSETLIST  \t0 1 0\t; 70000");
+        assert_eq!(lines[3], "\t3\t[-]\tEQ       \t1 -2 0\t; false -");
+        let constants: String = lines[5..16]
+            .iter()
+            .map(|line| format!("{} ", line.split('\t').nth(2).unwrap()))
+            .collect();
+        assert_eq!(
+            constants,
+            "nil false 0.1 100 -0 1e+15 9.007199254741e+15 0.0001 1e-05 inf \"\\\"\\\\\\a\\027\\233\" "
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Reading binary chunks, as `lundump.c`
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{Constant, Header, Instruction, LocalVar, Proto};
+
+/// The reasons why a binary chunk can't be loaded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadErrorKind {
+    /// The bytes don't start with [Header::SIGNATURE]
+    NotAChunk,
+    /// A version byte other than [Header::VERSION]
+    Version(u8),
+    /// A format byte other than that of the official implementation
+    Format(u8),
+    /// Sizes of types that no build of Lua 5.1 has
+    Header,
+    /// The bytes end before the chunk does
+    Truncated,
+    /// A negative or too large count, line or size
+    Integer,
+    /// A constant with an unknown type tag
+    Constant(u8),
+    /// An instruction with an unknown operation
+    Code(u32),
+    /// Functions nested more than `LUAI_MAXCCALLS` deep
+    TooDeep,
+}
+
+impl fmt::Display for LoadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAChunk => f.write_str("not a precompiled chunk"),
+            Self::Version(v) => write!(f, "version {}.{} not supported", v >> 4, v & 0xf),
+            Self::Format(format) => write!(f, "format {} not supported", format),
+            Self::Header => f.write_str("bad header"),
+            Self::Truncated => f.write_str("unexpected end"),
+            Self::Integer => f.write_str("bad integer"),
+            Self::Constant(tag) => write!(f, "bad constant type {}", tag),
+            Self::Code(i) => write!(f, "bad instruction {:#010x}", i),
+            Self::TooDeep => f.write_str("code too deep"),
+        }
+    }
+}
+
+/// An error of [load]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    kind: LoadErrorKind,
+    offset: usize,
+}
+
+impl LoadError {
+    pub fn kind(&self) -> LoadErrorKind {
+        self.kind
+    }
+
+    /// The byte offset of the value that can't be read
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in precompiled chunk at byte {}",
+            self.kind, self.offset
+        )
+    }
+}
+
+/// The header after the format byte, if Lua 5.1 can have it
+fn header(fields: &[u8]) -> Option<Header> {
+    let flag = |b: u8| match b {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    };
+    let header = Header {
+        little_endian: flag(fields[0])?,
+        int_size: fields[1],
+        size_t_size: fields[2],
+        instruction_size: fields[3],
+        number_size: fields[4],
+        integral: flag(fields[5])?,
+    };
+    let valid = (1..=8).contains(&header.int_size)
+        && (1..=8).contains(&header.size_t_size)
+        && header.instruction_size == 4
+        && match header.integral {
+            true => (1..=8).contains(&header.number_size),
+            false => matches!(header.number_size, 4 | 8),
+        };
+    valid.then_some(header)
+}
+
+/// The maximum nesting of functions, `LUAI_MAXCCALLS` in Lua 5.1
+const MAX_DEPTH: usize = 200;
+
+struct Loader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+    header: Header,
+    /// The nesting of [Loader::function]
+    depth: usize,
+}
+
+impl<'b> Loader<'b> {
+    fn fail(&self, kind: LoadErrorKind) -> LoadError {
+        LoadError {
+            kind,
+            offset: self.pos,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'b [u8], LoadError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or_else(|| self.fail(LoadErrorKind::Truncated))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads `size` bytes as an unsigned number
+    fn uint_of_size(&mut self, size: u8) -> Result<u64, LoadError> {
+        let bytes = self.take(usize::from(size))?;
+        let fold = |n: u64, &b: &u8| n << 8 | u64::from(b);
+        Ok(if self.header.little_endian {
+            bytes.iter().rev().fold(0, fold)
+        } else {
+            bytes.iter().fold(0, fold)
+        })
+    }
+
+    /// Reads `size` bytes as a two's complement number
+    fn int_of_size(&mut self, size: u8) -> Result<i64, LoadError> {
+        let n = self.uint_of_size(size)?;
+        let unused = 64 - 8 * u32::from(size);
+        Ok(((n << unused) as i64) >> unused)
+    }
+
+    fn int(&mut self) -> Result<u32, LoadError> {
+        let start = self.pos;
+        let n = self.int_of_size(self.header.int_size)?;
+        u32::try_from(n).map_err(|_| LoadError {
+            kind: LoadErrorKind::Integer,
+            offset: start,
+        })
+    }
+
+    fn count(&mut self) -> Result<usize, LoadError> {
+        Ok(self.int()? as usize)
+    }
+
+    fn size(&mut self) -> Result<usize, LoadError> {
+        let start = self.pos;
+        let n = self.uint_of_size(self.header.size_t_size)?;
+        usize::try_from(n).map_err(|_| LoadError {
+            kind: LoadErrorKind::Integer,
+            offset: start,
+        })
+    }
+
+    fn number(&mut self) -> Result<f64, LoadError> {
+        Ok(match (self.header.integral, self.header.number_size) {
+            (true, size) => self.int_of_size(size)? as f64,
+            (false, 4) => f64::from(f32::from_bits(self.uint_of_size(4)? as u32)),
+            (false, _) => f64::from_bits(self.uint_of_size(8)?),
+        })
+    }
+
+    /// Reads a string without its trailing NUL, or `None` for size 0
+    fn string(&mut self) -> Result<Option<Vec<u8>>, LoadError> {
+        match self.size()? {
+            0 => Ok(None),
+            len => Ok(Some(self.take(len)?[..len - 1].to_vec())),
+        }
+    }
+
+    fn header(&mut self) -> Result<(), LoadError> {
+        let start = self.bytes.iter().take(Header::SIGNATURE.len());
+        if start.zip(Header::SIGNATURE).any(|(a, b)| a != b) {
+            return Err(self.fail(LoadErrorKind::NotAChunk));
+        }
+        self.take(Header::SIGNATURE.len())?;
+        match self.byte()? {
+            Header::VERSION => {}
+            version => return Err(self.fail(LoadErrorKind::Version(version))),
+        }
+        match self.byte()? {
+            0 => {}
+            format => return Err(self.fail(LoadErrorKind::Format(format))),
+        }
+        let fields = self.take(6)?;
+        self.header = header(fields).ok_or_else(|| self.fail(LoadErrorKind::Header))?;
+        Ok(())
+    }
+
+    fn function(&mut self) -> Result<Proto, LoadError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.fail(LoadErrorKind::TooDeep));
+        }
+        self.depth += 1;
+        let source = self.string()?;
+        let mut proto = Proto::new(source, self.int()?);
+        proto.last_line_defined = self.int()?;
+        proto.num_upvalues = self.byte()?;
+        proto.num_params = self.byte()?;
+        proto.is_vararg = self.byte()?;
+        proto.max_stack_size = self.byte()?;
+
+        for _ in 0..self.count()? {
+            let start = self.pos;
+            let instruction = Instruction(self.uint_of_size(4)? as u32);
+            if instruction.opcode().is_none() {
+                return Err(LoadError {
+                    kind: LoadErrorKind::Code(instruction.0),
+                    offset: start,
+                });
+            }
+            proto.code.push(instruction);
+        }
+
+        for _ in 0..self.count()? {
+            let start = self.pos;
+            let constant = match self.byte()? {
+                0 => Constant::Nil,
+                1 => Constant::Bool(self.byte()? != 0),
+                3 => Constant::Number(self.number()?),
+                4 => match self.string()? {
+                    Some(s) => Constant::String(s),
+                    None => {
+                        return Err(LoadError {
+                            kind: LoadErrorKind::Constant(4),
+                            offset: start,
+                        })
+                    }
+                },
+                tag => {
+                    return Err(LoadError {
+                        kind: LoadErrorKind::Constant(tag),
+                        offset: start,
+                    })
+                }
+            };
+            proto.constants.push(constant);
+        }
+        for _ in 0..self.count()? {
+            let child = self.function()?;
+            proto.protos.push(child);
+        }
+
+        for _ in 0..self.count()? {
+            let line = self.int()?;
+            proto.line_info.push(line);
+        }
+        for _ in 0..self.count()? {
+            let name = self.string()?.unwrap_or_default();
+            let start_pc = self.int()?;
+            let end_pc = self.int()?;
+            proto.locals.push(LocalVar {
+                name,
+                start_pc,
+                end_pc,
+            });
+        }
+        for _ in 0..self.count()? {
+            let name = self.string()?.unwrap_or_default();
+            proto.upvalues.push(name);
+        }
+        self.depth -= 1;
+        Ok(proto)
+    }
+}
+
+/// Reads a binary chunk of `luac` or `string.dump`
+///
+/// Functions whose source is the same as that of their parent have no
+/// [source](Proto::source), like in the chunk. Bytes after the main function
+/// are ignored, as by Lua.
+pub fn load(bytes: &[u8]) -> Result<(Header, Proto), LoadError> {
+    let mut loader = Loader {
+        bytes,
+        pos: 0,
+        header: Header::default(),
+        depth: 0,
+    };
+    loader.header()?;
+    let proto = loader.function()?;
+    Ok((loader.header, proto))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::{load, LoadErrorKind};
+    use crate::{
+        ast,
+        bytecode::{compile, Header, Proto},
+        Dialect, Utf8Decoder,
+    };
+
+    fn proto(source: &str) -> Proto {
+        let chunk = ast::parse_str(source).unwrap();
+        compile(
+            &chunk,
+            Utf8Decoder::new(source),
+            Dialect::default(),
+            "@test.lua",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let main = proto(
+            "local t = {1, 2.5, 'x', nil, true}
+            local function f(a, ...)
+                return function() return a, t end, select('#', ...)
+            end
+            for i = -3, 3 do print(f(i)) end",
+        );
+        let headers = [
+            Header::default(),
+            Header {
+                little_endian: false,
+                int_size: 2,
+                size_t_size: 4,
+                ..Header::default()
+            },
+        ];
+        for header in headers {
+            assert_eq!(load(&main.dump(&header, false)), Ok((header, main.clone())));
+        }
+
+        // `-3` to `3` fit in every number type
+        let main = proto("for i = -3, 3 do print(i) end");
+        for (number_size, integral) in [(4, false), (1, true), (8, true)] {
+            let header = Header {
+                number_size,
+                integral,
+                ..Header::default()
+            };
+            assert_eq!(load(&main.dump(&header, false)), Ok((header, main.clone())));
+        }
+
+        let (_, stripped) = load(&main.dump(&Header::default(), true)).unwrap();
+        assert_eq!(stripped.source, None);
+        assert_eq!(stripped.code, main.code);
+        assert!(stripped.line_info.is_empty() && stripped.locals.is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let kind = |bytes: &[u8]| load(bytes).unwrap_err().kind();
+        let bytes = proto("local x = 'a' .. 1").dump(&Header::default(), false);
+        for len in 0..bytes.len() {
+            assert_eq!(kind(&bytes[..len]), LoadErrorKind::Truncated);
+        }
+        assert_eq!(kind(b"return 1"), LoadErrorKind::NotAChunk);
+        assert_eq!(kind(b"\x1bLuaR\0"), LoadErrorKind::Version(0x52));
+        assert_eq!(kind(b"\x1bLuaQ\x01"), LoadErrorKind::Format(1));
+        assert_eq!(
+            kind(b"\x1bLuaQ\0\x01\x04\x08\x04\x08\x02"),
+            LoadErrorKind::Header
+        );
+        assert_eq!(
+            kind(b"\x1bLuaQ\0\x01\x04\x08\x02\x08\x00"),
+            LoadErrorKind::Header
+        );
+
+        let mut bad = bytes.clone();
+        // The tag of the first constant, after 4 instructions
+        let tag = 12 + 8 + 10 + 4 + 4 + 4 + 4 + 16 + 4;
+        assert_eq!(bad[tag], 4);
+        bad[tag] = 2;
+        let error = load(&bad).unwrap_err();
+        assert_eq!(error.kind(), LoadErrorKind::Constant(2));
+        assert_eq!(error.offset(), tag);
+        bad[tag - 20] = 0x3f;
+        assert_eq!(kind(&bad), LoadErrorKind::Code(0x3f));
+
+        let mut bad = bytes;
+        // The line defined of the main function
+        bad[12 + 8 + 10..12 + 8 + 14].copy_from_slice(&[0xff; 4]);
+        assert_eq!(kind(&bad), LoadErrorKind::Integer);
+    }
+
+    #[test]
+    fn test_too_deep() {
+        let mut main = Proto::new(None, 0);
+        for _ in 1..200 {
+            let mut parent = Proto::new(None, 0);
+            parent.protos.push(main);
+            main = parent;
+        }
+        let bytes = main.dump(&Header::default(), false);
+        assert_eq!(load(&bytes).unwrap().1, main);
+
+        // A function without source, code and constants, with one child
+        let mut function = [0; 32];
+        function[28] = 1;
+        let mut bytes = bytes[..12].to_vec();
+        for _ in 0..100_000 {
+            bytes.extend_from_slice(&function);
+        }
+        let error = load(&bytes).unwrap_err();
+        assert_eq!(error.kind(), LoadErrorKind::TooDeep);
+        assert_eq!(error.offset(), 12 + 200 * 32);
+    }
+}
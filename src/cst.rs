@@ -0,0 +1,324 @@
+//! # Concrete Syntax Tree
+//!
+//! A lossless syntax tree built by [CstSink]. Every token of the input, including
+//! whitespace and comments, is attached to a [Node], so printing the tree
+//! reproduces the input byte-for-byte.
+//!
+//! This module requires the `alloc` feature.
+
+use alloc::vec::Vec;
+use core::{fmt, slice};
+
+use crate::{
+    encoding::ByteLen, Decoder, Dialect, Error, Events, Latin1Decoder, NodeKind, Parser, Position,
+    Token, TokenKind, Utf8Decoder,
+};
+
+/// A child of a [Node]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element<S> {
+    Node(Node<S>),
+    Token(Token<S>),
+}
+
+/// A node in the concrete syntax tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node<S> {
+    kind: NodeKind,
+    pub(crate) children: Vec<Element<S>>,
+}
+
+impl<S> Node<S> {
+    pub fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// The direct children of this node, in input order
+    pub fn children(&self) -> &[Element<S>] {
+        &self.children
+    }
+
+    /// The direct child nodes of this node
+    pub fn nodes(&self) -> impl Iterator<Item = &Node<S>> {
+        self.children.iter().filter_map(|child| match child {
+            Element::Node(node) => Some(node),
+            Element::Token(_) => None,
+        })
+    }
+
+    /// All tokens in this subtree, in input order
+    pub fn tokens(&self) -> Tokens<'_, S> {
+        Tokens {
+            stack: alloc::vec![self.children.iter()],
+        }
+    }
+}
+
+impl<S: Copy> Node<S> {
+    /// The start of the first token in this subtree
+    pub fn start(&self) -> Option<Position> {
+        self.tokens().next().map(Token::start)
+    }
+
+    /// The end of the last token in this subtree
+    pub fn end(&self) -> Option<Position> {
+        self.tokens().last().map(Token::end)
+    }
+
+    /// The tokens in this subtree that are not whitespace or comments
+    pub fn significant_tokens(&self) -> impl Iterator<Item = &Token<S>> {
+        self.tokens()
+            .filter(|token| !matches!(token.kind(), TokenKind::Whitespace | TokenKind::Comment))
+    }
+}
+
+impl<S: ?Sized + ByteLen> Node<&S> {
+    /// The original input covered by this node
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for token in self.tokens() {
+            bytes.extend_from_slice(token.span().as_bytes());
+        }
+        bytes
+    }
+}
+
+impl fmt::Display for Node<&str> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.tokens() {
+            f.write_str(token.span())?;
+        }
+        Ok(())
+    }
+}
+
+/// Depth-first iterator over the tokens of a [Node]
+pub struct Tokens<'a, S> {
+    stack: Vec<slice::Iter<'a, Element<S>>>,
+}
+
+impl<'a, S> Iterator for Tokens<'a, S> {
+    type Item = &'a Token<S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(Element::Token(token)) => return Some(token),
+                Some(Element::Node(node)) => self.stack.push(node.children.iter()),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// An [Events] handler that builds a [Node] tree
+#[derive(Debug, Clone)]
+pub struct CstSink<S> {
+    stack: Vec<Node<S>>,
+    root: Option<Node<S>>,
+    pub(crate) errors: Vec<Error>,
+}
+
+impl<S> CstSink<S> {
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            root: None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// The errors that the parser recovered from
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// The outermost node, once the parser has finished it
+    pub fn finish(self) -> Option<Node<S>> {
+        self.root
+    }
+}
+
+impl<S> Default for CstSink<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Events<S> for CstSink<S> {
+    fn enter_node(&mut self, kind: NodeKind, _start: Position) {
+        self.stack.push(Node::new(kind));
+    }
+
+    fn token(&mut self, token: Token<S>) {
+        if let Some(node) = self.stack.last_mut() {
+            node.children.push(Element::Token(token));
+        }
+    }
+
+    fn exit_node(&mut self, _kind: NodeKind, _end: Position) {
+        if let Some(node) = self.stack.pop() {
+            match self.stack.last_mut() {
+                Some(parent) => parent.children.push(Element::Node(node)),
+                None => self.root = Some(node),
+            }
+        }
+    }
+
+    fn error(&mut self, error: Error) {
+        self.errors.push(error);
+    }
+}
+
+/// Parse UTF-8 source text
+pub fn parse_str(text: &str) -> Result<Node<&str>, Error> {
+    parse_with(Utf8Decoder::new(text), Dialect::default())
+}
+
+/// Parse Latin-1 (Windows-1252) source bytes
+pub fn parse_latin1(bytes: &[u8]) -> Result<Node<&[u8]>, Error> {
+    parse_with(Latin1Decoder::new(bytes), Dialect::default())
+}
+
+/// Parse the input of any decoder with the syntax of a Lua version and its extensions
+pub fn parse_with<'a, D: Decoder<'a> + 'a>(
+    decoder: D,
+    dialect: Dialect,
+) -> Result<Node<&'a D::Slice>, Error> {
+    let mut sink = CstSink::new();
+    Parser::with_dialect(decoder, dialect)?.parse_events(&mut sink)?;
+    Ok(sink.finish().expect("parse_events finishes the main node"))
+}
+
+/// Parse the input of any decoder and recover from syntax errors
+///
+/// The skipped input is in [NodeKind::Error] nodes and [TokenKind::Error]
+/// tokens, so the tree still reproduces the input.
+pub fn parse_recovering<'a, D: Decoder<'a> + 'a>(
+    decoder: D,
+    dialect: Dialect,
+) -> (Node<&'a D::Slice>, Vec<Error>) {
+    let mut sink = CstSink::new();
+    Parser::recovering(decoder, dialect)
+        .parse_events(&mut sink)
+        .expect("a recovering parser reports all errors to the sink");
+    let errors = core::mem::take(&mut sink.errors);
+    (
+        sink.finish().expect("parse_events finishes the main node"),
+        errors,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use alloc::vec::Vec;
+    use std::string::ToString;
+
+    use super::{parse_latin1, parse_recovering, parse_str, Element, Node};
+    use crate::{Dialect, NodeKind, TokenKind, Utf8Decoder};
+
+    fn kinds<S>(node: &Node<S>) -> Vec<NodeKind> {
+        node.nodes().map(Node::kind).collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let text = "-- header\nlocal t = { x = 1, [2] = 'y'; z } -- trailing\n\n\
+            function t.f(a, ...)\n  return a + #t * -2, ...\nend\n\
+            if t then print(t:f(1)) elseif x then else --[[ long ]] end\n\
+            for i = 1, 10 do break end\nfor k, v in pairs(t) do t[k] = (v) end\n\
+            repeat local function g() end until true;\n";
+        let root = parse_str(text).unwrap();
+        assert_eq!(root.kind(), NodeKind::Main);
+        assert_eq!(root.to_string(), text);
+        assert_eq!(root.to_bytes(), text.as_bytes());
+        assert!(parse_str("local = 1").is_err());
+    }
+
+    #[test]
+    fn test_trivia() {
+        let root = parse_str("  x = 1 -- one\n").unwrap();
+        let children: Vec<_> = root
+            .children()
+            .iter()
+            .map(|child| match child {
+                Element::Node(node) => Err(node.kind()),
+                Element::Token(token) => Ok(token.kind()),
+            })
+            .collect();
+        assert_eq!(
+            children,
+            [
+                Ok(TokenKind::Whitespace),
+                Err(NodeKind::Chunk),
+                Ok(TokenKind::Whitespace),
+                Ok(TokenKind::Comment),
+                Ok(TokenKind::Whitespace),
+            ]
+        );
+
+        let chunk = root.nodes().next().unwrap();
+        assert_eq!(kinds(chunk), [NodeKind::ExprStmt]);
+        let stmt = chunk.nodes().next().unwrap();
+        assert_eq!(stmt.to_string(), "x = 1");
+        assert_eq!(kinds(stmt), [NodeKind::Suffixed, NodeKind::ExprList]);
+        assert_eq!(stmt.start().unwrap().column, 3);
+        assert_eq!(stmt.significant_tokens().count(), 3);
+    }
+
+    #[test]
+    fn test_nodes() {
+        let root = parse_str("local t = {a.b, c = f(x)}").unwrap();
+        let local = root.nodes().next().unwrap().nodes().next().unwrap();
+        assert_eq!(local.kind(), NodeKind::Local);
+        assert_eq!(kinds(local), [NodeKind::NameList, NodeKind::ExprList]);
+        let table = local.nodes().nth(1).unwrap().nodes().next().unwrap();
+        let table = table.nodes().next().unwrap();
+        assert_eq!(table.kind(), NodeKind::Table);
+        let fields: Vec<_> = table.nodes().map(|field| field.to_string()).collect();
+        assert_eq!(fields, ["a.b", "c = f(x)"]);
+        let first = table.nodes().next().unwrap().nodes().next().unwrap();
+        assert_eq!(first.kind(), NodeKind::Expr);
+        assert_eq!(kinds(first), [NodeKind::Suffixed]);
+    }
+
+    #[test]
+    fn test_recovering() {
+        let text = "local = 1 -- x\nprint('a\nfunction f() return end end\n";
+        let (root, errors) = parse_recovering(Utf8Decoder::new(text), Dialect::default());
+        assert_eq!(root.to_string(), text);
+        let lines: Vec<_> = errors.iter().map(|e| e.position().line).collect();
+        assert_eq!(lines, [1, 2, 3]);
+        let chunk = root.nodes().next().unwrap();
+        assert_eq!(
+            kinds(chunk),
+            [
+                NodeKind::Local,
+                NodeKind::Error,
+                NodeKind::Function,
+                NodeKind::Error
+            ]
+        );
+        let skipped = chunk.nodes().nth(1).unwrap();
+        assert_eq!(skipped.to_string(), "= 1 -- x\nprint(");
+        let invalid = chunk.tokens().find(|t| t.kind() == TokenKind::Error);
+        assert_eq!(invalid.unwrap().span(), "'a");
+    }
+
+    #[test]
+    fn test_latin1() {
+        let bytes = b"return '\xe4' -- \xf6\n";
+        let root = parse_latin1(bytes).unwrap();
+        assert_eq!(root.to_bytes(), bytes);
+    }
+}
@@ -0,0 +1,814 @@
+//! # Deserializing Lua Data
+//!
+//! A [serde] deserializer for data files like
+//! `return { name = "sluap", tags = { "lua", "parser" } }`.
+//!
+//! The input is a single value, optionally after `return`, that is made of
+//! literals and table constructors:
+//!
+//! | Lua                          | Rust                                      |
+//! |------------------------------|-------------------------------------------|
+//! | `nil`                        | `None`, `()` and unit structs             |
+//! | `true`, `1`, `-2.5`, `"s"`   | booleans, numbers, strings and bytes      |
+//! | `{ 1, 2 }`                   | sequences and tuples                      |
+//! | `{ a = 1, [2] = 3 }`         | maps and structs                          |
+//! | `"Variant"`, `{ Variant = 1 }` | enums                                   |
+//!
+//! Positional fields of a table read as a map have the keys 1, 2, 3 and so
+//! on, as in Lua. Tables can be nested up to 128 deep. NaN and the
+//! infinities are read from `0/0`, `1/0` and `-1/0`.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+};
+use core::fmt;
+
+use serde::de::{
+    self,
+    value::{BorrowedStrDeserializer, StringDeserializer, U64Deserializer},
+    DeserializeSeed, IntoDeserializer, Visitor,
+};
+
+use crate::{
+    encoding::ByteLen, Cdata, Decoder, Dialect, ErrorKind, Expected, Keyword, Latin1Decoder, Lexer,
+    LuaVersion, Position, Symbol, Token, TokenKind, Utf8Decoder,
+};
+
+/// An error while deserializing, with the position where it occurred
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    message: String,
+    position: Option<Position>,
+}
+
+impl Error {
+    fn at(message: impl fmt::Display, position: Position) -> Self {
+        Self {
+            message: message.to_string(),
+            position: Some(position),
+        }
+    }
+
+    /// Set the position unless it is already known
+    fn or_at(mut self, position: Position) -> Self {
+        self.position.get_or_insert(position);
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The position of the token where the error occurred
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+}
+
+impl From<crate::Error> for Error {
+    fn from(e: crate::Error) -> Self {
+        Self::at(e.kind(), e.position())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{}: {}", position, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl de::StdError for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            message: msg.to_string(),
+            position: None,
+        }
+    }
+}
+
+fn unexpected<S: Copy>(expected: Expected, token: Token<S>) -> Error {
+    let kind = ErrorKind::UnexpectedToken {
+        expected,
+        found: token.kind(),
+    };
+    crate::Error::new(kind, token.start()).into()
+}
+
+/// A number literal, with its sign applied
+enum Number {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+}
+
+/// The maximum nesting of tables, as in `serde_json`
+const MAX_DEPTH: u8 = 128;
+
+/// Deserialize a value from UTF-8 source text
+///
+/// Numbers are read as in Lua 5.4, which keeps integers exact.
+pub fn from_str<'de, T: de::Deserialize<'de>>(text: &'de str) -> Result<T, Error> {
+    from_decoder(Utf8Decoder::new(text), LuaVersion::Lua54.into())
+}
+
+/// Deserialize a value from Latin-1 (Windows-1252) source bytes
+///
+/// Numbers are read as in Lua 5.4, which keeps integers exact.
+pub fn from_latin1<'de, T: de::Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, Error> {
+    from_decoder(Latin1Decoder::new(bytes), LuaVersion::Lua54.into())
+}
+
+/// Deserialize a value with the syntax of a dialect
+pub fn from_decoder<'de, D: Decoder<'de> + 'de, T: de::Deserialize<'de>>(
+    decoder: D,
+    dialect: Dialect,
+) -> Result<T, Error> {
+    let mut deserializer = Deserializer::new(decoder, dialect);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Reads a value from the tokens of a [Lexer]
+pub struct Deserializer<'de, D: Decoder<'de>> {
+    lexer: Lexer<D>,
+    peeked: Option<Token<&'de D::Slice>>,
+    /// Whether no token was read yet, so a `return` may follow
+    start: bool,
+    /// The number of tables that may still be nested
+    remaining_depth: u8,
+}
+
+impl<'de, D: Decoder<'de>> Deserializer<'de, D> {
+    pub fn new(decoder: D, dialect: Dialect) -> Self {
+        Self {
+            lexer: Lexer::with_dialect(decoder, dialect),
+            peeked: None,
+            start: true,
+            remaining_depth: MAX_DEPTH,
+        }
+    }
+
+    /// Check that only an optional `;` follows the value
+    pub fn end(&mut self) -> Result<(), Error> {
+        self.eat(Symbol::Semicolon)?;
+        let token = self.next()?;
+        match token.kind() {
+            TokenKind::Eof => Ok(()),
+            _ => Err(unexpected(Expected::Eof, token)),
+        }
+    }
+
+    /// The next token that is not whitespace or a comment
+    fn peek(&mut self) -> Result<Token<&'de D::Slice>, Error> {
+        if let Some(token) = self.peeked {
+            return Ok(token);
+        }
+        loop {
+            let token = self.lexer.token()?;
+            match token.kind() {
+                TokenKind::Whitespace | TokenKind::Comment => continue,
+                TokenKind::Keyword(Keyword::Return) if self.start => {}
+                _ => {
+                    self.peeked = Some(token);
+                    return Ok(token);
+                }
+            }
+            self.start = false;
+        }
+    }
+
+    fn next(&mut self) -> Result<Token<&'de D::Slice>, Error> {
+        let token = self.peek()?;
+        self.peeked = None;
+        self.start = false;
+        Ok(token)
+    }
+
+    /// Skip the next token if it is `symbol`
+    fn eat(&mut self, symbol: Symbol) -> Result<bool, Error> {
+        let found = self.peek()?.kind() == TokenKind::Symbol(symbol);
+        if found {
+            self.next()?;
+        }
+        Ok(found)
+    }
+
+    fn expect(&mut self, symbol: Symbol) -> Result<(), Error> {
+        let token = self.next()?;
+        match token.kind() {
+            TokenKind::Symbol(s) if s == symbol => Ok(()),
+            _ => Err(unexpected(Expected::Symbol(symbol), token)),
+        }
+    }
+
+    /// Run `f` on the next value, adding its position to errors without one
+    fn value<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self, Token<&'de D::Slice>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let token = self.peek()?;
+        f(self, token).map_err(|e| e.or_at(token.start()))
+    }
+
+    fn number(&mut self) -> Result<Number, Error> {
+        let negative = self.eat(Symbol::Minus)?;
+        let token = self.next()?;
+        let number = match token.kind() {
+            TokenKind::Integer(i) => Number::Int(i),
+            TokenKind::Hex(u) | TokenKind::Cdata(Cdata::UInt64(u)) => Number::UInt(u),
+            TokenKind::Cdata(Cdata::Int64(i)) => Number::Int(i),
+            TokenKind::Number(f) => Number::Float(f),
+            _ => return Err(unexpected(Expected::Expression, token)),
+        };
+        let number = match (negative, number) {
+            (false, number) => number,
+            (true, Number::Int(i)) => i
+                .checked_neg()
+                .map_or(Number::Float(-(i as f64)), Number::Int),
+            (true, Number::UInt(u)) => match 0i64.checked_sub_unsigned(u) {
+                Some(i) => Number::Int(i),
+                None => Number::Float(-(u as f64)),
+            },
+            (true, Number::Float(f)) => Number::Float(-f),
+        };
+        let slash = self.peek()?;
+        if slash.kind() != TokenKind::Symbol(Symbol::Slash) {
+            return Ok(number);
+        }
+        // The serializer writes NaN and the infinities as `0/0`, `1/0` and `-1/0`
+        self.next()?;
+        let numerator = match number {
+            Number::Int(i) => i as f64,
+            Number::UInt(u) => u as f64,
+            Number::Float(f) => f,
+        };
+        let zero = matches!(
+            self.next()?.kind(),
+            TokenKind::Integer(0) | TokenKind::Number(0.0)
+        );
+        match [-1.0, 0.0, 1.0].contains(&numerator) && zero {
+            true => Ok(Number::Float(numerator / 0.0)),
+            false => {
+                Err(<Error as de::Error>::custom("expected 0/0, 1/0 or -1/0").or_at(slash.start()))
+            }
+        }
+    }
+
+    /// The value of a string token, decoded as text
+    fn text(&mut self) -> Result<Cow<'de, str>, Error> {
+        let bytes = self.bytes()?;
+        D::Slice::decode(bytes).ok_or_else(|| de::Error::custom("invalid UTF-8 in string"))
+    }
+
+    fn bytes(&mut self) -> Result<Cow<'de, [u8]>, Error> {
+        let token = self.next()?;
+        match token.kind() {
+            TokenKind::String | TokenKind::LongString { .. } => Ok(token.string_value().unwrap()),
+            _ => Err(unexpected(Expected::Expression, token)),
+        }
+    }
+
+    fn table<V: Visitor<'de>>(&mut self, visitor: V, seq: bool) -> Result<V::Value, Error> {
+        let brace = self.peek()?.start();
+        self.expect(Symbol::BraceL)?;
+        self.nested(brace, |de| de.fields(visitor, seq))
+    }
+
+    /// Run `f` inside of the table that starts at `brace`
+    fn nested<T>(
+        &mut self,
+        brace: Position,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        self.remaining_depth = match self.remaining_depth.checked_sub(1) {
+            Some(depth) => depth,
+            None => return Err(Error::at("recursion limit exceeded", brace)),
+        };
+        let value = f(self)?;
+        self.remaining_depth += 1;
+        Ok(value)
+    }
+
+    /// Visit the fields of a table after its `{`
+    fn fields<V: Visitor<'de>>(&mut self, visitor: V, seq: bool) -> Result<V::Value, Error> {
+        let mut table = Table {
+            de: self,
+            index: 0,
+            first: true,
+            done: false,
+        };
+        let value = if seq {
+            visitor.visit_seq(&mut table)?
+        } else {
+            visitor.visit_map(&mut table)?
+        };
+        if table.has_next()? {
+            let token = self.peek()?;
+            return Err(Error::at("too many fields in table", token.start()));
+        }
+        Ok(value)
+    }
+}
+
+/// Decode the text of a name token
+fn name<S: ?Sized + ByteLen>(token: Token<&S>) -> Cow<'_, str> {
+    S::decode(Cow::Borrowed(token.span().as_bytes())).unwrap()
+}
+
+fn visit_text<'de, V: Visitor<'de>>(visitor: V, text: Cow<'de, str>) -> Result<V::Value, Error> {
+    match text {
+        Cow::Borrowed(text) => visitor.visit_borrowed_str(text),
+        Cow::Owned(text) => visitor.visit_string(text),
+    }
+}
+
+fn seed_text<'de, T: DeserializeSeed<'de>>(
+    seed: T,
+    text: Cow<'de, str>,
+) -> Result<T::Value, Error> {
+    match text {
+        Cow::Borrowed(text) => seed.deserialize(BorrowedStrDeserializer::new(text)),
+        Cow::Owned(text) => seed.deserialize(StringDeserializer::new(text)),
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.value(|de, _| match de.number()? {
+                    Number::Int(i) => visitor.visit_i64(i),
+                    Number::UInt(u) => visitor.visit_u64(u),
+                    // Floats with an integral value, e.g. from Lua 5.1 or 5.2
+                    Number::Float(f) if f.fract() == 0.0 && f >= 0.0 && f < u64::MAX as f64 => {
+                        visitor.visit_u64(f as u64)
+                    }
+                    Number::Float(f) if f.fract() == 0.0 && f >= i64::MIN as f64 && f < 0.0 => {
+                        visitor.visit_i64(f as i64)
+                    }
+                    Number::Float(f) => visitor.visit_f64(f),
+                })
+            }
+        )*
+    };
+}
+
+impl<'de, D: Decoder<'de>> de::Deserializer<'de> for &mut Deserializer<'de, D> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.value(|de, token| match token.kind() {
+            TokenKind::Keyword(Keyword::Nil) => {
+                de.next()?;
+                visitor.visit_unit()
+            }
+            TokenKind::Keyword(Keyword::True | Keyword::False) => de.deserialize_bool(visitor),
+            TokenKind::Symbol(Symbol::Minus)
+            | TokenKind::Integer(_)
+            | TokenKind::Hex(_)
+            | TokenKind::Number(_)
+            | TokenKind::Cdata(Cdata::Int64(_) | Cdata::UInt64(_)) => match de.number()? {
+                Number::Int(i) => visitor.visit_i64(i),
+                Number::UInt(u) => visitor.visit_u64(u),
+                Number::Float(f) => visitor.visit_f64(f),
+            },
+            TokenKind::String | TokenKind::LongString { .. } => de.deserialize_str(visitor),
+            TokenKind::Symbol(Symbol::BraceL) => {
+                de.next()?;
+                // A table that starts with a positional field is a sequence
+                let seq = !matches!(
+                    de.peek()?.kind(),
+                    TokenKind::Name | TokenKind::Symbol(Symbol::BracketL | Symbol::BraceR)
+                );
+                de.nested(token.start(), |de| de.fields(visitor, seq))
+            }
+            _ => Err(unexpected(Expected::Expression, token)),
+        })
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.value(|de, token| match de.next()?.kind() {
+            TokenKind::Keyword(Keyword::True) => visitor.visit_bool(true),
+            TokenKind::Keyword(Keyword::False) => visitor.visit_bool(false),
+            _ => Err(unexpected(Expected::Expression, token)),
+        })
+    }
+
+    deserialize_integer! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.value(|de, _| match de.number()? {
+            Number::Int(i) => visitor.visit_f64(i as f64),
+            Number::UInt(u) => visitor.visit_f64(u as f64),
+            Number::Float(f) => visitor.visit_f64(f),
+        })
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.value(|de, _| visit_text(visitor, de.text()?))
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.value(|de, _| match de.bytes()? {
+            Cow::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+            Cow::Owned(bytes) => visitor.visit_byte_buf(bytes),
+        })
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.peek()?.kind() == TokenKind::Keyword(Keyword::Nil) {
+            self.next()?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let token = self.next()?;
+        match token.kind() {
+            TokenKind::Keyword(Keyword::Nil) => visitor.visit_unit(),
+            found => Err(Error::at(
+                format_args!("'nil' expected near {}", found),
+                token.start(),
+            )),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.value(|de, _| de.table(visitor, true))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.value(|de, _| de.table(visitor, false))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.value(|de, token| match token.kind() {
+            TokenKind::String | TokenKind::LongString { .. } => {
+                visitor.visit_enum(de.text()?.into_deserializer())
+            }
+            TokenKind::Symbol(Symbol::BraceL) => {
+                de.next()?;
+                de.nested(token.start(), |de| {
+                    let value = visitor.visit_enum(Variant { de: &mut *de })?;
+                    if !de.eat(Symbol::Comma)? {
+                        de.eat(Symbol::Semicolon)?;
+                    }
+                    de.expect(Symbol::BraceR)?;
+                    Ok(value)
+                })
+            }
+            _ => Err(unexpected(Expected::Expression, token)),
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+}
+
+/// The fields of a table constructor after its `{`
+struct Table<'a, 'de, D: Decoder<'de>> {
+    de: &'a mut Deserializer<'de, D>,
+    /// The number of positional fields so far
+    index: u64,
+    first: bool,
+    /// Whether the closing `}` was read
+    done: bool,
+}
+
+impl<'de, D: Decoder<'de>> Table<'_, 'de, D> {
+    /// Move to the next field, or read the `}` and return false
+    fn has_next(&mut self) -> Result<bool, Error> {
+        if self.done {
+            return Ok(false);
+        }
+        if !self.first && !self.de.eat(Symbol::Comma)? && !self.de.eat(Symbol::Semicolon)? {
+            self.de.expect(Symbol::BraceR)?;
+            self.done = true;
+            return Ok(false);
+        }
+        self.first = false;
+        self.done = self.de.eat(Symbol::BraceR)?;
+        Ok(!self.done)
+    }
+}
+
+impl<'de, D: Decoder<'de>> de::SeqAccess<'de> for Table<'_, 'de, D> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if !self.has_next()? {
+            return Ok(None);
+        }
+        let token = self.de.peek()?;
+        if let TokenKind::Name | TokenKind::Symbol(Symbol::BracketL) = token.kind() {
+            return Err(Error::at("expected a positional field", token.start()));
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+
+impl<'de, D: Decoder<'de>> de::MapAccess<'de> for Table<'_, 'de, D> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if !self.has_next()? {
+            return Ok(None);
+        }
+        let token = self.de.peek()?;
+        let key = match token.kind() {
+            TokenKind::Name => {
+                self.de.next()?;
+                self.de.expect(Symbol::Assign)?;
+                seed_text(seed, name(token)).map_err(|e| e.or_at(token.start()))?
+            }
+            TokenKind::Symbol(Symbol::BracketL) => {
+                self.de.next()?;
+                let key = seed.deserialize(&mut *self.de)?;
+                self.de.expect(Symbol::BracketR)?;
+                self.de.expect(Symbol::Assign)?;
+                key
+            }
+            // A positional field, which is also the value
+            _ => {
+                self.index += 1;
+                seed.deserialize(U64Deserializer::new(self.index))
+                    .map_err(|e: Error| e.or_at(token.start()))?
+            }
+        };
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+/// An enum variant in a table with a single field, i.e. `{ Variant = value }`
+struct Variant<'a, 'de, D: Decoder<'de>> {
+    de: &'a mut Deserializer<'de, D>,
+}
+
+impl<'de, D: Decoder<'de>> de::EnumAccess<'de> for Variant<'_, 'de, D> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<(T::Value, Self), Error> {
+        let token = self.de.next()?;
+        let variant = match token.kind() {
+            TokenKind::Name => seed_text(seed, name(token)).map_err(|e| e.or_at(token.start()))?,
+            TokenKind::Symbol(Symbol::BracketL) => {
+                let variant = seed.deserialize(&mut *self.de)?;
+                self.de.expect(Symbol::BracketR)?;
+                variant
+            }
+            _ => return Err(unexpected(Expected::Name, token)),
+        };
+        self.de.expect(Symbol::Assign)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, D: Decoder<'de>> de::VariantAccess<'de> for Variant<'_, 'de, D> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{
+        borrow::Cow,
+        boxed::Box,
+        collections::BTreeMap,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+
+    use serde::{de::IgnoredAny, Deserialize};
+
+    use super::{from_latin1, from_str, Error};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct Package<'a> {
+        name: &'a str,
+        version: (u8, u8),
+        description: Option<String>,
+        tags: Vec<String>,
+        build: Build,
+        size: f64,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Build {
+        None,
+        Make(String),
+        Command { run: Vec<String> },
+    }
+
+    #[test]
+    fn test_struct() {
+        let text = r#"
+            -- A package
+            return {
+                name = "sluap",
+                version = { 0, 1 },
+                description = nil,
+                tags = { 'lua', [[parser]]; },
+                build = { Command = { run = { "cargo", "build" } } },
+                size = 1.5e3,
+            }
+        "#;
+        let package: Package = from_str(text).unwrap();
+        assert_eq!(
+            package,
+            Package {
+                name: "sluap",
+                version: (0, 1),
+                description: None,
+                tags: vec!["lua".to_string(), "parser".to_string()],
+                build: Build::Command {
+                    run: vec!["cargo".to_string(), "build".to_string()]
+                },
+                size: 1500.0,
+            }
+        );
+
+        assert_eq!(from_str::<Build>(r#""None""#), Ok(Build::None));
+        assert_eq!(
+            from_str::<Build>(r#"{ Make = "all" }"#),
+            Ok(Build::Make("all".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(from_str::<i64>("return -0x10;"), Ok(-16));
+        assert_eq!(from_str::<u64>("0xffffffffffffffff"), Ok(u64::MAX));
+        assert_eq!(from_str::<i32>("-2.0"), Ok(-2));
+        assert_eq!(from_str::<Option<bool>>("true"), Ok(Some(true)));
+        assert_eq!(from_str::<()>("nil"), Ok(()));
+        assert_eq!(from_str::<char>(r#""\65""#), Ok('A'));
+
+        let map: BTreeMap<u64, String> = from_str(r#"{ "a", [5] = "e", "b" }"#).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&2], "b");
+        assert_eq!(map[&5], "e");
+
+        // Escapes need an owned string, plain strings are borrowed
+        let text: Cow<str> = from_str(r#""a\tb""#).unwrap();
+        assert_eq!(text, "a\tb");
+        let bytes: &[u8] = from_str("'abc'").unwrap();
+        assert_eq!(bytes, b"abc");
+        assert_eq!(from_latin1::<String>(b"'caf\xe9'"), Ok("café".to_string()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text| from_str::<Package>(text).unwrap_err();
+        let position = |e: Error| e.position().map(|p| (p.line, p.column));
+        let at = |line, column| Some((line, column));
+
+        let e: Error = error("{ name = 'x', version = { 1, 300 } }");
+        assert_eq!(position(e.clone()), at(1, 30));
+        assert_eq!(
+            e.to_string(),
+            "1:30: invalid value: integer `300`, expected u8"
+        );
+        assert_eq!(position(error("{ name = 'x', size = }")), at(1, 22));
+        assert_eq!(position(error("{ name = 'x', other = 1 }")), at(1, 15));
+        assert_eq!(position(error("{\n  name = 'x',\n}")), at(1, 1));
+        assert_eq!(
+            from_str::<(u8, u8)>("{ 1, 2, 3 }").unwrap_err().to_string(),
+            "1:9: too many fields in table"
+        );
+        assert_eq!(
+            from_str::<u8>("1 2").unwrap_err().to_string(),
+            "1:3: <eof> expected near <number>"
+        );
+        assert_eq!(position(from_str::<String>("'abc").unwrap_err()), at(1, 1));
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let tables = |n| "{".repeat(n) + &"}".repeat(n);
+        assert!(from_str::<IgnoredAny>(&tables(128)).is_ok());
+        let e = from_str::<IgnoredAny>(&"{".repeat(200_000)).unwrap_err();
+        assert_eq!(e.to_string(), "1:129: recursion limit exceeded");
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        enum Nested {
+            A(Box<Nested>),
+            B,
+        }
+        let e = from_str::<Nested>(&"{ A = ".repeat(200_000)).unwrap_err();
+        assert_eq!(e.position().map(|p| p.column), Some(1 + 128 * 6));
+    }
+}
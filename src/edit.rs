@@ -0,0 +1,830 @@
+//! # Editing Lua Data Files
+//!
+//! A [Document] changes fields of table constructors in Lua source, e.g. a
+//! config file, and keeps the comments and whitespace of everything else.
+//!
+//! The root table is the table that the file returns, as in `return { ... }`,
+//! or, in a file without `return`, its global assignments, as in a rockspec
+//! with `version = "1.0-1"`. A [Table] navigates the tables by key and index.
+//! [Document::insert], [Document::replace] and [Document::remove] change the
+//! field at a path of keys and parse the text again.
+//!
+//! This module requires the `alloc` feature.
+
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{fmt, ops::Range};
+
+use crate::{
+    cst::{self, Element, Node},
+    encoding::ByteLen,
+    keywords::is_name,
+    unescape::quote_key,
+    Dialect, ErrorKind, Expected, NodeKind, Position, Symbol, Token, TokenKind, Utf8Decoder,
+};
+
+/// The key of a table field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key<'k> {
+    /// A string key, i.e. `name = ...` or `["name"] = ...`
+    Name(Cow<'k, str>),
+    /// A positional field, counting from 1, or an integer key like `[1] = ...`
+    Index(usize),
+}
+
+impl<'k> From<&'k str> for Key<'k> {
+    fn from(name: &'k str) -> Self {
+        Self::Name(Cow::Borrowed(name))
+    }
+}
+
+impl From<usize> for Key<'_> {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
+/// The reasons why an edit fails
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The input or a new value is not valid Lua
+    Syntax(crate::Error),
+    /// No field has the key
+    NotFound,
+    /// The value at the path is not a table constructor
+    NotTable,
+    /// A field with the key already exists
+    Exists,
+    /// The key can't be inserted, e.g. an index after the end of the table
+    InvalidKey,
+}
+
+impl From<crate::Error> for Error {
+    fn from(e: crate::Error) -> Self {
+        Self::Syntax(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(e) => e.fmt(f),
+            Self::NotFound => f.write_str("no field with this key"),
+            Self::NotTable => f.write_str("not a table constructor"),
+            Self::Exists => f.write_str("a field with this key already exists"),
+            Self::InvalidKey => f.write_str("this key can't be inserted"),
+        }
+    }
+}
+
+/// A range of the text and its replacement
+type Splice = (Range<usize>, String);
+
+/// Remove the spans from the tokens, which only keep their positions
+fn strip(node: &Node<&str>) -> Node<()> {
+    let mut stripped = Node::new(node.kind());
+    stripped.children = node
+        .children()
+        .iter()
+        .map(|child| match child {
+            Element::Node(node) => Element::Node(strip(node)),
+            Element::Token(t) => Element::Token(Token::new_at((), t.kind(), t.start(), t.end())),
+        })
+        .collect();
+    stripped
+}
+
+fn range(node: &Node<()>) -> Range<usize> {
+    let start = node.start().map_or(0, |p| p.offset);
+    start..node.end().map_or(start, |p| p.offset)
+}
+
+/// The start of the line of `at`, if only spaces and tabs precede it
+fn line_start(text: &str, at: usize) -> Option<usize> {
+    let before = text[..at].trim_end_matches([' ', '\t']);
+    (before.is_empty() || before.ends_with(['\n', '\r'])).then_some(before.len())
+}
+
+/// The start of the next line, if only spaces, tabs and a line comment follow `at`
+fn line_end(text: &str, at: usize) -> Option<usize> {
+    let mut rest = text[at..].trim_start_matches([' ', '\t']);
+    if let Some(comment) = rest.strip_prefix("--") {
+        let long = comment
+            .strip_prefix('[')
+            .is_some_and(|c| c.trim_start_matches('=').starts_with('['));
+        if !long {
+            rest = comment.trim_start_matches(|c| c != '\n' && c != '\r');
+        }
+    }
+    let rest = match rest.as_bytes() {
+        [] => rest,
+        [b'\r', b'\n', ..] | [b'\n', b'\r', ..] => &rest[2..],
+        [b'\r' | b'\n', ..] => &rest[1..],
+        _ => return None,
+    };
+    Some(text.len() - rest.len())
+}
+
+/// The parse error of a new value, with positions relative to the value
+fn value_error(kind: ErrorKind, mut pos: Position, prefix: usize) -> Error {
+    pos.offset -= prefix;
+    if pos.line == 1 {
+        pos.column -= prefix;
+    }
+    Error::Syntax(crate::Error::new(kind, pos))
+}
+
+/// Lua source that is edited in place
+#[derive(Debug, Clone)]
+pub struct Document {
+    text: String,
+    dialect: Dialect,
+    /// The syntax tree of the text, whose tokens only have positions
+    root: Node<()>,
+}
+
+impl Document {
+    pub fn parse(text: impl Into<String>) -> Result<Self, Error> {
+        Self::with_dialect(text, Dialect::default())
+    }
+
+    pub fn with_dialect(text: impl Into<String>, dialect: Dialect) -> Result<Self, Error> {
+        let text = text.into();
+        let root = strip(&cst::parse_with(Utf8Decoder::new(&text), dialect)?);
+        Ok(Self {
+            text,
+            dialect,
+            root,
+        })
+    }
+
+    /// The current source text
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The returned table, or the global assignments of a file without `return`
+    pub fn root(&self) -> Option<Table<'_>> {
+        let chunk = self.root.nodes().find(|n| n.kind() == NodeKind::Chunk)?;
+        match chunk.nodes().find(|n| n.kind() == NodeKind::Return) {
+            Some(ret) => {
+                let mut exprs = ret.nodes().flat_map(Node::nodes);
+                match (exprs.next(), exprs.next()) {
+                    (Some(expr), None) => self.value(expr).as_table(),
+                    _ => None,
+                }
+            }
+            None => Some(Table {
+                doc: self,
+                node: chunk,
+            }),
+        }
+    }
+
+    /// The value at a path of keys
+    pub fn get(&self, path: &[Key]) -> Option<Value<'_>> {
+        self.field(path)
+            .ok()
+            .map(|(_, field)| self.value(field.value))
+    }
+
+    /// Replace the value at a path with the source of an expression
+    pub fn replace(&mut self, path: &[Key], value: &str) -> Result<(), Error> {
+        self.check_value(value)?;
+        let (_, field) = self.field(path)?;
+        let splices = vec![(range(field.value), value.to_string())];
+        self.splice(&splices)
+    }
+
+    /// Insert a field into the table at a path
+    ///
+    /// An existing index moves the positional fields from there on back.
+    pub fn insert(&mut self, path: &[Key], key: Key, value: &str) -> Result<(), Error> {
+        self.check_value(value)?;
+        let table = self.table(path)?;
+        let fields = table.fields();
+        let existing = fields.iter().find(|f| f.key.as_ref() == Some(&key));
+
+        if table.node.kind() == NodeKind::Chunk {
+            let name = match key {
+                Key::Name(name) if is_name(&name) => name,
+                _ => return Err(Error::InvalidKey),
+            };
+            if existing.is_some() {
+                return Err(Error::Exists);
+            }
+            let field = format!("{} = {}", name, value);
+            let splices = self.append_stmt(table.node, &field);
+            return self.splice(&splices);
+        }
+
+        let splices = match key {
+            Key::Name(name) => {
+                if existing.is_some() {
+                    return Err(Error::Exists);
+                }
+                let mut field = String::new();
+                quote_key(&mut field, &name);
+                field.push_str(" = ");
+                field.push_str(value);
+                self.append_field(table.node, &field)
+            }
+            Key::Index(index) => {
+                let positional: Vec<_> = fields.iter().filter(|f| f.positional).collect();
+                match index.checked_sub(1).map(|i| positional.get(i)) {
+                    Some(Some(field)) => self.insert_before(table.node, field.node, value),
+                    Some(None) if index == positional.len() + 1 => {
+                        self.append_field(table.node, value)
+                    }
+                    _ => return Err(Error::InvalidKey),
+                }
+            }
+        };
+        self.splice(&splices)
+    }
+
+    /// Remove the field at a path, with its line if it is on a line of its own
+    pub fn remove(&mut self, path: &[Key]) -> Result<(), Error> {
+        let (table, field) = self.field(path)?;
+        let text = &self.text;
+        let node = range(field.node);
+        let (prev, sep) = separators(table.node, field.node);
+        let end = sep.clone().map_or(node.end, |sep| sep.end);
+
+        let removed =
+            if let (Some(start), Some(end)) = (line_start(text, node.start), line_end(text, end)) {
+                start..end
+            } else if let Some(sep) = sep {
+                if line_end(text, sep.end).is_some() {
+                    text[..node.start].trim_end_matches([' ', '\t']).len()..sep.end
+                } else {
+                    let rest = text[sep.end..].trim_start_matches([' ', '\t']);
+                    node.start..text.len() - rest.len()
+                }
+            } else if let Some(prev) = prev {
+                prev.start..node.end
+            } else {
+                // Keep the space on one side of the only field
+                text[..node.start].trim_end_matches([' ', '\t']).len()..node.end
+            };
+        self.splice(&[(removed, String::new())])
+    }
+
+    fn value<'d>(&'d self, node: &'d Node<()>) -> Value<'d> {
+        Value { doc: self, node }
+    }
+
+    /// The text of a token
+    fn span(&self, token: &Token<()>) -> &str {
+        &self.text[token.start().offset..token.end().offset]
+    }
+
+    /// The field at a path and the table that contains it
+    fn field(&self, path: &[Key]) -> Result<(Table<'_>, Field<'_>), Error> {
+        let (key, path) = path.split_last().ok_or(Error::NotFound)?;
+        let table = self.table(path)?;
+        let field = table.find(key).ok_or(Error::NotFound)?;
+        Ok((table, field))
+    }
+
+    /// The table constructor at a path
+    fn table(&self, path: &[Key]) -> Result<Table<'_>, Error> {
+        let mut table = self.root().ok_or(Error::NotTable)?;
+        for key in path {
+            let field = table.find(key).ok_or(Error::NotFound)?;
+            table = self.value(field.value).as_table().ok_or(Error::NotTable)?;
+        }
+        Ok(table)
+    }
+
+    /// Check that `value` is a single expression without comments around it
+    fn check_value(&self, value: &str) -> Result<(), Error> {
+        const PREFIX: &str = "return ";
+        let source = format!("{}{}", PREFIX, value);
+        let root = cst::parse_with(Utf8Decoder::new(&source), self.dialect)
+            .map_err(|e| value_error(e.kind(), e.position(), PREFIX.len()))?;
+        let expr = root
+            .nodes()
+            .flat_map(Node::nodes)
+            .flat_map(Node::nodes)
+            .flat_map(Node::nodes)
+            .next();
+        let expr = expr.and_then(|expr| Some(expr.start()?.offset..expr.end()?.offset));
+        let outside = root.tokens().find(|t| {
+            let offset = t.start().offset;
+            offset >= PREFIX.len()
+                && t.kind() != TokenKind::Whitespace
+                && !expr.as_ref().is_some_and(|e| e.contains(&offset))
+        });
+        match (expr, outside) {
+            (Some(_), None) => Ok(()),
+            (expr, token) => {
+                let expected = match expr {
+                    Some(_) => Expected::Eof,
+                    None => Expected::Expression,
+                };
+                let (found, pos) = match token {
+                    Some(t) => (t.kind(), t.start()),
+                    None => (TokenKind::Eof, root.end().unwrap_or(Position::START)),
+                };
+                let kind = ErrorKind::UnexpectedToken { expected, found };
+                Err(value_error(kind, pos, PREFIX.len()))
+            }
+        }
+    }
+
+    /// The line break of the text
+    fn newline(&self) -> &'static str {
+        if self.text.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+
+    /// The separator of the fields of a table, `,` by default
+    fn separator(&self, table: &Node<()>) -> &str {
+        table
+            .children()
+            .iter()
+            .find_map(|child| match child {
+                Element::Token(t) if is_separator(t) => Some(self.span(t)),
+                _ => None,
+            })
+            .unwrap_or(",")
+    }
+
+    /// Add a field after the last one, in the layout of the table
+    fn append_field(&self, table: &Node<()>, field: &str) -> Vec<Splice> {
+        let text = &self.text;
+        let sep = self.separator(table);
+        let last = table
+            .nodes()
+            .filter(|n| n.kind() == NodeKind::TableField)
+            .last();
+        let Some(last) = last else {
+            // After the `{`
+            let at = range(table).start + 1;
+            let space = if text[at..].starts_with(char::is_whitespace) {
+                ""
+            } else {
+                " "
+            };
+            return vec![(at..at, format!(" {}{}", field, space))];
+        };
+        let node = range(last);
+        let (_, trailing) = separators(table, last);
+        let end = trailing.clone().map_or(node.end, |sep| sep.end);
+
+        if let (Some(start), Some(line)) = (line_start(text, node.start), line_end(text, end)) {
+            let indent = &text[start..node.start];
+            let (added, sep) = match trailing {
+                Some(_) => ("", sep),
+                None => (sep, ""),
+            };
+            let newline = self.newline();
+            let insert = format!("{}{}{}{}", indent, field, sep, newline);
+            vec![
+                (node.end..node.end, added.to_string()),
+                (line..line, insert),
+            ]
+        } else if trailing.is_some() {
+            vec![(end..end, format!(" {}{}", field, sep))]
+        } else {
+            vec![(end..end, format!("{} {}", sep, field))]
+        }
+    }
+
+    /// Add a positional field before another one
+    fn insert_before(&self, table: &Node<()>, next: &Node<()>, value: &str) -> Vec<Splice> {
+        let sep = self.separator(table);
+        let at = range(next).start;
+        let insert = match line_start(&self.text, at) {
+            Some(start) => {
+                let indent = &self.text[start..at];
+                format!("{}{}{}{}", value, sep, self.newline(), indent)
+            }
+            None => format!("{}{} ", value, sep),
+        };
+        vec![(at..at, insert)]
+    }
+
+    /// Add a statement on a new line after the last one
+    fn append_stmt(&self, chunk: &Node<()>, stmt: &str) -> Vec<Splice> {
+        let text = &self.text;
+        let newline = self.newline();
+        let (at, indent) = match chunk.nodes().last() {
+            Some(last) => {
+                let node = range(last);
+                let indent =
+                    line_start(text, node.start).map_or("", |start| &text[start..node.start]);
+                (line_end(text, node.end), indent)
+            }
+            None => (Some(text.len()), ""),
+        };
+        let at = at.unwrap_or(range(chunk).end);
+        let mut insert = String::new();
+        if at == text.len() && !text.is_empty() && !text.ends_with(['\n', '\r']) {
+            insert.push_str(newline);
+        }
+        insert = format!("{}{}{}{}", insert, indent, stmt, newline);
+        vec![(at..at, insert)]
+    }
+
+    /// Replace ranges of the text, in ascending order, and parse it again
+    fn splice(&mut self, replacements: &[Splice]) -> Result<(), Error> {
+        let mut text = String::with_capacity(self.text.len());
+        let mut end = 0;
+        for (range, replacement) in replacements {
+            text.push_str(&self.text[end..range.start]);
+            text.push_str(replacement);
+            end = range.end;
+        }
+        text.push_str(&self.text[end..]);
+        *self = Self::with_dialect(text, self.dialect)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn is_separator(token: &Token<()>) -> bool {
+    matches!(
+        token.kind(),
+        TokenKind::Symbol(Symbol::Comma | Symbol::Semicolon)
+    )
+}
+
+/// The separators before and after a field or statement, if any
+fn separators(parent: &Node<()>, node: &Node<()>) -> (Option<Range<usize>>, Option<Range<usize>>) {
+    let children = parent.children();
+    let index = children
+        .iter()
+        .position(|child| matches!(child, Element::Node(n) if core::ptr::eq(n, node)))
+        .expect("the node is a child of its parent");
+    let significant = |child: &Element<()>| match child {
+        Element::Token(t) if matches!(t.kind(), TokenKind::Whitespace | TokenKind::Comment) => None,
+        Element::Token(t) if is_separator(t) => Some(Some(t.start().offset..t.end().offset)),
+        _ => Some(None),
+    };
+    let prev = children[..index]
+        .iter()
+        .rev()
+        .find_map(significant)
+        .flatten();
+    let next = children[index + 1..].iter().find_map(significant).flatten();
+    (prev, next)
+}
+
+/// A field of a table constructor, or a global assignment
+struct Field<'d> {
+    /// The `TableField` or `ExprStmt`
+    node: &'d Node<()>,
+    key: Option<Key<'d>>,
+    /// Whether the field has no key
+    positional: bool,
+    value: &'d Node<()>,
+}
+
+/// A table constructor in a [Document]
+#[derive(Debug, Clone, Copy)]
+pub struct Table<'d> {
+    doc: &'d Document,
+    /// The `Table`, or the `Chunk` for global assignments
+    node: &'d Node<()>,
+}
+
+impl<'d> Table<'d> {
+    /// The value of a field
+    pub fn get<'k>(&self, key: impl Into<Key<'k>>) -> Option<Value<'d>> {
+        let field = self.find(&key.into())?;
+        Some(self.doc.value(field.value))
+    }
+
+    /// The number of fields, including those with other keys than names and indices
+    pub fn len(&self) -> usize {
+        self.fields().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The fields with names and indices as keys, in source order
+    pub fn iter(&self) -> impl Iterator<Item = (Key<'d>, Value<'d>)> {
+        let doc = self.doc;
+        self.fields()
+            .into_iter()
+            .filter_map(move |field| Some((field.key?, doc.value(field.value))))
+    }
+
+    fn find(&self, key: &Key) -> Option<Field<'d>> {
+        self.fields()
+            .into_iter()
+            .find(|field| field.key.as_ref() == Some(key))
+    }
+
+    fn fields(&self) -> Vec<Field<'d>> {
+        let doc = self.doc;
+        if self.node.kind() == NodeKind::Chunk {
+            return self
+                .node
+                .nodes()
+                .filter_map(|stmt| global_assignment(doc, stmt))
+                .collect();
+        }
+        let mut index = 0;
+        self.node
+            .nodes()
+            .filter(|n| n.kind() == NodeKind::TableField)
+            .filter_map(|node| {
+                let value = node.nodes().last()?;
+                let first = node.significant_tokens().next();
+                let (key, positional) = match first.map(Token::kind) {
+                    Some(TokenKind::Name) => {
+                        let name = doc.span(first.unwrap());
+                        (Some(Key::Name(Cow::Borrowed(name))), false)
+                    }
+                    Some(TokenKind::Symbol(Symbol::BracketL)) if node.nodes().count() == 2 => {
+                        (doc.value(node.nodes().next()?).as_key(), false)
+                    }
+                    _ => {
+                        index += 1;
+                        (Some(Key::Index(index)), true)
+                    }
+                };
+                Some(Field {
+                    node,
+                    key,
+                    positional,
+                    value,
+                })
+            })
+            .collect()
+    }
+}
+
+/// An assignment of a single expression to a single name
+fn global_assignment<'d>(doc: &'d Document, stmt: &'d Node<()>) -> Option<Field<'d>> {
+    if stmt.kind() != NodeKind::ExprStmt {
+        return None;
+    }
+    let mut nodes = stmt.nodes();
+    let (target, exprs) = (nodes.next()?, nodes.next()?);
+    let mut names = target.children().iter();
+    let name = match (names.next(), names.next()) {
+        (Some(Element::Token(name)), None) if name.kind() == TokenKind::Name => doc.span(name),
+        _ => return None,
+    };
+    let is_assign = stmt.children().iter().any(
+        |child| matches!(child, Element::Token(t) if t.kind() == TokenKind::Symbol(Symbol::Assign)),
+    );
+    let mut values = exprs.nodes();
+    match (nodes.next(), is_assign, values.next(), values.next()) {
+        (None, true, Some(value), None) => Some(Field {
+            node: stmt,
+            key: Some(Key::Name(Cow::Borrowed(name))),
+            positional: false,
+            value,
+        }),
+        _ => None,
+    }
+}
+
+/// An expression in a [Document]
+#[derive(Debug, Clone, Copy)]
+pub struct Value<'d> {
+    doc: &'d Document,
+    /// The `Expr` node
+    node: &'d Node<()>,
+}
+
+impl<'d> Value<'d> {
+    /// The source text of the expression
+    pub fn source(&self) -> &'d str {
+        &self.doc.text[range(self.node)]
+    }
+
+    /// The position of the first byte of the expression
+    pub fn start(&self) -> Position {
+        self.node.start().unwrap_or(Position::START)
+    }
+
+    /// The table, if this is a table constructor
+    pub fn as_table(&self) -> Option<Table<'d>> {
+        match self.node.children() {
+            [Element::Node(node)] if node.kind() == NodeKind::Table => Some(Table {
+                doc: self.doc,
+                node,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The value of a string literal
+    pub fn as_str(&self) -> Option<Cow<'d, str>> {
+        let token = match self.node.children() {
+            [Element::Token(token)] => token,
+            _ => return None,
+        };
+        let token = Token::new_at(
+            self.doc.span(token),
+            token.kind(),
+            token.start(),
+            token.end(),
+        );
+        match token.kind() {
+            TokenKind::String | TokenKind::LongString { .. } => str::decode(token.string_value()?),
+            _ => None,
+        }
+    }
+
+    /// The key that a literal in brackets stands for
+    fn as_key(&self) -> Option<Key<'d>> {
+        let index = match self.node.children() {
+            [Element::Token(token)] => match token.kind() {
+                TokenKind::Integer(i) => usize::try_from(i).ok(),
+                TokenKind::Hex(u) => usize::try_from(u).ok(),
+                TokenKind::Number(f) if f.fract() == 0.0 && f >= 0.0 => Some(f as usize),
+                _ => None,
+            },
+            _ => None,
+        };
+        match index {
+            Some(0) => None,
+            Some(index) => Some(Key::Index(index)),
+            None => self.as_str().map(Key::Name),
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.source())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+
+    use super::{Document, Error, Key};
+
+    const CONFIG: &str = "\
+-- Build configuration
+return {
+    name = \"sluap\", -- the crate
+    version = 3;
+    deps = { \"memchr\", \"unicode-xid\" },
+    [\"with space\"] = true,
+    build = {
+        jobs = 4,
+        targets = {
+            \"x86\",
+            \"arm\", -- mobile
+        },
+    },
+}
+";
+
+    #[test]
+    fn test_navigate() {
+        let doc = Document::parse(CONFIG).unwrap();
+        let root = doc.root().unwrap();
+        assert_eq!(root.len(), 5);
+        assert_eq!(root.get("name").unwrap().as_str().unwrap(), "sluap");
+        assert_eq!(root.get("with space").unwrap().source(), "true");
+        let deps = root.get("deps").unwrap().as_table().unwrap();
+        assert_eq!(deps.get(2).unwrap().as_str().unwrap(), "unicode-xid");
+        assert!(deps.get(3).is_none());
+        let path = ["build".into(), "targets".into(), Key::Index(2)];
+        assert_eq!(doc.get(&path).unwrap().source(), "\"arm\"");
+        let keys: std::vec::Vec<_> = root.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys[1], Key::from("version"));
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut doc = Document::parse(CONFIG).unwrap();
+        doc.replace(&["version".into()], "4").unwrap();
+        doc.insert(&["build".into()], "release".into(), "true")
+            .unwrap();
+        doc.insert(&["build".into(), "targets".into()], 3.into(), "'wasm'")
+            .unwrap();
+        doc.insert(&["build".into(), "targets".into()], 1.into(), "'x64'")
+            .unwrap();
+        doc.insert(&["deps".into()], "odd key".into(), "1").unwrap();
+        doc.remove(&["name".into()]).unwrap();
+        doc.remove(&["deps".into(), 1.into()]).unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "\
+-- Build configuration
+return {
+    version = 4;
+    deps = { \"unicode-xid\", [\"odd key\"] = 1 },
+    [\"with space\"] = true,
+    build = {
+        jobs = 4,
+        targets = {
+            'x64',
+            \"x86\",
+            \"arm\", -- mobile
+            'wasm',
+        },
+        release = true,
+    },
+}
+"
+        );
+
+        assert_eq!(doc.insert(&[], "version".into(), "5"), Err(Error::Exists));
+        assert_eq!(doc.replace(&["missing".into()], "1"), Err(Error::NotFound));
+        assert_eq!(
+            doc.insert(&["version".into()], "x".into(), "1"),
+            Err(Error::NotTable)
+        );
+        assert_eq!(
+            doc.replace(&["version".into()], "1 -- comment")
+                .unwrap_err()
+                .to_string(),
+            "1:3: <eof> expected near <comment>"
+        );
+        assert_eq!(
+            doc.replace(&["version".into()], "1 +")
+                .unwrap_err()
+                .to_string(),
+            "1:4: expression expected near <eof>"
+        );
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut doc =
+            Document::parse("package = \"sluap\"\r\nversion = \"1.0-1\"\r\nlocal x = 1").unwrap();
+        assert_eq!(
+            doc.root()
+                .unwrap()
+                .get("version")
+                .unwrap()
+                .as_str()
+                .unwrap(),
+            "1.0-1"
+        );
+        doc.replace(&["version".into()], "\"1.1-1\"").unwrap();
+        doc.insert(&[], "license".into(), "\"MIT\"").unwrap();
+        doc.remove(&["package".into()]).unwrap();
+        assert_eq!(
+            doc.as_str(),
+            "version = \"1.1-1\"\r\nlocal x = 1\r\nlicense = \"MIT\"\r\n"
+        );
+
+        let mut doc = Document::parse("return {}").unwrap();
+        doc.insert(&[], "a".into(), "{ 1 }").unwrap();
+        doc.insert(&["a".into()], 2.into(), "2").unwrap();
+        doc.insert(&[], "b".into(), "2").unwrap();
+        assert_eq!(doc.as_str(), "return { a = { 1, 2 }, b = 2 }");
+        doc.remove(&["b".into()]).unwrap();
+        doc.remove(&["a".into(), 1.into()]).unwrap();
+        assert_eq!(doc.as_str(), "return { a = { 2 } }");
+        doc.remove(&["a".into(), 1.into()]).unwrap();
+        assert_eq!(doc.as_str(), "return { a = { } }");
+        doc.insert(&["a".into()], 1.into(), "'z'").unwrap();
+        assert_eq!(doc.as_str(), "return { a = { 'z' } }");
+        doc.remove(&["a".into()]).unwrap();
+        assert_eq!(doc.as_str(), "return { }");
+
+        let mut doc = Document::parse("return { 1, 2; 3, }").unwrap();
+        doc.remove(&[2.into()]).unwrap();
+        assert_eq!(doc.as_str(), "return { 1, 3, }");
+        doc.remove(&[2.into()]).unwrap();
+        assert_eq!(doc.as_str(), "return { 1, }");
+        doc.remove(&[1.into()]).unwrap();
+        assert_eq!(doc.as_str(), "return { }");
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut doc = Document::parse("return { x = 1 }").unwrap();
+        doc.insert(&[], "a\nb".into(), "2").unwrap();
+        doc.insert(&[], "]]".into(), "3").unwrap();
+        doc.insert(&[], "end".into(), "4").unwrap();
+        assert_eq!(
+            doc.as_str(),
+            "return { x = 1, [\"a\\nb\"] = 2, [\"]]\"] = 3, [\"end\"] = 4 }"
+        );
+        let root = doc.root().unwrap();
+        assert_eq!(root.get("a\nb").unwrap().source(), "2");
+        assert_eq!(root.get("]]").unwrap().source(), "3");
+    }
+}
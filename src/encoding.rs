@@ -1,3 +1,5 @@
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, string::String};
 use core::{
     fmt::{self, Write},
     str::Chars,
@@ -6,7 +8,13 @@ use core::{
 pub trait ByteLen {
     fn len(&self) -> usize;
 
+    fn as_bytes(&self) -> &[u8];
+
     fn split_at(&self, mid: usize) -> (&Self, &Self);
+
+    /// Decode bytes in the encoding of the input, e.g. a string value
+    #[cfg(feature = "alloc")]
+    fn decode(bytes: Cow<'_, [u8]>) -> Option<Cow<'_, str>>;
 }
 
 impl ByteLen for [u8] {
@@ -14,9 +22,25 @@ impl ByteLen for [u8] {
         self.len()
     }
 
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+
     fn split_at(&self, mid: usize) -> (&Self, &Self) {
         self.split_at(mid)
     }
+
+    #[cfg(feature = "alloc")]
+    fn decode(bytes: Cow<'_, [u8]>) -> Option<Cow<'_, str>> {
+        use alloc::string::ToString;
+
+        Some(match bytes {
+            Cow::Borrowed(bytes) if bytes.is_ascii() => {
+                Cow::Borrowed(core::str::from_utf8(bytes).ok()?)
+            }
+            bytes => Cow::Owned(Latin1Decoded(&bytes).to_string()),
+        })
+    }
 }
 
 impl ByteLen for str {
@@ -24,9 +48,21 @@ impl ByteLen for str {
         self.len()
     }
 
+    fn as_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+
     fn split_at(&self, mid: usize) -> (&Self, &Self) {
         self.split_at(mid)
     }
+
+    #[cfg(feature = "alloc")]
+    fn decode(bytes: Cow<'_, [u8]>) -> Option<Cow<'_, str>> {
+        match bytes {
+            Cow::Borrowed(bytes) => core::str::from_utf8(bytes).ok().map(Cow::Borrowed),
+            Cow::Owned(bytes) => String::from_utf8(bytes).ok().map(Cow::Owned),
+        }
+    }
 }
 
 /// Infallible decoder
@@ -55,6 +91,12 @@ pub struct Latin1Decoder<'a> {
     rest: &'a [u8],
 }
 
+impl<'a> Latin1Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { rest: bytes }
+    }
+}
+
 const W1252_X8: [char; 32] = [
     '\u{208c}', '\u{81}', '\u{201a}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
     '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{8D}', '\u{017D}', '\u{8F}',
@@ -167,8 +209,3 @@ mod tests {
         assert_eq!(win1252_char_decode(0xfb), 'û');
     }
 }
-impl<'a> Latin1Decoder<'a> {
-    pub fn new(bytes: &'a [u8]) -> Self {
-        Self { rest: bytes }
-    }
-}
@@ -0,0 +1,169 @@
+//! # Errors
+
+use core::fmt;
+
+use crate::{Keyword, Position, Symbol, TokenKind};
+
+/// What the parser was looking for when it found an unexpected token
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// A specific symbol
+    Symbol(Symbol),
+    /// An identifier
+    Name,
+    /// Any expression
+    Expression,
+    /// A statement
+    Statement,
+    /// `(`, `{` or a string after a method name
+    Arguments,
+    /// A name or `...` in a parameter list
+    Parameter,
+    /// A Luau type
+    Type,
+    /// The end of a block after `return` or `break`
+    BlockEnd,
+    /// The end of the input
+    Eof,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Self::Name => f.write_str("<name>"),
+            Self::Expression => f.write_str("expression"),
+            Self::Statement => f.write_str("statement"),
+            Self::Arguments => f.write_str("function arguments"),
+            Self::Parameter => f.write_str("<name> or '...'"),
+            Self::Type => f.write_str("type"),
+            Self::BlockEnd => f.write_str("end of block"),
+            Self::Eof => f.write_str("<eof>"),
+        }
+    }
+}
+
+/// The different kinds of lexer and parser errors
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// A character that does not start any token
+    UnexpectedChar(char),
+    /// A string without closing quote on the same line
+    UnterminatedString,
+    /// A `[[` string without matching `]]`
+    UnterminatedLongString,
+    /// A `--[[` comment without matching `]]`
+    UnterminatedLongComment,
+    /// `[=` not followed by more `=` and `[`
+    InvalidLongStringDelimiter,
+    /// A `[[` inside of `[[...]]`, which Lua 5.1 deprecates
+    NestedLongBracket,
+    /// A backslash followed by an unknown character
+    InvalidEscape,
+    /// A decimal escape greater than 255
+    EscapeTooLarge,
+    /// A numeric literal that can't be parsed
+    MalformedNumber,
+    /// The parser found a token it did not expect here
+    UnexpectedToken {
+        expected: Expected,
+        found: TokenKind,
+    },
+    /// A keyword that closes a construct is missing
+    MissingKeyword(Keyword),
+    /// The left side of an assignment is not a variable
+    InvalidAssignment,
+    /// The `(` of a call on a new line, which could start a new statement
+    AmbiguousSyntax,
+    /// An attribute other than `const` or `close`
+    UnknownAttribute,
+    /// Too many braces are open inside an interpolated string
+    NestingTooDeep,
+    /// The syntax tree is nested too deeply
+    TooManyLevels,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+            Self::UnterminatedString => f.write_str("unfinished string"),
+            Self::UnterminatedLongString => f.write_str("unfinished long string"),
+            Self::UnterminatedLongComment => f.write_str("unfinished long comment"),
+            Self::InvalidLongStringDelimiter => f.write_str("invalid long string delimiter"),
+            Self::NestedLongBracket => f.write_str("nesting of [[...]] is deprecated"),
+            Self::InvalidEscape => f.write_str("invalid escape sequence"),
+            Self::EscapeTooLarge => f.write_str("escape sequence too large"),
+            Self::MalformedNumber => f.write_str("malformed number"),
+            Self::UnexpectedToken { expected, found } => {
+                write!(f, "{} expected near {}", expected, found)
+            }
+            Self::MissingKeyword(keyword) => write!(f, "'{}' expected", keyword),
+            Self::InvalidAssignment => f.write_str("cannot assign to this expression"),
+            Self::AmbiguousSyntax => {
+                f.write_str("ambiguous syntax (function call x new statement)")
+            }
+            Self::UnknownAttribute => f.write_str("unknown attribute"),
+            Self::NestingTooDeep => f.write_str("string interpolation nested too deeply"),
+            Self::TooManyLevels => f.write_str("chunk has too many syntax levels"),
+        }
+    }
+}
+
+/// An error from the lexer or parser
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Error {
+    kind: ErrorKind,
+    pos: Position,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, pos: Position) -> Self {
+        Self { kind, pos }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The position in the input where the error was detected
+    pub fn position(&self) -> Position {
+        self.pos
+    }
+
+    /// The byte offset in the input where the error was detected
+    pub fn offset(&self) -> usize {
+        self.pos.offset
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pos, self.kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+
+    use super::{Error, ErrorKind, Expected};
+    use crate::{Keyword, Position, Symbol, TokenKind};
+
+    #[test]
+    fn test_display() {
+        let pos = Position {
+            offset: 12,
+            line: 2,
+            column: 5,
+        };
+        let error = Error::new(ErrorKind::MissingKeyword(Keyword::End), pos);
+        assert_eq!(error.to_string(), "2:5: 'end' expected");
+        let kind = ErrorKind::UnexpectedToken {
+            expected: Expected::Symbol(Symbol::Assign),
+            found: TokenKind::Eof,
+        };
+        assert_eq!(kind.to_string(), "'=' expected near <eof>");
+    }
+}
@@ -0,0 +1,601 @@
+//! # Formatter
+//!
+//! Prints canonical Lua source from a [cst::Node](crate::cst::Node). Because the
+//! concrete syntax tree keeps all trivia, comments are preserved, and at most one
+//! blank line between statements is kept.
+//!
+//! Table constructors and argument lists are printed on a single line if they
+//! fit into [Options::line_width], and with one item per line otherwise.
+//!
+//! This module requires the `alloc` feature.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    cst::{self, Element, Node},
+    encoding::ByteLen,
+    Error, InterpPart, NodeKind, Symbol, Token, TokenKind,
+};
+
+/// The indentation of nested blocks
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Indent {
+    /// The given number of spaces per level
+    Spaces(usize),
+    /// One tab per level, counted as 4 columns
+    Tabs,
+}
+
+/// The delimiters of short strings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Keep the quotes of the input
+    Preserve,
+    /// Use `"` unless the string contains a `"`
+    Double,
+    /// Use `'` unless the string contains a `'`
+    Single,
+}
+
+/// Formatting options
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub indent: Indent,
+    pub quote_style: QuoteStyle,
+    /// The maximum line width before tables and arguments are split
+    pub line_width: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            indent: Indent::Spaces(4),
+            quote_style: QuoteStyle::Double,
+            line_width: 80,
+        }
+    }
+}
+
+/// Everything but the output, to undo a layout attempt
+#[derive(Copy, Clone)]
+struct State {
+    indent: usize,
+    column: usize,
+    /// The number of line breaks written so far
+    lines: usize,
+    /// The next token goes on a new line
+    newline: bool,
+    /// The next token follows a blank line
+    blank: bool,
+    /// The previous token and its parent node, for spacing
+    prev: Option<(TokenKind, NodeKind)>,
+    /// Line breaks in the input since the last token
+    newlines: usize,
+    /// Nothing was written since the start of the current block
+    block_start: bool,
+    /// Don't try to split tables and arguments
+    flat: bool,
+}
+
+struct Formatter<'o> {
+    options: &'o Options,
+    out: Vec<u8>,
+    state: State,
+}
+
+/// Whether there must be a space between two tokens on the same line
+fn needs_space(prev: (TokenKind, NodeKind), cur: (TokenKind, NodeKind)) -> bool {
+    use TokenKind::Symbol as Sym;
+    match (prev, cur) {
+        // `t[ [[x]] ]` and `- -x` must not become `t[[[x]]]` and `--x`
+        ((Sym(Symbol::BracketL), _), (TokenKind::LongString { .. }, _)) => true,
+        ((TokenKind::LongString { .. }, _), (Sym(Symbol::BracketR), _)) => true,
+        ((Sym(Symbol::Minus), NodeKind::Unary), (Sym(Symbol::Minus) | TokenKind::Comment, _)) => {
+            true
+        }
+        ((Sym(Symbol::Minus | Symbol::Hash | Symbol::Tilde), NodeKind::Unary), _) => false,
+        // `local x: number`, unlike `a:b()`
+        ((Sym(Symbol::Colon), _), (_, NodeKind::Type)) => true,
+        ((Sym(Symbol::ParenL | Symbol::BracketL | Symbol::Dot | Symbol::Colon), _), _) => false,
+        (_, (TokenKind::Comment, _)) => true,
+        // `{x}` inside `` `a{x}b` ``, but Luau rejects `{{` and `}}` there
+        (
+            (TokenKind::InterpString(InterpPart::Begin | InterpPart::Mid), _),
+            (Sym(Symbol::BraceL), _),
+        ) => true,
+        (
+            (Sym(Symbol::BraceR), _),
+            (TokenKind::InterpString(InterpPart::Mid | InterpPart::End), _),
+        ) => true,
+        ((TokenKind::InterpString(InterpPart::Begin | InterpPart::Mid), _), _) => false,
+        (_, (TokenKind::InterpString(InterpPart::Mid | InterpPart::End), _)) => false,
+        // `Array<T>?`, `...T`, `T...` and `typeof(x)`
+        ((Sym(Symbol::Lt), NodeKind::Generics | NodeKind::Type), _) => false,
+        (_, (Sym(Symbol::Lt | Symbol::Gt), NodeKind::Generics | NodeKind::Type)) => false,
+        (_, (Sym(Symbol::Question), _)) => false,
+        ((Sym(Symbol::Dot3), NodeKind::Type), _) => false,
+        (
+            (TokenKind::Name, NodeKind::Type | NodeKind::Generics),
+            (Sym(Symbol::Dot3 | Symbol::ParenL), _),
+        ) => false,
+        // `::name::` and `local x <const>`
+        ((Sym(Symbol::Colon2), NodeKind::Label), (TokenKind::Name, _)) => false,
+        ((TokenKind::Name, NodeKind::Label), _) => false,
+        ((Sym(Symbol::Lt), NodeKind::Attrib), _) => false,
+        (_, (Sym(Symbol::Gt), NodeKind::Attrib)) => false,
+        ((Sym(Symbol::BraceL), _), (Sym(Symbol::BraceR), _)) => false,
+        (_, (Sym(Symbol::ParenL), NodeKind::Call | NodeKind::MethodCall | NodeKind::FuncBody)) => {
+            false
+        }
+        (_, (Sym(Symbol::BracketL), NodeKind::Index)) => false,
+        (_, (Sym(s), _)) => !matches!(
+            s,
+            Symbol::ParenR
+                | Symbol::BracketR
+                | Symbol::Comma
+                | Symbol::Semicolon
+                | Symbol::Dot
+                | Symbol::Colon
+        ),
+        _ => true,
+    }
+}
+
+/// Whether a comment extends to the end of the line
+fn is_line_comment(comment: &[u8]) -> bool {
+    match comment.strip_prefix(b"--[") {
+        Some(rest) => {
+            let level = rest.iter().take_while(|&&b| b == b'=').count();
+            rest.get(level) != Some(&b'[')
+        }
+        None => true,
+    }
+}
+
+fn count_newlines(whitespace: &[u8]) -> usize {
+    match whitespace.iter().filter(|&&b| b == b'\n').count() {
+        0 => whitespace.iter().filter(|&&b| b == b'\r').count(),
+        n => n,
+    }
+}
+
+fn is_trivia(kind: TokenKind) -> bool {
+    matches!(kind, TokenKind::Whitespace | TokenKind::Comment)
+}
+
+fn first_token<S: Copy>(node: &Node<S>) -> Option<TokenKind> {
+    node.significant_tokens().next().map(Token::kind)
+}
+
+impl<'o> Formatter<'o> {
+    fn new(options: &'o Options) -> Self {
+        Self {
+            options,
+            out: Vec::new(),
+            state: State {
+                indent: 0,
+                column: 0,
+                lines: 0,
+                newline: false,
+                blank: false,
+                prev: None,
+                newlines: 0,
+                block_start: true,
+                flat: false,
+            },
+        }
+    }
+
+    /// Request a line break, and keep a blank line from the input if `blank` is set
+    fn newline(&mut self, blank: bool) {
+        if !self.out.is_empty() {
+            let state = &mut self.state;
+            state.newline = true;
+            state.blank |= blank && state.newlines >= 2 && !state.block_start;
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8], kind: TokenKind, parent: NodeKind) {
+        let state = &mut self.state;
+        if state.newline {
+            self.out.push(b'\n');
+            if state.blank {
+                self.out.push(b'\n');
+                state.lines += 1;
+            }
+            state.lines += 1;
+            state.column = 0;
+            for _ in 0..state.indent {
+                match self.options.indent {
+                    Indent::Spaces(width) => {
+                        self.out.extend(core::iter::repeat_n(b' ', width));
+                        state.column += width;
+                    }
+                    Indent::Tabs => {
+                        self.out.push(b'\t');
+                        state.column += 4;
+                    }
+                }
+            }
+        } else if let Some(prev) = state.prev {
+            if needs_space(prev, (kind, parent)) {
+                self.out.push(b' ');
+                state.column += 1;
+            }
+        }
+        self.out.extend_from_slice(bytes);
+        match bytes.iter().rposition(|&b| b == b'\n') {
+            Some(pos) => state.column = bytes.len() - pos - 1,
+            None => state.column += bytes.len(),
+        }
+        state.newline = false;
+        state.blank = false;
+        state.newlines = 0;
+        state.block_start = false;
+        state.prev = Some((kind, parent));
+    }
+
+    fn token<S: ?Sized + ByteLen>(&mut self, token: &Token<&S>, parent: NodeKind) {
+        let bytes = token.span().as_bytes();
+        match token.kind() {
+            TokenKind::Whitespace => self.state.newlines += count_newlines(bytes),
+            TokenKind::Comment => {
+                if self.state.newlines > 0 {
+                    self.newline(true);
+                }
+                self.write(bytes, TokenKind::Comment, parent);
+                if is_line_comment(bytes) {
+                    self.state.newline = true;
+                }
+            }
+            TokenKind::String => {
+                let quote = match self.options.quote_style {
+                    QuoteStyle::Preserve => bytes[0],
+                    QuoteStyle::Double => b'"',
+                    QuoteStyle::Single => b'\'',
+                };
+                let contents = &bytes[1..bytes.len() - 1];
+                if quote == bytes[0] || contents.contains(&quote) {
+                    self.write(bytes, TokenKind::String, parent);
+                } else {
+                    let mut converted = Vec::with_capacity(bytes.len());
+                    converted.push(quote);
+                    converted.extend_from_slice(contents);
+                    converted.push(quote);
+                    self.write(&converted, TokenKind::String, parent);
+                }
+            }
+            kind => self.write(bytes, kind, parent),
+        }
+    }
+
+    fn node<S: ?Sized + ByteLen>(&mut self, node: &Node<&S>) {
+        match node.kind() {
+            NodeKind::Chunk => self.chunk(node),
+            NodeKind::Table => self.try_flat(node, Self::table::<S>),
+            // Only `'(' explist ')'` arguments have an expression list
+            NodeKind::Call | NodeKind::MethodCall
+                if node.nodes().any(|args| args.kind() == NodeKind::ExprList) =>
+            {
+                self.try_flat(node, Self::call::<S>)
+            }
+            _ => self.children(node),
+        }
+    }
+
+    /// Print `node` on a single line if possible, and with `broken` otherwise
+    fn try_flat<S: ?Sized + ByteLen>(
+        &mut self,
+        node: &Node<&S>,
+        broken: fn(&mut Self, &Node<&S>, bool),
+    ) {
+        if self.state.flat {
+            return broken(self, node, false);
+        }
+        let (len, state) = (self.out.len(), self.state);
+        self.state.flat = true;
+        broken(self, node, false);
+        self.state.flat = false;
+        // A pending line break before the first token is fine
+        let lines = state.lines + state.newline as usize + state.blank as usize;
+        if self.state.lines == lines && self.state.column <= self.options.line_width {
+            return;
+        }
+        self.out.truncate(len);
+        self.state = state;
+        broken(self, node, true);
+    }
+
+    fn children<S: ?Sized + ByteLen>(&mut self, node: &Node<&S>) {
+        // Trivia after a block are indented like the block, and the closing
+        // keyword goes on a new line if the block is not empty
+        let mut after_block = None;
+        let children = node.children();
+        for (i, child) in children.iter().enumerate() {
+            match child {
+                Element::Token(token) if is_trivia(token.kind()) => {
+                    // Comments before a block are indented like its statements
+                    let inner = after_block.is_none()
+                        && node.kind() != NodeKind::Main
+                        && children[i + 1..].iter().find_map(|child| match child {
+                            Element::Node(node) => Some(node.kind() == NodeKind::Chunk),
+                            Element::Token(token) => (!is_trivia(token.kind())).then_some(false),
+                        }) == Some(true);
+                    self.state.indent += inner as usize;
+                    self.token(token, node.kind());
+                    self.state.indent -= inner as usize;
+                }
+                Element::Token(token) => {
+                    if let Some(non_empty) = after_block.take() {
+                        self.leave_block(non_empty);
+                    }
+                    self.token(token, node.kind());
+                }
+                Element::Node(child)
+                    if child.kind() == NodeKind::Chunk && node.kind() != NodeKind::Main =>
+                {
+                    self.state.indent += 1;
+                    self.state.block_start = true;
+                    self.chunk(child);
+                    after_block = Some(child.tokens().next().is_some());
+                }
+                Element::Node(child) => {
+                    if let Some(non_empty) = after_block.take() {
+                        self.leave_block(non_empty);
+                    }
+                    if matches!(child.kind(), NodeKind::ElseIf | NodeKind::Else) {
+                        self.newline(false);
+                    }
+                    self.node(child);
+                }
+            }
+        }
+        // The closing keyword of `elseif` and `else` blocks is in the parent
+        if let Some(non_empty) = after_block {
+            self.leave_block(non_empty);
+        }
+    }
+
+    fn leave_block(&mut self, non_empty: bool) {
+        self.state.indent -= 1;
+        if non_empty {
+            self.newline(false);
+        }
+    }
+
+    fn chunk<S: ?Sized + ByteLen>(&mut self, chunk: &Node<&S>) {
+        let children = chunk.children();
+        for (i, child) in children.iter().enumerate() {
+            match child {
+                Element::Token(token) if token.kind() == TokenKind::Symbol(Symbol::Semicolon) => {
+                    // `a = b; (f)()` is ambiguous without the semicolon
+                    let next = children[i + 1..].iter().find_map(|child| match child {
+                        Element::Node(stmt) => first_token(stmt),
+                        Element::Token(_) => None,
+                    });
+                    if next == Some(TokenKind::Symbol(Symbol::ParenL)) {
+                        self.token(token, NodeKind::Chunk);
+                    }
+                }
+                Element::Token(token) => self.token(token, NodeKind::Chunk),
+                Element::Node(stmt) => {
+                    self.newline(true);
+                    self.node(stmt);
+                }
+            }
+        }
+    }
+
+    /// `'{' [fieldlist] '}'`, with `,` separators and a trailing `,` if split
+    fn table<S: ?Sized + ByteLen>(&mut self, table: &Node<&S>, broken: bool) {
+        let fields = table.nodes().count();
+        let broken = broken && fields > 0;
+        let mut seen = 0;
+        for child in table.children() {
+            match child {
+                Element::Token(token) => match token.kind() {
+                    TokenKind::Symbol(Symbol::Comma | Symbol::Semicolon) => {}
+                    TokenKind::Symbol(Symbol::BraceR) => {
+                        if broken {
+                            self.state.indent -= 1;
+                            self.newline(false);
+                        }
+                        self.token(token, NodeKind::Table);
+                    }
+                    _ => {
+                        self.token(token, NodeKind::Table);
+                        if broken && token.kind() == TokenKind::Symbol(Symbol::BraceL) {
+                            self.state.indent += 1;
+                        }
+                    }
+                },
+                Element::Node(field) => {
+                    if broken {
+                        self.newline(false);
+                    }
+                    self.node(field);
+                    seen += 1;
+                    if broken || seen < fields {
+                        self.write(b",", TokenKind::Symbol(Symbol::Comma), NodeKind::Table);
+                    }
+                }
+            }
+        }
+    }
+
+    /// `'(' explist ')'`, with one argument per line if split
+    fn call<S: ?Sized + ByteLen>(&mut self, call: &Node<&S>, broken: bool) {
+        if !broken {
+            return self.children(call);
+        }
+        for child in call.children() {
+            match child {
+                Element::Token(token) => match token.kind() {
+                    TokenKind::Symbol(Symbol::ParenL) => {
+                        self.token(token, call.kind());
+                        self.state.indent += 1;
+                    }
+                    TokenKind::Symbol(Symbol::ParenR) => {
+                        self.state.indent -= 1;
+                        self.newline(false);
+                        self.token(token, call.kind());
+                    }
+                    _ => self.token(token, call.kind()),
+                },
+                Element::Node(list) if list.kind() == NodeKind::ExprList => {
+                    for item in list.children() {
+                        match item {
+                            Element::Token(token) => self.token(token, NodeKind::ExprList),
+                            Element::Node(expr) => {
+                                self.newline(false);
+                                self.node(expr);
+                            }
+                        }
+                    }
+                }
+                Element::Node(node) => self.node(node),
+            }
+        }
+    }
+}
+
+/// Format a parsed tree
+pub fn format_node<S: ?Sized + ByteLen>(node: &Node<&S>, options: &Options) -> Vec<u8> {
+    let mut formatter = Formatter::new(options);
+    formatter.node(node);
+    if !formatter.out.is_empty() {
+        formatter.out.push(b'\n');
+    }
+    formatter.out
+}
+
+/// Format UTF-8 source text
+pub fn format_str(text: &str, options: &Options) -> Result<String, Error> {
+    let bytes = format_node(&cst::parse_str(text)?, options);
+    Ok(String::from_utf8(bytes).expect("formatting only inserts ASCII"))
+}
+
+/// Format Latin-1 (Windows-1252) source bytes
+pub fn format_latin1(bytes: &[u8], options: &Options) -> Result<Vec<u8>, Error> {
+    Ok(format_node(&cst::parse_latin1(bytes)?, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_latin1, format_node, format_str, Indent, Options, QuoteStyle};
+    use crate::{cst, Dialect, LuaVersion, Utf8Decoder};
+
+    fn format(text: &str) -> alloc::string::String {
+        let formatted = format_str(text, &Options::default()).unwrap();
+        let again = format_str(&formatted, &Options::default()).unwrap();
+        assert_eq!(formatted, again, "formatting is idempotent");
+        formatted
+    }
+
+    #[test]
+    fn test_spacing() {
+        assert_eq!(format("x=1+2*-y"), "x = 1 + 2 * -y\n");
+        assert_eq!(format("local a,b=#t,not c"), "local a, b = #t, not c\n");
+        assert_eq!(
+            format("print ( a . b : c ( 1 ) [ 2 ] )"),
+            "print(a.b:c(1)[2])\n"
+        );
+        assert_eq!(format("f'x' g{1;2,} h{}"), "f \"x\"\ng { 1, 2 }\nh {}\n");
+        assert_eq!(format("x = - -y .. t[ [[s]] ]"), "x = - -y .. t[ [[s]] ]\n");
+        assert_eq!(
+            format("local f = function ( a , ... ) end"),
+            "local f = function(a, ...) end\n"
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let text =
+            "function f(a) if a then return 1 elseif b then else while true do break end end end";
+        assert_eq!(
+            format(text),
+            "function f(a)\n    if a then\n        return 1\n    elseif b then\n    else\n        \
+             while true do\n            break\n        end\n    end\nend\n"
+        );
+        assert_eq!(format("a = b; (f)(); x = 1;"), "a = b;\n(f)()\nx = 1\n");
+        assert_eq!(format("repeat x() until y"), "repeat\n    x()\nuntil y\n");
+    }
+
+    #[test]
+    fn test_comments() {
+        let text = "-- header\n\n\n\nlocal x = 1 -- one\n\
+            do\n-- inside\nend\n\
+            f(--[[a]] 1)\n\
+            t = { 1, -- first\n 2 }\n\
+            -- end\n";
+        assert_eq!(
+            format(text),
+            "-- header\n\nlocal x = 1 -- one\ndo\n    -- inside\nend\n\
+             f(--[[a]] 1)\nt = {\n    1, -- first\n    2,\n}\n-- end\n"
+        );
+        assert_eq!(
+            format("while a do b() --[[b]] end -- c"),
+            "while a do\n    b() --[[b]]\nend -- c\n"
+        );
+    }
+
+    #[test]
+    fn test_line_width() {
+        let options = Options {
+            indent: Indent::Tabs,
+            quote_style: QuoteStyle::Preserve,
+            line_width: 20,
+        };
+        let text = "call(first, 'second', { third = 3 })";
+        assert_eq!(
+            format_str(text, &options).unwrap(),
+            "call(\n\tfirst,\n\t'second',\n\t{ third = 3 }\n)\n"
+        );
+        assert_eq!(
+            format_str("t = {1, 2}", &options).unwrap(),
+            "t = { 1, 2 }\n"
+        );
+    }
+
+    #[test]
+    fn test_quotes() {
+        let single = Options {
+            quote_style: QuoteStyle::Single,
+            ..Options::default()
+        };
+        assert_eq!(
+            format_str("x = \"a\" .. \"b'c\"", &single).unwrap(),
+            "x = 'a' .. \"b'c\"\n"
+        );
+        assert_eq!(format("x = 'a' .. 'b\"c'"), "x = \"a\" .. 'b\"c'\n");
+        assert_eq!(
+            format_latin1(b"x='\xe4'", &Options::default()).unwrap(),
+            b"x = \"\xe4\"\n"
+        );
+    }
+
+    #[test]
+    fn test_versions() {
+        let text = "local x<const>,y=1 ::top:: x=~x//2 -- c\n goto top";
+        let root = cst::parse_with(Utf8Decoder::new(text), LuaVersion::Lua54.into()).unwrap();
+        assert_eq!(
+            format_node(&root, &Options::default()),
+            b"local x <const>, y = 1\n::top::\nx = ~x // 2 -- c\ngoto top\n"
+        );
+    }
+
+    #[test]
+    fn test_luau() {
+        let text = "type T<K...> ={[string]:Array<number>?,f:(x:number,...any)->()|nil}\n\
+            local function f<T>(a:T,...:number):...T x+=`a{ {b=1} }c{d}`::string continue end";
+        let root = cst::parse_with(Utf8Decoder::new(text), Dialect::LUAU).unwrap();
+        assert_eq!(
+            core::str::from_utf8(&format_node(&root, &Options::default())).unwrap(),
+            "type T<K...> = { [string]: Array<number>?, f: (x: number, ...any) -> () | nil }\n\
+             local function f<T>(a: T, ...: number): ...T\n\
+             \x20   x += `a{ { b = 1 } }c{d}` :: string\n\
+             \x20   continue\n\
+             end\n"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! # Incremental Parsing
+//!
+//! Updates a tree from [cst::parse_recovering] after an edit of the input.
+//! Only the innermost statement that contains the edit is lexed and parsed
+//! again. The tokens of the other subtrees are reused, with their spans
+//! taken from the new input and their positions shifted by the edit.
+//!
+//! This module requires the `alloc` feature.
+
+use alloc::vec::Vec;
+
+use crate::{
+    cst::{self, CstSink, Element, Node},
+    encoding::ByteLen,
+    Decoder, Dialect, Error, ErrorKind, NodeKind, Parser, Position, Token,
+};
+
+/// A change of the input, in byte offsets
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// The start of the changed range
+    pub start: usize,
+    /// The end of the replaced range in the old input
+    pub old_end: usize,
+    /// The end of the replacement in the new input
+    pub new_end: usize,
+}
+
+/// The kinds of nodes that [Parser::parse_stmt] starts
+fn is_stmt(kind: NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Do
+            | NodeKind::While
+            | NodeKind::Repeat
+            | NodeKind::If
+            | NodeKind::NumericFor
+            | NodeKind::GenericFor
+            | NodeKind::Function
+            | NodeKind::LocalFunction
+            | NodeKind::Local
+            | NodeKind::Return
+            | NodeKind::Break
+            | NodeKind::Goto
+            | NodeKind::Label
+            | NodeKind::Continue
+            | NodeKind::TypeAlias
+            | NodeKind::ExprStmt
+    )
+}
+
+fn last_token<S>(node: &Node<S>) -> Option<&Token<S>> {
+    node.children().iter().rev().find_map(|child| match child {
+        Element::Token(token) => Some(token),
+        Element::Node(node) => last_token(node),
+    })
+}
+
+/// Whether the edit is strictly between the first and last byte of a node
+fn contains<S: Copy>(node: &Node<S>, edit: &Edit) -> bool {
+    match (node.tokens().next(), last_token(node)) {
+        (Some(first), Some(last)) => {
+            first.start().offset < edit.start && edit.old_end < last.end().offset
+        }
+        _ => false,
+    }
+}
+
+/// The child indices from the root to the innermost node that contains the edit
+fn find_path<S: Copy>(root: &Node<S>, edit: &Edit) -> Vec<usize> {
+    let mut path = Vec::new();
+    let mut node = root;
+    while let Some((index, child)) =
+        node.children()
+            .iter()
+            .enumerate()
+            .find_map(|(index, child)| match child {
+                Element::Node(child) if contains(child, edit) => Some((index, child)),
+                _ => None,
+            })
+    {
+        path.push(index);
+        node = child;
+    }
+    path
+}
+
+/// Maps the tokens outside of the reparsed statement to the new input
+struct Reuse<'a, T: ?Sized> {
+    input: &'a T,
+    /// The end of the reparsed statement before the edit
+    old_end: Position,
+    new_end: Position,
+}
+
+impl<'a, T: ?Sized + ByteLen> Reuse<'a, T> {
+    fn position(&self, pos: Position) -> Position {
+        if pos.offset < self.old_end.offset {
+            return pos;
+        }
+        Position {
+            offset: pos.offset - self.old_end.offset + self.new_end.offset,
+            line: pos.line - self.old_end.line + self.new_end.line,
+            column: match pos.line == self.old_end.line {
+                true => pos.column - self.old_end.column + self.new_end.column,
+                false => pos.column,
+            },
+        }
+    }
+
+    fn token<S: ?Sized>(&self, token: &Token<&S>) -> Token<&'a T> {
+        let (start, end) = (self.position(token.start()), self.position(token.end()));
+        let (before, _) = self.input.split_at(end.offset);
+        let span = before.split_at(start.offset).1;
+        Token::new_at(span, token.kind(), start, end)
+    }
+
+    /// Copy a node, replacing the node at the end of `path` with `stmt`
+    fn node<S: ?Sized>(
+        &self,
+        node: &Node<&S>,
+        path: &[usize],
+        stmt: &mut Option<Node<&'a T>>,
+    ) -> Node<&'a T> {
+        let mut copy = Node::new(node.kind());
+        for (index, child) in node.children().iter().enumerate() {
+            copy.children.push(match child {
+                Element::Node(child) if path.first() == Some(&index) => match path {
+                    [_] => Element::Node(stmt.take().expect("the path ends once")),
+                    _ => Element::Node(self.node(child, &path[1..], stmt)),
+                },
+                Element::Node(child) => Element::Node(self.node(child, &[], stmt)),
+                Element::Token(token) => Element::Token(self.token(token)),
+            });
+        }
+        copy
+    }
+}
+
+/// Parse the statement at the end of `path` again, if it stays the same kind
+/// of statement and ends at the same place
+fn reparse_at<'a, D: Decoder<'a> + 'a, S: ?Sized>(
+    old: &Node<&S>,
+    errors: &[Error],
+    path: &[usize],
+    decoder: D,
+    dialect: Dialect,
+    edit: Edit,
+) -> Option<(Node<&'a D::Slice>, Vec<Error>)> {
+    let mut open = Vec::new();
+    // Every level of the parser opens a node or consumes a token before the
+    // next one, so this is at least the nesting of the statement
+    let mut levels = 0;
+    let mut node = old;
+    for &index in path {
+        open.push(node.kind());
+        let before = &node.children()[..index];
+        levels += 1 + before
+            .iter()
+            .filter(|child| matches!(child, Element::Token(_)))
+            .count();
+        node = match &node.children()[index] {
+            Element::Node(child) => child,
+            Element::Token(_) => return None,
+        };
+    }
+    // The lexer can't continue inside of an interpolated string
+    if !is_stmt(node.kind()) || open.contains(&NodeKind::InterpString) {
+        return None;
+    }
+    let start = node.tokens().next()?.start();
+    let old_end = last_token(node)?.end();
+
+    let input = decoder.as_slice();
+    let mut sink = CstSink::new();
+    let mut parser = Parser::resume(decoder, dialect, start, &open, levels);
+    parser.parse_stmt_events(&mut sink).ok()?;
+    let new_errors = core::mem::take(&mut sink.errors);
+    // The full parse may nest less deeply
+    if new_errors
+        .iter()
+        .any(|e| e.kind() == ErrorKind::TooManyLevels)
+    {
+        return None;
+    }
+    let stmt = sink.finish()?;
+    let new_end = last_token(&stmt)?.end();
+    if stmt.kind() != node.kind() || new_end.offset + edit.old_end != old_end.offset + edit.new_end
+    {
+        return None;
+    }
+
+    let reuse = Reuse {
+        input,
+        old_end,
+        new_end,
+    };
+    let tree = reuse.node(old, path, &mut Some(stmt));
+    let before = errors.iter().filter(|e| e.offset() < start.offset);
+    let after = errors.iter().filter(|e| e.offset() >= old_end.offset);
+    let errors = before
+        .copied()
+        .chain(new_errors)
+        .chain(after.map(|e| Error::new(e.kind(), reuse.position(e.position()))))
+        .collect();
+    Some((tree, errors))
+}
+
+/// Parse the input after an edit, reusing the unchanged parts of the old tree
+///
+/// `old` and `errors` are the result of [cst::parse_recovering] or of this
+/// function for the input before the edit. `decoder` reads the entire input
+/// after the edit. The result is the same as from [cst::parse_recovering],
+/// which this falls back to if no statement around the edit can be reused.
+pub fn reparse<'a, D: Decoder<'a> + 'a, S: ?Sized>(
+    old: &Node<&S>,
+    errors: &[Error],
+    decoder: D,
+    dialect: Dialect,
+    edit: Edit,
+) -> (Node<&'a D::Slice>, Vec<Error>) {
+    let path = find_path(old, &edit);
+    // Start with the innermost statement
+    for len in (1..=path.len()).rev() {
+        let decoder = decoder.clone();
+        if let Some(result) = reparse_at(old, errors, &path[..len], decoder, dialect, edit) {
+            return result;
+        }
+    }
+    cst::parse_recovering(decoder, dialect)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::String;
+
+    use super::{find_path, reparse, reparse_at, Edit};
+    use crate::{cst::parse_recovering, Dialect, LuaVersion, Utf8Decoder};
+
+    /// Replace `old` with `new` in `text` and check the result against a full parse
+    ///
+    /// Returns whether the innermost statement around the edit was reused.
+    fn check(text: &str, old: &str, new: &str, dialect: Dialect) -> bool {
+        let start = text.find(old).unwrap();
+        let edit = Edit {
+            start,
+            old_end: start + old.len(),
+            new_end: start + new.len(),
+        };
+        let mut edited = String::from(text);
+        edited.replace_range(start..edit.old_end, new);
+
+        let (tree, errors) = parse_recovering(Utf8Decoder::new(text), dialect);
+        let decoder = Utf8Decoder::new(&edited);
+        let result = reparse(&tree, &errors, decoder.clone(), dialect, edit);
+        assert_eq!(result, parse_recovering(decoder.clone(), dialect));
+
+        let path = find_path(&tree, &edit);
+        (1..=path.len())
+            .rev()
+            .find_map(|len| {
+                reparse_at(&tree, &errors, &path[..len], decoder.clone(), dialect, edit)
+            })
+            .is_some()
+    }
+
+    #[test]
+    fn test_reparse() {
+        let text =
+            "local function f(a)\n  if a then\n    return a + 1\n  end\nend\nprint(f(2)) -- x\n";
+        let lua51 = Dialect::default();
+        assert!(check(text, "a + 1", "a *\n\n  (2 + 3)", lua51));
+        assert!(check(text, "a then", "not a then", lua51));
+        // An error inside of the `if` is reported from its chunk
+        assert!(check(text, "return a + 1", "x = = 1", lua51));
+        // An extra `end` changes the structure of the whole input
+        assert!(!check(text, "+ 1", "end", lua51));
+        assert!(check("x = f('a')\ny = 2", "'a'", "'a',\n  'b'", lua51));
+        assert!(!check("x = 1", "1", "2", lua51));
+
+        let text = "local t = {\n  a = 1, -- a\n  b = { c = 'd' },\n}\nreturn t";
+        assert!(check(text, "'d'", "[[e\nf]]", LuaVersion::Lua54.into()));
+        assert!(check(text, "-- a", "--[[ a ]]", lua51));
+        assert!(!check(text, "1, -- a", "1, --[[ a", lua51));
+    }
+}
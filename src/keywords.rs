@@ -1,3 +1,7 @@
+use core::fmt;
+
+use crate::LuaVersion;
+
 /// Reserved Identifiers
 ///
 /// These identifiers have a special syntactic meaning.
@@ -25,6 +29,8 @@ pub enum Keyword {
     For,
     /// `function`
     Function,
+    /// `goto`, since Lua 5.2
+    Goto,
     /// `if`
     If,
     /// `in`
@@ -63,6 +69,7 @@ impl Keyword {
             b"false" => Some(Self::False),
             b"for" => Some(Self::For),
             b"function" => Some(Self::Function),
+            b"goto" => Some(Self::Goto),
             b"if" => Some(Self::If),
             b"in" => Some(Self::In),
             b"local" => Some(Self::Local),
@@ -78,6 +85,59 @@ impl Keyword {
             _ => None,
         }
     }
+
+    /// The first Lua version that reserves this keyword
+    pub fn since(self) -> LuaVersion {
+        match self {
+            Self::Goto => LuaVersion::Lua52,
+            _ => LuaVersion::Lua51,
+        }
+    }
+
+    /// Returns the source text of the keyword
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::And => "and",
+            Self::Break => "break",
+            Self::Do => "do",
+            Self::Else => "else",
+            Self::ElseIf => "elseif",
+            Self::End => "end",
+            Self::False => "false",
+            Self::For => "for",
+            Self::Function => "function",
+            Self::Goto => "goto",
+            Self::If => "if",
+            Self::In => "in",
+            Self::Local => "local",
+            Self::Nil => "nil",
+            Self::Not => "not",
+            Self::Or => "or",
+            Self::Repeat => "repeat",
+            Self::Return => "return",
+            Self::Then => "then",
+            Self::True => "true",
+            Self::Until => "until",
+            Self::While => "while",
+        }
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whether `text` can be a table key without brackets
+#[cfg(feature = "alloc")]
+pub(crate) fn is_name(text: &str) -> bool {
+    let mut bytes = text.bytes();
+    bytes
+        .next()
+        .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_')
+        && bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+        && Keyword::from_bytes(text.as_bytes()).is_none()
 }
 
 #[cfg(test)]
@@ -95,6 +155,7 @@ mod tests {
         assert_eq!(Keyword::from_bytes(b"false"), Some(Keyword::False));
         assert_eq!(Keyword::from_bytes(b"for"), Some(Keyword::For));
         assert_eq!(Keyword::from_bytes(b"function"), Some(Keyword::Function));
+        assert_eq!(Keyword::from_bytes(b"goto"), Some(Keyword::Goto));
         assert_eq!(Keyword::from_bytes(b"if"), Some(Keyword::If));
         assert_eq!(Keyword::from_bytes(b"in"), Some(Keyword::In));
         assert_eq!(Keyword::from_bytes(b"local"), Some(Keyword::Local));
@@ -108,4 +169,14 @@ mod tests {
         assert_eq!(Keyword::from_bytes(b"until"), Some(Keyword::Until));
         assert_eq!(Keyword::from_bytes(b"while"), Some(Keyword::While));
     }
+
+    #[test]
+    fn test_as_str() {
+        for keyword in [Keyword::And, Keyword::ElseIf, Keyword::While] {
+            assert_eq!(
+                Keyword::from_bytes(keyword.as_str().as_bytes()),
+                Some(keyword)
+            );
+        }
+    }
 }
@@ -1,27 +1,39 @@
-use lexical_core::{format::STANDARD, NumberFormatBuilder, ParseFloatOptions, ParseIntegerOptions};
+use lexical_core::{format::STANDARD, ParseFloatOptions};
 use memchr::{memchr, memchr2};
 use unicode_xid::UnicodeXID;
 
 use crate::{
     encoding::ByteLen,
-    tokens::{Symbol, Token},
-    Decoder, Keyword, TokenKind,
+    tokens::{Cdata, InterpPart, Symbol, Token},
+    unescape::escape,
+    Decoder, Dialect, Error, ErrorKind, Keyword, LuaVersion, Position, TokenKind,
 };
 
+/// An error kind and the byte offset where it occurred
+type ScanError = (ErrorKind, usize);
+
+#[derive(Clone)]
 pub struct Lexer<D> {
     decoder: D,
+    /// Length of the entire input, in bytes
+    len: usize,
+    /// Position at the start of the next token
+    pos: Position,
     float_options: ParseFloatOptions,
-    int_options: ParseIntegerOptions,
+    dialect: Dialect,
+    /// One bit per `{` that is open inside an interpolated string, which is
+    /// set if the brace starts an interpolation
+    braces: u64,
+    /// The number of bits in `braces`
+    brace_depth: u8,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct Error {}
-
 fn is_string_block<'a, D: Decoder<'a>>(decoder: &D) -> Option<(usize, D)> {
     let mut test = decoder.clone();
     if let Some('[') = test.next_char() {
         let mut level = 0;
-        while let Some('=') = test.next_char() {
+        while let Some('=') = test.peek_char() {
+            test.next_char();
             level += 1;
         }
         if let Some('[') = test.next_char() {
@@ -31,7 +43,8 @@ fn is_string_block<'a, D: Decoder<'a>>(decoder: &D) -> Option<(usize, D)> {
     None
 }
 
-fn scan_to_string_end<'a, D: Decoder<'a>>(test: &mut D, level: usize) -> Result<(), Error> {
+/// Returns `false` if there is no closing bracket of the given level
+fn scan_to_string_end<'a, D: Decoder<'a>>(test: &mut D, level: usize) -> bool {
     loop {
         if let Some(skip) = memchr(b']', test.as_bytes()) {
             test.skip_bytes(skip);
@@ -46,33 +59,160 @@ fn scan_to_string_end<'a, D: Decoder<'a>>(test: &mut D, level: usize) -> Result<
                     next = test.next_char();
                 }
                 if end_level == level && next == Some(']') {
-                    return Ok(());
+                    return true;
                 }
             }
         } else {
-            // Missing end of block comment
-            return Err(Error {});
+            // Missing end of long string or block comment
+            return false;
+        }
+    }
+}
+
+/// `x * 2^exp`, like `ldexp` in C
+fn ldexp(mut x: f64, mut exp: i32) -> f64 {
+    /// `2^exp` for a normal exponent
+    fn pow2(exp: i32) -> f64 {
+        f64::from_bits(((exp + 1023) as u64) << 52)
+    }
+    while exp > 1000 && x.is_finite() && x != 0.0 {
+        x *= pow2(1000);
+        exp -= 1000;
+    }
+    while exp < -1000 && x != 0.0 {
+        x *= pow2(-1000);
+        exp += 1000;
+    }
+    x * pow2(exp.clamp(-1000, 1000))
+}
+
+/// A hexadecimal float after the `0x`, like `lua_strx2number` in Lua 5.2
+///
+/// Returns the value, the length and whether there was a fraction or exponent.
+fn scan_hex_float(input: &[u8]) -> Option<(f64, usize, bool)> {
+    let mut value = 0.0;
+    let mut exp = 0i32;
+    let mut digits = 0;
+    let mut dot = false;
+    let mut len = 0;
+    while let Some(&b) = input.get(len) {
+        if let Some(digit) = char::from(b).to_digit(16) {
+            value = value * 16.0 + f64::from(digit);
+            digits += 1;
+            if dot {
+                exp -= 4;
+            }
+        } else if b == b'.' && !dot {
+            dot = true;
+        } else {
+            break;
+        }
+        len += 1;
+    }
+    if digits == 0 {
+        return None;
+    }
+    let mut float = dot;
+    if let Some(b'p' | b'P') = input.get(len) {
+        let mut rest = &input[len + 1..];
+        let negative = match rest.first() {
+            Some(&sign @ (b'+' | b'-')) => {
+                rest = &rest[1..];
+                sign == b'-'
+            }
+            _ => false,
+        };
+        let count = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+        if count == 0 {
+            return None;
         }
+        let value = rest[..count].iter().fold(0i32, |acc, digit| {
+            acc.saturating_mul(10)
+                .saturating_add(i32::from(digit - b'0'))
+        });
+        exp = exp.saturating_add(if negative { -value } else { value });
+        len = input.len() - rest.len() + count;
+        float = true;
     }
+    Some((ldexp(value, exp), len, float))
+}
+
+/// The value of a decimal or hexadecimal integer literal, if it fits
+fn integer_literal(digits: &[u8]) -> Option<u64> {
+    let (digits, radix) = match digits {
+        [b'0', b'x' | b'X', hex @ ..] => (hex, 16),
+        _ => (digits, 10),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    digits.iter().try_fold(0u64, |acc, &digit| {
+        let digit = char::from(digit).to_digit(radix)?;
+        acc.checked_mul(radix.into())?.checked_add(digit.into())
+    })
 }
 
 impl<'a, D: Decoder<'a>> Lexer<D> {
+    /// Create a lexer for Lua 5.1
     pub fn new(decoder: D) -> Self {
+        Self::with_version(decoder, LuaVersion::Lua51)
+    }
+
+    /// Create a lexer for the tokens of a specific Lua version
+    pub fn with_version(decoder: D, version: LuaVersion) -> Self {
+        Self::with_dialect(decoder, version.into())
+    }
+
+    /// Create a lexer for the tokens of a Lua version and its extensions
+    pub fn with_dialect(decoder: D, dialect: Dialect) -> Self {
         let float_options = ParseFloatOptions::builder()
-            .lossy(true)
             .nan_string(None)
             .inf_string(None)
             .infinity_string(None)
             .build()
             .unwrap();
-        let int_options = ParseIntegerOptions::new();
         Self {
+            len: decoder.as_bytes().len(),
+            pos: Position::START,
             decoder,
             float_options,
-            int_options,
+            dialect,
+            braces: 0,
+            brace_depth: 0,
         }
     }
 
+    /// Create a lexer that continues at a token boundary of the input
+    ///
+    /// The decoder reads the entire input. The position must not be inside
+    /// an interpolated string.
+    pub fn resume(decoder: D, dialect: Dialect, pos: Position) -> Self {
+        let mut lexer = Self::with_dialect(decoder, dialect);
+        lexer.decoder.skip_bytes(pos.offset);
+        lexer.pos = pos;
+        lexer
+    }
+
+    /// The Lua version of this lexer
+    pub fn version(&self) -> LuaVersion {
+        self.dialect.version
+    }
+
+    /// The Lua version and extensions of this lexer
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// The current byte offset from the start of the input
+    pub fn offset(&self) -> usize {
+        self.len - self.decoder.as_bytes().len()
+    }
+
+    /// The position at the start of the next token
+    pub fn position(&self) -> Position {
+        self.pos
+    }
+
     fn next_char(&mut self) -> Option<char> {
         self.decoder.next_char()
     }
@@ -85,19 +225,119 @@ impl<'a, D: Decoder<'a>> Lexer<D> {
         self.decoder.peek_char()
     }
 
+    /// `symbol`, or `compound` if it is followed by `=` in Luau
+    fn compound(&mut self, symbol: Symbol, compound: Symbol) -> TokenKind {
+        if self.dialect.luau && self.peek_char() == Some('=') {
+            self.pop_peeked();
+            TokenKind::Symbol(compound)
+        } else {
+            TokenKind::Symbol(symbol)
+        }
+    }
+
+    /// Remember whether a `{` starts an interpolation, to know what its `}` ends
+    fn push_brace(&mut self, interp: bool, offset: usize) -> Result<(), ScanError> {
+        if u32::from(self.brace_depth) == u64::BITS {
+            return Err((ErrorKind::NestingTooDeep, offset));
+        }
+        self.braces = (self.braces << 1) | u64::from(interp);
+        self.brace_depth += 1;
+        Ok(())
+    }
+
+    /// Returns `true` if the closed brace started an interpolation
+    fn pop_brace(&mut self) -> bool {
+        let interp = self.braces & 1 == 1;
+        self.braces >>= 1;
+        self.brace_depth -= 1;
+        interp
+    }
+
     pub fn token(&mut self) -> Result<Token<&'a D::Slice>, Error> {
         let start = self.decoder.as_slice();
+        let start_pos = self.pos;
         let kind = self.token_kind()?;
         let end = self.decoder.as_slice();
         let len = start.len() - end.len();
         let (span, _) = start.split_at(len);
-        Ok(Token::new(span, kind))
+        Ok(Token::new_at(span, kind, start_pos, self.pos))
     }
 
+    /// Scan the next token
+    ///
+    /// After an error, the lexer stays at the start of the invalid token.
     pub fn token_kind(&mut self) -> Result<TokenKind, Error> {
+        let start = self.decoder.clone();
+        let braces = (self.braces, self.brace_depth);
+        match self.scan() {
+            Ok(kind) => {
+                let len = self.decoder.offset_from(start.as_bytes());
+                self.pos.advance(&start.as_bytes()[..len]);
+                Ok(kind)
+            }
+            Err((kind, offset)) => {
+                let mut pos = self.pos;
+                pos.advance(&start.as_bytes()[..offset - self.pos.offset]);
+                self.decoder = start;
+                (self.braces, self.brace_depth) = braces;
+                Err(Error::new(kind, pos))
+            }
+        }
+    }
+
+    /// Skip the invalid input after an error from this lexer, to continue lexing
+    ///
+    /// An unfinished long string or comment extends to the end of the input,
+    /// any other error to the end of its line. Returns the skipped input as
+    /// a [TokenKind::Error] token.
+    pub fn skip_error(&mut self, error: &Error) -> Token<&'a D::Slice> {
+        let start = self.decoder.as_slice();
+        let start_pos = self.pos;
+        let rest = self.decoder.as_bytes();
+        let len = match error.kind() {
+            ErrorKind::UnterminatedLongString | ErrorKind::UnterminatedLongComment => rest.len(),
+            _ => {
+                let from = error
+                    .offset()
+                    .saturating_sub(self.pos.offset)
+                    .min(rest.len());
+                memchr2(b'\n', b'\r', &rest[from..]).map_or(rest.len(), |end| from + end)
+            }
+        };
+        if len == 0 {
+            self.next_char();
+        } else {
+            self.decoder.skip_bytes(len);
+        }
+        let len = self.decoder.offset_from(rest);
+        self.pos.advance(&rest[..len]);
+        let (span, _) = start.split_at(len);
+        Token::new_at(span, TokenKind::Error, start_pos, self.pos)
+    }
+
+    /// Lua 5.1 rejects a `[[` inside of a long string or comment of level 0
+    ///
+    /// `content` starts after the opening bracket, which the lexer has closed.
+    fn check_nesting(&self, level: usize, content: &D) -> Result<(), ScanError> {
+        if level > 0 || self.dialect.version != LuaVersion::Lua51 || self.dialect.luau {
+            return Ok(());
+        }
+        let bytes = content.as_bytes();
+        let len = bytes.len() - self.decoder.as_bytes().len() - 2;
+        match bytes[..len].windows(2).position(|pair| pair == b"[[") {
+            Some(nested) => Err((
+                ErrorKind::NestedLongBracket,
+                self.len - bytes.len() + nested,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn scan(&mut self) -> Result<TokenKind, ScanError> {
         let start = self.decoder.as_bytes();
+        let offset = self.offset();
         match self.next_char() {
-            Some('+') => Ok(TokenKind::Symbol(Symbol::Plus)),
+            Some('+') => Ok(self.compound(Symbol::Plus, Symbol::PlusEq)),
             Some('-') => match self.peek_char() {
                 Some('-') => {
                     self.pop_peeked();
@@ -105,9 +345,13 @@ impl<'a, D: Decoder<'a>> Lexer<D> {
                     let _c_start = self.decoder.as_bytes();
 
                     if let Some((level, mut test)) = is_string_block(&self.decoder) {
-                        let _block_start = test.as_bytes();
-                        scan_to_string_end(&mut test, level)?;
+                        let content = test.clone();
+                        if !scan_to_string_end(&mut test, level) {
+                            let kind = ErrorKind::UnterminatedLongComment;
+                            return Err((kind, offset));
+                        }
                         self.decoder = test;
+                        self.check_nesting(level, &content)?;
                     } else {
                         // This is a standard newline comment
                         let newline = memchr2(b'\n', b'\r', _c_start).unwrap_or(_c_start.len());
@@ -116,12 +360,22 @@ impl<'a, D: Decoder<'a>> Lexer<D> {
                     }
                     Ok(TokenKind::Comment)
                 }
-                _ => Ok(TokenKind::Symbol(Symbol::Minus)),
+                Some('>') if self.dialect.luau => {
+                    self.pop_peeked();
+                    Ok(TokenKind::Symbol(Symbol::Arrow))
+                }
+                _ => Ok(self.compound(Symbol::Minus, Symbol::MinusEq)),
             },
-            Some('*') => Ok(TokenKind::Symbol(Symbol::Times)),
-            Some('/') => Ok(TokenKind::Symbol(Symbol::Slash)),
-            Some('%') => Ok(TokenKind::Symbol(Symbol::Percent)),
-            Some('^') => Ok(TokenKind::Symbol(Symbol::Caret)),
+            Some('*') => Ok(self.compound(Symbol::Times, Symbol::TimesEq)),
+            Some('/') => match self.peek_char() {
+                Some('/') if self.dialect.version >= LuaVersion::Lua53 => {
+                    self.pop_peeked();
+                    Ok(TokenKind::Symbol(Symbol::Slash2))
+                }
+                _ => Ok(self.compound(Symbol::Slash, Symbol::SlashEq)),
+            },
+            Some('%') => Ok(self.compound(Symbol::Percent, Symbol::PercentEq)),
+            Some('^') => Ok(self.compound(Symbol::Caret, Symbol::CaretEq)),
             Some('#') => Ok(TokenKind::Symbol(Symbol::Hash)),
             Some('=') => match self.peek_char() {
                 Some('=') => {
@@ -130,15 +384,25 @@ impl<'a, D: Decoder<'a>> Lexer<D> {
                 }
                 _ => Ok(TokenKind::Symbol(Symbol::Assign)),
             },
-            Some('~') => match self.next_char() {
-                Some('=') => Ok(TokenKind::Symbol(Symbol::NotEq)),
-                _ => Err(Error {}),
+            Some('~') => match self.peek_char() {
+                Some('=') => {
+                    self.pop_peeked();
+                    Ok(TokenKind::Symbol(Symbol::NotEq))
+                }
+                _ if self.dialect.version >= LuaVersion::Lua53 => {
+                    Ok(TokenKind::Symbol(Symbol::Tilde))
+                }
+                _ => Err((ErrorKind::UnexpectedChar('~'), offset)),
             },
             Some('<') => match self.peek_char() {
                 Some('=') => {
                     self.pop_peeked();
                     Ok(TokenKind::Symbol(Symbol::LtEq))
                 }
+                Some('<') if self.dialect.version >= LuaVersion::Lua53 => {
+                    self.pop_peeked();
+                    Ok(TokenKind::Symbol(Symbol::Shl))
+                }
                 _ => Ok(TokenKind::Symbol(Symbol::Lt)),
             },
             Some('>') => match self.peek_char() {
@@ -146,20 +410,61 @@ impl<'a, D: Decoder<'a>> Lexer<D> {
                     self.pop_peeked();
                     Ok(TokenKind::Symbol(Symbol::GtEq))
                 }
+                Some('>') if self.dialect.version >= LuaVersion::Lua53 => {
+                    self.pop_peeked();
+                    Ok(TokenKind::Symbol(Symbol::Shr))
+                }
                 _ => Ok(TokenKind::Symbol(Symbol::Gt)),
             },
+            Some('&') if self.dialect.version >= LuaVersion::Lua53 || self.dialect.luau => {
+                Ok(TokenKind::Symbol(Symbol::Amp))
+            }
+            Some('|') if self.dialect.version >= LuaVersion::Lua53 || self.dialect.luau => {
+                Ok(TokenKind::Symbol(Symbol::Pipe))
+            }
+            Some('?') if self.dialect.luau => Ok(TokenKind::Symbol(Symbol::Question)),
             Some('(') => Ok(TokenKind::Symbol(Symbol::ParenL)),
             Some(')') => Ok(TokenKind::Symbol(Symbol::ParenR)),
-            Some('{') => Ok(TokenKind::Symbol(Symbol::BraceL)),
+            Some('{') => {
+                if self.brace_depth > 0 {
+                    self.push_brace(false, offset)?;
+                }
+                Ok(TokenKind::Symbol(Symbol::BraceL))
+            }
+            Some('}') if self.brace_depth > 0 && self.pop_brace() => {
+                self.interp_token(InterpPart::Mid, InterpPart::End, offset)
+            }
             Some('}') => Ok(TokenKind::Symbol(Symbol::BraceR)),
             Some('[') => match self.peek_char() {
-                Some('[') => todo!(),
-                Some('=') => todo!(),
+                Some('[' | '=') => {
+                    let mut level = 0;
+                    while let Some('=') = self.peek_char() {
+                        self.pop_peeked();
+                        level += 1;
+                    }
+                    if self.next_char() != Some('[') {
+                        let kind = ErrorKind::InvalidLongStringDelimiter;
+                        return Err((kind, offset));
+                    }
+                    let content = self.decoder.clone();
+                    if !scan_to_string_end(&mut self.decoder, level) {
+                        let kind = ErrorKind::UnterminatedLongString;
+                        return Err((kind, offset));
+                    }
+                    self.check_nesting(level, &content)?;
+                    Ok(TokenKind::LongString { level })
+                }
                 _ => Ok(TokenKind::Symbol(Symbol::BracketL)),
             },
             Some(']') => Ok(TokenKind::Symbol(Symbol::BracketR)),
             Some(';') => Ok(TokenKind::Symbol(Symbol::Semicolon)),
-            Some(':') => Ok(TokenKind::Symbol(Symbol::Colon)),
+            Some(':') => match self.peek_char() {
+                Some(':') if self.dialect.version >= LuaVersion::Lua52 || self.dialect.luau => {
+                    self.pop_peeked();
+                    Ok(TokenKind::Symbol(Symbol::Colon2))
+                }
+                _ => Ok(TokenKind::Symbol(Symbol::Colon)),
+            },
             Some(',') => Ok(TokenKind::Symbol(Symbol::Comma)),
             Some('.') => match self.peek_char() {
                 Some('.') => {
@@ -169,13 +474,20 @@ impl<'a, D: Decoder<'a>> Lexer<D> {
                             self.pop_peeked();
                             Ok(TokenKind::Symbol(Symbol::Dot3))
                         }
-                        _ => Ok(TokenKind::Symbol(Symbol::Dot2)),
+                        _ => Ok(self.compound(Symbol::Dot2, Symbol::Dot2Eq)),
                     }
                 }
+                Some(c) if c.is_ascii_digit() => {
+                    let kind = self.number_token(start, offset)?;
+                    self.numeral_end(start, kind, offset)
+                }
                 _ => Ok(TokenKind::Symbol(Symbol::Dot)),
             },
-            Some('\'') => self.string_token(b'\''),
-            Some('"') => self.string_token(b'"'),
+            Some('\'') => self.string_token(b'\'', offset),
+            Some('"') => self.string_token(b'"', offset),
+            Some('`') if self.dialect.luau => {
+                self.interp_token(InterpPart::Begin, InterpPart::Simple, offset)
+            }
             Some(c) if c.is_whitespace() => {
                 while let Some(c) = self.peek_char() {
                     if c.is_whitespace() {
@@ -196,76 +508,198 @@ impl<'a, D: Decoder<'a>> Lexer<D> {
                     }
                 }
                 let len = self.decoder.offset_from(start);
-                let (bytes, _) = start.split_at(len as usize);
-                if let Some(keyword) = Keyword::from_bytes(bytes) {
-                    Ok(TokenKind::Keyword(keyword))
-                } else {
-                    Ok(TokenKind::Name)
+                let (bytes, _) = start.split_at(len);
+                match Keyword::from_bytes(bytes) {
+                    Some(keyword) if keyword.since() <= self.dialect.version => {
+                        Ok(TokenKind::Keyword(keyword))
+                    }
+                    _ => Ok(TokenKind::Name),
                 }
             }
-            Some(c) if c.is_ascii_digit() => match self.peek_char() {
-                Some('x') => {
-                    self.pop_peeked();
-                    let input = self.decoder.as_bytes();
-
-                    const HEX: u128 = NumberFormatBuilder::hexadecimal();
-                    let (x, len) = lexical_core::parse_partial_with_options::<u32, HEX>(
-                        input,
-                        &self.int_options,
-                    )
-                    .map_err(|_e| Error {})?;
-                    let skip = len - self.decoder.offset_from(input);
-                    self.decoder.skip_bytes(skip);
-                    Ok(TokenKind::Hex(x))
+            Some(c) if c.is_ascii_digit() => {
+                let kind = match self.peek_char() {
+                    Some('x' | 'X') if c == '0' => {
+                        self.pop_peeked();
+                        self.hex_token(offset)?
+                    }
+                    _ => self.number_token(start, offset)?,
+                };
+                self.numeral_end(start, kind, offset)
+            }
+            Some(c) => Err((ErrorKind::UnexpectedChar(c), offset)),
+            None => Ok(TokenKind::Eof),
+        }
+    }
+
+    /// A decimal number starting at `start`, which may begin with a `.`
+    fn number_token(&mut self, start: &[u8], offset: usize) -> Result<TokenKind, ScanError> {
+        let (x, len) =
+            lexical_core::parse_partial_with_options::<f64, STANDARD>(start, &self.float_options)
+                .map_err(|_e| (ErrorKind::MalformedNumber, offset))?;
+        let skip = len - self.decoder.offset_from(start);
+        self.decoder.skip_bytes(skip);
+        let digits = &start[..len];
+        if self.dialect.version >= LuaVersion::Lua53 && digits.iter().all(u8::is_ascii_digit) {
+            // Integers that don't fit into an `i64` are floats
+            let integer = core::str::from_utf8(digits)
+                .ok()
+                .and_then(|s| s.parse().ok());
+            if let Some(integer) = integer {
+                return Ok(TokenKind::Integer(integer));
+            }
+        }
+        Ok(TokenKind::Number(x))
+    }
+
+    /// A hexadecimal number after the `0x`
+    fn hex_token(&mut self, offset: usize) -> Result<TokenKind, ScanError> {
+        let input = self.decoder.as_bytes();
+        if self.dialect.version >= LuaVersion::Lua52 {
+            let (value, len, float) =
+                scan_hex_float(input).ok_or((ErrorKind::MalformedNumber, offset))?;
+            if float || self.dialect.version == LuaVersion::Lua52 {
+                self.decoder.skip_bytes(len);
+                return Ok(TokenKind::Number(value));
+            }
+        }
+        let len = input.iter().take_while(|b| b.is_ascii_hexdigit()).count();
+        if len == 0 {
+            return Err((ErrorKind::MalformedNumber, offset));
+        }
+        let mut digits = input[..len]
+            .iter()
+            .map(|&b| u64::from(char::from(b).to_digit(16).unwrap()));
+        let value = if self.dialect.version >= LuaVersion::Lua53 {
+            // Wrap around on overflow
+            digits.fold(0u64, |acc, digit| acc.wrapping_mul(16).wrapping_add(digit))
+        } else {
+            // Like `strtoul`, saturate on overflow
+            digits
+                .try_fold(0u64, |acc, digit| acc.checked_mul(16)?.checked_add(digit))
+                .unwrap_or(u64::MAX)
+        };
+        self.decoder.skip_bytes(len);
+        Ok(TokenKind::Hex(value))
+    }
+
+    /// The suffix of the number at `start`, after which Lua's `read_numeral`
+    /// would not read any more of `[%w_.]`
+    fn numeral_end(
+        &mut self,
+        start: &[u8],
+        kind: TokenKind,
+        offset: usize,
+    ) -> Result<TokenKind, ScanError> {
+        let kind = self.number_suffix(start, kind, offset)?;
+        match self.decoder.as_bytes().first() {
+            Some(b) if b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.') => {
+                Err((ErrorKind::MalformedNumber, offset))
+            }
+            _ => Ok(kind),
+        }
+    }
+
+    /// LuaJIT's `LL`, `ULL` and `i` suffixes after the number at `start`
+    fn number_suffix(
+        &mut self,
+        start: &[u8],
+        kind: TokenKind,
+        offset: usize,
+    ) -> Result<TokenKind, ScanError> {
+        if !self.dialect.luajit {
+            return Ok(kind);
+        }
+        let (len, unsigned) = match self.decoder.as_bytes() {
+            [b'u' | b'U', b'l' | b'L', b'l' | b'L', ..] => (3, true),
+            [b'l' | b'L', b'l' | b'L', ..] => (2, false),
+            [b'i' | b'I', ..] => {
+                let value = match kind {
+                    TokenKind::Hex(value) => value as f64,
+                    TokenKind::Integer(value) => value as f64,
+                    TokenKind::Number(value) => value,
+                    _ => unreachable!("not a number: {:?}", kind),
+                };
+                self.decoder.skip_bytes(1);
+                return Ok(TokenKind::Cdata(Cdata::Imaginary(value)));
+            }
+            _ => return Ok(kind),
+        };
+        let digits = &start[..self.decoder.offset_from(start)];
+        let value = integer_literal(digits).ok_or((ErrorKind::MalformedNumber, offset))?;
+        self.decoder.skip_bytes(len);
+        Ok(TokenKind::Cdata(if unsigned {
+            Cdata::UInt64(value)
+        } else {
+            // `0xffffffffffffffffLL` is `-1LL`
+            Cdata::Int64(value as i64)
+        }))
+    }
+
+    /// The rest of a part of an interpolated string, up to a `{` that
+    /// starts an interpolation (`open`) or the closing backtick (`close`)
+    fn interp_token(
+        &mut self,
+        open: InterpPart,
+        close: InterpPart,
+        offset: usize,
+    ) -> Result<TokenKind, ScanError> {
+        loop {
+            let input = self.decoder.as_bytes();
+            let count = input
+                .iter()
+                .position(|b| matches!(b, b'`' | b'{' | b'\\' | b'\n' | b'\r'));
+            let count = match count {
+                Some(count) if !matches!(input[count], b'\n' | b'\r') => count,
+                // Unescaped newline or EOF
+                _ => break Err((ErrorKind::UnterminatedString, offset)),
+            };
+            self.decoder.skip_bytes(count + 1);
+            match input[count] {
+                b'`' => break Ok(TokenKind::InterpString(close)),
+                b'{' => {
+                    self.push_brace(true, offset)?;
+                    break Ok(TokenKind::InterpString(open));
                 }
                 _ => {
-                    let (x, len) = lexical_core::parse_partial_with_options::<f32, STANDARD>(
-                        start,
-                        &self.float_options,
-                    )
-                    .map_err(|_e| Error {})?;
-                    let skip = len - self.decoder.offset_from(start);
-                    self.decoder.skip_bytes(skip);
-                    Ok(TokenKind::Number(x))
+                    let escape_at = self.offset() - 1;
+                    let len = match self.decoder.as_bytes().first() {
+                        Some(b'{' | b'`') => 1,
+                        _ => {
+                            escape(self.decoder.as_bytes(), self.dialect)
+                                .map_err(|kind| (kind, escape_at))?
+                                .1
+                        }
+                    };
+                    self.decoder.skip_bytes(len);
                 }
-            },
-            Some(_) => Err(Error {}),
-            None => Ok(TokenKind::Eof),
+            }
         }
     }
 
-    fn string_token(&mut self, quote_char: u8) -> Result<TokenKind, Error> {
+    fn string_token(&mut self, quote_char: u8, offset: usize) -> Result<TokenKind, ScanError> {
         loop {
             let input = self.decoder.as_bytes();
             if let Some(count) = memchr2(quote_char, b'\\', input) {
+                if memchr2(b'\n', b'\r', &input[..count]).is_some() {
+                    // Unescaped newline in a string
+                    break Err((ErrorKind::UnterminatedString, offset));
+                }
                 self.decoder.skip_bytes(count);
+                let escape_at = self.offset();
                 let next = self
                     .next_char()
                     .expect("memchr2 should prove there is a char here");
                 if next == '\\' {
-                    let _ = match self.next_char() {
-                        Some('a') => Ok("bell"),
-                        Some('b') => Ok("backspace"),
-                        Some('f') => Ok("form feed"),
-                        Some('n') => Ok("newline"),
-                        Some('r') => Ok("carriage return"),
-                        Some('t') => Ok("horizontal tab"),
-                        Some('v') => Ok("vertical tab"),
-                        Some('\\') => Ok("backslash"),
-                        Some('"') => Ok("quotation mark [double quote]"),
-                        Some('\'') => Ok("quotation mark [single quote]"),
-                        Some(x) if x.is_ascii_digit() => {
-                            todo!("digit escape")
-                        }
-                        _ => Err(Error { /* invalid escape sequence */}),
-                    }?;
+                    let (_, len) = escape(self.decoder.as_bytes(), self.dialect)
+                        .map_err(|kind| (kind, escape_at))?;
+                    self.decoder.skip_bytes(len);
                 } else {
                     assert_eq!(next, quote_char as char);
                     break Ok(TokenKind::String);
                 }
             } else {
                 // Reached EOF before string end delimiter
-                break Err(Error {});
+                break Err((ErrorKind::UnterminatedString, offset));
             }
         }
     }
@@ -273,7 +707,23 @@ impl<'a, D: Decoder<'a>> Lexer<D> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{tokens::Symbol, Keyword, Latin1Decoder, Lexer, Token, TokenKind, Utf8Decoder};
+    use crate::{
+        tokens::Symbol, Cdata, Decoder, Dialect, Error, ErrorKind, InterpPart, Keyword,
+        Latin1Decoder, Lexer, LuaVersion, Position, Token, TokenKind, Utf8Decoder,
+    };
+
+    /// Returns the next token without positions
+    fn next_token<'a, D: Decoder<'a>>(lexer: &mut Lexer<D>) -> Result<Token<&'a D::Slice>, Error> {
+        lexer.token().map(|t| Token::new(t.span(), t.kind()))
+    }
+
+    fn at(offset: usize, line: usize, column: usize) -> Position {
+        Position {
+            offset,
+            line,
+            column,
+        }
+    }
 
     #[test]
     fn test_keywords() {
@@ -325,24 +775,427 @@ mod tests {
         assert_eq!(lexer.token_kind(), Ok(TokenKind::Eof));
     }
 
+    #[test]
+    fn test_number_precision() {
+        let decoder =
+            Utf8Decoder::new("16777217 .5 0.1 0XfF 0xFFFFFFFFFF 0x10000000000000000 1e400 5");
+        let mut lexer = Lexer::new(decoder);
+        let mut next = || {
+            let kind = lexer.token_kind();
+            assert_eq!(lexer.token_kind(), Ok(TokenKind::Whitespace));
+            kind
+        };
+        assert_eq!(next(), Ok(TokenKind::Number(16777217.0)));
+        assert_eq!(next(), Ok(TokenKind::Number(0.5)));
+        assert_eq!(next(), Ok(TokenKind::Number(0.1)));
+        assert_eq!(next(), Ok(TokenKind::Hex(0xFF)));
+        assert_eq!(next(), Ok(TokenKind::Hex(0xFF_FFFF_FFFF)));
+        assert_eq!(next(), Ok(TokenKind::Hex(u64::MAX)));
+        assert_eq!(next(), Ok(TokenKind::Number(f64::INFINITY)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Number(5.0)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Eof));
+
+        // Like `read_numeral`, a number extends over all of `[%w_.]`
+        for text in ["0x", "5x", "1..2", "3.0.1", "0x1g", "0x1.8", "7_", "1e5e"] {
+            let mut lexer = Lexer::new(Utf8Decoder::new(text));
+            assert_eq!(
+                lexer.token_kind(),
+                Err(Error::new(ErrorKind::MalformedNumber, Position::START)),
+                "{}",
+                text
+            );
+        }
+        let mut lexer = Lexer::with_version(Utf8Decoder::new("x=.5x"), LuaVersion::Lua54);
+        lexer.token_kind().unwrap();
+        lexer.token_kind().unwrap();
+        let kind = ErrorKind::MalformedNumber;
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(2, 1, 3))));
+    }
+
+    #[test]
+    fn test_integers() {
+        let decoder = Utf8Decoder::new("3 3.0 1e2 9223372036854775807 9223372036854775808 0xff 0x1p4 0x.8 0xffffffffffffffffff");
+        let mut lexer = Lexer::with_version(decoder, LuaVersion::Lua53);
+        let mut next = || {
+            let kind = lexer.token_kind();
+            lexer.token_kind().unwrap();
+            kind
+        };
+        assert_eq!(next(), Ok(TokenKind::Integer(3)));
+        assert_eq!(next(), Ok(TokenKind::Number(3.0)));
+        assert_eq!(next(), Ok(TokenKind::Number(100.0)));
+        assert_eq!(next(), Ok(TokenKind::Integer(i64::MAX)));
+        assert_eq!(next(), Ok(TokenKind::Number(9223372036854775808.0)));
+        assert_eq!(next(), Ok(TokenKind::Hex(0xFF)));
+        assert_eq!(next(), Ok(TokenKind::Number(16.0)));
+        assert_eq!(next(), Ok(TokenKind::Number(0.5)));
+        assert_eq!(next(), Ok(TokenKind::Hex(u64::MAX)));
+
+        // Lua 5.2 has hexadecimal floats, but no integers
+        let decoder = Utf8Decoder::new("0xA.8P1 0x10 0x1p-2 0x1p");
+        let mut lexer = Lexer::with_version(decoder, LuaVersion::Lua52);
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Number(21.0)));
+        lexer.token_kind().unwrap();
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Number(16.0)));
+        lexer.token_kind().unwrap();
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Number(0.25)));
+        lexer.token_kind().unwrap();
+        let kind = ErrorKind::MalformedNumber;
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(20, 1, 21))));
+
+        // Lua 5.1 has no hexadecimal exponents
+        let mut lexer = Lexer::new(Utf8Decoder::new("0x1p4"));
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, Position::START)));
+    }
+
+    #[test]
+    fn test_version_symbols() {
+        let text = "goto :: // & | ~ << >>";
+        let mut lexer = Lexer::with_version(Utf8Decoder::new(text), LuaVersion::Lua53);
+        let expected = [
+            TokenKind::Keyword(Keyword::Goto),
+            TokenKind::Symbol(Symbol::Colon2),
+            TokenKind::Symbol(Symbol::Slash2),
+            TokenKind::Symbol(Symbol::Amp),
+            TokenKind::Symbol(Symbol::Pipe),
+            TokenKind::Symbol(Symbol::Tilde),
+            TokenKind::Symbol(Symbol::Shl),
+            TokenKind::Symbol(Symbol::Shr),
+        ];
+        for kind in expected {
+            assert_eq!(lexer.token_kind(), Ok(kind));
+            lexer.token_kind().unwrap();
+        }
+
+        let mut lexer = Lexer::with_version(Utf8Decoder::new("goto::a//b~c"), LuaVersion::Lua52);
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Keyword(Keyword::Goto)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Symbol(Symbol::Colon2)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Name));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Symbol(Symbol::Slash)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Symbol(Symbol::Slash)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Name));
+        let kind = ErrorKind::UnexpectedChar('~');
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(10, 1, 11))));
+
+        let mut lexer = Lexer::new(Utf8Decoder::new("goto::"));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Name));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Symbol(Symbol::Colon)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Symbol(Symbol::Colon)));
+    }
+
+    #[test]
+    fn test_version_escapes() {
+        let text = "'\\x41\\z\n  \\u{48}'";
+        let mut lexer = Lexer::with_version(Utf8Decoder::new(text), LuaVersion::Lua53);
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(text, TokenKind::String))
+        );
+        let mut lexer = Lexer::with_version(Utf8Decoder::new(text), LuaVersion::Lua52);
+        let kind = ErrorKind::InvalidEscape;
+        assert_eq!(next_token(&mut lexer), Err(Error::new(kind, at(10, 2, 3))));
+        let mut lexer = Lexer::new(Utf8Decoder::new(text));
+        assert_eq!(next_token(&mut lexer), Err(Error::new(kind, at(1, 1, 2))));
+    }
+
+    #[test]
+    fn test_luajit_suffixes() {
+        let dialect = Dialect::LUAJIT;
+        let text = "1LL 0x10ull 18446744073709551615ULL 2.5I 0x2i 5";
+        let mut lexer = Lexer::with_dialect(Utf8Decoder::new(text), dialect);
+        let mut next = || {
+            let kind = lexer.token_kind();
+            lexer.token_kind().unwrap();
+            kind
+        };
+        assert_eq!(next(), Ok(TokenKind::Cdata(Cdata::Int64(1))));
+        assert_eq!(next(), Ok(TokenKind::Cdata(Cdata::UInt64(16))));
+        assert_eq!(next(), Ok(TokenKind::Cdata(Cdata::UInt64(u64::MAX))));
+        assert_eq!(next(), Ok(TokenKind::Cdata(Cdata::Imaginary(2.5))));
+        assert_eq!(next(), Ok(TokenKind::Cdata(Cdata::Imaginary(2.0))));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Number(5.0)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Eof));
+
+        let kind = ErrorKind::MalformedNumber;
+        for text in ["5x", "1LLx", "2i_", "1ll.5"] {
+            let mut lexer = Lexer::with_dialect(Utf8Decoder::new(text), dialect);
+            assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(0, 1, 1))));
+        }
+        let mut lexer = Lexer::with_dialect(Utf8Decoder::new("18446744073709551616LL"), dialect);
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(0, 1, 1))));
+        let mut lexer = Lexer::with_dialect(Utf8Decoder::new("1e2LL"), dialect);
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(0, 1, 1))));
+    }
+
+    #[test]
+    fn test_luau_tokens() {
+        let text = "a+=1 b..=c -> x? `s{ {t} }\\{u\\`{v}w` `x`";
+        let mut lexer = Lexer::with_dialect(Utf8Decoder::new(text), Dialect::LUAU);
+        let mut kinds = [TokenKind::Eof; 32];
+        let mut len = 0;
+        loop {
+            let token = next_token(&mut lexer).unwrap();
+            if token.kind() == TokenKind::Eof {
+                break;
+            }
+            if token.kind() != TokenKind::Whitespace {
+                kinds[len] = token.kind();
+                len += 1;
+            }
+        }
+        let sym = TokenKind::Symbol;
+        assert_eq!(
+            kinds[..len],
+            [
+                TokenKind::Name,
+                sym(Symbol::PlusEq),
+                TokenKind::Number(1.0),
+                TokenKind::Name,
+                sym(Symbol::Dot2Eq),
+                TokenKind::Name,
+                sym(Symbol::Arrow),
+                TokenKind::Name,
+                sym(Symbol::Question),
+                TokenKind::InterpString(InterpPart::Begin),
+                sym(Symbol::BraceL),
+                TokenKind::Name,
+                sym(Symbol::BraceR),
+                TokenKind::InterpString(InterpPart::Mid),
+                TokenKind::Name,
+                TokenKind::InterpString(InterpPart::End),
+                TokenKind::InterpString(InterpPart::Simple),
+            ]
+        );
+
+        let mut lexer = Lexer::with_dialect(Utf8Decoder::new("x `a{b}c\n`"), Dialect::LUAU);
+        lexer.token_kind().unwrap();
+        lexer.token_kind().unwrap();
+        lexer.token_kind().unwrap();
+        lexer.token_kind().unwrap();
+        let kind = ErrorKind::UnterminatedString;
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(6, 1, 7))));
+
+        // Plain Lua reads `-=` as two tokens and has no backticks
+        let mut lexer = Lexer::new(Utf8Decoder::new("-=`"));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Symbol(Symbol::Minus)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Symbol(Symbol::Assign)));
+        let kind = ErrorKind::UnexpectedChar('`');
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(2, 1, 3))));
+    }
+
     #[test]
     fn test_strings() {
         let mut lexer = Lexer::new(Utf8Decoder::new(
             r#"'Hello World!' 'Two\nLines' "double quotes""#,
         ));
         assert_eq!(
-            lexer.token(),
+            next_token(&mut lexer),
             Ok(Token::new("'Hello World!'", TokenKind::String))
         );
-        assert_eq!(lexer.token(), Ok(Token::new(" ", TokenKind::Whitespace)));
         assert_eq!(
-            lexer.token(),
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
             Ok(Token::new(r#"'Two\nLines'"#, TokenKind::String))
         );
-        assert_eq!(lexer.token(), Ok(Token::new(" ", TokenKind::Whitespace)));
         assert_eq!(
-            lexer.token(),
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
             Ok(Token::new(r#""double quotes""#, TokenKind::String))
         );
     }
+
+    #[test]
+    fn test_decimal_escapes() {
+        let mut lexer = Lexer::new(Utf8Decoder::new(r#"'\65\066\0677' "\255" '\256'"#));
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(r#"'\65\066\0677'"#, TokenKind::String))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(r#""\255""#, TokenKind::String))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Err(Error::new(ErrorKind::EscapeTooLarge, at(23, 1, 24)))
+        );
+    }
+
+    #[test]
+    fn test_escaped_newlines() {
+        let mut lexer = Lexer::new(Utf8Decoder::new("'a\\\nb' 'c\\\r\nd' 'e\nf'"));
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new("'a\\\nb'", TokenKind::String))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new("'c\\\r\nd'", TokenKind::String))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        // An unescaped newline ends the string
+        assert_eq!(
+            next_token(&mut lexer),
+            Err(Error::new(ErrorKind::UnterminatedString, at(15, 3, 4)))
+        );
+    }
+
+    #[test]
+    fn test_long_strings() {
+        let mut lexer = Lexer::new(Utf8Decoder::new(
+            "[[one]] [==[two]]\n]=]]==] [[\nthree]] [=x",
+        ));
+        let token = next_token(&mut lexer).unwrap();
+        assert_eq!(
+            token,
+            Token::new("[[one]]", TokenKind::LongString { level: 0 })
+        );
+        assert_eq!(token.long_string_contents(), Some("one"));
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        let token = next_token(&mut lexer).unwrap();
+        assert_eq!(token.kind(), TokenKind::LongString { level: 2 });
+        assert_eq!(token.long_string_contents(), Some("two]]\n]=]"));
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        let token = next_token(&mut lexer).unwrap();
+        assert_eq!(token.span(), "[[\nthree]]");
+        assert_eq!(token.long_string_contents(), Some("three"));
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        let kind = ErrorKind::InvalidLongStringDelimiter;
+        assert_eq!(next_token(&mut lexer), Err(Error::new(kind, at(37, 3, 9))));
+    }
+
+    #[test]
+    fn test_nested_long_brackets() {
+        let kind = ErrorKind::NestedLongBracket;
+        let error =
+            |text, dialect| Lexer::with_dialect(Utf8Decoder::new(text), dialect).token_kind();
+        let lua51 = Dialect::default();
+        assert_eq!(
+            error("[[a\n[[b]]", lua51),
+            Err(Error::new(kind, at(4, 2, 1)))
+        );
+        assert_eq!(
+            error("--[[ [[ ]]", lua51),
+            Err(Error::new(kind, at(5, 1, 6)))
+        );
+        // The first `]]` closes the string
+        assert_eq!(
+            error("[[a]] [[", lua51),
+            Ok(TokenKind::LongString { level: 0 })
+        );
+        assert_eq!(
+            error("[=[ [[ ]=]", lua51),
+            Ok(TokenKind::LongString { level: 1 })
+        );
+        let lua52 = LuaVersion::Lua52.into();
+        assert_eq!(
+            error("[[ [[ ]]", lua52),
+            Ok(TokenKind::LongString { level: 0 })
+        );
+        assert_eq!(
+            error("[[ [[ ]]", Dialect::LUAU),
+            Ok(TokenKind::LongString { level: 0 })
+        );
+    }
+
+    #[test]
+    fn test_unterminated_long_string() {
+        let mut lexer = Lexer::new(Latin1Decoder::new(b"[==[ ]=] ]]"));
+        let kind = ErrorKind::UnterminatedLongString;
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, Position::START)));
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut lexer = Lexer::new(Utf8Decoder::new("-- line\n--[[ block\n ]] --[==[ ]] ]==]"));
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new("-- line", TokenKind::Comment))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new("\n", TokenKind::Whitespace))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new("--[[ block\n ]]", TokenKind::Comment))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new(" ", TokenKind::Whitespace))
+        );
+        assert_eq!(
+            next_token(&mut lexer),
+            Ok(Token::new("--[==[ ]] ]==]", TokenKind::Comment))
+        );
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Eof));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut lexer = Lexer::new(Latin1Decoder::new(b"x --[[ "));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Name));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Whitespace));
+        let kind = ErrorKind::UnterminatedLongComment;
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(2, 1, 3))));
+
+        let mut lexer = Lexer::new(Latin1Decoder::new(b"'\\q' @"));
+        assert_eq!(
+            next_token(&mut lexer),
+            Err(Error::new(ErrorKind::InvalidEscape, at(1, 1, 2)))
+        );
+        let mut lexer = Lexer::new(Latin1Decoder::new(b" @"));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Whitespace));
+        let kind = ErrorKind::UnexpectedChar('@');
+        assert_eq!(lexer.token_kind(), Err(Error::new(kind, at(1, 1, 2))));
+    }
+
+    #[test]
+    fn test_positions() {
+        let mut lexer = Lexer::new(Utf8Decoder::new("a\r\n  b --[[\n\n]] 'c'"));
+        let a = lexer.token().unwrap();
+        assert_eq!((a.start(), a.end()), (at(0, 1, 1), at(1, 1, 2)));
+        let ws = lexer.token().unwrap();
+        assert_eq!((ws.start(), ws.end()), (at(1, 1, 2), at(5, 2, 3)));
+        let b = lexer.token().unwrap();
+        assert_eq!((b.start(), b.end()), (at(5, 2, 3), at(6, 2, 4)));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Whitespace));
+        let comment = lexer.token().unwrap();
+        assert_eq!(comment.kind(), TokenKind::Comment);
+        assert_eq!(comment.end(), at(15, 4, 3));
+        assert_eq!(lexer.token_kind(), Ok(TokenKind::Whitespace));
+        assert_eq!(lexer.position(), at(16, 4, 4));
+        let c = lexer.token().unwrap();
+        assert_eq!((c.start(), c.end()), (at(16, 4, 4), at(19, 4, 7)));
+    }
 }
@@ -4,14 +4,64 @@
 //! This is a `no_std` crate that implements parsing of Lua 5.1
 //!
 //! See: <https://www.lua.org/manual/5.1/manual.html>
+//!
+//! The syntax of Lua 5.2, 5.3 and 5.4 can be enabled with [LuaVersion],
+//! and the extensions of LuaJIT and Luau with [Dialect].
+//!
+//! ## Features
+//!
+//! - `alloc`: Enables the [ast], [cst], [format], [incremental], [resolve],
+//!   [lint], [edit] and [bytecode] modules
+//! - `cli`: Builds the `sluap` command-line tool
+//! - `lsp`: Builds the `sluap-lsp` language server
+//! - `serde`: Enables the [de] and [ser] modules to read and write Lua data
+//!   files with serde
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
 mod encoding;
 pub use encoding::{Decoder, Latin1Decoded, Latin1Decoder, Utf8Decoder};
+mod version;
+pub use version::{Dialect, LuaVersion};
 mod keywords;
 pub use keywords::Keyword;
+mod position;
+pub use position::Position;
 mod tokens;
-pub use tokens::{Symbol, Token, TokenKind};
+pub use tokens::{Cdata, InterpPart, Symbol, Token, TokenKind};
+mod error;
+pub use error::{Error, ErrorKind, Expected};
 mod lexer;
-pub use lexer::{Error, Lexer};
+pub use lexer::Lexer;
+mod unescape;
+pub use unescape::Unescape;
 mod parser;
-pub use parser::{Parser, Sink};
+pub use parser::{
+    BinOp, Chunk, Events, ExprList, FuncName, If, NameList, NodeKind, Parser, Sink, Table, UnOp,
+    VarList,
+};
+#[cfg(feature = "alloc")]
+pub mod ast;
+#[cfg(feature = "alloc")]
+pub mod bytecode;
+#[cfg(feature = "alloc")]
+pub mod cst;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "serde")]
+pub use de::{from_latin1, from_str, Deserializer};
+#[cfg(feature = "alloc")]
+pub mod edit;
+#[cfg(feature = "alloc")]
+pub mod format;
+#[cfg(feature = "alloc")]
+pub mod incremental;
+#[cfg(feature = "alloc")]
+pub mod lint;
+#[cfg(feature = "alloc")]
+pub mod resolve;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "serde")]
+pub use ser::{to_string, to_string_pretty, Serializer};
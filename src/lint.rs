@@ -0,0 +1,729 @@
+//! # Lints
+//!
+//! Checks a [cst](crate::cst) tree for code that is valid Lua but likely
+//! a mistake, with the names from [resolve](crate::resolve).
+//!
+//! This module requires the `alloc` feature.
+
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    cst::{Element, Node},
+    encoding::ByteLen,
+    resolve::{resolve, Binding, DeclKind, Resolution},
+    Dialect, Keyword, LuaVersion, NodeKind, Position, Symbol, Token, TokenKind,
+};
+
+/// A check of the linter, all of which are enabled by default
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Lint {
+    /// Reading a global that is neither known nor assigned in the input
+    UndefinedGlobal,
+    /// Assigning a global that is not known
+    ImplicitGlobal,
+    /// A local variable that is never read
+    UnusedLocal,
+    /// A function parameter that is never read
+    UnusedParam,
+    /// A local variable with the name of another visible local variable
+    Shadowing,
+    /// A statement after a `return` or `break` in the same block
+    Unreachable,
+    /// A `break` that is not in a loop of the same function
+    BreakOutsideLoop,
+    /// A key that occurs twice in a table constructor
+    DuplicateKey,
+    /// Comparing a local variable with `nil` before anything is assigned to it
+    NilComparison,
+    /// A block without any statements
+    EmptyBlock,
+    /// A `...` outside of a vararg function
+    InvalidVararg,
+}
+
+impl Lint {
+    /// All lints
+    pub const ALL: [Self; 11] = [
+        Self::UndefinedGlobal,
+        Self::ImplicitGlobal,
+        Self::UnusedLocal,
+        Self::UnusedParam,
+        Self::Shadowing,
+        Self::Unreachable,
+        Self::BreakOutsideLoop,
+        Self::DuplicateKey,
+        Self::NilComparison,
+        Self::EmptyBlock,
+        Self::InvalidVararg,
+    ];
+
+    /// The name of the lint, e.g. `unused-local`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UndefinedGlobal => "undefined-global",
+            Self::ImplicitGlobal => "implicit-global",
+            Self::UnusedLocal => "unused-local",
+            Self::UnusedParam => "unused-param",
+            Self::Shadowing => "shadowing",
+            Self::Unreachable => "unreachable",
+            Self::BreakOutsideLoop => "break-outside-loop",
+            Self::DuplicateKey => "duplicate-key",
+            Self::NilComparison => "nil-comparison",
+            Self::EmptyBlock => "empty-block",
+            Self::InvalidVararg => "invalid-vararg",
+        }
+    }
+
+    /// Returns the lint with a name from [Lint::name]
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The lints to check and the known globals
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Config<'a> {
+    /// The dialect whose standard library is known
+    pub dialect: Dialect,
+    /// More globals that may be read and assigned
+    pub globals: &'a [&'a str],
+    /// The lints that are not reported
+    pub disabled: &'a [Lint],
+}
+
+impl Config<'_> {
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        !self.disabled.contains(&lint)
+    }
+
+    fn is_global(&self, name: &[u8]) -> bool {
+        self.globals.iter().any(|global| global.as_bytes() == name)
+            || is_builtin(name, self.dialect)
+    }
+}
+
+/// The globals of the standard library of a dialect
+fn is_builtin(name: &[u8], dialect: Dialect) -> bool {
+    const COMMON: &[&str] = &[
+        "_G",
+        "_VERSION",
+        "assert",
+        "collectgarbage",
+        "dofile",
+        "error",
+        "getmetatable",
+        "ipairs",
+        "load",
+        "loadfile",
+        "next",
+        "pairs",
+        "pcall",
+        "print",
+        "rawequal",
+        "rawget",
+        "rawset",
+        "require",
+        "select",
+        "setmetatable",
+        "tonumber",
+        "tostring",
+        "type",
+        "xpcall",
+        "coroutine",
+        "debug",
+        "io",
+        "math",
+        "os",
+        "package",
+        "string",
+        "table",
+    ];
+    let Ok(name) = core::str::from_utf8(name) else {
+        return false;
+    };
+    let version = dialect.version;
+    COMMON.contains(&name)
+        || match name {
+            "getfenv" | "setfenv" | "loadstring" | "unpack" | "module" | "gcinfo" | "newproxy" => {
+                version == LuaVersion::Lua51 || dialect.luajit
+            }
+            "rawlen" => version >= LuaVersion::Lua52,
+            // LuaJIT has the syntax of Lua 5.2 but the environments of Lua 5.1
+            "_ENV" => version >= LuaVersion::Lua52 && !dialect.luajit,
+            "bit32" => (version == LuaVersion::Lua52 && !dialect.luajit) || dialect.luau,
+            "utf8" => version >= LuaVersion::Lua53 || dialect.luau,
+            "warn" => version == LuaVersion::Lua54,
+            "bit" | "jit" => dialect.luajit,
+            "typeof" | "buffer" => dialect.luau,
+            _ => false,
+        }
+}
+
+/// A problem that a [Lint] found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub start: Position,
+    pub end: Position,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.start, self.message, self.lint)
+    }
+}
+
+/// Check a tree from [parse_with](crate::cst::parse_with) or
+/// [parse_recovering](crate::cst::parse_recovering)
+///
+/// The diagnostics are sorted by their position.
+pub fn lint<T: ?Sized + ByteLen>(root: &Node<&T>, config: &Config) -> Vec<Diagnostic> {
+    let resolution = resolve(root);
+    let mut linter = Linter {
+        config,
+        references: offsets(resolution.references.iter().map(|r| &r.token)),
+        declarations: offsets(resolution.declarations.iter().map(|d| &d.token)),
+        resolution: &resolution,
+        uninitialized: BTreeSet::new(),
+        loops: alloc::vec![0],
+        ancestors: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    linter.names();
+    linter.varargs();
+    linter.walk(root);
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.start);
+    diagnostics
+}
+
+/// The index of each token by its offset
+fn offsets<'t, S: Copy + 't>(tokens: impl Iterator<Item = &'t Token<S>>) -> BTreeMap<usize, usize> {
+    tokens
+        .enumerate()
+        .map(|(index, token)| (token.start().offset, index))
+        .collect()
+}
+
+fn text<T: ?Sized + ByteLen>(span: &T) -> Cow<'_, str> {
+    String::from_utf8_lossy(span.as_bytes())
+}
+
+fn is_trivia(element: &Element<impl Copy>) -> bool {
+    matches!(
+        element,
+        Element::Token(token) if matches!(token.kind(), TokenKind::Whitespace | TokenKind::Comment)
+    )
+}
+
+fn is_symbol<S: Copy>(element: &Element<S>, symbols: &[Symbol]) -> bool {
+    matches!(
+        element,
+        Element::Token(token) if matches!(token.kind(), TokenKind::Symbol(s) if symbols.contains(&s))
+    )
+}
+
+fn is_keyword<S: Copy>(element: &Element<S>, keywords: &[Keyword]) -> bool {
+    matches!(
+        element,
+        Element::Token(token) if matches!(token.kind(), TokenKind::Keyword(k) if keywords.contains(&k))
+    )
+}
+
+/// A key in a table constructor
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Key<'a> {
+    String(Cow<'a, [u8]>),
+    /// The bits of a number
+    Number(u64),
+}
+
+impl Key<'_> {
+    fn number(value: f64) -> Self {
+        // `-0.0` and `0.0` are the same key
+        Self::Number((value + 0.0).to_bits())
+    }
+}
+
+impl fmt::Display for Key<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(bytes) => write!(f, "'{}'", String::from_utf8_lossy(bytes)),
+            Self::Number(bits) => write!(f, "{}", f64::from_bits(*bits)),
+        }
+    }
+}
+
+struct Linter<'r, 'a, T: ?Sized> {
+    config: &'r Config<'r>,
+    resolution: &'r Resolution<&'a T>,
+    /// The index of the reference at each offset
+    references: BTreeMap<usize, usize>,
+    /// The index of the declaration at each offset
+    declarations: BTreeMap<usize, usize>,
+    /// The `local` declarations without a value
+    uninitialized: BTreeSet<usize>,
+    /// The number of loops around the current node in each function
+    loops: Vec<usize>,
+    /// The kinds and starts of the nodes around the current node
+    ancestors: Vec<(NodeKind, usize)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'r, 'a, T: ?Sized + ByteLen> Linter<'r, 'a, T> {
+    fn report(&mut self, lint: Lint, start: Position, end: Position, message: String) {
+        if self.config.is_enabled(lint) {
+            self.diagnostics.push(Diagnostic {
+                lint,
+                start,
+                end,
+                message,
+            });
+        }
+    }
+
+    fn report_token(&mut self, lint: Lint, token: &Token<&T>, message: String) {
+        self.report(lint, token.start(), token.end(), message);
+    }
+
+    fn report_node(&mut self, lint: Lint, node: &Node<&T>, message: String) {
+        if let (Some(start), Some(end)) = (node.start(), node.end()) {
+            self.report(lint, start, end, message);
+        }
+    }
+
+    /// The lints of [Resolution::references] and [Resolution::declarations]
+    fn names(&mut self) {
+        let resolution = self.resolution;
+        let assigned: BTreeSet<&[u8]> = resolution
+            .references
+            .iter()
+            .filter(|reference| reference.write && reference.binding == Binding::Global)
+            .map(|reference| reference.token.span().as_bytes())
+            .collect();
+        for reference in &resolution.references {
+            let name = reference.token.span().as_bytes();
+            if reference.binding != Binding::Global || self.config.is_global(name) {
+                continue;
+            }
+            let name = text(reference.token.span());
+            if reference.write {
+                let message = format!("assignment to undeclared global '{}'", name);
+                self.report_token(Lint::ImplicitGlobal, &reference.token, message);
+            } else if !assigned.contains(reference.token.span().as_bytes()) {
+                let message = format!("undefined global '{}'", name);
+                self.report_token(Lint::UndefinedGlobal, &reference.token, message);
+            }
+        }
+
+        for (index, declaration) in resolution.declarations.iter().enumerate() {
+            let token = &declaration.token;
+            let name = text(token.span());
+            // Names that start with `_` are unused on purpose, and the value
+            // of a `<close>` variable is used when it goes out of scope
+            if matches!(declaration.kind, DeclKind::SelfParam | DeclKind::CloseLocal)
+                || name.starts_with('_')
+            {
+                continue;
+            }
+            let read = resolution
+                .references_to(index)
+                .any(|reference| !reference.write);
+            if !read {
+                let (lint, message) = match declaration.kind {
+                    DeclKind::Param => (Lint::UnusedParam, format!("unused parameter '{}'", name)),
+                    _ => (Lint::UnusedLocal, format!("unused variable '{}'", name)),
+                };
+                self.report_token(lint, token, message);
+            }
+            if let Some(shadowed) = declaration.shadows {
+                let line = resolution.declarations[shadowed].token.start().line;
+                let message = format!("'{}' shadows the variable on line {}", name, line);
+                self.report_token(Lint::Shadowing, token, message);
+            }
+        }
+    }
+
+    /// The lints of [Resolution::invalid_varargs]
+    fn varargs(&mut self) {
+        for token in &self.resolution.invalid_varargs {
+            let message = String::from("cannot use '...' outside a vararg function");
+            self.report_token(Lint::InvalidVararg, token, message);
+        }
+    }
+
+    /// Whether a statement never continues with the next statement
+    fn exits(stmt: &Node<&T>) -> bool {
+        let block_exits = |block: &Node<&T>| block.nodes().last().is_some_and(Self::exits);
+        match stmt.kind() {
+            NodeKind::Return | NodeKind::Break => true,
+            NodeKind::Do => stmt.nodes().any(block_exits),
+            // Every branch exits, including an `else`
+            NodeKind::If => {
+                let mut branches = stmt.nodes().filter_map(|child| match child.kind() {
+                    NodeKind::Chunk => Some(child),
+                    NodeKind::ElseIf | NodeKind::Else => {
+                        child.nodes().find(|n| n.kind() == NodeKind::Chunk)
+                    }
+                    _ => None,
+                });
+                let has_else = stmt.nodes().any(|child| child.kind() == NodeKind::Else);
+                has_else && branches.all(block_exits)
+            }
+            _ => false,
+        }
+    }
+
+    /// Report the first statement after one that exits the block
+    fn chunk(&mut self, chunk: &Node<&T>) {
+        let mut stmts = chunk.nodes().filter(|stmt| stmt.kind() != NodeKind::Error);
+        while let Some(stmt) = stmts.next() {
+            if Self::exits(stmt) {
+                if let Some(next) = stmts.next() {
+                    let message = String::from("unreachable code");
+                    self.report_node(Lint::Unreachable, next, message);
+                }
+                break;
+            }
+        }
+    }
+
+    /// Report a block of a statement without any statements
+    fn empty_block(&mut self, node: &Node<&T>) {
+        let empty = node
+            .nodes()
+            .any(|child| child.kind() == NodeKind::Chunk && child.nodes().next().is_none());
+        if let (true, Some(keyword)) = (empty, node.tokens().next()) {
+            let message = format!("empty '{}' block", text(keyword.span()));
+            self.report_token(Lint::EmptyBlock, keyword, message);
+        }
+    }
+
+    /// Check the keys of a table constructor
+    fn table(&mut self, table: &Node<&'a T>) {
+        let mut keys = BTreeSet::new();
+        let mut position = 0;
+        for field in table.nodes() {
+            let significant: Vec<_> = field.children().iter().filter(|e| !is_trivia(e)).collect();
+            let (key, token) = match significant.as_slice() {
+                [Element::Token(name), _, _] if name.kind() == TokenKind::Name => {
+                    (Key::String(Cow::Borrowed(name.span().as_bytes())), name)
+                }
+                [_, Element::Node(expr), _, _, _] => {
+                    let mut tokens = expr.significant_tokens();
+                    let (Some(token), None) = (tokens.next(), tokens.next()) else {
+                        continue;
+                    };
+                    let key = match token.kind() {
+                        TokenKind::String | TokenKind::LongString { .. } => {
+                            match token.string_value() {
+                                Some(value) => Key::String(value),
+                                None => continue,
+                            }
+                        }
+                        TokenKind::Number(value) => Key::number(value),
+                        TokenKind::Integer(value) => Key::number(value as f64),
+                        TokenKind::Hex(value) => Key::number(value as f64),
+                        _ => continue,
+                    };
+                    (key, token)
+                }
+                [Element::Node(expr)] => {
+                    position += 1;
+                    let Some(token) = expr.significant_tokens().next() else {
+                        continue;
+                    };
+                    (Key::number(position as f64), token)
+                }
+                _ => continue,
+            };
+            if keys.contains(&key) {
+                let message = format!("duplicate key {}", key);
+                self.report_token(Lint::DuplicateKey, token, message);
+            } else {
+                keys.insert(key);
+            }
+        }
+    }
+
+    /// Check the comparisons with `nil` in an expression
+    ///
+    /// The binary operators of an expression are not nested, so only
+    /// comparisons between `and` and `or` compare a single name.
+    fn comparisons(&mut self, expr: &Node<&'a T>) {
+        let items: Vec<_> = expr.children().iter().filter(|e| !is_trivia(e)).collect();
+        let is_nil = |element: &Element<&T>| is_keyword(element, &[Keyword::Nil]);
+        let is_bound = |index: Option<usize>| {
+            index
+                .and_then(|index| items.get(index))
+                .is_none_or(|element| is_keyword(element, &[Keyword::And, Keyword::Or]))
+        };
+        for index in 1..items.len().saturating_sub(1) {
+            if !is_symbol(items[index], &[Symbol::Eq, Symbol::NotEq])
+                || !is_bound(index.checked_sub(2))
+                || !is_bound(Some(index + 2))
+            {
+                continue;
+            }
+            let operand = match (items[index - 1], items[index + 1]) {
+                (operand, nil) | (nil, operand) if is_nil(nil) => operand,
+                _ => continue,
+            };
+            let Element::Node(operand) = operand else {
+                continue;
+            };
+            match operand.children() {
+                [Element::Token(name)] if operand.kind() == NodeKind::Suffixed => {
+                    self.fresh_nil(name)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Report a name of a local without a value that is compared with `nil`
+    /// before anything can be assigned to it
+    fn fresh_nil(&mut self, name: &Token<&'a T>) {
+        let resolution = self.resolution;
+        let Some(&index) = self.references.get(&name.start().offset) else {
+            return;
+        };
+        let Binding::Local(declaration) = resolution.references[index].binding else {
+            return;
+        };
+        if !self.uninitialized.contains(&declaration) {
+            return;
+        }
+        let first = resolution.references_to(declaration).next();
+        if first.map(|reference| reference.token.start()) != Some(name.start()) {
+            return;
+        }
+        // A loop could assign to it in an earlier iteration
+        let start = resolution.declarations[declaration].token.start().offset;
+        let repeated = self.ancestors.iter().any(|&(kind, offset)| {
+            offset > start
+                && matches!(
+                    kind,
+                    NodeKind::While
+                        | NodeKind::Repeat
+                        | NodeKind::NumericFor
+                        | NodeKind::GenericFor
+                )
+        });
+        if !repeated {
+            let message = format!("'{}' is always nil here", text(name.span()));
+            self.report_token(Lint::NilComparison, name, message);
+        }
+    }
+
+    fn walk(&mut self, node: &Node<&'a T>) {
+        let start = node.start().map_or(0, |start| start.offset);
+        self.ancestors.push((node.kind(), start));
+        match node.kind() {
+            NodeKind::Chunk => self.chunk(node),
+            NodeKind::Table => self.table(node),
+            NodeKind::Expr => self.comparisons(node),
+            NodeKind::Local
+                if !node
+                    .children()
+                    .iter()
+                    .any(|e| is_symbol(e, &[Symbol::Assign])) =>
+            {
+                let names = node.nodes().filter(|n| n.kind() == NodeKind::NameList);
+                for name in names.flat_map(Node::significant_tokens) {
+                    let declaration = self.declarations.get(&name.start().offset);
+                    self.uninitialized.extend(declaration);
+                }
+            }
+            NodeKind::Do
+            | NodeKind::While
+            | NodeKind::Repeat
+            | NodeKind::If
+            | NodeKind::ElseIf
+            | NodeKind::Else
+            | NodeKind::NumericFor
+            | NodeKind::GenericFor => self.empty_block(node),
+            NodeKind::Break if self.loops.last() == Some(&0) => {
+                let message = String::from("'break' outside of a loop");
+                self.report_node(Lint::BreakOutsideLoop, node, message);
+            }
+            _ => {}
+        }
+        let is_loop = matches!(
+            node.kind(),
+            NodeKind::While | NodeKind::Repeat | NodeKind::NumericFor | NodeKind::GenericFor
+        );
+        if is_loop {
+            *self.loops.last_mut().expect("the main chunk") += 1;
+        }
+        if node.kind() == NodeKind::FuncBody {
+            self.loops.push(0);
+        }
+        for child in node.nodes() {
+            self.walk(child);
+        }
+        if node.kind() == NodeKind::FuncBody {
+            self.loops.pop();
+        }
+        if is_loop {
+            *self.loops.last_mut().expect("the main chunk") -= 1;
+        }
+        self.ancestors.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::{lint, Config, Lint};
+    use crate::{cst, Dialect, LuaVersion, Utf8Decoder};
+
+    /// The lints with their lines
+    fn check(text: &str, config: &Config) -> Vec<(Lint, usize)> {
+        let root = cst::parse_with(Utf8Decoder::new(text), config.dialect).unwrap();
+        let diagnostics = lint(&root, config);
+        diagnostics.iter().map(|d| (d.lint, d.start.line)).collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let text = "local a, _b = 1\n\
+            function f(x, y)\n\
+              local a = x\n\
+              return a, z, string\n\
+            end\n\
+            g = 1 print(g)\n\
+            local t = { a = 1, ['a'] = 2, 3, [1] = 4, [2.0] = 5 }\n\
+            do return t end\n\
+            print(1, function() return ... end)";
+        let config = Config::default();
+        assert_eq!(
+            check(text, &config),
+            [
+                (Lint::UnusedLocal, 1),
+                (Lint::ImplicitGlobal, 2),
+                (Lint::UnusedParam, 2),
+                (Lint::Shadowing, 3),
+                (Lint::UndefinedGlobal, 4),
+                (Lint::ImplicitGlobal, 6),
+                (Lint::DuplicateKey, 7),
+                (Lint::DuplicateKey, 7),
+                (Lint::Unreachable, 9),
+                (Lint::InvalidVararg, 9),
+            ]
+        );
+
+        let config = Config {
+            globals: &["f", "g", "z"],
+            disabled: &[Lint::UnusedLocal, Lint::DuplicateKey, Lint::InvalidVararg],
+            ..Config::default()
+        };
+        assert_eq!(
+            check(text, &config),
+            [
+                (Lint::UnusedParam, 2),
+                (Lint::Shadowing, 3),
+                (Lint::Unreachable, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builtins() {
+        let text = "print(gcinfo(), newproxy(), _ENV, bit32)";
+        let check = |dialect: Dialect| {
+            let config = Config {
+                dialect,
+                ..Config::default()
+            };
+            let root = cst::parse_str(text).unwrap();
+            let diagnostics = lint(&root, &config);
+            diagnostics
+                .into_iter()
+                .map(|d| d.message)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            check(LuaVersion::Lua51.into()),
+            ["undefined global '_ENV'", "undefined global 'bit32'"]
+        );
+        assert_eq!(
+            check(Dialect::LUAJIT),
+            ["undefined global '_ENV'", "undefined global 'bit32'"]
+        );
+        assert_eq!(
+            check(LuaVersion::Lua52.into()),
+            ["undefined global 'gcinfo'", "undefined global 'newproxy'"]
+        );
+    }
+
+    #[test]
+    fn test_attributes() {
+        let text = "local x <close>, y <const> = f()\n\
+            local z <close> = nil";
+        let config = Config {
+            dialect: LuaVersion::Lua54.into(),
+            globals: &["f"],
+            ..Config::default()
+        };
+        assert_eq!(check(text, &config), [(Lint::UnusedLocal, 1)]);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let text = "return { [1] = 3, 4, a = 5, [\"a\"] = 6, [2.5] = 7, [5 / 2] = 8, [2.5] = 9 }";
+        let root = cst::parse_str(text).unwrap();
+        let messages: Vec<_> = lint(&root, &Config::default())
+            .into_iter()
+            .map(|d| (d.start.column, d.message))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (19, "duplicate key 1".into()),
+                (30, "duplicate key 'a'".into()),
+                (65, "duplicate key 2.5".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let text = "local x\n\
+            if x == nil then end\n\
+            local y\n\
+            while true do if nil ~= y and 1 then y = 1 else break end end\n\
+            local function f() break end\n\
+            if f then return 1 else return 2 end\n\
+            f()";
+        let config = Config {
+            dialect: LuaVersion::Lua52.into(),
+            ..Config::default()
+        };
+        assert_eq!(
+            check(text, &config),
+            [
+                (Lint::EmptyBlock, 2),
+                (Lint::NilComparison, 2),
+                (Lint::BreakOutsideLoop, 5),
+                (Lint::Unreachable, 7),
+            ]
+        );
+    }
+}
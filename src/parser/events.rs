@@ -0,0 +1,267 @@
+//! # Streaming Events
+//!
+//! A push-style alternative to the [Sink] for inputs that are too large for
+//! a syntax tree, e.g. generated table dumps. The parser only keeps its own
+//! call stack, so [Parser::parse_events] doesn't allocate.
+
+use super::{BinOp, NodeKind, Parser, Sink, UnOp};
+use crate::{Cdata, Decoder, Error, Position, Token};
+
+/// Receives the structure of the input from [Parser::parse_events]
+///
+/// `S` is the type of the spans in the input, i.e. `&str` or `&[u8]`
+///
+/// The events form a tree: every [Events::enter_node] is matched by an
+/// [Events::exit_node] of the same kind, unless the parser stops with an
+/// error. Every token in the input, including whitespace and comments, is
+/// passed to [Events::token] between those calls.
+pub trait Events<S> {
+    /// Called before the first token of a syntax node, which starts at `start`
+    fn enter_node(&mut self, kind: NodeKind, start: Position);
+    /// Called for every token in order
+    fn token(&mut self, token: Token<S>);
+    /// Called after the last token of a syntax node, which ends at `end`
+    ///
+    /// An empty node ends where it starts.
+    fn exit_node(&mut self, kind: NodeKind, end: Position);
+    /// Called for every error that a parser created with [Parser::recovering]
+    /// recovers from
+    fn error(&mut self, _error: Error) {}
+}
+
+/// Forwards the structure of the syntax tree to [Events]
+struct EventSink<'a, E>(&'a mut E);
+
+impl<'a, S, E: Events<S>> Sink<S> for EventSink<'a, E> {
+    type If = ();
+    type Var = ();
+    type VarList = ();
+    type FuncName = ();
+    type Expr = ();
+    type ExprList = ();
+    type NameList = ();
+    type Table = ();
+    type FuncBody = ();
+    type Call = ();
+    type Stmt = ();
+    type Chunk = ();
+
+    fn chunk(&mut self) {}
+    fn block(&mut self, _chunk: ()) {}
+    fn while_stmt(&mut self, _expr: (), _block: ()) {}
+    fn repeat_stmt(&mut self, _block: (), _expr: ()) {}
+    fn if_stmt(&mut self, _exp: (), _block: ()) {}
+    fn numeric_for_stmt(
+        &mut self,
+        _name: S,
+        _start: (),
+        _limit: (),
+        _step: Option<()>,
+        _block: (),
+    ) {
+    }
+    fn generic_for_stmt(&mut self, _names: (), _exprs: (), _block: ()) {}
+    fn function_stmt(&mut self, _name: (), _body: ()) {}
+    fn local_function_stmt(&mut self, _name: S, _body: ()) {}
+    fn local_stmt(&mut self, _names: (), _exprs: Option<()>) {}
+    fn assign_stmt(&mut self, _vars: (), _exprs: ()) {}
+    fn call_stmt(&mut self, _call: ()) {}
+    fn return_stmt(&mut self, _exprs: Option<()>) {}
+    fn break_stmt(&mut self) {}
+    fn goto_stmt(&mut self, _label: S) {}
+    fn label_stmt(&mut self, _name: S) {}
+    fn continue_stmt(&mut self) {}
+    fn compound_assign_stmt(&mut self, _var: (), _op: BinOp, _expr: ()) {}
+    fn type_alias_stmt(&mut self, _export: bool, _name: S, _ty: S) {}
+
+    fn var_list(&mut self) {}
+    fn expr_list(&mut self) {}
+    fn name_list(&mut self) {}
+    fn func_name(&mut self, _name: S) {}
+    fn table(&mut self) {}
+    fn func_body(&mut self, _params: (), _vararg: bool, _block: ()) {}
+
+    fn name_var(&mut self, _name: S) {}
+    fn index_var(&mut self, _prefix: (), _key: ()) {}
+    fn field_var(&mut self, _prefix: (), _name: S) {}
+
+    fn call(&mut self, _prefix: (), _args: ()) {}
+    fn method_call(&mut self, _prefix: (), _name: S, _args: ()) {}
+
+    fn nil_expr(&mut self) {}
+    fn bool_expr(&mut self, _value: bool) {}
+    fn number_expr(&mut self, _span: S, _value: f64) {}
+    fn integer_expr(&mut self, _span: S, _value: i64) {}
+    fn cdata_expr(&mut self, _span: S, _value: Cdata) {}
+    fn string_expr(&mut self, _span: S) {}
+    fn interp_string_expr(&mut self, _parts: ()) {}
+    fn if_expr(&mut self, _cond: (), _then: (), _else: ()) {}
+    fn vararg_expr(&mut self) {}
+    fn function_expr(&mut self, _body: ()) {}
+    fn table_expr(&mut self, _table: ()) {}
+    fn binary_expr(&mut self, _lhs: (), _op: BinOp, _rhs: ()) {}
+    fn unary_expr(&mut self, _op: UnOp, _expr: ()) {}
+    fn paren_expr(&mut self, _expr: ()) {}
+    fn var_expr(&mut self, _var: ()) {}
+    fn call_expr(&mut self, _call: ()) {}
+
+    const TOKENS: bool = true;
+
+    fn enter(&mut self, kind: NodeKind, start: Position) {
+        self.0.enter_node(kind, start);
+    }
+
+    fn token(&mut self, token: Token<S>) {
+        self.0.token(token);
+    }
+
+    fn exit(&mut self, kind: NodeKind, end: Position) {
+        self.0.exit_node(kind, end);
+    }
+
+    fn error(&mut self, error: Error) {
+        self.0.error(error);
+    }
+}
+
+impl<'i, D: Decoder<'i>> Parser<'i, D> {
+    /// Parse an entire input and report its syntax tree to `events`
+    ///
+    /// The outermost node is [NodeKind::Main].
+    pub fn parse_events<E: Events<&'i D::Slice>>(&mut self, events: &mut E) -> Result<(), Error> {
+        self.parse_main(&mut EventSink(events))?;
+        Ok(())
+    }
+
+    /// Parse a single statement and report its syntax tree to `events`
+    #[cfg(feature = "alloc")]
+    pub(crate) fn parse_stmt_events<E: Events<&'i D::Slice>>(
+        &mut self,
+        events: &mut E,
+    ) -> Result<(), Error> {
+        self.parse_stmt(&mut EventSink(events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::Events;
+    use std::format;
+
+    use crate::{ErrorKind, NodeKind, Parser, Position, Token, TokenKind};
+
+    /// Extracts the source text of a field in `return { ... }`
+    struct Field<'a> {
+        input: &'a str,
+        name: &'a str,
+        depth: usize,
+        matched: bool,
+        start: usize,
+        value: Option<&'a str>,
+    }
+
+    /// Main, Chunk, Return, ExprList, Expr, Table, TableField
+    const FIELD_DEPTH: usize = 7;
+
+    impl<'a> Events<&'a str> for Field<'a> {
+        fn enter_node(&mut self, kind: NodeKind, start: Position) {
+            self.depth += 1;
+            if self.matched && kind == NodeKind::Expr && self.depth == FIELD_DEPTH + 1 {
+                self.start = start.offset;
+            }
+        }
+
+        fn token(&mut self, token: Token<&'a str>) {
+            if self.depth == FIELD_DEPTH && token.kind() == TokenKind::Name {
+                self.matched = token.span() == self.name;
+            }
+        }
+
+        fn exit_node(&mut self, kind: NodeKind, end: Position) {
+            if self.matched && kind == NodeKind::Expr && self.depth == FIELD_DEPTH + 1 {
+                self.value = Some(&self.input[self.start..end.offset]);
+                self.matched = false;
+            }
+            self.depth -= 1;
+        }
+    }
+
+    /// Records the events as `(depth, kind, start, end)`
+    #[derive(Default)]
+    struct Nodes {
+        stack: Vec<(NodeKind, usize)>,
+        nodes: Vec<(usize, NodeKind, usize, usize)>,
+        tokens: usize,
+    }
+
+    impl<S> Events<S> for Nodes {
+        fn enter_node(&mut self, kind: NodeKind, start: Position) {
+            self.stack.push((kind, start.offset));
+        }
+
+        fn token(&mut self, _token: Token<S>) {
+            self.tokens += 1;
+        }
+
+        fn exit_node(&mut self, kind: NodeKind, end: Position) {
+            let (entered, start) = self.stack.pop().unwrap();
+            assert_eq!(entered, kind);
+            self.nodes.push((self.stack.len(), kind, start, end.offset));
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let mut nodes = Nodes::default();
+        let mut parser = Parser::new_from_str("do  end -- x\n").unwrap();
+        parser.parse_events(&mut nodes).unwrap();
+        assert!(nodes.stack.is_empty());
+        assert_eq!(
+            nodes.nodes,
+            [
+                (3, NodeKind::Chunk, 4, 4),
+                (2, NodeKind::Do, 0, 7),
+                (1, NodeKind::Chunk, 0, 7),
+                (0, NodeKind::Main, 0, 13),
+            ]
+        );
+        assert_eq!(nodes.tokens, 6);
+    }
+
+    #[test]
+    fn test_deep_events() {
+        let input = format!("x = a{}", " .. a".repeat(100_000));
+        let mut nodes = Nodes::default();
+        let mut parser = Parser::new_from_str(&input).unwrap();
+        let error = parser.parse_events(&mut nodes).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TooManyLevels);
+
+        // Left operands don't nest
+        let input = format!("x = a{}", " + a".repeat(100_000));
+        let mut parser = Parser::new_from_str(&input).unwrap();
+        parser.parse_events(&mut Nodes::default()).unwrap();
+    }
+
+    #[test]
+    fn test_field() {
+        let input =
+            "return {\n  name = 'x',\n  deps = { version = 1 },\n  version = 2 ^ 3, -- y\n}";
+        let mut field = Field {
+            input,
+            name: "version",
+            depth: 0,
+            matched: false,
+            start: 0,
+            value: None,
+        };
+        Parser::new_from_str(input)
+            .unwrap()
+            .parse_events(&mut field)
+            .unwrap();
+        assert_eq!(field.depth, 0);
+        assert_eq!(field.value, Some("2 ^ 3"));
+    }
+}
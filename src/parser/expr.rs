@@ -0,0 +1,566 @@
+//! # Expressions
+//!
+//! This is a precedence climbing parser modelled after `subexpr` in `lparser.c`.
+//!
+//! See: <https://www.lua.org/manual/5.1/manual.html#2.5>
+
+use super::{ExprList, NameList, NodeKind, Parser, Sink, Table};
+use crate::{
+    Decoder, Error, ErrorKind, Expected, InterpPart, Keyword, LuaVersion, Symbol, Token, TokenKind,
+};
+
+/// Binary Operators
+///
+/// See: <https://www.lua.org/manual/5.1/manual.html#2.5.6> and
+/// <https://www.lua.org/manual/5.3/manual.html#3.4.8>
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinOp {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `//` (Lua 5.3)
+    IDiv,
+    /// `%`
+    Mod,
+    /// `^`
+    Pow,
+    /// `..`
+    Concat,
+    /// `&` (Lua 5.3)
+    BAnd,
+    /// `|` (Lua 5.3)
+    BOr,
+    /// `~` (Lua 5.3)
+    BXor,
+    /// `<<` (Lua 5.3)
+    Shl,
+    /// `>>` (Lua 5.3)
+    Shr,
+    /// `==`
+    Eq,
+    /// `~=`
+    NotEq,
+    /// `<`
+    Lt,
+    /// `<=`
+    LtEq,
+    /// `>`
+    Gt,
+    /// `>=`
+    GtEq,
+    /// `and`
+    And,
+    /// `or`
+    Or,
+}
+
+impl BinOp {
+    /// Returns the binary operator for a token, if any
+    pub fn from_token_kind(kind: TokenKind) -> Option<Self> {
+        match kind {
+            TokenKind::Symbol(Symbol::Plus) => Some(Self::Add),
+            TokenKind::Symbol(Symbol::Minus) => Some(Self::Sub),
+            TokenKind::Symbol(Symbol::Times) => Some(Self::Mul),
+            TokenKind::Symbol(Symbol::Slash) => Some(Self::Div),
+            TokenKind::Symbol(Symbol::Slash2) => Some(Self::IDiv),
+            TokenKind::Symbol(Symbol::Percent) => Some(Self::Mod),
+            TokenKind::Symbol(Symbol::Caret) => Some(Self::Pow),
+            TokenKind::Symbol(Symbol::Dot2) => Some(Self::Concat),
+            TokenKind::Symbol(Symbol::Amp) => Some(Self::BAnd),
+            TokenKind::Symbol(Symbol::Pipe) => Some(Self::BOr),
+            TokenKind::Symbol(Symbol::Tilde) => Some(Self::BXor),
+            TokenKind::Symbol(Symbol::Shl) => Some(Self::Shl),
+            TokenKind::Symbol(Symbol::Shr) => Some(Self::Shr),
+            TokenKind::Symbol(Symbol::Eq) => Some(Self::Eq),
+            TokenKind::Symbol(Symbol::NotEq) => Some(Self::NotEq),
+            TokenKind::Symbol(Symbol::Lt) => Some(Self::Lt),
+            TokenKind::Symbol(Symbol::LtEq) => Some(Self::LtEq),
+            TokenKind::Symbol(Symbol::Gt) => Some(Self::Gt),
+            TokenKind::Symbol(Symbol::GtEq) => Some(Self::GtEq),
+            TokenKind::Keyword(Keyword::And) => Some(Self::And),
+            TokenKind::Keyword(Keyword::Or) => Some(Self::Or),
+            _ => None,
+        }
+    }
+
+    /// Left and right priority, as in the `priority` table of `lparser.c` in Lua 5.3
+    ///
+    /// The operators of Lua 5.1 have the same relative priorities in both versions.
+    /// An operator is right associative if the right priority is lower than the left one.
+    pub fn priority(self) -> (u8, u8) {
+        match self {
+            Self::Add | Self::Sub => (10, 10),
+            Self::Mul | Self::Div | Self::IDiv | Self::Mod => (11, 11),
+            Self::Pow => (14, 13),
+            Self::Concat => (9, 8),
+            Self::BAnd => (6, 6),
+            Self::BOr => (4, 4),
+            Self::BXor => (5, 5),
+            Self::Shl | Self::Shr => (7, 7),
+            Self::Eq | Self::NotEq | Self::Lt | Self::LtEq | Self::Gt | Self::GtEq => (3, 3),
+            Self::And => (2, 2),
+            Self::Or => (1, 1),
+        }
+    }
+}
+
+/// Unary Operators
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnOp {
+    /// `not`
+    Not,
+    /// `-`
+    Neg,
+    /// `#`
+    Len,
+    /// `~` (Lua 5.3)
+    BNot,
+}
+
+impl UnOp {
+    /// Priority of all unary operators
+    pub const PRIORITY: u8 = 12;
+
+    /// Returns the unary operator for a token, if any
+    pub fn from_token_kind(kind: TokenKind) -> Option<Self> {
+        match kind {
+            TokenKind::Keyword(Keyword::Not) => Some(Self::Not),
+            TokenKind::Symbol(Symbol::Minus) => Some(Self::Neg),
+            TokenKind::Symbol(Symbol::Hash) => Some(Self::Len),
+            TokenKind::Symbol(Symbol::Tilde) => Some(Self::BNot),
+            _ => None,
+        }
+    }
+}
+
+/// The result of parsing a prefix expression
+///
+/// Statements need to know whether this was a variable (assignment)
+/// or a function call.
+pub(super) enum Suffixed<V, C, E> {
+    Var(V),
+    Call(C),
+    Expr(E),
+}
+
+type SuffixedOf<S, P> = Suffixed<<P as Sink<S>>::Var, <P as Sink<S>>::Call, <P as Sink<S>>::Expr>;
+
+impl<'i, D: Decoder<'i>> Parser<'i, D> {
+    /// 2.5 – Expressions
+    pub fn parse_expr<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Expr, Error> {
+        self.start_node(sink, NodeKind::Expr)?;
+        let expr = self.parse_subexpr(sink, 0)?;
+        self.finish_node(sink, NodeKind::Expr)?;
+        Ok(expr)
+    }
+
+    /// `explist ::= {exp ','} exp`
+    pub fn parse_expr_list<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+    ) -> Result<P::ExprList, Error> {
+        self.start_node(sink, NodeKind::ExprList)?;
+        let mut list = sink.expr_list();
+        list.push_expr(self.parse_expr(sink)?);
+        while self.peek().kind() == TokenKind::Symbol(Symbol::Comma) {
+            self.next()?;
+            list.push_expr(self.parse_expr(sink)?);
+        }
+        self.finish_node(sink, NodeKind::ExprList)?;
+        Ok(list)
+    }
+
+    fn parse_subexpr<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        limit: u8,
+    ) -> Result<P::Expr, Error> {
+        self.enter_level()?;
+        let lhs = if let Some(op) = UnOp::from_token_kind(self.peek().kind()) {
+            self.start_node(sink, NodeKind::Unary)?;
+            self.next()?;
+            let expr = self.parse_subexpr(sink, UnOp::PRIORITY)?;
+            self.finish_node(sink, NodeKind::Unary)?;
+            sink.unary_expr(op, expr)
+        } else {
+            let expr = self.parse_simple_expr(sink)?;
+            self.parse_cast(sink)?;
+            expr
+        };
+        let expr = self.parse_binary_rest(sink, lhs, limit)?;
+        self.leave_level();
+        Ok(expr)
+    }
+
+    /// Expand `lhs` while the next operator binds tighter than `limit`
+    fn parse_binary_rest<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        mut lhs: P::Expr,
+        limit: u8,
+    ) -> Result<P::Expr, Error> {
+        while let Some(op) = BinOp::from_token_kind(self.peek().kind()) {
+            let (left, right) = op.priority();
+            if left <= limit {
+                break;
+            }
+            self.next()?;
+            let rhs = self.parse_subexpr(sink, right)?;
+            lhs = sink.binary_expr(lhs, op, rhs);
+        }
+        Ok(lhs)
+    }
+
+    /// Parse an expression where the leading `Name` was already consumed
+    pub(super) fn parse_expr_from_name<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        name: Token<&'i D::Slice>,
+    ) -> Result<P::Expr, Error> {
+        self.enter_level()?;
+        self.start_node_at(sink, NodeKind::Expr, name.start())?;
+        self.start_node_at(sink, NodeKind::Suffixed, name.start())?;
+        let var = sink.name_var(name.span());
+        let suffixed = self.parse_suffixes(sink, Suffixed::Var(var))?;
+        self.finish_node(sink, NodeKind::Suffixed)?;
+        let expr = Self::suffixed_expr(sink, suffixed);
+        self.parse_cast(sink)?;
+        let expr = self.parse_binary_rest(sink, expr, 0)?;
+        self.finish_node(sink, NodeKind::Expr)?;
+        self.leave_level();
+        Ok(expr)
+    }
+
+    fn parse_simple_expr<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Expr, Error> {
+        let token = self.peek();
+        match token.kind() {
+            TokenKind::Number(value) => {
+                self.next()?;
+                Ok(sink.number_expr(token.span(), value))
+            }
+            TokenKind::Hex(value) => {
+                self.next()?;
+                if self.version() >= LuaVersion::Lua53 {
+                    Ok(sink.integer_expr(token.span(), value as i64))
+                } else {
+                    Ok(sink.number_expr(token.span(), value as f64))
+                }
+            }
+            TokenKind::Integer(value) => {
+                self.next()?;
+                Ok(sink.integer_expr(token.span(), value))
+            }
+            TokenKind::Cdata(value) => {
+                self.next()?;
+                Ok(sink.cdata_expr(token.span(), value))
+            }
+            TokenKind::String
+            | TokenKind::LongString { .. }
+            | TokenKind::InterpString(InterpPart::Simple) => {
+                self.next()?;
+                Ok(sink.string_expr(token.span()))
+            }
+            TokenKind::InterpString(InterpPart::Begin) => {
+                self.start_node(sink, NodeKind::InterpString)?;
+                self.next()?;
+                let mut parts = sink.expr_list();
+                parts.push_expr(sink.string_expr(token.span()));
+                loop {
+                    parts.push_expr(self.parse_expr(sink)?);
+                    let token = self.peek();
+                    match token.kind() {
+                        TokenKind::InterpString(part @ (InterpPart::Mid | InterpPart::End)) => {
+                            self.next()?;
+                            parts.push_expr(sink.string_expr(token.span()));
+                            if part == InterpPart::End {
+                                break;
+                            }
+                        }
+                        _ => return Err(self.unexpected(Expected::Symbol(Symbol::BraceR))),
+                    }
+                }
+                self.finish_node(sink, NodeKind::InterpString)?;
+                Ok(sink.interp_string_expr(parts))
+            }
+            TokenKind::Keyword(Keyword::If) if self.dialect().luau => {
+                self.start_node(sink, NodeKind::IfExpr)?;
+                self.next()?;
+                let expr = self.parse_if_expr(sink)?;
+                self.finish_node(sink, NodeKind::IfExpr)?;
+                Ok(expr)
+            }
+            TokenKind::Keyword(Keyword::Nil) => {
+                self.next()?;
+                Ok(sink.nil_expr())
+            }
+            TokenKind::Keyword(Keyword::True) => {
+                self.next()?;
+                Ok(sink.bool_expr(true))
+            }
+            TokenKind::Keyword(Keyword::False) => {
+                self.next()?;
+                Ok(sink.bool_expr(false))
+            }
+            TokenKind::Symbol(Symbol::Dot3) => {
+                self.next()?;
+                Ok(sink.vararg_expr())
+            }
+            TokenKind::Symbol(Symbol::BraceL) => {
+                let table = self.parse_table(sink)?;
+                Ok(sink.table_expr(table))
+            }
+            TokenKind::Keyword(Keyword::Function) => {
+                self.start_node(sink, NodeKind::FunctionExpr)?;
+                self.next()?;
+                let body = self.parse_func_body(sink)?;
+                self.finish_node(sink, NodeKind::FunctionExpr)?;
+                Ok(sink.function_expr(body))
+            }
+            _ => {
+                let suffixed = self.parse_suffixed(sink)?;
+                Ok(Self::suffixed_expr(sink, suffixed))
+            }
+        }
+    }
+
+    /// `exp then exp {elseif exp then exp} else exp` after the `if` (Luau)
+    fn parse_if_expr<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Expr, Error> {
+        self.enter_level()?;
+        let cond = self.parse_expr(sink)?;
+        self.expect_keyword(Keyword::Then)?;
+        let then = self.parse_expr(sink)?;
+        let else_ = if self.peek().kind() == TokenKind::Keyword(Keyword::ElseIf) {
+            self.next()?;
+            self.parse_if_expr(sink)?
+        } else {
+            self.expect_keyword(Keyword::Else)?;
+            self.parse_expr(sink)?
+        };
+        self.leave_level();
+        Ok(sink.if_expr(cond, then, else_))
+    }
+
+    fn suffixed_expr<P: Sink<&'i D::Slice>>(
+        sink: &mut P,
+        suffixed: SuffixedOf<&'i D::Slice, P>,
+    ) -> P::Expr {
+        match suffixed {
+            Suffixed::Var(var) => sink.var_expr(var),
+            Suffixed::Call(call) => sink.call_expr(call),
+            Suffixed::Expr(expr) => expr,
+        }
+    }
+
+    /// `prefixexp ::= var | functioncall | '(' exp ')'`
+    pub(super) fn parse_suffixed<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+    ) -> Result<SuffixedOf<&'i D::Slice, P>, Error> {
+        let token = self.peek();
+        let primary = match token.kind() {
+            TokenKind::Name => {
+                self.start_node(sink, NodeKind::Suffixed)?;
+                self.next()?;
+                Suffixed::Var(sink.name_var(token.span()))
+            }
+            TokenKind::Symbol(Symbol::ParenL) => {
+                self.start_node(sink, NodeKind::Suffixed)?;
+                self.start_node(sink, NodeKind::Paren)?;
+                self.next()?;
+                let expr = self.parse_expr(sink)?;
+                self.expect_symbol(Symbol::ParenR)?;
+                self.finish_node(sink, NodeKind::Paren)?;
+                Suffixed::Expr(sink.paren_expr(expr))
+            }
+            _ => return Err(self.unexpected(Expected::Expression)),
+        };
+        let suffixed = self.parse_suffixes(sink, primary)?;
+        self.finish_node(sink, NodeKind::Suffixed)?;
+        Ok(suffixed)
+    }
+
+    fn parse_suffixes<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        mut current: SuffixedOf<&'i D::Slice, P>,
+    ) -> Result<SuffixedOf<&'i D::Slice, P>, Error> {
+        loop {
+            current = match self.peek().kind() {
+                TokenKind::Symbol(Symbol::Dot) => {
+                    self.start_node(sink, NodeKind::Field)?;
+                    self.next()?;
+                    let name = self.expect_name()?;
+                    self.finish_node(sink, NodeKind::Field)?;
+                    let prefix = Self::suffixed_expr(sink, current);
+                    Suffixed::Var(sink.field_var(prefix, name))
+                }
+                TokenKind::Symbol(Symbol::BracketL) => {
+                    self.start_node(sink, NodeKind::Index)?;
+                    self.next()?;
+                    let key = self.parse_expr(sink)?;
+                    self.expect_symbol(Symbol::BracketR)?;
+                    self.finish_node(sink, NodeKind::Index)?;
+                    let prefix = Self::suffixed_expr(sink, current);
+                    Suffixed::Var(sink.index_var(prefix, key))
+                }
+                TokenKind::Symbol(Symbol::Colon) => {
+                    self.start_node(sink, NodeKind::MethodCall)?;
+                    self.next()?;
+                    let name = self.expect_name()?;
+                    let args = self.parse_args(sink)?;
+                    self.finish_node(sink, NodeKind::MethodCall)?;
+                    let prefix = Self::suffixed_expr(sink, current);
+                    Suffixed::Call(sink.method_call(prefix, name, args))
+                }
+                TokenKind::Symbol(Symbol::ParenL | Symbol::BraceL)
+                | TokenKind::String
+                | TokenKind::LongString { .. } => {
+                    self.start_node(sink, NodeKind::Call)?;
+                    let args = self.parse_args(sink)?;
+                    self.finish_node(sink, NodeKind::Call)?;
+                    let prefix = Self::suffixed_expr(sink, current);
+                    Suffixed::Call(sink.call(prefix, args))
+                }
+                _ => break Ok(current),
+            };
+        }
+    }
+
+    /// `args ::= '(' [explist] ')' | tableconstructor | String`
+    fn parse_args<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::ExprList, Error> {
+        let token = self.peek();
+        match token.kind() {
+            TokenKind::String | TokenKind::LongString { .. } => {
+                self.next()?;
+                let mut args = sink.expr_list();
+                args.push_expr(sink.string_expr(token.span()));
+                Ok(args)
+            }
+            TokenKind::Symbol(Symbol::BraceL) => {
+                let table = self.parse_table(sink)?;
+                let mut args = sink.expr_list();
+                args.push_expr(sink.table_expr(table));
+                Ok(args)
+            }
+            TokenKind::Symbol(Symbol::ParenL) => {
+                // Lua 5.1, LuaJIT and Luau reject `f\n(g)`, which could be two statements
+                let dialect = self.dialect();
+                let strict = dialect.version == LuaVersion::Lua51 || dialect.luajit;
+                if strict && token.start().line != self.prev_end.line {
+                    return Err(self.error(ErrorKind::AmbiguousSyntax));
+                }
+                self.next()?;
+                if self.peek().kind() == TokenKind::Symbol(Symbol::ParenR) {
+                    self.next()?;
+                    Ok(sink.expr_list())
+                } else {
+                    let args = self.parse_expr_list(sink)?;
+                    self.expect_symbol(Symbol::ParenR)?;
+                    Ok(args)
+                }
+            }
+            _ => Err(self.unexpected(Expected::Arguments)),
+        }
+    }
+
+    /// `funcbody ::= '(' [parlist] ')' block end`
+    ///
+    /// Luau adds generics before the parameters and type annotations.
+    pub(super) fn parse_func_body<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+    ) -> Result<P::FuncBody, Error> {
+        self.start_node(sink, NodeKind::FuncBody)?;
+        if self.dialect().luau && self.peek().kind() == TokenKind::Symbol(Symbol::Lt) {
+            self.parse_generics(sink)?;
+        }
+        self.expect_symbol(Symbol::ParenL)?;
+        self.start_node(sink, NodeKind::Params)?;
+        let mut params = sink.name_list();
+        let mut vararg = false;
+        if self.peek().kind() != TokenKind::Symbol(Symbol::ParenR) {
+            loop {
+                let token = self.peek();
+                match token.kind() {
+                    TokenKind::Name => params.push_name(token.span()),
+                    TokenKind::Symbol(Symbol::Dot3) => {
+                        self.next()?;
+                        self.parse_annotation(sink)?;
+                        vararg = true;
+                        break;
+                    }
+                    _ => return Err(self.unexpected(Expected::Parameter)),
+                }
+                self.next()?;
+                self.parse_annotation(sink)?;
+                if self.peek().kind() == TokenKind::Symbol(Symbol::Comma) {
+                    self.next()?;
+                } else {
+                    break;
+                }
+            }
+        }
+        self.finish_node(sink, NodeKind::Params)?;
+        self.expect_symbol(Symbol::ParenR)?;
+        self.parse_annotation(sink)?;
+        let block = self.parse_chunk(sink)?;
+        self.expect_keyword(Keyword::End)?;
+        self.finish_node(sink, NodeKind::FuncBody)?;
+        Ok(sink.func_body(params, vararg, block))
+    }
+
+    /// `tableconstructor ::= '{' [fieldlist] '}'`
+    fn parse_table<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Table, Error> {
+        self.start_node(sink, NodeKind::Table)?;
+        self.expect_symbol(Symbol::BraceL)?;
+        let mut table = sink.table();
+        loop {
+            let token = self.peek();
+            if token.kind() == TokenKind::Symbol(Symbol::BraceR) {
+                break;
+            }
+            self.start_node(sink, NodeKind::TableField)?;
+            match token.kind() {
+                TokenKind::Symbol(Symbol::BracketL) => {
+                    self.next()?;
+                    let key = self.parse_expr(sink)?;
+                    self.expect_symbol(Symbol::BracketR)?;
+                    self.expect_symbol(Symbol::Assign)?;
+                    let value = self.parse_expr(sink)?;
+                    table.push_keyed(key, value);
+                }
+                TokenKind::Name => {
+                    // `Name '=' exp` needs a second token of lookahead
+                    self.next()?;
+                    if self.peek().kind() == TokenKind::Symbol(Symbol::Assign) {
+                        self.next()?;
+                        let value = self.parse_expr(sink)?;
+                        table.push_named(token.span(), value);
+                    } else {
+                        let value = self.parse_expr_from_name(sink, token)?;
+                        table.push_item(value);
+                    }
+                }
+                _ => {
+                    let value = self.parse_expr(sink)?;
+                    table.push_item(value);
+                }
+            }
+            self.finish_node(sink, NodeKind::TableField)?;
+            match self.peek().kind() {
+                TokenKind::Symbol(Symbol::Comma | Symbol::Semicolon) => {
+                    self.next()?;
+                }
+                _ => break,
+            }
+        }
+        self.expect_symbol(Symbol::BraceR)?;
+        self.finish_node(sink, NodeKind::Table)?;
+        Ok(table)
+    }
+}
@@ -5,15 +5,19 @@
 //! In this module:
 //! - `'l` refers to the lifetime of a lexer
 //! - `'i` refers to the lifetime of the input
-use crate::{Decoder, Keyword, Latin1Decoder, Lexer, Symbol, Token, TokenKind, Utf8Decoder};
+use crate::{
+    encoding::ByteLen, Cdata, Decoder, Dialect, Error, ErrorKind, Expected, Keyword, Latin1Decoder,
+    Lexer, LuaVersion, Position, Symbol, Token, TokenKind, Utf8Decoder,
+};
 
-pub struct Error {}
-
-impl From<super::lexer::Error> for Error {
-    fn from(_: super::lexer::Error) -> Self {
-        Self {}
-    }
-}
+mod expr;
+use expr::Suffixed;
+pub use expr::{BinOp, UnOp};
+mod node;
+pub use node::NodeKind;
+mod events;
+mod types;
+pub use events::Events;
 
 pub trait Chunk<S> {
     fn push_stmt(&mut self, stmt: S);
@@ -24,12 +28,90 @@ pub trait If<E, C> {
     fn else_clause(&mut self, block: C);
 }
 
-pub trait Sink {
+pub trait ExprList<E> {
+    fn push_expr(&mut self, expr: E);
+}
+
+pub trait VarList<V> {
+    fn push_var(&mut self, var: V);
+}
+
+/// `funcname ::= Name {'.' Name} [':' Name]`
+pub trait FuncName<S> {
+    fn push_field(&mut self, name: S);
+    fn method(&mut self, name: S);
+}
+
+pub trait NameList<S> {
+    fn push_name(&mut self, name: S);
+    /// `'<' Name '>'` after the last name in a `local` statement (Lua 5.4)
+    fn push_attrib(&mut self, attrib: S);
+}
+
+/// Fields of a table constructor
+pub trait Table<S, E> {
+    /// `exp`
+    fn push_item(&mut self, value: E);
+    /// `Name '=' exp`
+    fn push_named(&mut self, name: S, value: E);
+    /// `'[' exp ']' '=' exp`
+    fn push_keyed(&mut self, key: E, value: E);
+}
+
+impl<S> Chunk<S> for () {
+    fn push_stmt(&mut self, _stmt: S) {}
+}
+
+impl<E, C> If<E, C> for () {
+    fn else_if_clause(&mut self, _exp: E, _block: C) {}
+    fn else_clause(&mut self, _block: C) {}
+}
+
+impl<E> ExprList<E> for () {
+    fn push_expr(&mut self, _expr: E) {}
+}
+
+impl<V> VarList<V> for () {
+    fn push_var(&mut self, _var: V) {}
+}
+
+impl<S> FuncName<S> for () {
+    fn push_field(&mut self, _name: S) {}
+    fn method(&mut self, _name: S) {}
+}
+
+impl<S> NameList<S> for () {
+    fn push_name(&mut self, _name: S) {}
+    fn push_attrib(&mut self, _attrib: S) {}
+}
+
+impl<S, E> Table<S, E> for () {
+    fn push_item(&mut self, _value: E) {}
+    fn push_named(&mut self, _name: S, _value: E) {}
+    fn push_keyed(&mut self, _key: E, _value: E) {}
+}
+
+/// Receives the syntax elements recognized by the [Parser]
+///
+/// `S` is the type of the spans in the input, i.e. `&str` or `&[u8]`
+///
+/// In addition to the semantic callbacks, the parser reports the structure
+/// of the concrete syntax tree to [Sink::enter] and [Sink::exit]. If
+/// [Sink::TOKENS] is set, every token in the input, including whitespace
+/// and comments, is passed to [Sink::token] between those calls.
+///
+/// To receive only the structure, implement [Events] instead.
+pub trait Sink<S> {
     type If: If<Self::Expr, Self::Chunk> + Into<Self::Stmt>;
     type Var;
-    type VarList;
+    type VarList: VarList<Self::Var>;
+    type FuncName: FuncName<S>;
     type Expr;
-    type ExprList;
+    type ExprList: ExprList<Self::Expr>;
+    type NameList: NameList<S>;
+    type Table: Table<S, Self::Expr>;
+    type FuncBody;
+    type Call;
     type Stmt;
     type Chunk: Chunk<Self::Stmt>;
 
@@ -38,135 +120,865 @@ pub trait Sink {
     fn while_stmt(&mut self, expr: Self::Expr, block: Self::Chunk) -> Self::Stmt;
     fn repeat_stmt(&mut self, block: Self::Chunk, expr: Self::Expr) -> Self::Stmt;
     fn if_stmt(&mut self, exp: Self::Expr, block: Self::Chunk) -> Self::If;
+    /// `for Name '=' exp ',' exp [',' exp] do block end`
+    fn numeric_for_stmt(
+        &mut self,
+        name: S,
+        start: Self::Expr,
+        limit: Self::Expr,
+        step: Option<Self::Expr>,
+        block: Self::Chunk,
+    ) -> Self::Stmt;
+    /// `for namelist in explist do block end`
+    fn generic_for_stmt(
+        &mut self,
+        names: Self::NameList,
+        exprs: Self::ExprList,
+        block: Self::Chunk,
+    ) -> Self::Stmt;
+    /// `function funcname funcbody`
+    fn function_stmt(&mut self, name: Self::FuncName, body: Self::FuncBody) -> Self::Stmt;
+    /// `local function Name funcbody`
+    fn local_function_stmt(&mut self, name: S, body: Self::FuncBody) -> Self::Stmt;
+    /// `local namelist ['=' explist]`
+    fn local_stmt(&mut self, names: Self::NameList, exprs: Option<Self::ExprList>) -> Self::Stmt;
+    /// `varlist '=' explist`
+    fn assign_stmt(&mut self, vars: Self::VarList, exprs: Self::ExprList) -> Self::Stmt;
+    fn call_stmt(&mut self, call: Self::Call) -> Self::Stmt;
+    /// `return [explist]`
+    fn return_stmt(&mut self, exprs: Option<Self::ExprList>) -> Self::Stmt;
+    fn break_stmt(&mut self) -> Self::Stmt;
+    /// `goto Name` (Lua 5.2)
+    fn goto_stmt(&mut self, label: S) -> Self::Stmt;
+    /// `'::' Name '::'` (Lua 5.2)
+    fn label_stmt(&mut self, name: S) -> Self::Stmt;
+    /// `continue` (Luau)
+    fn continue_stmt(&mut self) -> Self::Stmt;
+    /// `var binop'=' exp`, e.g. `x += 1` (Luau)
+    fn compound_assign_stmt(&mut self, var: Self::Var, op: BinOp, expr: Self::Expr) -> Self::Stmt;
+    /// `['export'] 'type' Name '=' Type` (Luau), `ty` is the source text of the type
+    fn type_alias_stmt(&mut self, export: bool, name: S, ty: S) -> Self::Stmt;
+
+    fn var_list(&mut self) -> Self::VarList;
+    fn expr_list(&mut self) -> Self::ExprList;
+    fn name_list(&mut self) -> Self::NameList;
+    fn func_name(&mut self, name: S) -> Self::FuncName;
+    fn table(&mut self) -> Self::Table;
+    /// `funcbody ::= '(' [parlist] ')' block end`
+    fn func_body(
+        &mut self,
+        params: Self::NameList,
+        vararg: bool,
+        block: Self::Chunk,
+    ) -> Self::FuncBody;
+
+    /// `Name`
+    fn name_var(&mut self, name: S) -> Self::Var;
+    /// `prefixexp '[' exp ']'`
+    fn index_var(&mut self, prefix: Self::Expr, key: Self::Expr) -> Self::Var;
+    /// `prefixexp '.' Name`
+    fn field_var(&mut self, prefix: Self::Expr, name: S) -> Self::Var;
+
+    /// `prefixexp args`
+    fn call(&mut self, prefix: Self::Expr, args: Self::ExprList) -> Self::Call;
+    /// `prefixexp ':' Name args`
+    fn method_call(&mut self, prefix: Self::Expr, name: S, args: Self::ExprList) -> Self::Call;
+
+    fn nil_expr(&mut self) -> Self::Expr;
+    fn bool_expr(&mut self, value: bool) -> Self::Expr;
+    /// A numeric literal, the span is the original text
+    fn number_expr(&mut self, span: S, value: f64) -> Self::Expr;
+    /// An integer literal (Lua 5.3), the span is the original text
+    fn integer_expr(&mut self, span: S, value: i64) -> Self::Expr;
+    /// A number literal with a suffix (LuaJIT), the span is the original text
+    fn cdata_expr(&mut self, span: S, value: Cdata) -> Self::Expr;
+    /// A string literal, the span includes the delimiters
+    fn string_expr(&mut self, span: S) -> Self::Expr;
+    /// An interpolated string (Luau)
+    ///
+    /// The parts alternate between the strings, which are passed to
+    /// [Sink::string_expr] with their backticks and braces, and the
+    /// interpolated expressions.
+    fn interp_string_expr(&mut self, parts: Self::ExprList) -> Self::Expr;
+    /// `if exp then exp {elseif exp then exp} else exp` (Luau)
+    ///
+    /// Each `elseif` is passed as a nested `if` expression.
+    fn if_expr(&mut self, cond: Self::Expr, then: Self::Expr, else_: Self::Expr) -> Self::Expr;
+    /// `...`
+    fn vararg_expr(&mut self) -> Self::Expr;
+    fn function_expr(&mut self, body: Self::FuncBody) -> Self::Expr;
+    fn table_expr(&mut self, table: Self::Table) -> Self::Expr;
+    fn binary_expr(&mut self, lhs: Self::Expr, op: BinOp, rhs: Self::Expr) -> Self::Expr;
+    fn unary_expr(&mut self, op: UnOp, expr: Self::Expr) -> Self::Expr;
+    /// `'(' exp ')'`, which truncates multiple results to one
+    fn paren_expr(&mut self, expr: Self::Expr) -> Self::Expr;
+    fn var_expr(&mut self, var: Self::Var) -> Self::Expr;
+    fn call_expr(&mut self, call: Self::Call) -> Self::Expr;
+
+    /// Called for every error that a parser created with [Parser::recovering]
+    /// recovers from
+    fn error(&mut self, _error: Error) {}
+
+    /// Whether the parser should report all tokens to [Sink::token]
+    const TOKENS: bool = false;
+
+    /// Called before the first token of a syntax node, which starts at `start`
+    fn enter(&mut self, _kind: NodeKind, _start: Position) {}
+    /// Called for every token in order, if [Sink::TOKENS] is set
+    fn token(&mut self, _token: Token<S>) {}
+    /// Called after the last token of a syntax node, which ends at `end`
+    ///
+    /// An empty node ends where it starts.
+    fn exit(&mut self, _kind: NodeKind, _end: Position) {}
+}
+
+/// The maximum nesting of blocks and expressions, `LUAI_MAXCCALLS` in Lua 5.1
+const MAX_LEVELS: usize = 200;
+/// The maximum nesting of nodes
+///
+/// A level opens at most 7 nodes before the next one, e.g. `ExprStmt`,
+/// `Suffixed`, `Call`, `Table`, `TableField` and `Expr` after a `Chunk`, so
+/// only [MAX_LEVELS] limits valid input.
+const MAX_DEPTH: usize = MAX_LEVELS * 8;
+
+/// Tokens that end a block, see `block_follow` in `lparser.c`
+fn is_block_follow(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Keyword(Keyword::End | Keyword::Until | Keyword::Else | Keyword::ElseIf)
+            | TokenKind::Eof
+    )
+}
+
+/// Tokens where the parser continues after an error
+fn is_sync(kind: TokenKind) -> bool {
+    is_block_follow(kind)
+        || matches!(
+            kind,
+            TokenKind::Keyword(
+                Keyword::Local
+                    | Keyword::Function
+                    | Keyword::If
+                    | Keyword::While
+                    | Keyword::For
+                    | Keyword::Do
+                    | Keyword::Repeat
+                    | Keyword::Return
+                    | Keyword::Break
+                    | Keyword::Goto
+            )
+        )
+}
+
+/// The operator of a compound assignment (Luau)
+fn compound_op(kind: TokenKind) -> Option<BinOp> {
+    match kind {
+        TokenKind::Symbol(Symbol::PlusEq) => Some(BinOp::Add),
+        TokenKind::Symbol(Symbol::MinusEq) => Some(BinOp::Sub),
+        TokenKind::Symbol(Symbol::TimesEq) => Some(BinOp::Mul),
+        TokenKind::Symbol(Symbol::SlashEq) => Some(BinOp::Div),
+        TokenKind::Symbol(Symbol::PercentEq) => Some(BinOp::Mod),
+        TokenKind::Symbol(Symbol::CaretEq) => Some(BinOp::Pow),
+        TokenKind::Symbol(Symbol::Dot2Eq) => Some(BinOp::Concat),
+        _ => None,
+    }
+}
+
+/// Tokens after `continue` that make it a name in Luau, e.g. `continue = 1`
+fn continues_expr(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Symbol(
+            Symbol::ParenL
+                | Symbol::Dot
+                | Symbol::BracketL
+                | Symbol::Colon
+                | Symbol::Assign
+                | Symbol::Comma
+                | Symbol::BraceL
+        ) | TokenKind::String
+            | TokenKind::LongString { .. }
+    ) || compound_op(kind).is_some()
 }
 
 pub struct Parser<'i, D: Decoder<'i>> {
+    /// The entire input
+    input: &'i D::Slice,
     inner: Lexer<D>,
     next: Token<&'i D::Slice>,
+    /// The end of the last consumed token
+    prev_end: Position,
+    /// The start of the last started node
+    node_start: Position,
+    /// Re-lexes the input for [Sink::token], behind `inner`
+    emitted: Lexer<D>,
+    /// The kinds of the nodes that are not finished yet
+    open: [NodeKind; MAX_DEPTH],
+    depth: usize,
+    /// The nesting of blocks and expressions, see [Parser::enter_level]
+    levels: usize,
+    /// Whether errors are reported to [Sink::error] instead of returned
+    recover: bool,
 }
 
-fn next_semantic<'i, D: Decoder<'i>>(inner: &mut Lexer<D>) -> Result<Token<&'i D::Slice>, Error> {
-    let mut next = inner.token()?;
-    while matches!(next.kind(), TokenKind::Whitespace | TokenKind::Comment) {
-        next = inner.token()?;
+/// The next token that is not whitespace or a comment
+///
+/// If `recover` is set, input with lexer errors is skipped, too.
+fn next_semantic<'i, D: Decoder<'i>>(
+    inner: &mut Lexer<D>,
+    recover: bool,
+) -> Result<Token<&'i D::Slice>, Error> {
+    loop {
+        match inner.token() {
+            Ok(next) if matches!(next.kind(), TokenKind::Whitespace | TokenKind::Comment) => {}
+            Ok(next) => return Ok(next),
+            Err(error) if recover => {
+                inner.skip_error(&error);
+            }
+            Err(error) => return Err(error),
+        }
     }
-    Ok(next)
 }
 
 impl<'i, D: Decoder<'i>> Parser<'i, D> {
+    /// Create a parser for Lua 5.1
     pub fn new(decoder: D) -> Result<Self, Error> {
-        let mut inner = Lexer::new(decoder);
-        let next = next_semantic(&mut inner)?;
-        Ok(Self { inner, next })
+        Self::with_version(decoder, LuaVersion::Lua51)
+    }
+
+    /// Create a parser for the syntax of a specific Lua version
+    pub fn with_version(decoder: D, version: LuaVersion) -> Result<Self, Error> {
+        Self::with_dialect(decoder, version.into())
+    }
+
+    /// Create a parser for the syntax of a Lua version and its extensions
+    pub fn with_dialect(decoder: D, dialect: Dialect) -> Result<Self, Error> {
+        Self::create(decoder, dialect, false)
+    }
+
+    /// Create a parser that recovers from syntax errors
+    ///
+    /// Instead of stopping at the first error, the parser reports every error
+    /// to [Sink::error], skips the input up to the next statement in a
+    /// [NodeKind::Error] node and continues. Input that the lexer can't read
+    /// is reported as [TokenKind::Error] tokens.
+    pub fn recovering(decoder: D, dialect: Dialect) -> Self {
+        Self::create(decoder, dialect, true).expect("lexer errors are skipped")
+    }
+
+    /// Create a recovering parser that continues at the start of a statement
+    ///
+    /// `open` are the kinds of the nodes that contain the statement, and
+    /// `levels` is at least the nesting of [Parser::enter_level] there.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn resume(
+        decoder: D,
+        dialect: Dialect,
+        start: Position,
+        open: &[NodeKind],
+        levels: usize,
+    ) -> Self {
+        let input = decoder.as_slice();
+        let inner = Lexer::resume(decoder, dialect, start);
+        let mut parser = Self::from_lexer(input, inner, true).expect("lexer errors are skipped");
+        parser.depth = open.len().min(MAX_DEPTH);
+        parser.open[..parser.depth].copy_from_slice(&open[..parser.depth]);
+        parser.levels = levels.min(MAX_LEVELS);
+        parser
+    }
+
+    fn create(decoder: D, dialect: Dialect, recover: bool) -> Result<Self, Error> {
+        let input = decoder.as_slice();
+        Self::from_lexer(input, Lexer::with_dialect(decoder, dialect), recover)
+    }
+
+    fn from_lexer(input: &'i D::Slice, mut inner: Lexer<D>, recover: bool) -> Result<Self, Error> {
+        let start = inner.position();
+        let emitted = inner.clone();
+        let next = next_semantic(&mut inner, recover)?;
+        Ok(Self {
+            input,
+            inner,
+            next,
+            prev_end: start,
+            node_start: start,
+            emitted,
+            open: [NodeKind::Main; MAX_DEPTH],
+            depth: 0,
+            levels: 0,
+            recover,
+        })
+    }
+
+    /// The Lua version of this parser
+    pub fn version(&self) -> LuaVersion {
+        self.inner.version()
+    }
+
+    /// The Lua version and extensions of this parser
+    pub fn dialect(&self) -> Dialect {
+        self.inner.dialect()
     }
 
     fn peek(&self) -> Token<&'i D::Slice> {
         self.next
     }
 
+    /// The token after the next one, for the contextual keywords of Luau
+    fn peek2(&self) -> Result<Token<&'i D::Slice>, Error> {
+        next_semantic(&mut self.inner.clone(), self.recover)
+    }
+
+    /// The input between two positions
+    fn span(&self, start: Position, end: Position) -> &'i D::Slice {
+        let (before, _) = self.input.split_at(end.offset);
+        before.split_at(start.offset).1
+    }
+
     fn next(&mut self) -> Result<Token<&'i D::Slice>, Error> {
         let old = self.peek();
-        self.next = next_semantic(&mut self.inner)?;
+        self.next = next_semantic(&mut self.inner, self.recover)?;
+        self.prev_end = old.end();
         Ok(old)
     }
 
-    pub fn parse_expr<P: Sink>(&mut self, sink: &mut P) -> Result<P::Expr, Error> {
-        todo!()
+    /// Report all tokens before `offset` to the sink, if it wants them
+    ///
+    /// When recovering, this also reports the lexer errors.
+    fn emit_until<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        offset: usize,
+    ) -> Result<(), Error> {
+        if P::TOKENS || self.recover {
+            while self.emitted.offset() < offset {
+                let token = match self.emitted.token() {
+                    Ok(token) => token,
+                    Err(error) if self.recover => {
+                        sink.error(error);
+                        self.emitted.skip_error(&error)
+                    }
+                    Err(error) => return Err(error),
+                };
+                if P::TOKENS {
+                    sink.token(token);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a node at the next token
+    fn start_node<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        kind: NodeKind,
+    ) -> Result<(), Error> {
+        let start = self.next.start();
+        self.start_node_at(sink, kind, start)
+    }
+
+    /// Start a node at an already consumed token
+    ///
+    /// This is only valid if no other node was started or finished since
+    /// that token was consumed.
+    fn start_node_at<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        kind: NodeKind,
+        start: Position,
+    ) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::new(ErrorKind::TooManyLevels, start));
+        }
+        self.emit_until(sink, start.offset)?;
+        self.open[self.depth] = kind;
+        self.depth += 1;
+        self.node_start = start;
+        sink.enter(kind, start);
+        Ok(())
+    }
+
+    /// Finish a node after the last consumed token
+    fn finish_node<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        kind: NodeKind,
+    ) -> Result<(), Error> {
+        self.emit_until(sink, self.prev_end.offset)?;
+        self.depth -= 1;
+        debug_assert_eq!(self.open[self.depth], kind);
+        // An empty node starts at the next token, after `prev_end`
+        sink.exit(kind, self.prev_end.max(self.node_start));
+        Ok(())
+    }
+
+    /// Enter a nested block or expression, like `enterlevel` in `lparser.c`
+    ///
+    /// Every call that may recurse without opening a node counts a level,
+    /// so that deeply nested input fails instead of overflowing the stack.
+    fn enter_level(&mut self) -> Result<(), Error> {
+        if self.levels == MAX_LEVELS {
+            return Err(self.error(ErrorKind::TooManyLevels));
+        }
+        self.levels += 1;
+        Ok(())
+    }
+
+    fn leave_level(&mut self) {
+        self.levels -= 1;
+    }
+
+    /// An error at the start of the next token
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error::new(kind, self.next.start())
+    }
+
+    /// An error for an unexpected next token
+    fn unexpected(&self, expected: Expected) -> Error {
+        let found = self.peek().kind();
+        self.error(ErrorKind::UnexpectedToken { expected, found })
+    }
+
+    fn expect_symbol(&mut self, symbol: Symbol) -> Result<(), Error> {
+        if TokenKind::Symbol(symbol) == self.peek().kind() {
+            self.next()?;
+            Ok(())
+        } else {
+            Err(self.unexpected(Expected::Symbol(symbol)))
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<&'i D::Slice, Error> {
+        if self.peek().kind() == TokenKind::Name {
+            Ok(self.next()?.span())
+        } else {
+            Err(self.unexpected(Expected::Name))
+        }
     }
 
     fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), Error> {
-        if TokenKind::Keyword(keyword) == self.next()?.kind() {
+        if TokenKind::Keyword(keyword) == self.peek().kind() {
+            self.next()?;
             Ok(())
         } else {
-            Err(Error { /* missing {keyword} */})
+            Err(self.error(ErrorKind::MissingKeyword(keyword)))
         }
     }
 
     /// 2.4.2 – Blocks
-    fn parse_block<P: Sink>(&mut self, sink: &mut P) -> Result<P::Chunk, Error> {
-        if self.next()?.kind() != TokenKind::Keyword(Keyword::Do) {
-            return Err(Error { /* missing do at start of block */});
-        }
+    fn parse_block<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Chunk, Error> {
+        self.expect_keyword(Keyword::Do)?;
         let chunk = self.parse_chunk(sink)?;
         self.expect_keyword(Keyword::End)?;
         Ok(chunk)
     }
 
-    pub fn parse_stmt<P: Sink>(&mut self, sink: &mut P) -> Result<P::Stmt, Error> {
-        // Reminder: There are no empty statements
+    /// `attrib ::= ['<' Name '>']` (Lua 5.4)
+    fn parse_attrib<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        names: &mut P::NameList,
+    ) -> Result<(), Error> {
+        if self.version() < LuaVersion::Lua54 || self.peek().kind() != TokenKind::Symbol(Symbol::Lt)
+        {
+            return Ok(());
+        }
+        self.start_node(sink, NodeKind::Attrib)?;
+        self.next()?;
+        let start = self.next.start();
+        let attrib = self.expect_name()?;
+        if !matches!(attrib.as_bytes(), b"const" | b"close") {
+            return Err(Error::new(ErrorKind::UnknownAttribute, start));
+        }
+        self.expect_symbol(Symbol::Gt)?;
+        self.finish_node(sink, NodeKind::Attrib)?;
+        names.push_attrib(attrib);
+        Ok(())
+    }
+
+    /// Whether the next name is Luau's `continue`, `type` or `export`
+    ///
+    /// Returns the kind of the statement it starts.
+    fn contextual_keyword(&self) -> Result<Option<NodeKind>, Error> {
+        let token = self.peek();
+        if !self.dialect().luau || token.kind() != TokenKind::Name {
+            return Ok(None);
+        }
+        let next = self.peek2()?;
+        Ok(match (token.span().as_bytes(), next.kind()) {
+            (b"continue", kind) if !continues_expr(kind) => Some(NodeKind::Continue),
+            (b"type", TokenKind::Name) => Some(NodeKind::TypeAlias),
+            (b"export", TokenKind::Name) if next.span().as_bytes() == b"type" => {
+                Some(NodeKind::TypeAlias)
+            }
+            _ => None,
+        })
+    }
+
+    /// Whether a `for` loop is numeric, with the first name as next token
+    ///
+    /// In Luau, this looks past the type annotation of the name.
+    fn is_numeric_for(&self) -> Result<bool, Error> {
+        if !self.dialect().luau {
+            return Ok(self.peek2()?.kind() == TokenKind::Symbol(Symbol::Assign));
+        }
+        let mut ahead = self.inner.clone();
+        loop {
+            match next_semantic(&mut ahead, self.recover)?.kind() {
+                TokenKind::Symbol(Symbol::Assign) => break Ok(true),
+                TokenKind::Keyword(Keyword::In | Keyword::Do) | TokenKind::Eof => break Ok(false),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn parse_stmt<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Stmt, Error> {
+        // Reminder: Empty statements are handled in `parse_chunk`
         match self.peek().kind() {
-            TokenKind::Whitespace | TokenKind::Comment => unreachable!(),
+            TokenKind::Whitespace | TokenKind::Comment | TokenKind::Error => unreachable!(),
 
             TokenKind::Keyword(Keyword::Do) => {
+                self.start_node(sink, NodeKind::Do)?;
                 let block = self.parse_block(sink)?;
+                self.finish_node(sink, NodeKind::Do)?;
                 Ok(sink.block(block))
             }
             TokenKind::Keyword(Keyword::While) => {
+                self.start_node(sink, NodeKind::While)?;
                 self.next()?; // pop the keyword
                 let expr = self.parse_expr(sink)?;
                 let block = self.parse_block(sink)?;
+                self.finish_node(sink, NodeKind::While)?;
                 Ok(sink.while_stmt(expr, block))
             }
             TokenKind::Keyword(Keyword::Repeat) => {
+                self.start_node(sink, NodeKind::Repeat)?;
                 self.next()?; // pop the keyword
                 let block = self.parse_chunk(sink)?;
                 self.expect_keyword(Keyword::Until)?;
                 let expr = self.parse_expr(sink)?;
+                self.finish_node(sink, NodeKind::Repeat)?;
                 Ok(sink.repeat_stmt(block, expr))
             }
             TokenKind::Keyword(Keyword::If) => {
+                self.start_node(sink, NodeKind::If)?;
                 self.next()?; // pop the keyword
                 let exp = self.parse_expr(sink)?;
                 self.expect_keyword(Keyword::Then)?;
                 let block = self.parse_chunk(sink)?;
                 let mut builder = sink.if_stmt(exp, block);
 
-                let mut next = self.next()?;
-                loop {
-                    if next.kind() == TokenKind::Keyword(Keyword::ElseIf) {
-                        let exp = self.parse_expr(sink)?;
-                        self.expect_keyword(Keyword::Then)?;
-                        let block = self.parse_chunk(sink)?;
-                        builder.else_if_clause(exp, block);
-                        next = self.next()?;
-                    } else {
-                        break;
-                    }
+                while self.peek().kind() == TokenKind::Keyword(Keyword::ElseIf) {
+                    self.start_node(sink, NodeKind::ElseIf)?;
+                    self.next()?;
+                    let exp = self.parse_expr(sink)?;
+                    self.expect_keyword(Keyword::Then)?;
+                    let block = self.parse_chunk(sink)?;
+                    self.finish_node(sink, NodeKind::ElseIf)?;
+                    builder.else_if_clause(exp, block);
                 }
-                if next.kind() == TokenKind::Keyword(Keyword::Else) {
+                if self.peek().kind() == TokenKind::Keyword(Keyword::Else) {
+                    self.start_node(sink, NodeKind::Else)?;
+                    self.next()?;
                     let block = self.parse_chunk(sink)?;
+                    self.finish_node(sink, NodeKind::Else)?;
                     builder.else_clause(block);
                 }
+                self.expect_keyword(Keyword::End)?;
+                self.finish_node(sink, NodeKind::If)?;
                 Ok(builder.into())
             }
-            TokenKind::Keyword(_) => todo!(),
-            TokenKind::Name => todo!(),
-            TokenKind::Symbol(_) => todo!(),
-            TokenKind::String => todo!(),
-            TokenKind::Hex(_) => todo!(),
-            TokenKind::Number(_) => todo!(),
-            TokenKind::Eof => todo!(),
+            TokenKind::Keyword(Keyword::For) => {
+                let start = self.next()?.start(); // pop the keyword
+                let numeric = self.is_numeric_for()?;
+                let name = self.peek();
+                self.expect_name()?;
+                if numeric {
+                    self.start_node_at(sink, NodeKind::NumericFor, start)?;
+                    self.parse_annotation(sink)?;
+                    self.expect_symbol(Symbol::Assign)?;
+                    let start = self.parse_expr(sink)?;
+                    self.expect_symbol(Symbol::Comma)?;
+                    let limit = self.parse_expr(sink)?;
+                    let step = if self.peek().kind() == TokenKind::Symbol(Symbol::Comma) {
+                        self.next()?;
+                        Some(self.parse_expr(sink)?)
+                    } else {
+                        None
+                    };
+                    let block = self.parse_block(sink)?;
+                    self.finish_node(sink, NodeKind::NumericFor)?;
+                    Ok(sink.numeric_for_stmt(name.span(), start, limit, step, block))
+                } else {
+                    self.start_node_at(sink, NodeKind::GenericFor, start)?;
+                    self.start_node_at(sink, NodeKind::NameList, name.start())?;
+                    let mut names = sink.name_list();
+                    names.push_name(name.span());
+                    self.parse_annotation(sink)?;
+                    while self.peek().kind() == TokenKind::Symbol(Symbol::Comma) {
+                        self.next()?;
+                        names.push_name(self.expect_name()?);
+                        self.parse_annotation(sink)?;
+                    }
+                    self.finish_node(sink, NodeKind::NameList)?;
+                    self.expect_keyword(Keyword::In)?;
+                    let exprs = self.parse_expr_list(sink)?;
+                    let block = self.parse_block(sink)?;
+                    self.finish_node(sink, NodeKind::GenericFor)?;
+                    Ok(sink.generic_for_stmt(names, exprs, block))
+                }
+            }
+            TokenKind::Keyword(Keyword::Function) => {
+                self.start_node(sink, NodeKind::Function)?;
+                self.next()?; // pop the keyword
+                self.start_node(sink, NodeKind::FuncName)?;
+                let mut name = sink.func_name(self.expect_name()?);
+                while self.peek().kind() == TokenKind::Symbol(Symbol::Dot) {
+                    self.next()?;
+                    name.push_field(self.expect_name()?);
+                }
+                if self.peek().kind() == TokenKind::Symbol(Symbol::Colon) {
+                    self.next()?;
+                    name.method(self.expect_name()?);
+                }
+                self.finish_node(sink, NodeKind::FuncName)?;
+                let body = self.parse_func_body(sink)?;
+                self.finish_node(sink, NodeKind::Function)?;
+                Ok(sink.function_stmt(name, body))
+            }
+            TokenKind::Keyword(Keyword::Local) => {
+                let start = self.next()?.start(); // pop the keyword
+                if self.peek().kind() == TokenKind::Keyword(Keyword::Function) {
+                    self.start_node_at(sink, NodeKind::LocalFunction, start)?;
+                    self.next()?;
+                    let name = self.expect_name()?;
+                    let body = self.parse_func_body(sink)?;
+                    self.finish_node(sink, NodeKind::LocalFunction)?;
+                    Ok(sink.local_function_stmt(name, body))
+                } else {
+                    self.start_node_at(sink, NodeKind::Local, start)?;
+                    self.start_node(sink, NodeKind::NameList)?;
+                    let mut names = sink.name_list();
+                    names.push_name(self.expect_name()?);
+                    self.parse_attrib(sink, &mut names)?;
+                    self.parse_annotation(sink)?;
+                    while self.peek().kind() == TokenKind::Symbol(Symbol::Comma) {
+                        self.next()?;
+                        names.push_name(self.expect_name()?);
+                        self.parse_attrib(sink, &mut names)?;
+                        self.parse_annotation(sink)?;
+                    }
+                    self.finish_node(sink, NodeKind::NameList)?;
+                    let exprs = if self.peek().kind() == TokenKind::Symbol(Symbol::Assign) {
+                        self.next()?;
+                        Some(self.parse_expr_list(sink)?)
+                    } else {
+                        None
+                    };
+                    self.finish_node(sink, NodeKind::Local)?;
+                    Ok(sink.local_stmt(names, exprs))
+                }
+            }
+            TokenKind::Keyword(Keyword::Return) => {
+                self.start_node(sink, NodeKind::Return)?;
+                self.next()?; // pop the keyword
+                let exprs = match self.peek().kind() {
+                    TokenKind::Symbol(Symbol::Semicolon) => None,
+                    kind if is_block_follow(kind) => None,
+                    _ => Some(self.parse_expr_list(sink)?),
+                };
+                self.finish_node(sink, NodeKind::Return)?;
+                Ok(sink.return_stmt(exprs))
+            }
+            TokenKind::Keyword(Keyword::Break) => {
+                self.start_node(sink, NodeKind::Break)?;
+                self.next()?; // pop the keyword
+                self.finish_node(sink, NodeKind::Break)?;
+                Ok(sink.break_stmt())
+            }
+            TokenKind::Keyword(Keyword::Goto) => {
+                self.start_node(sink, NodeKind::Goto)?;
+                self.next()?; // pop the keyword
+                let label = self.expect_name()?;
+                self.finish_node(sink, NodeKind::Goto)?;
+                Ok(sink.goto_stmt(label))
+            }
+            TokenKind::Symbol(Symbol::Colon2) if self.version() >= LuaVersion::Lua52 => {
+                self.start_node(sink, NodeKind::Label)?;
+                self.next()?;
+                let name = self.expect_name()?;
+                self.expect_symbol(Symbol::Colon2)?;
+                self.finish_node(sink, NodeKind::Label)?;
+                Ok(sink.label_stmt(name))
+            }
+            TokenKind::Name => match self.contextual_keyword()? {
+                Some(NodeKind::Continue) => {
+                    self.start_node(sink, NodeKind::Continue)?;
+                    self.next()?;
+                    self.finish_node(sink, NodeKind::Continue)?;
+                    Ok(sink.continue_stmt())
+                }
+                Some(_) => self.parse_type_alias(sink),
+                None => self.parse_expr_stmt(sink),
+            },
+            TokenKind::Symbol(Symbol::ParenL) => self.parse_expr_stmt(sink),
+            TokenKind::Keyword(_)
+            | TokenKind::Symbol(_)
+            | TokenKind::String
+            | TokenKind::LongString { .. }
+            | TokenKind::InterpString(_)
+            | TokenKind::Hex(_)
+            | TokenKind::Integer(_)
+            | TokenKind::Number(_)
+            | TokenKind::Cdata(_)
+            | TokenKind::Eof => Err(self.unexpected(Expected::Statement)),
         }
     }
 
-    pub fn parse_chunk<P: Sink>(&mut self, sink: &mut P) -> Result<P::Chunk, Error> {
+    /// `stat ::= varlist '=' explist | functioncall`, or a compound assignment in Luau
+    fn parse_expr_stmt<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Stmt, Error> {
+        let start = self.next.start();
+        self.start_node(sink, NodeKind::ExprStmt)?;
+        let stmt = match self.parse_suffixed(sink)? {
+            Suffixed::Call(call) => sink.call_stmt(call),
+            Suffixed::Var(var) if compound_op(self.peek().kind()).is_some() => {
+                let op = compound_op(self.next()?.kind()).unwrap();
+                let expr = self.parse_expr(sink)?;
+                sink.compound_assign_stmt(var, op, expr)
+            }
+            Suffixed::Var(var) => {
+                let mut vars = sink.var_list();
+                vars.push_var(var);
+                while self.peek().kind() == TokenKind::Symbol(Symbol::Comma) {
+                    self.next()?;
+                    let start = self.next.start();
+                    match self.parse_suffixed(sink)? {
+                        Suffixed::Var(var) => vars.push_var(var),
+                        _ => return Err(Error::new(ErrorKind::InvalidAssignment, start)),
+                    }
+                }
+                self.expect_symbol(Symbol::Assign)?;
+                let exprs = self.parse_expr_list(sink)?;
+                sink.assign_stmt(vars, exprs)
+            }
+            Suffixed::Expr(_) => return Err(Error::new(ErrorKind::InvalidAssignment, start)),
+        };
+        self.finish_node(sink, NodeKind::ExprStmt)?;
+        Ok(stmt)
+    }
+
+    pub fn parse_chunk<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Chunk, Error> {
+        self.parse_statements(sink, false)
+    }
+
+    /// The statements of a chunk, up to the end of its block
+    ///
+    /// When recovering in the main chunk, only the end of input ends it.
+    fn parse_statements<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        main: bool,
+    ) -> Result<P::Chunk, Error> {
+        self.enter_level()?;
+        self.start_node(sink, NodeKind::Chunk)?;
         let mut chunk: P::Chunk = sink.chunk();
         loop {
             match self.peek().kind() {
-                TokenKind::Keyword(
-                    Keyword::End | Keyword::Until | Keyword::Else | Keyword::ElseIf,
-                )
-                | TokenKind::Eof => break Ok(chunk),
-                TokenKind::Whitespace | TokenKind::Comment => unreachable!(),
-                _ => {
-                    let stmt = self.parse_stmt(sink)?;
+                TokenKind::Eof => break,
+                kind if is_block_follow(kind) => {
+                    if !(main && self.recover) {
+                        break;
+                    }
+                    // An `end` without a block
+                    let error = self.unexpected(Expected::Eof);
+                    self.recover(sink, error, self.depth, true)?;
+                }
+                TokenKind::Whitespace | TokenKind::Comment | TokenKind::Error => unreachable!(),
+                TokenKind::Symbol(Symbol::Semicolon) if self.version() >= LuaVersion::Lua52 => {
+                    // An empty statement
+                    self.next()?;
+                }
+                kind => {
+                    // Since Lua 5.2, `break` is an ordinary statement
+                    let is_last = match kind {
+                        TokenKind::Keyword(Keyword::Return) => true,
+                        TokenKind::Keyword(Keyword::Break) => self.version() < LuaVersion::Lua52,
+                        TokenKind::Name => self.contextual_keyword()? == Some(NodeKind::Continue),
+                        _ => false,
+                    };
+                    let (depth, levels, start) = (self.depth, self.levels, self.next.start());
+                    let stmt = match self.parse_stmt(sink) {
+                        Ok(stmt) => stmt,
+                        Err(error) if self.recover => {
+                            self.levels = levels;
+                            let stuck = self.next.start() == start;
+                            self.recover(sink, error, depth, stuck)?;
+                            continue;
+                        }
+                        Err(error) => return Err(error),
+                    };
                     if self.peek().kind() == TokenKind::Symbol(Symbol::Semicolon) {
                         self.next()?;
                     }
                     chunk.push_stmt(stmt);
+                    if is_last && !is_block_follow(self.peek().kind()) {
+                        // `laststat` must be the last statement of a block
+                        let error = self.unexpected(Expected::BlockEnd);
+                        if !self.recover {
+                            return Err(error);
+                        }
+                        self.recover(sink, error, self.depth, false)?;
+                    }
                 }
             }
         }
+        self.finish_node(sink, NodeKind::Chunk)?;
+        self.leave_level();
+        Ok(chunk)
+    }
+
+    /// Report an error and skip the input up to the next statement
+    ///
+    /// This finishes the nodes that were started after `depth`, and puts the
+    /// skipped tokens into a [NodeKind::Error] node. If `skip` is set, the
+    /// next token is skipped even if it could start a statement.
+    fn recover<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+        error: Error,
+        depth: usize,
+        skip: bool,
+    ) -> Result<(), Error> {
+        while self.depth > depth {
+            self.finish_node(sink, self.open[self.depth - 1])?;
+        }
+        self.start_node(sink, NodeKind::Error)?;
+        sink.error(error);
+        if skip && self.peek().kind() != TokenKind::Eof {
+            self.next()?;
+        }
+        while !is_sync(self.peek().kind()) {
+            self.next()?;
+        }
+        self.finish_node(sink, NodeKind::Error)
+    }
+
+    /// Parse an entire input, i.e. a chunk followed by the end of input
+    ///
+    /// A parser created with [Parser::recovering] reports all errors to
+    /// [Sink::error] instead of returning them.
+    pub fn parse_main<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<P::Chunk, Error> {
+        self.start_node_at(sink, NodeKind::Main, Position::START)?;
+        let chunk = self.parse_statements(sink, true)?;
+        if self.peek().kind() != TokenKind::Eof {
+            return Err(self.unexpected(Expected::Eof));
+        }
+        // Trailing whitespace and comments
+        self.emit_until(sink, self.next.start().offset)?;
+        self.depth -= 1;
+        sink.exit(NodeKind::Main, self.next.start());
+        Ok(chunk)
     }
 }
 
@@ -181,3 +993,6 @@ impl<'i> Parser<'i, Latin1Decoder<'i>> {
         Self::new(Latin1Decoder::new(bytes))
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,103 @@
+//! # Syntax Nodes
+//!
+//! The kinds of nodes reported to [Sink::enter](super::Sink::enter) and
+//! [Sink::exit](super::Sink::exit), or to [Events](super::Events).
+
+/// The kind of a node in the concrete syntax tree
+///
+/// Whitespace and comments between two nodes belong to the parent node,
+/// so every node starts and ends with a non-trivia token.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// The entire input, including leading and trailing trivia
+    Main,
+    /// A sequence of statements
+    Chunk,
+
+    /// `do block end`
+    Do,
+    /// `while exp do block end`
+    While,
+    /// `repeat block until exp`
+    Repeat,
+    /// `if exp then block {elseif} [else] end`
+    If,
+    /// `elseif exp then block`
+    ElseIf,
+    /// `else block`
+    Else,
+    /// `for Name '=' exp ',' exp [',' exp] do block end`
+    NumericFor,
+    /// `for namelist in explist do block end`
+    GenericFor,
+    /// `function funcname funcbody`
+    Function,
+    /// `local function Name funcbody`
+    LocalFunction,
+    /// `local namelist ['=' explist]`
+    Local,
+    /// `return [explist]`
+    Return,
+    /// `break`
+    Break,
+    /// `goto Name` (Lua 5.2)
+    Goto,
+    /// `'::' Name '::'` (Lua 5.2)
+    Label,
+    /// `continue` (Luau)
+    Continue,
+    /// `['export'] 'type' Name [Generics] '=' Type` (Luau)
+    TypeAlias,
+    /// An assignment or a function call statement
+    ExprStmt,
+
+    /// `Name {'.' Name} [':' Name]`
+    FuncName,
+    /// `'(' [parlist] ')' block end`
+    FuncBody,
+    /// The parameters between the parentheses of a [NodeKind::FuncBody]
+    Params,
+    /// `Name {',' Name}`
+    NameList,
+    /// `'<' Name '>'` after a name in a `local` statement (Lua 5.4)
+    Attrib,
+    /// `exp {',' exp}`
+    ExprList,
+
+    /// A complete expression, binary operators are not nested
+    Expr,
+    /// A unary operator and its operand
+    Unary,
+    /// `'(' exp ')'`
+    Paren,
+    /// `function funcbody`
+    FunctionExpr,
+    /// A name or parenthesized expression followed by suffixes
+    Suffixed,
+    /// `'.' Name`
+    Field,
+    /// `'[' exp ']'`
+    Index,
+    /// `args`
+    Call,
+    /// `':' Name args`
+    MethodCall,
+    /// `'{' [fieldlist] '}'`
+    Table,
+    /// `'[' exp ']' '=' exp | Name '=' exp | exp`
+    TableField,
+    /// An interpolated string with its expressions (Luau)
+    InterpString,
+    /// `if exp then exp {elseif exp then exp} else exp` (Luau)
+    IfExpr,
+    /// `'::' Type` after an expression (Luau)
+    Cast,
+
+    /// The tokens that were skipped after a syntax error, see [Parser::recovering](super::Parser::recovering)
+    Error,
+
+    /// A type annotation, nested types are not separate nodes (Luau)
+    Type,
+    /// `'<' Name {',' Name} '>'` of a generic function or type (Luau)
+    Generics,
+}
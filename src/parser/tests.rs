@@ -0,0 +1,723 @@
+extern crate std;
+
+use std::{format, string::String, vec::Vec};
+
+use crate::{
+    Cdata, Dialect, Error, ErrorKind, Expected, Keyword, LuaVersion, Position, Symbol, TokenKind,
+    Utf8Decoder,
+};
+
+use super::{BinOp, Chunk, ExprList, FuncName, If, NameList, Parser, Sink, Table, UnOp, VarList};
+
+/// Renders everything as S-expressions
+struct Sexp;
+
+fn list(items: Vec<String>) -> String {
+    items.join(" ")
+}
+
+struct Names(Vec<String>);
+
+impl<S: Into<String>> NameList<S> for Names {
+    fn push_name(&mut self, name: S) {
+        self.0.push(name.into());
+    }
+
+    fn push_attrib(&mut self, attrib: S) {
+        if let Some(last) = self.0.last_mut() {
+            *last += &format!("<{}>", attrib.into());
+        }
+    }
+}
+
+impl VarList<String> for Vec<String> {
+    fn push_var(&mut self, var: String) {
+        self.push(var);
+    }
+}
+
+impl<S: Into<String>> FuncName<S> for String {
+    fn push_field(&mut self, name: S) {
+        *self += ".";
+        *self += &name.into();
+    }
+
+    fn method(&mut self, name: S) {
+        *self += ":";
+        *self += &name.into();
+    }
+}
+
+impl ExprList<String> for Vec<String> {
+    fn push_expr(&mut self, expr: String) {
+        self.push(expr);
+    }
+}
+
+impl Chunk<String> for Vec<String> {
+    fn push_stmt(&mut self, stmt: String) {
+        self.push(stmt);
+    }
+}
+
+impl<S: Into<String>> Table<S, String> for Vec<String> {
+    fn push_item(&mut self, value: String) {
+        self.push(value);
+    }
+
+    fn push_named(&mut self, name: S, value: String) {
+        self.push(format!("({} = {})", name.into(), value));
+    }
+
+    fn push_keyed(&mut self, key: String, value: String) {
+        self.push(format!("([{}] = {})", key, value));
+    }
+}
+
+struct IfSexp(String);
+
+impl If<String, Vec<String>> for IfSexp {
+    fn else_if_clause(&mut self, exp: String, block: Vec<String>) {
+        self.0 += &format!(" (elseif {} ({}))", exp, list(block));
+    }
+
+    fn else_clause(&mut self, block: Vec<String>) {
+        self.0 += &format!(" (else ({}))", list(block));
+    }
+}
+
+impl From<IfSexp> for String {
+    fn from(value: IfSexp) -> Self {
+        format!("(if {})", value.0)
+    }
+}
+
+impl<'a> Sink<&'a str> for Sexp {
+    type If = IfSexp;
+    type Var = String;
+    type VarList = Vec<String>;
+    type FuncName = String;
+    type Expr = String;
+    type ExprList = Vec<String>;
+    type NameList = Names;
+    type Table = Vec<String>;
+    type FuncBody = String;
+    type Call = String;
+    type Stmt = String;
+    type Chunk = Vec<String>;
+
+    fn chunk(&mut self) -> Self::Chunk {
+        Vec::new()
+    }
+
+    fn block(&mut self, chunk: Self::Chunk) -> Self::Stmt {
+        format!("(do {})", list(chunk))
+    }
+
+    fn while_stmt(&mut self, expr: Self::Expr, block: Self::Chunk) -> Self::Stmt {
+        format!("(while {} ({}))", expr, list(block))
+    }
+
+    fn repeat_stmt(&mut self, block: Self::Chunk, expr: Self::Expr) -> Self::Stmt {
+        format!("(repeat ({}) {})", list(block), expr)
+    }
+
+    fn if_stmt(&mut self, exp: Self::Expr, block: Self::Chunk) -> Self::If {
+        IfSexp(format!("{} ({})", exp, list(block)))
+    }
+
+    fn numeric_for_stmt(
+        &mut self,
+        name: &'a str,
+        start: Self::Expr,
+        limit: Self::Expr,
+        step: Option<Self::Expr>,
+        block: Self::Chunk,
+    ) -> Self::Stmt {
+        let step = step.map(|s| format!(" {}", s)).unwrap_or_default();
+        format!(
+            "(for {} {} {}{} ({}))",
+            name,
+            start,
+            limit,
+            step,
+            list(block)
+        )
+    }
+
+    fn generic_for_stmt(
+        &mut self,
+        names: Self::NameList,
+        exprs: Self::ExprList,
+        block: Self::Chunk,
+    ) -> Self::Stmt {
+        format!(
+            "(for ({}) in ({}) ({}))",
+            list(names.0),
+            list(exprs),
+            list(block)
+        )
+    }
+
+    fn function_stmt(&mut self, name: Self::FuncName, body: Self::FuncBody) -> Self::Stmt {
+        format!("(function {} {})", name, body)
+    }
+
+    fn local_function_stmt(&mut self, name: &'a str, body: Self::FuncBody) -> Self::Stmt {
+        format!("(local function {} {})", name, body)
+    }
+
+    fn local_stmt(&mut self, names: Self::NameList, exprs: Option<Self::ExprList>) -> Self::Stmt {
+        match exprs {
+            Some(exprs) => format!("(local ({}) ({}))", list(names.0), list(exprs)),
+            None => format!("(local ({}))", list(names.0)),
+        }
+    }
+
+    fn assign_stmt(&mut self, vars: Self::VarList, exprs: Self::ExprList) -> Self::Stmt {
+        format!("(set ({}) ({}))", list(vars), list(exprs))
+    }
+
+    fn call_stmt(&mut self, call: Self::Call) -> Self::Stmt {
+        call
+    }
+
+    fn return_stmt(&mut self, exprs: Option<Self::ExprList>) -> Self::Stmt {
+        match exprs {
+            Some(exprs) => format!("(return {})", list(exprs)),
+            None => "(return)".into(),
+        }
+    }
+
+    fn break_stmt(&mut self) -> Self::Stmt {
+        "(break)".into()
+    }
+
+    fn goto_stmt(&mut self, label: &'a str) -> Self::Stmt {
+        format!("(goto {})", label)
+    }
+
+    fn label_stmt(&mut self, name: &'a str) -> Self::Stmt {
+        format!("(label {})", name)
+    }
+
+    fn continue_stmt(&mut self) -> Self::Stmt {
+        "(continue)".into()
+    }
+
+    fn compound_assign_stmt(&mut self, var: Self::Var, op: BinOp, expr: Self::Expr) -> Self::Stmt {
+        format!("(set{:?} {} {})", op, var, expr)
+    }
+
+    fn type_alias_stmt(&mut self, export: bool, name: &'a str, ty: &'a str) -> Self::Stmt {
+        let export = if export { "export " } else { "" };
+        format!("({}type {} {:?})", export, name, ty)
+    }
+
+    fn var_list(&mut self) -> Self::VarList {
+        Vec::new()
+    }
+
+    fn expr_list(&mut self) -> Self::ExprList {
+        Vec::new()
+    }
+
+    fn name_list(&mut self) -> Self::NameList {
+        Names(Vec::new())
+    }
+
+    fn func_name(&mut self, name: &'a str) -> Self::FuncName {
+        name.into()
+    }
+
+    fn table(&mut self) -> Self::Table {
+        Vec::new()
+    }
+
+    fn func_body(
+        &mut self,
+        params: Self::NameList,
+        vararg: bool,
+        block: Self::Chunk,
+    ) -> Self::FuncBody {
+        let dots = if vararg { " ..." } else { "" };
+        format!("({}{}) ({})", list(params.0), dots, list(block))
+    }
+
+    fn name_var(&mut self, name: &'a str) -> Self::Var {
+        name.into()
+    }
+
+    fn index_var(&mut self, prefix: Self::Expr, key: Self::Expr) -> Self::Var {
+        format!("(index {} {})", prefix, key)
+    }
+
+    fn field_var(&mut self, prefix: Self::Expr, name: &'a str) -> Self::Var {
+        format!("(field {} {})", prefix, name)
+    }
+
+    fn call(&mut self, prefix: Self::Expr, args: Self::ExprList) -> Self::Call {
+        format!("(call {} ({}))", prefix, list(args))
+    }
+
+    fn method_call(
+        &mut self,
+        prefix: Self::Expr,
+        name: &'a str,
+        args: Self::ExprList,
+    ) -> Self::Call {
+        format!("(method {} {} ({}))", prefix, name, list(args))
+    }
+
+    fn nil_expr(&mut self) -> Self::Expr {
+        "nil".into()
+    }
+
+    fn bool_expr(&mut self, value: bool) -> Self::Expr {
+        format!("{}", value)
+    }
+
+    fn number_expr(&mut self, _span: &'a str, value: f64) -> Self::Expr {
+        format!("{}", value)
+    }
+
+    fn integer_expr(&mut self, _span: &'a str, value: i64) -> Self::Expr {
+        format!("{}i", value)
+    }
+
+    fn cdata_expr(&mut self, _span: &'a str, value: Cdata) -> Self::Expr {
+        format!("{:?}", value)
+    }
+
+    fn string_expr(&mut self, span: &'a str) -> Self::Expr {
+        span.into()
+    }
+
+    fn interp_string_expr(&mut self, parts: Self::ExprList) -> Self::Expr {
+        format!("(interp {})", list(parts))
+    }
+
+    fn if_expr(&mut self, cond: Self::Expr, then: Self::Expr, else_: Self::Expr) -> Self::Expr {
+        format!("(if {} {} {})", cond, then, else_)
+    }
+
+    fn vararg_expr(&mut self) -> Self::Expr {
+        "...".into()
+    }
+
+    fn function_expr(&mut self, body: Self::FuncBody) -> Self::Expr {
+        format!("(function {})", body)
+    }
+
+    fn table_expr(&mut self, table: Self::Table) -> Self::Expr {
+        format!("{{{}}}", list(table))
+    }
+
+    fn binary_expr(&mut self, lhs: Self::Expr, op: BinOp, rhs: Self::Expr) -> Self::Expr {
+        format!("({:?} {} {})", op, lhs, rhs)
+    }
+
+    fn unary_expr(&mut self, op: UnOp, expr: Self::Expr) -> Self::Expr {
+        format!("({:?} {})", op, expr)
+    }
+
+    fn paren_expr(&mut self, expr: Self::Expr) -> Self::Expr {
+        format!("(paren {})", expr)
+    }
+
+    fn var_expr(&mut self, var: Self::Var) -> Self::Expr {
+        var
+    }
+
+    fn call_expr(&mut self, call: Self::Call) -> Self::Expr {
+        call
+    }
+}
+
+fn expr(text: &str) -> String {
+    let mut parser = Parser::new_from_str(text).ok().unwrap();
+    parser.parse_expr(&mut Sexp).ok().unwrap()
+}
+
+fn stmt(text: &str) -> String {
+    let mut parser = Parser::new_from_str(text).ok().unwrap();
+    parser.parse_stmt(&mut Sexp).ok().unwrap()
+}
+
+fn chunk(text: &str) -> Option<String> {
+    let mut parser = Parser::new_from_str(text).ok()?;
+    parser.parse_main(&mut Sexp).ok().map(list)
+}
+
+fn chunk_with(text: &str, dialect: impl Into<Dialect>) -> Result<String, Error> {
+    let mut parser = Parser::with_dialect(Utf8Decoder::new(text), dialect.into())?;
+    parser.parse_main(&mut Sexp).map(list)
+}
+
+/// A position on the first line
+fn at(offset: usize) -> Position {
+    Position {
+        offset,
+        line: 1,
+        column: offset + 1,
+    }
+}
+
+fn error(text: &str) -> Error {
+    let mut parser = Parser::new_from_str(text).unwrap();
+    parser.parse_main(&mut Sexp).err().unwrap()
+}
+
+#[test]
+fn test_precedence() {
+    assert_eq!(expr("1 + 2 * 3"), "(Add 1 (Mul 2 3))");
+    assert_eq!(expr("1 * 2 + 3"), "(Add (Mul 1 2) 3)");
+    assert_eq!(expr("1 - 2 - 3"), "(Sub (Sub 1 2) 3)");
+    assert_eq!(expr("a or b and c"), "(Or a (And b c))");
+    assert_eq!(expr("a < b == c"), "(Eq (Lt a b) c)");
+    assert_eq!(expr("not a == b"), "(Eq (Not a) b)");
+    assert_eq!(expr("a .. b + c .. d"), "(Concat a (Concat (Add b c) d))");
+}
+
+#[test]
+fn test_right_associative() {
+    assert_eq!(expr("2 ^ 3 ^ 2"), "(Pow 2 (Pow 3 2))");
+    assert_eq!(expr("-2 ^ 2"), "(Neg (Pow 2 2))");
+    assert_eq!(expr("#t ^ 2"), "(Len (Pow t 2))");
+    assert_eq!(expr("'a' .. 'b' .. 'c'"), "(Concat 'a' (Concat 'b' 'c'))");
+}
+
+#[test]
+fn test_simple_exprs() {
+    assert_eq!(expr("nil"), "nil");
+    assert_eq!(expr("true"), "true");
+    assert_eq!(expr("false"), "false");
+    assert_eq!(expr("..."), "...");
+    assert_eq!(expr("0x10"), "16");
+    assert_eq!(expr("\"str\""), "\"str\"");
+    assert_eq!(expr("function(a, b, ...) end"), "(function (a b ...) ())");
+    assert_eq!(expr("function(...) end"), "(function ( ...) ())");
+}
+
+#[test]
+fn test_tables() {
+    assert_eq!(expr("{}"), "{}");
+    assert_eq!(expr("{1, 2; 3,}"), "{1 2 3}");
+    assert_eq!(expr("{a = 1, [b] = 2, c}"), "{(a = 1) ([b] = 2) c}");
+    assert_eq!(expr("{a.b, c + 1}"), "{(field a b) (Add c 1)}");
+}
+
+#[test]
+fn test_prefix_exprs() {
+    assert_eq!(expr("a.b.c"), "(field (field a b) c)");
+    assert_eq!(expr("a[1]"), "(index a 1)");
+    assert_eq!(expr("f(1, 2)"), "(call f (1 2))");
+    assert_eq!(expr("f()"), "(call f ())");
+    assert_eq!(expr("f'x'"), "(call f ('x'))");
+    assert_eq!(expr("f{}"), "(call f ({}))");
+    assert_eq!(expr("f[[x]]"), "(call f ([[x]]))");
+    assert_eq!(expr("o:m(x)"), "(method o m (x))");
+    assert_eq!(expr("(f)()"), "(call (paren f) ())");
+    assert_eq!(expr("a.b:c().d"), "(field (method (field a b) c ()) d)");
+}
+
+#[test]
+fn test_control_stmts() {
+    assert_eq!(stmt("while a < 10 do end"), "(while (Lt a 10) ())");
+    assert_eq!(stmt("repeat until x"), "(repeat () x)");
+    assert_eq!(
+        stmt("if a then elseif b then else end"),
+        "(if a () (elseif b ()) (else ()))"
+    );
+}
+
+#[test]
+fn test_for_stmts() {
+    assert_eq!(stmt("for i = 1, 10 do end"), "(for i 1 10 ())");
+    assert_eq!(stmt("for i = 10, 1, -1 do end"), "(for i 10 1 (Neg 1) ())");
+    assert_eq!(
+        stmt("for k, v in pairs(t) do f(k) end"),
+        "(for (k v) in ((call pairs (t))) ((call f (k))))"
+    );
+}
+
+#[test]
+fn test_function_stmts() {
+    assert_eq!(stmt("function f() end"), "(function f () ())");
+    assert_eq!(
+        stmt("function a.b.c:m(x) return x end"),
+        "(function a.b.c:m (x) ((return x)))"
+    );
+    assert_eq!(
+        stmt("local function f(...) end"),
+        "(local function f ( ...) ())"
+    );
+}
+
+#[test]
+fn test_local_and_assign() {
+    assert_eq!(stmt("local a"), "(local (a))");
+    assert_eq!(stmt("local a, b = 1, 2"), "(local (a b) (1 2))");
+    assert_eq!(stmt("a = 1"), "(set (a) (1))");
+    assert_eq!(
+        stmt("a.b, c[1] = f()"),
+        "(set ((field a b) (index c 1)) ((call f ())))"
+    );
+    assert_eq!(stmt("o:m()"), "(method o m ())");
+}
+
+#[test]
+fn test_chunks() {
+    assert_eq!(
+        chunk("local x = 1; print(x) return"),
+        Some("(local (x) (1)) (call print (x)) (return)".into())
+    );
+    assert_eq!(
+        chunk("while true do break end"),
+        Some("(while true ((break)))".into())
+    );
+    assert_eq!(chunk("return 1, 2;"), Some("(return 1 2)".into()));
+}
+
+#[test]
+fn test_invalid_stmts() {
+    assert_eq!(chunk("return 1 print(x)"), None);
+    assert_eq!(chunk("do break x = 1 end"), None);
+    assert_eq!(chunk("f() = 1"), None);
+    assert_eq!(chunk("(a) = 1"), None);
+    assert_eq!(chunk("x"), None);
+    assert_eq!(chunk("1 + 1"), None);
+    assert_eq!(chunk("end"), None);
+}
+
+#[test]
+fn test_errors() {
+    let kind = ErrorKind::MissingKeyword(Keyword::End);
+    assert_eq!(error("while x do f()"), Error::new(kind, at(14)));
+    let kind = ErrorKind::MissingKeyword(Keyword::Then);
+    assert_eq!(error("if x else end"), Error::new(kind, at(5)));
+    let kind = ErrorKind::UnexpectedToken {
+        expected: Expected::Symbol(Symbol::Assign),
+        found: TokenKind::Eof,
+    };
+    assert_eq!(error("a, b"), Error::new(kind, at(4)));
+    let kind = ErrorKind::UnexpectedToken {
+        expected: Expected::Expression,
+        found: TokenKind::Symbol(Symbol::ParenR),
+    };
+    assert_eq!(error("x = (1 + )"), Error::new(kind, at(9)));
+    let kind = ErrorKind::UnexpectedToken {
+        expected: Expected::Eof,
+        found: TokenKind::Keyword(Keyword::End),
+    };
+    assert_eq!(error("x = 1 end"), Error::new(kind, at(6)));
+    assert_eq!(
+        error("a, f() = 1"),
+        Error::new(ErrorKind::InvalidAssignment, at(3))
+    );
+    let pos = error("x = 1\r\nlocal\n\tend").position();
+    assert_eq!((pos.offset, pos.line, pos.column), (14, 3, 2));
+    assert_eq!(
+        error("x = 'abc"),
+        Error::new(ErrorKind::UnterminatedString, at(4))
+    );
+    with_stack(|| {
+        let deep = format!("x = {}1{}", "(".repeat(1000), ")".repeat(1000));
+        assert_eq!(error(&deep).kind(), ErrorKind::TooManyLevels);
+    });
+}
+
+#[test]
+fn test_ambiguous_calls() {
+    let kind = ErrorKind::AmbiguousSyntax;
+    let pos = error("local x = f\n(g)()").position();
+    assert_eq!((pos.offset, pos.line, pos.column), (12, 2, 1));
+    assert_eq!(error("a:b\n(c)").kind(), kind);
+    assert_eq!(error("f[[a\n]](b)\n(c)").kind(), kind);
+    assert_eq!(chunk("f(\ng\n)"), Some("(call f (g))".into()));
+    assert_eq!(chunk("f\n'a'\n{}"), chunk("f 'a' {}"));
+    assert_eq!(chunk("local x = f;\n(g)()"), chunk("local x = f; (g)()"));
+    // Lua 5.2 reads a call
+    assert_eq!(
+        chunk_with("f\n(g)", LuaVersion::Lua52),
+        Ok("(call f (g))".into())
+    );
+    let error = chunk_with("f\n(g)", Dialect::LUAJIT).unwrap_err();
+    assert_eq!(error.kind(), kind);
+}
+
+/// Runs `f` with enough stack for [super::MAX_LEVELS] in debug builds
+fn with_stack(f: impl FnOnce() + Send + 'static) {
+    let thread = std::thread::Builder::new().stack_size(32 << 20);
+    thread.spawn(f).unwrap().join().unwrap();
+}
+
+#[test]
+fn test_deep_exprs() {
+    with_stack(deep_exprs);
+}
+
+fn deep_exprs() {
+    // Right operands, unary operators and calls of tables nest
+    let concat = format!("x = a{}", " .. a".repeat(100_000));
+    assert_eq!(error(&concat).kind(), ErrorKind::TooManyLevels);
+    let unary = format!("x = {}1", "- ".repeat(100_000));
+    assert_eq!(error(&unary).kind(), ErrorKind::TooManyLevels);
+    let calls = format!("x = {{{}}}", "f{".repeat(100_000));
+    assert_eq!(error(&calls).kind(), ErrorKind::TooManyLevels);
+}
+
+#[test]
+fn test_max_levels() {
+    with_stack(max_levels);
+}
+
+fn max_levels() {
+    // The main chunk and every block are a level
+    let blocks = |n| format!("{}{}", "do ".repeat(n), "end ".repeat(n));
+    assert!(chunk(&blocks(199)).is_some());
+    let error_200 = error(&blocks(200));
+    assert_eq!(error_200.kind(), ErrorKind::TooManyLevels);
+    assert_eq!(error_200.offset(), 600);
+    // The main chunk, the expression and every nested expression
+    let parens = |n| format!("x = {}1{}", "(".repeat(n), ")".repeat(n));
+    assert!(chunk(&parens(198)).is_some());
+    assert_eq!(error(&parens(199)).kind(), ErrorKind::TooManyLevels);
+    // The outer table is in the expression, the others are fields
+    let tables = |n| format!("x = {}{}", "{".repeat(n), "}".repeat(n));
+    assert!(chunk(&tables(199)).is_some());
+    assert_eq!(error(&tables(200)).kind(), ErrorKind::TooManyLevels);
+    // The argument and the body of every function
+    let functions = |n| format!("{}{}", "f(function() ".repeat(n), "end) ".repeat(n));
+    assert!(chunk(&functions(99)).is_some());
+    assert_eq!(error(&functions(100)).kind(), ErrorKind::TooManyLevels);
+}
+
+#[test]
+fn test_goto_and_labels() {
+    let text = "for i = 1, 3 do if i == 2 then goto continue end ::continue:: end";
+    assert_eq!(
+        chunk_with(text, LuaVersion::Lua52),
+        Ok("(for i 1 3 ((if (Eq i 2) ((goto continue))) (label continue)))".into())
+    );
+    assert!(chunk_with(text, LuaVersion::Lua51).is_err());
+    assert_eq!(chunk("goto = 1"), Some("(set (goto) (1))".into()));
+    assert!(chunk_with("goto = 1", LuaVersion::Lua52).is_err());
+}
+
+#[test]
+fn test_empty_stmts() {
+    let text = ";; while x do break; f() end return;";
+    assert_eq!(
+        chunk_with(text, LuaVersion::Lua52),
+        Ok("(while x ((break) (call f ()))) (return)".into())
+    );
+    assert_eq!(chunk(text), None);
+    assert!(chunk_with("return;;", LuaVersion::Lua54).is_err());
+}
+
+#[test]
+fn test_integers_and_bitwise() {
+    let expr = |text: &str| {
+        let decoder = Utf8Decoder::new(text);
+        let mut parser = Parser::with_version(decoder, LuaVersion::Lua53).unwrap();
+        parser.parse_expr(&mut Sexp).unwrap()
+    };
+    assert_eq!(expr("1 + 2.0 + 0x10"), "(Add (Add 1i 2) 16i)");
+    assert_eq!(expr("0xffffffffffffffff"), "-1i");
+    assert_eq!(
+        expr("a | b ~ c & d << 1"),
+        "(BOr a (BXor b (BAnd c (Shl d 1i))))"
+    );
+    assert_eq!(expr("1 << 2 .. 3"), "(Shl 1i (Concat 2i 3i))");
+    assert_eq!(expr("a == b | c"), "(Eq a (BOr b c))");
+    assert_eq!(expr("~a ~ b // 2"), "(BXor (BNot a) (IDiv b 2i))");
+    assert_eq!(expr("-x ^ 2 // 3"), "(IDiv (Neg (Pow x 2i)) 3i)");
+}
+
+#[test]
+fn test_attribs() {
+    assert_eq!(
+        chunk_with("local a <const>, b, c <close> = 1", LuaVersion::Lua54),
+        Ok("(local (a<const> b c<close>) (1i))".into())
+    );
+    let kind = ErrorKind::UnknownAttribute;
+    assert_eq!(
+        chunk_with("local a <mut> = 1", LuaVersion::Lua54),
+        Err(Error::new(kind, at(9)))
+    );
+    assert!(chunk_with("local a <const> = 1", LuaVersion::Lua53).is_err());
+}
+
+#[test]
+fn test_luajit() {
+    assert_eq!(
+        chunk_with(
+            "x = 42LL + 0xffULL * 1.5i - 0xffffffffffffffffll",
+            Dialect::LUAJIT
+        ),
+        Ok("(set (x) ((Sub (Add Int64(42) (Mul UInt64(255) Imaginary(1.5))) Int64(-1))))".into())
+    );
+    assert_eq!(
+        chunk_with("goto a ::a:: return '\\u{20AC}'", Dialect::LUAJIT),
+        Ok("(goto a) (label a) (return '\\u{20AC}')".into())
+    );
+    let kind = ErrorKind::MalformedNumber;
+    assert_eq!(
+        chunk_with("x = 1.5LL", Dialect::LUAJIT),
+        Err(Error::new(kind, at(4)))
+    );
+    // Without the flag, the suffix is a name
+    assert!(chunk_with("x = 1LL", LuaVersion::Lua52).is_err());
+}
+
+#[test]
+fn test_luau() {
+    let luau = |text| chunk_with(text, Dialect::LUAU);
+    assert_eq!(
+        luau("x += 1 t.y ..= 'a' continue = 2"),
+        Ok("(setAdd x 1) (setConcat (field t y) 'a') (set (continue) (2))".into())
+    );
+    assert_eq!(
+        luau("while x do if y then continue end continue end"),
+        Ok("(while x ((if y ((continue))) (continue)))".into())
+    );
+    let kind = ErrorKind::UnexpectedToken {
+        expected: Expected::BlockEnd,
+        found: TokenKind::Name,
+    };
+    assert_eq!(
+        luau("while x do continue x() end"),
+        Err(Error::new(kind, at(20)))
+    );
+    assert_eq!(
+        luau("return if a then 1 elseif b then 2 else 3, `x{a}y{b}z`, `plain`"),
+        Ok("(return (if a 1 (if b 2 3)) (interp `x{ a }y{ b }z`) `plain`)".into())
+    );
+    assert_eq!(
+        luau("local f = `{ {1} }`"),
+        Ok("(local (f) ((interp `{ {1} }`)))".into())
+    );
+    assert_eq!(
+        luau("type Id = number export type List<T> = { T } type(x)"),
+        Ok("(type Id \"number\") (export type List \"{ T }\") (call type (x))".into())
+    );
+    assert_eq!(
+        luau("local function f<T>(a: T?, ...: number): (T, ...string) return a :: any end"),
+        Ok("(local function f (a ...) ((return a)))".into())
+    );
+    assert_eq!(
+        luau("for i: number = 1, 2 do end for k: string, v in t do end"),
+        Ok("(for i 1 2 ()) (for (k v) in (t) ())".into())
+    );
+    assert_eq!(
+        luau("local t: { [string]: (x: number) -> () | nil, n: typeof(f()) } = {}"),
+        Ok("(local (t) ({}))".into())
+    );
+    assert!(luau("x = `a{b`").is_err());
+    assert!(luau("local x: = 1").is_err());
+    // Plain Lua has none of it
+    assert!(chunk("x += 1").is_none());
+    assert!(chunk("x = `a`").is_none());
+    assert!(chunk("local x: number = 1").is_none());
+}
@@ -0,0 +1,236 @@
+//! # Luau Types
+//!
+//! Type annotations are only checked for their syntax and reported as
+//! [NodeKind::Type] nodes. The semantic callbacks of the [Sink] don't see
+//! them, except for the source text of a type alias.
+
+use super::{NodeKind, Parser, Sink};
+use crate::{encoding::ByteLen, Decoder, Error, Expected, Keyword, Symbol, TokenKind};
+
+fn is_union_op(kind: TokenKind) -> bool {
+    matches!(kind, TokenKind::Symbol(Symbol::Pipe | Symbol::Amp))
+}
+
+impl<'i, D: Decoder<'i>> Parser<'i, D> {
+    /// `[':' Type]` after a name or a parameter list in Luau
+    pub(super) fn parse_annotation<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+    ) -> Result<(), Error> {
+        if self.dialect().luau && self.peek().kind() == TokenKind::Symbol(Symbol::Colon) {
+            self.next()?;
+            self.parse_type(sink)?;
+        }
+        Ok(())
+    }
+
+    /// `['::' Type]` after a simple expression in Luau
+    pub(super) fn parse_cast<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<(), Error> {
+        if self.dialect().luau && self.peek().kind() == TokenKind::Symbol(Symbol::Colon2) {
+            self.start_node(sink, NodeKind::Cast)?;
+            self.next()?;
+            self.parse_type(sink)?;
+            self.finish_node(sink, NodeKind::Cast)?;
+        }
+        Ok(())
+    }
+
+    /// `['export'] 'type' Name [generics] '=' Type`
+    pub(super) fn parse_type_alias<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+    ) -> Result<P::Stmt, Error> {
+        self.start_node(sink, NodeKind::TypeAlias)?;
+        let export = self.next()?.span().as_bytes() == b"export";
+        if export {
+            self.next()?; // pop `type`
+        }
+        let name = self.expect_name()?;
+        if self.peek().kind() == TokenKind::Symbol(Symbol::Lt) {
+            self.parse_generics(sink)?;
+        }
+        self.expect_symbol(Symbol::Assign)?;
+        let start = self.peek().start();
+        self.parse_type(sink)?;
+        let ty = self.span(start, self.prev_end);
+        self.finish_node(sink, NodeKind::TypeAlias)?;
+        Ok(sink.type_alias_stmt(export, name, ty))
+    }
+
+    /// `'<' Name ['...'] ['=' Type] {',' Name ['...'] ['=' Type]} '>'`
+    pub(super) fn parse_generics<P: Sink<&'i D::Slice>>(
+        &mut self,
+        sink: &mut P,
+    ) -> Result<(), Error> {
+        self.start_node(sink, NodeKind::Generics)?;
+        self.expect_symbol(Symbol::Lt)?;
+        loop {
+            self.expect_name()?;
+            if self.peek().kind() == TokenKind::Symbol(Symbol::Dot3) {
+                self.next()?;
+            }
+            // Defaults are only valid in type aliases
+            if self.peek().kind() == TokenKind::Symbol(Symbol::Assign) {
+                self.next()?;
+                self.parse_type(sink)?;
+            }
+            if self.peek().kind() != TokenKind::Symbol(Symbol::Comma) {
+                break;
+            }
+            self.next()?;
+        }
+        self.expect_symbol(Symbol::Gt)?;
+        self.finish_node(sink, NodeKind::Generics)
+    }
+
+    /// A type as a [NodeKind::Type] node, nested types are not separate nodes
+    pub(super) fn parse_type<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<(), Error> {
+        self.start_node(sink, NodeKind::Type)?;
+        self.parse_union(sink)?;
+        self.finish_node(sink, NodeKind::Type)
+    }
+
+    /// `['|' | '&'] simpletype {'?'} {('|' | '&') simpletype {'?'}}`
+    fn parse_union<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<(), Error> {
+        if is_union_op(self.peek().kind()) {
+            self.next()?;
+        }
+        loop {
+            self.parse_simple_type(sink)?;
+            while self.peek().kind() == TokenKind::Symbol(Symbol::Question) {
+                self.next()?;
+            }
+            if !is_union_op(self.peek().kind()) {
+                break Ok(());
+            }
+            self.next()?;
+        }
+    }
+
+    fn parse_simple_type<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<(), Error> {
+        self.enter_level()?;
+        let token = self.peek();
+        match token.kind() {
+            TokenKind::Keyword(Keyword::Nil | Keyword::True | Keyword::False)
+            | TokenKind::String
+            | TokenKind::LongString { .. } => {
+                self.next()?;
+            }
+            TokenKind::Name
+                if token.span().as_bytes() == b"typeof"
+                    && self.peek2()?.kind() == TokenKind::Symbol(Symbol::ParenL) =>
+            {
+                self.next()?;
+                self.next()?;
+                self.parse_expr(sink)?;
+                self.expect_symbol(Symbol::ParenR)?;
+            }
+            TokenKind::Name => {
+                self.next()?;
+                if self.peek().kind() == TokenKind::Symbol(Symbol::Dot) {
+                    self.next()?;
+                    self.expect_name()?;
+                }
+                match self.peek().kind() {
+                    TokenKind::Symbol(Symbol::Lt) => self.parse_type_args(sink)?,
+                    // A generic type pack, i.e. `T...`
+                    TokenKind::Symbol(Symbol::Dot3) => {
+                        self.next()?;
+                    }
+                    _ => {}
+                }
+            }
+            TokenKind::Symbol(Symbol::BraceL) => self.parse_table_type(sink)?,
+            TokenKind::Symbol(Symbol::Lt) => {
+                // A generic function type
+                self.parse_generics(sink)?;
+                self.parse_type_pack(sink)?;
+                self.expect_symbol(Symbol::Arrow)?;
+                self.parse_union(sink)?;
+            }
+            TokenKind::Symbol(Symbol::ParenL) => {
+                // A function type, or a type in parentheses
+                self.parse_type_pack(sink)?;
+                if self.peek().kind() == TokenKind::Symbol(Symbol::Arrow) {
+                    self.next()?;
+                    self.parse_union(sink)?;
+                }
+            }
+            TokenKind::Symbol(Symbol::Dot3) => {
+                // A variadic type in a pack
+                self.next()?;
+                self.parse_simple_type(sink)?;
+            }
+            _ => return Err(self.unexpected(Expected::Type)),
+        }
+        self.leave_level();
+        Ok(())
+    }
+
+    /// `'(' [[Name ':'] Type {',' [Name ':'] Type}] ')'`
+    fn parse_type_pack<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<(), Error> {
+        self.expect_symbol(Symbol::ParenL)?;
+        if self.peek().kind() != TokenKind::Symbol(Symbol::ParenR) {
+            loop {
+                // The names of parameters in function types
+                if self.peek().kind() == TokenKind::Name
+                    && self.peek2()?.kind() == TokenKind::Symbol(Symbol::Colon)
+                {
+                    self.next()?;
+                    self.next()?;
+                }
+                self.parse_union(sink)?;
+                if self.peek().kind() != TokenKind::Symbol(Symbol::Comma) {
+                    break;
+                }
+                self.next()?;
+            }
+        }
+        self.expect_symbol(Symbol::ParenR)
+    }
+
+    /// `'<' [Type {',' Type}] '>'` after the name of a generic type
+    fn parse_type_args<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<(), Error> {
+        self.expect_symbol(Symbol::Lt)?;
+        if self.peek().kind() != TokenKind::Symbol(Symbol::Gt) {
+            loop {
+                self.parse_union(sink)?;
+                if self.peek().kind() != TokenKind::Symbol(Symbol::Comma) {
+                    break;
+                }
+                self.next()?;
+            }
+        }
+        self.expect_symbol(Symbol::Gt)
+    }
+
+    /// `'{' [field {sep field} [sep]] '}'` or `'{' Type '}'` for arrays
+    ///
+    /// `field ::= Name ':' Type | '[' Type ']' ':' Type`
+    fn parse_table_type<P: Sink<&'i D::Slice>>(&mut self, sink: &mut P) -> Result<(), Error> {
+        self.expect_symbol(Symbol::BraceL)?;
+        while self.peek().kind() != TokenKind::Symbol(Symbol::BraceR) {
+            match self.peek().kind() {
+                TokenKind::Symbol(Symbol::BracketL) => {
+                    self.next()?;
+                    self.parse_union(sink)?;
+                    self.expect_symbol(Symbol::BracketR)?;
+                    self.expect_symbol(Symbol::Colon)?;
+                }
+                TokenKind::Name if self.peek2()?.kind() == TokenKind::Symbol(Symbol::Colon) => {
+                    self.next()?;
+                    self.next()?;
+                }
+                _ => {}
+            }
+            self.parse_union(sink)?;
+            match self.peek().kind() {
+                TokenKind::Symbol(Symbol::Comma | Symbol::Semicolon) => {
+                    self.next()?;
+                }
+                _ => break,
+            }
+        }
+        self.expect_symbol(Symbol::BraceR)
+    }
+}
@@ -0,0 +1,79 @@
+//! # Source Positions
+
+use core::fmt;
+
+use memchr::memchr2;
+
+/// A location in the input
+///
+/// Lines and columns start at 1, columns count bytes. As in `llex.c`,
+/// each of `\n`, `\r`, `\r\n` and `\n\r` is a single line break.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// Byte offset from the start of the input
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of the first byte of the input
+    pub const START: Self = Self {
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+
+    /// Move this position past `bytes`
+    pub fn advance(&mut self, bytes: &[u8]) {
+        let mut rest = bytes;
+        while let Some(index) = memchr2(b'\n', b'\r', rest) {
+            let mut skip = index + 1;
+            if let Some(&next) = rest.get(skip) {
+                if (next == b'\n' || next == b'\r') && next != rest[index] {
+                    skip += 1;
+                }
+            }
+            rest = &rest[skip..];
+            self.line += 1;
+            self.column = 1;
+        }
+        self.column += rest.len();
+        self.offset += bytes.len();
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::START
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Position;
+
+    fn after(bytes: &[u8]) -> (usize, usize, usize) {
+        let mut pos = Position::START;
+        pos.advance(bytes);
+        (pos.offset, pos.line, pos.column)
+    }
+
+    #[test]
+    fn test_advance() {
+        assert_eq!(after(b""), (0, 1, 1));
+        assert_eq!(after(b"abc"), (3, 1, 4));
+        assert_eq!(after(b"a\nbc"), (4, 2, 3));
+        assert_eq!(after(b"a\r\nb"), (4, 2, 2));
+        assert_eq!(after(b"a\n\rb"), (4, 2, 2));
+        assert_eq!(after(b"a\n\nb"), (4, 3, 2));
+        assert_eq!(after(b"a\r\rb"), (4, 3, 2));
+        assert_eq!(after(b"\r\n\r\n"), (4, 3, 1));
+    }
+}
@@ -0,0 +1,462 @@
+//! # Name Resolution
+//!
+//! Resolves every name in a [cst](crate::cst) tree to the local variable it refers to,
+//! following the lexical scoping of Lua. Names without a visible local
+//! variable are global.
+//!
+//! This module requires the `alloc` feature.
+
+use alloc::vec::Vec;
+
+use crate::{
+    cst::{Element, Node},
+    encoding::ByteLen,
+    NodeKind, Symbol, Token, TokenKind,
+};
+
+/// What declares a local variable
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeclKind {
+    /// A name in `local namelist`
+    Local,
+    /// A name with the `<close>` attribute in `local namelist`
+    CloseLocal,
+    /// `local function Name`
+    LocalFunction,
+    /// A parameter of a function
+    Param,
+    /// The implicit `self` parameter of `function t:name()`
+    ///
+    /// The token of the declaration is the name of the method.
+    SelfParam,
+    /// A variable of a numeric or generic `for`
+    ForVar,
+}
+
+/// A local variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration<S> {
+    pub kind: DeclKind,
+    /// The name in the declaration
+    pub token: Token<S>,
+    /// The function that declares the variable, `0` is the main chunk
+    pub function: usize,
+    /// The local variable with the same name that was visible before, if any
+    pub shadows: Option<usize>,
+}
+
+/// The variable that a [Reference] refers to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Binding {
+    /// The index of a local variable of the same function
+    Local(usize),
+    /// The index of a local variable of an enclosing function
+    Upvalue(usize),
+    /// A field of the global environment
+    Global,
+}
+
+impl Binding {
+    /// The index of the declaration of a local variable or upvalue
+    pub fn declaration(self) -> Option<usize> {
+        match self {
+            Self::Local(index) | Self::Upvalue(index) => Some(index),
+            Self::Global => None,
+        }
+    }
+}
+
+/// A use of a name as a variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference<S> {
+    pub token: Token<S>,
+    pub binding: Binding,
+    /// Whether the variable is assigned to without being read
+    pub write: bool,
+}
+
+/// The variables of a syntax tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution<S> {
+    /// All local variables, in input order
+    pub declarations: Vec<Declaration<S>>,
+    /// All uses of variables, in input order
+    pub references: Vec<Reference<S>>,
+    /// The uses of `...` outside of a vararg function, which Lua rejects
+    pub invalid_varargs: Vec<Token<S>>,
+}
+
+impl<S> Resolution<S> {
+    /// The references to the declaration with an index
+    pub fn references_to(&self, declaration: usize) -> impl Iterator<Item = &Reference<S>> {
+        self.references
+            .iter()
+            .filter(move |reference| reference.binding.declaration() == Some(declaration))
+    }
+}
+
+/// Resolve the names in a tree from [parse_with](crate::cst::parse_with) or
+/// [parse_recovering](crate::cst::parse_recovering)
+pub fn resolve<'a, T: ?Sized + ByteLen>(root: &Node<&'a T>) -> Resolution<&'a T> {
+    let mut resolver = Resolver {
+        resolution: Resolution {
+            declarations: Vec::new(),
+            references: Vec::new(),
+            invalid_varargs: Vec::new(),
+        },
+        scopes: Vec::new(),
+        functions: Vec::new(),
+        count: 0,
+    };
+    // The main chunk is a vararg function
+    resolver.function(true, |resolver| resolver.walk(root));
+    resolver.resolution
+}
+
+/// The tokens that are direct children of a node
+fn tokens<S: Copy>(node: &Node<S>) -> impl Iterator<Item = &Token<S>> {
+    node.children().iter().filter_map(|child| match child {
+        Element::Token(token) => Some(token),
+        Element::Node(_) => None,
+    })
+}
+
+fn names<S: Copy>(node: &Node<S>) -> impl Iterator<Item = &Token<S>> {
+    tokens(node).filter(|token| token.kind() == TokenKind::Name)
+}
+
+/// Whether the elements after a name start with a `<close>` attribute
+fn is_close<T: ?Sized + ByteLen>(elements: &[Element<&T>]) -> bool {
+    let attrib = elements.iter().find(|element| {
+        !matches!(
+            element,
+            Element::Token(token) if matches!(token.kind(), TokenKind::Whitespace | TokenKind::Comment)
+        )
+    });
+    match attrib {
+        Some(Element::Node(attrib)) if attrib.kind() == NodeKind::Attrib => {
+            names(attrib).any(|name| name.span().as_bytes() == b"close")
+        }
+        _ => false,
+    }
+}
+
+fn find_child<S>(node: &Node<S>, kind: NodeKind) -> Option<&Node<S>> {
+    node.nodes().find(|child| child.kind() == kind)
+}
+
+/// A function that is being walked
+struct Function {
+    id: usize,
+    vararg: bool,
+}
+
+struct Resolver<'a, T: ?Sized> {
+    resolution: Resolution<&'a T>,
+    /// The names and declarations that are visible in each open block
+    scopes: Vec<Vec<(&'a [u8], usize)>>,
+    functions: Vec<Function>,
+    /// The number of functions so far
+    count: usize,
+}
+
+impl<'a, T: ?Sized + ByteLen> Resolver<'a, T> {
+    fn lookup(&self, name: &[u8]) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(declared, _)| *declared == name)
+            .map(|&(_, index)| index)
+    }
+
+    fn declare(&mut self, kind: DeclKind, token: &Token<&'a T>) {
+        let name = match kind {
+            DeclKind::SelfParam => b"self",
+            _ => token.span().as_bytes(),
+        };
+        let index = self.resolution.declarations.len();
+        self.resolution.declarations.push(Declaration {
+            kind,
+            token: *token,
+            function: self.functions.last().map_or(0, |function| function.id),
+            shadows: self.lookup(name),
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name, index));
+        }
+    }
+
+    fn reference(&mut self, token: &Token<&'a T>, write: bool) {
+        let binding = match self.lookup(token.span().as_bytes()) {
+            Some(index) => {
+                let function = self.resolution.declarations[index].function;
+                match self.functions.last() {
+                    Some(current) if current.id != function => Binding::Upvalue(index),
+                    _ => Binding::Local(index),
+                }
+            }
+            None => Binding::Global,
+        };
+        self.resolution.references.push(Reference {
+            token: *token,
+            binding,
+            write,
+        });
+    }
+
+    fn function(&mut self, vararg: bool, f: impl FnOnce(&mut Self)) {
+        self.functions.push(Function {
+            id: self.count,
+            vararg,
+        });
+        self.count += 1;
+        f(self);
+        self.functions.pop();
+    }
+
+    fn block(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn nodes(&mut self, node: &Node<&'a T>) {
+        for child in node.nodes() {
+            self.walk(child);
+        }
+    }
+
+    /// Walk a node with variables that are declared for its block
+    fn scoped(&mut self, node: &Node<&'a T>, kind: DeclKind, vars: &[&Token<&'a T>]) {
+        for child in node.nodes() {
+            match child.kind() {
+                NodeKind::Chunk => self.block(|resolver| {
+                    for var in vars {
+                        resolver.declare(kind, var);
+                    }
+                    resolver.nodes(child);
+                }),
+                NodeKind::NameList => {}
+                _ => self.walk(child),
+            }
+        }
+    }
+
+    /// Walk a `funcbody`, with the method name of `function t:name()`
+    fn func_body(&mut self, node: &Node<&'a T>, method: Option<&Token<&'a T>>) {
+        let params = find_child(node, NodeKind::Params);
+        let vararg = params
+            .into_iter()
+            .flat_map(tokens)
+            .any(|token| token.kind() == TokenKind::Symbol(Symbol::Dot3));
+        self.function(vararg, |resolver| {
+            for child in node.nodes() {
+                match child.kind() {
+                    NodeKind::Chunk => resolver.block(|resolver| {
+                        if let Some(method) = method {
+                            resolver.declare(DeclKind::SelfParam, method);
+                        }
+                        for param in params.into_iter().flat_map(names) {
+                            resolver.declare(DeclKind::Param, param);
+                        }
+                        resolver.nodes(child);
+                    }),
+                    // Only types, which have no variables
+                    NodeKind::Params => {}
+                    _ => resolver.walk(child),
+                }
+            }
+        });
+    }
+
+    /// The name at the start of a `Suffixed` node
+    fn prefix_name<'n>(node: &'n Node<&'a T>) -> Option<&'n Token<&'a T>> {
+        if node.kind() != NodeKind::Suffixed {
+            return None;
+        }
+        match node.children().first() {
+            Some(Element::Token(token)) if token.kind() == TokenKind::Name => Some(token),
+            _ => None,
+        }
+    }
+
+    fn walk(&mut self, node: &Node<&'a T>) {
+        match node.kind() {
+            NodeKind::Chunk => self.block(|resolver| resolver.nodes(node)),
+            // The condition is in the scope of the block
+            NodeKind::Repeat => self.block(|resolver| {
+                for child in node.nodes() {
+                    match child.kind() {
+                        NodeKind::Chunk => resolver.nodes(child),
+                        _ => resolver.walk(child),
+                    }
+                }
+            }),
+            // The names are visible after the statement
+            NodeKind::Local => {
+                for child in node.nodes() {
+                    if child.kind() != NodeKind::NameList {
+                        self.walk(child);
+                    }
+                }
+                let list = find_child(node, NodeKind::NameList);
+                let children = list.map_or(&[][..], |list| list.children());
+                for (index, child) in children.iter().enumerate() {
+                    match child {
+                        Element::Token(name) if name.kind() == TokenKind::Name => {
+                            let kind = if is_close(&children[index + 1..]) {
+                                DeclKind::CloseLocal
+                            } else {
+                                DeclKind::Local
+                            };
+                            self.declare(kind, name);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            // The name is visible in the body, for recursion
+            NodeKind::LocalFunction => {
+                for name in names(node) {
+                    self.declare(DeclKind::LocalFunction, name);
+                }
+                self.nodes(node);
+            }
+            NodeKind::Function => {
+                let name = find_child(node, NodeKind::FuncName);
+                let name: Vec<_> = name
+                    .into_iter()
+                    .flat_map(Node::significant_tokens)
+                    .collect();
+                // `function f()` assigns to `f`, `function t.f()` reads `t`
+                if let Some(first) = name.first() {
+                    self.reference(first, name.len() == 1);
+                }
+                let colon = name
+                    .iter()
+                    .position(|token| token.kind() == TokenKind::Symbol(Symbol::Colon));
+                let method = colon.and_then(|index| name.get(index + 1)).copied();
+                for child in node.nodes() {
+                    match child.kind() {
+                        NodeKind::FuncBody => self.func_body(child, method),
+                        NodeKind::FuncName => {}
+                        _ => self.walk(child),
+                    }
+                }
+            }
+            NodeKind::FuncBody => self.func_body(node, None),
+            NodeKind::NumericFor => {
+                let vars: Vec<_> = names(node).collect();
+                self.scoped(node, DeclKind::ForVar, &vars);
+            }
+            NodeKind::GenericFor => {
+                let list = find_child(node, NodeKind::NameList);
+                let vars: Vec<_> = list.into_iter().flat_map(names).collect();
+                self.scoped(node, DeclKind::ForVar, &vars);
+            }
+            // Names before the `=` of an assignment are written
+            NodeKind::ExprStmt => {
+                let assign =
+                    tokens(node).any(|token| token.kind() == TokenKind::Symbol(Symbol::Assign));
+                for child in node.nodes() {
+                    match Self::prefix_name(child) {
+                        Some(name) if child.children().len() == 1 => self.reference(name, assign),
+                        _ => self.walk(child),
+                    }
+                }
+            }
+            NodeKind::Suffixed => {
+                if let Some(name) = Self::prefix_name(node) {
+                    self.reference(name, false);
+                }
+                self.nodes(node);
+            }
+            // Types have no variables
+            NodeKind::Type | NodeKind::Generics | NodeKind::TypeAlias => {}
+            _ => {
+                for token in tokens(node) {
+                    if token.kind() == TokenKind::Symbol(Symbol::Dot3)
+                        && node.kind() == NodeKind::Expr
+                        && !self
+                            .functions
+                            .last()
+                            .is_some_and(|function| function.vararg)
+                    {
+                        self.resolution.invalid_varargs.push(*token);
+                    }
+                }
+                self.nodes(node);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::{resolve, Binding, DeclKind};
+    use crate::cst;
+
+    #[test]
+    fn test_resolve() {
+        let text = "local a, b = 1\n\
+            function g(x, ...)\n\
+              local a = a + x\n\
+              return function() return a, b, ... end\n\
+            end\n\
+            function t:m() self.y = z end\n\
+            repeat local r until r\n\
+            for i = 1, 2 do i = nil end\n\
+            print(...)";
+        let root = cst::parse_str(text).unwrap();
+        let resolution = resolve(&root);
+
+        let declarations: Vec<_> = resolution
+            .declarations
+            .iter()
+            .map(|d| (d.token.span(), d.kind, d.function, d.shadows))
+            .collect();
+        assert_eq!(
+            declarations,
+            [
+                ("a", DeclKind::Local, 0, None),
+                ("b", DeclKind::Local, 0, None),
+                ("x", DeclKind::Param, 1, None),
+                ("a", DeclKind::Local, 1, Some(0)),
+                ("m", DeclKind::SelfParam, 3, None),
+                ("r", DeclKind::Local, 0, None),
+                ("i", DeclKind::ForVar, 0, None),
+            ]
+        );
+
+        let references: Vec<_> = resolution
+            .references
+            .iter()
+            .map(|r| (r.token.span(), r.binding, r.write))
+            .collect();
+        assert_eq!(
+            references,
+            [
+                ("g", Binding::Global, true),
+                ("a", Binding::Upvalue(0), false),
+                ("x", Binding::Local(2), false),
+                ("a", Binding::Upvalue(3), false),
+                ("b", Binding::Upvalue(1), false),
+                ("t", Binding::Global, false),
+                ("self", Binding::Local(4), false),
+                ("z", Binding::Global, false),
+                ("r", Binding::Local(5), false),
+                ("i", Binding::Local(6), true),
+                ("print", Binding::Global, false),
+            ]
+        );
+        assert_eq!(resolution.references_to(3).count(), 1);
+
+        let varargs = &resolution.invalid_varargs;
+        assert_eq!(varargs.len(), 1);
+        assert_eq!(varargs[0].start().line, 4);
+    }
+}
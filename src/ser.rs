@@ -0,0 +1,837 @@
+//! # Serializing Lua Data
+//!
+//! A [serde] serializer that writes values as Lua 5.1 table constructors,
+//! which [de](crate::de) reads back. Types map to Lua in the same way:
+//!
+//! - `None`, `()` and unit structs are `nil`
+//! - sequences and tuples are tables with positional fields, `{ 1, 2 }`
+//! - maps and structs are tables with keys, `{ name = 1, ["a b"] = 2 }`
+//! - unit variants are strings, other variants tables with a single field,
+//!   e.g. `{ Variant = { 1, 2 } }`
+//!
+//! Strings with line breaks are written as long strings, others with the
+//! escapes of Lua 5.1. Infinite and NaN floats are written as `1/0`, `-1/0`
+//! and `0/0`, which are valid expressions but not literals. [de](crate::de)
+//! reads exactly these expressions back.
+
+use alloc::string::{String, ToString};
+use core::fmt::{self, Write};
+
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::{
+    format::Indent,
+    unescape::{escape_char, quote, quote_key},
+};
+
+/// An error while serializing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+}
+
+impl Error {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ser::StdError for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            message: msg.to_string(),
+        }
+    }
+}
+
+fn invalid_key() -> Error {
+    ser::Error::custom("table keys must be strings, numbers or booleans")
+}
+
+/// Serialization options
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// Put each table field on its own line with this indentation
+    pub indent: Option<Indent>,
+    /// Write a data file, i.e. `return` before the value and a line break after it
+    pub return_value: bool,
+}
+
+/// Serialize a value as a Lua expression on a single line
+pub fn to_string<T: ?Sized + Serialize>(value: &T) -> Result<String, Error> {
+    to_string_with(value, &Options::default())
+}
+
+/// Serialize a value as a Lua expression with one table field per line
+pub fn to_string_pretty<T: ?Sized + Serialize>(value: &T) -> Result<String, Error> {
+    let options = Options {
+        indent: Some(Indent::Spaces(4)),
+        ..Options::default()
+    };
+    to_string_with(value, &options)
+}
+
+/// Serialize a value with the given layout
+pub fn to_string_with<T: ?Sized + Serialize>(
+    value: &T,
+    options: &Options,
+) -> Result<String, Error> {
+    let mut out = String::new();
+    if options.return_value {
+        out.push_str("return ");
+    }
+    value.serialize(&mut Serializer::new(&mut out, options.indent))?;
+    if options.return_value {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Writes values to a [String]
+pub struct Serializer<'o> {
+    out: &'o mut String,
+    indent: Option<Indent>,
+    /// The number of enclosing tables
+    level: usize,
+}
+
+impl<'o> Serializer<'o> {
+    /// Create a serializer, which splits tables into lines if `indent` is set
+    pub fn new(out: &'o mut String, indent: Option<Indent>) -> Self {
+        Self {
+            out,
+            indent,
+            level: 0,
+        }
+    }
+
+    fn begin_table(&mut self) {
+        self.out.push('{');
+        self.level += 1;
+    }
+
+    fn begin_field(&mut self, first: bool) {
+        if !first {
+            self.out.push(',');
+        }
+        match self.indent {
+            Some(indent) => self.newline(indent),
+            None => self.out.push(' '),
+        }
+    }
+
+    /// Close a table, with a trailing `,` if it is split into lines
+    fn end_table(&mut self, empty: bool) {
+        self.level -= 1;
+        match self.indent {
+            _ if empty => {}
+            Some(indent) => {
+                self.out.push(',');
+                self.newline(indent);
+            }
+            None => self.out.push(' '),
+        }
+        self.out.push('}');
+    }
+
+    fn newline(&mut self, indent: Indent) {
+        self.out.push('\n');
+        for _ in 0..self.level {
+            match indent {
+                Indent::Spaces(n) => self.out.extend((0..n).map(|_| ' ')),
+                Indent::Tabs => self.out.push('\t'),
+            }
+        }
+    }
+
+    /// Write `key = `, with brackets around keys that aren't names
+    fn key(&mut self, key: &str) {
+        quote_key(self.out, key);
+        self.out.push_str(" = ");
+    }
+
+    /// Open the table with the single field of an enum variant
+    fn begin_variant(&mut self, variant: &str) {
+        self.begin_table();
+        self.begin_field(true);
+        self.key(variant);
+    }
+}
+
+impl<'a, 'o> ser::Serializer for &'a mut Serializer<'o> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Table<'a, 'o>;
+    type SerializeTuple = Table<'a, 'o>;
+    type SerializeTupleStruct = Table<'a, 'o>;
+    type SerializeTupleVariant = Table<'a, 'o>;
+    type SerializeMap = Table<'a, 'o>;
+    type SerializeStruct = Table<'a, 'o>;
+    type SerializeStructVariant = Table<'a, 'o>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.out.push_str(if v { "true" } else { "false" });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        write!(self.out, "{}", v).unwrap();
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        // Lua 5.3 and later read decimal literals above i64::MAX as floats,
+        // but wrap hexadecimal ones around
+        if v > i64::MAX as u64 {
+            write!(self.out, "0x{:x}", v).unwrap();
+        } else {
+            write!(self.out, "{}", v).unwrap();
+        }
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        if v.is_nan() {
+            self.out.push_str("0/0");
+        } else if v.is_infinite() {
+            self.out.push_str(if v > 0.0 { "1/0" } else { "-1/0" });
+        } else {
+            // Debug keeps the `.0` of integral values, and uses exponents
+            write!(self.out, "{:?}", v).unwrap();
+        }
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        quote(self.out, v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.out.push('"');
+        for &b in v {
+            if b.is_ascii() {
+                escape_char(self.out, b as char);
+            } else {
+                write!(self.out, "\\{}", b).unwrap();
+            }
+        }
+        self.out.push('"');
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.out.push_str("nil");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.begin_variant(variant);
+        value.serialize(&mut *self)?;
+        self.end_table(false);
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Table<'a, 'o>, Error> {
+        self.begin_table();
+        Ok(Table::new(self, false))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Table<'a, 'o>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Table<'a, 'o>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Table<'a, 'o>, Error> {
+        self.begin_variant(variant);
+        self.begin_table();
+        Ok(Table::new(self, true))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Table<'a, 'o>, Error> {
+        self.begin_table();
+        Ok(Table::new(self, false))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Table<'a, 'o>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Table<'a, 'o>, Error> {
+        self.serialize_tuple_variant(name, variant_index, variant, len)
+    }
+}
+
+/// Writes the fields of a table constructor
+pub struct Table<'a, 'o> {
+    ser: &'a mut Serializer<'o>,
+    /// No field was written yet
+    empty: bool,
+    /// The table is the value of an enum variant, which needs another `}`
+    variant: bool,
+}
+
+impl<'a, 'o> Table<'a, 'o> {
+    fn new(ser: &'a mut Serializer<'o>, variant: bool) -> Self {
+        Self {
+            ser,
+            empty: true,
+            variant,
+        }
+    }
+
+    fn begin_field(&mut self) {
+        self.ser.begin_field(self.empty);
+        self.empty = false;
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.begin_field();
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.begin_field();
+        self.ser.key(key);
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.ser.end_table(self.empty);
+        if self.variant {
+            self.ser.end_table(false);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Table<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Table::end(self)
+    }
+}
+
+impl ser::SerializeTuple for Table<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Table::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Table<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Table::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Table<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Table::end(self)
+    }
+}
+
+impl ser::SerializeMap for Table<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.begin_field();
+        key.serialize(KeySerializer {
+            ser: &mut *self.ser,
+        })
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Table::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Table<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Table::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Table<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Table::end(self)
+    }
+}
+
+/// Writes a map key and the following `=`
+struct KeySerializer<'a, 'o> {
+    ser: &'a mut Serializer<'o>,
+}
+
+impl KeySerializer<'_, '_> {
+    /// Write a key that needs brackets
+    fn bracketed(self, f: impl FnOnce(&mut Serializer) -> Result<(), Error>) -> Result<(), Error> {
+        self.ser.out.push('[');
+        f(self.ser)?;
+        self.ser.out.push_str("] = ");
+        Ok(())
+    }
+}
+
+impl ser::Serializer for KeySerializer<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.bracketed(|ser| ser.serialize_bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.bracketed(|ser| ser.serialize_i64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.bracketed(|ser| ser.serialize_u64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        // A NaN key is an error in Lua
+        if v.is_nan() {
+            return Err(invalid_key());
+        }
+        self.bracketed(|ser| ser.serialize_f64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.ser.key(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.bracketed(|ser| ser.serialize_bytes(v))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(invalid_key())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(invalid_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{
+        collections::BTreeMap,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{to_string, to_string_pretty, to_string_with, Options};
+    use crate::{format::Indent, Lexer, LuaVersion, TokenKind, Utf8Decoder};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Package {
+        name: String,
+        version: (u8, u8),
+        description: Option<String>,
+        build: Build,
+        #[serde(rename = "end")]
+        size: f64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum Build {
+        None,
+        Make(String),
+        Command { run: Vec<String> },
+    }
+
+    /// Check that the text lexes as Lua 5.1
+    fn lex(text: &str) {
+        let mut lexer = Lexer::new(Utf8Decoder::new(text));
+        while lexer.token().unwrap().kind() != TokenKind::Eof {}
+    }
+
+    #[test]
+    fn test_to_string() {
+        let package = Package {
+            name: "sluap".into(),
+            version: (0, 1),
+            description: None,
+            build: Build::Command {
+                run: vec!["cargo".into(), "build".into()],
+            },
+            size: 1.5e300,
+        };
+        let text = to_string(&package).unwrap();
+        assert_eq!(
+            text,
+            "{ name = \"sluap\", version = { 0, 1 }, description = nil, \
+             build = { Command = { run = { \"cargo\", \"build\" } } }, [\"end\"] = 1.5e300 }"
+        );
+        assert_eq!(crate::from_str(&text), Ok(package));
+
+        assert_eq!(to_string(&Build::None).unwrap(), "\"None\"");
+        assert_eq!(to_string(&Vec::<u8>::new()).unwrap(), "{}");
+        assert_eq!(to_string(&[f64::INFINITY, 2.0]).unwrap(), "{ 1/0, 2.0 }");
+        assert_eq!(to_string(&u64::MAX).unwrap(), "0xffffffffffffffff");
+        assert_eq!(
+            crate::from_str(&to_string(&u64::MAX).unwrap()),
+            Ok(u64::MAX)
+        );
+        assert!(to_string(&BTreeMap::from([((), 1)])).is_err());
+    }
+
+    #[test]
+    fn test_pretty() {
+        let map = BTreeMap::from([("a b", vec![Build::Make("x".into())]), ("c", vec![])]);
+        let text = to_string_pretty(&map).unwrap();
+        assert_eq!(
+            text,
+            "{\n    [\"a b\"] = {\n        {\n            Make = \"x\",\n        },\n    },\n    c = {},\n}"
+        );
+        let options = Options {
+            indent: Some(Indent::Tabs),
+            return_value: true,
+        };
+        let text = to_string_with(&BTreeMap::from([(1, 2)]), &options).unwrap();
+        assert_eq!(text, "return {\n\t[1] = 2,\n}\n");
+        assert_eq!(crate::from_str(&text), Ok(BTreeMap::from([(1, 2)])));
+    }
+
+    #[test]
+    fn test_strings() {
+        let strings = [
+            "plain",
+            "quote \" and \\",
+            "\x01\x1f2\x7f\té",
+            "\nfirst\nsecond",
+            "]]\n]=]",
+            "ends\n]",
+            "line\r\nbreak",
+        ];
+        let text = to_string(&strings).unwrap();
+        assert_eq!(
+            text,
+            "{ \"plain\", \"quote \\\" and \\\\\", \"\\001\\0312\\127\\té\", \
+             [[\n\nfirst\nsecond]], [==[]]\n]=]]==], [=[ends\n]]=], \"line\\r\\nbreak\" }"
+        );
+        lex(&text);
+        assert_eq!(crate::from_str(&text), Ok(strings.map(String::from)));
+
+        assert_eq!(to_string(&Bytes(b"\xff\x00")).unwrap(), "\"\\255\\000\"");
+
+        // Lua 5.1 rejects a nested `[[` in a long string of level 0
+        let text = to_string(&"[[a\nb").unwrap();
+        assert_eq!(text, "[=[[[a\nb]=]");
+        let lua51 = LuaVersion::Lua51.into();
+        assert_eq!(
+            crate::de::from_decoder(Utf8Decoder::new(&text), lua51),
+            Ok(String::from("[[a\nb"))
+        );
+    }
+
+    #[test]
+    fn test_keys() {
+        let map = BTreeMap::from([("a\nb", 1), ("]", 2), ("end", 3), ("x", 4)]);
+        let text = to_string(&map).unwrap();
+        assert_eq!(
+            text,
+            "{ [\"]\"] = 2, [\"a\\nb\"] = 1, [\"end\"] = 3, x = 4 }"
+        );
+        lex(&text);
+        let map = map.into_iter().map(|(k, v)| (String::from(k), v));
+        assert_eq!(crate::from_str(&text), Ok(BTreeMap::from_iter(map)));
+    }
+
+    #[test]
+    fn test_non_finite() {
+        let text = to_string(&[f64::NAN, f64::INFINITY, f64::NEG_INFINITY]).unwrap();
+        assert_eq!(text, "{ 0/0, 1/0, -1/0 }");
+        let floats: Vec<f64> = crate::from_str(&text).unwrap();
+        assert!(floats[0].is_nan());
+        assert_eq!(floats[1..], [f64::INFINITY, f64::NEG_INFINITY]);
+
+        let error = crate::from_str::<f64>("2/0").unwrap_err();
+        assert_eq!(error.to_string(), "1:2: expected 0/0, 1/0 or -1/0");
+    }
+
+    /// Bytes that serialize with `serialize_bytes`
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+}
@@ -1,16 +1,40 @@
 //! # Syntax Tokens
 
-use crate::Keyword;
+use core::fmt;
+
+use crate::{encoding::ByteLen, Keyword, Position};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Token<S> {
     span: S,
     kind: TokenKind,
+    start: Position,
+    end: Position,
 }
 
-impl<S: ?Sized + Copy> Token<S> {
+impl<S: Copy> Token<S> {
+    /// Create a token at the start of the input
     pub fn new(span: S, kind: TokenKind) -> Self {
-        Self { span, kind }
+        Self::new_at(span, kind, Position::START, Position::START)
+    }
+
+    pub fn new_at(span: S, kind: TokenKind, start: Position, end: Position) -> Self {
+        Self {
+            span,
+            kind,
+            start,
+            end,
+        }
+    }
+
+    /// The position of the first byte of this token
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    /// The position just after the last byte of this token
+    pub fn end(&self) -> Position {
+        self.end
     }
 
     pub fn kind(&self) -> TokenKind {
@@ -22,6 +46,27 @@ impl<S: ?Sized + Copy> Token<S> {
     }
 }
 
+impl<'a, S: ?Sized + ByteLen> Token<&'a S> {
+    /// Returns the contents of a [TokenKind::LongString]
+    ///
+    /// This strips the brackets and, as Lua does, a newline that
+    /// immediately follows the opening bracket.
+    pub fn long_string_contents(&self) -> Option<&'a S> {
+        if let TokenKind::LongString { level } = self.kind {
+            let (_, rest) = self.span.split_at(level + 2);
+            let (inner, _) = rest.split_at(rest.len() - level - 2);
+            let skip = match inner.as_bytes() {
+                [b'\r', b'\n', ..] | [b'\n', b'\r', ..] => 2,
+                [b'\r' | b'\n', ..] => 1,
+                _ => 0,
+            };
+            Some(inner.split_at(skip).1)
+        } else {
+            None
+        }
+    }
+}
+
 /// Output of the lexer
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenKind {
@@ -37,14 +82,56 @@ pub enum TokenKind {
     Comment,
     /// A string (with escaping)
     String,
+    /// A long bracket string, i.e. `[[...]]` or `[==[...]==]` (no escaping)
+    LongString {
+        /// The number of `=` in the brackets
+        level: usize,
+    },
     /// A hexadecimal integer literal
-    Hex(u32),
+    ///
+    /// Lua 5.1 saturates the value at [u64::MAX], Lua 5.3 and later wrap it
+    /// around. Lua 5.2 reads all hexadecimal literals as [TokenKind::Number].
+    Hex(u64),
+    /// A decimal integer literal that fits into an [i64], since Lua 5.3
+    Integer(i64),
     /// A floating point literal
-    Number(f32),
+    Number(f64),
+    /// A number literal with a LuaJIT suffix
+    Cdata(Cdata),
+    /// A part of an interpolated string in backticks (Luau)
+    ///
+    /// The expressions between the parts are lexed as usual.
+    InterpString(InterpPart),
+    /// Input that was skipped after a lexer error, see [Lexer::skip_error](crate::Lexer::skip_error)
+    Error,
     /// End of input
     Eof,
 }
 
+/// The value of a LuaJIT number literal, which creates a `cdata` object
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cdata {
+    /// An integer with an `LL` suffix, e.g. `42LL`
+    Int64(i64),
+    /// An integer with an `ULL` suffix, e.g. `0xffULL`
+    UInt64(u64),
+    /// An imaginary number with an `i` suffix, e.g. `1.5i`
+    Imaginary(f64),
+}
+
+/// Where a [TokenKind::InterpString] token is in the interpolated string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpPart {
+    /// `` `...` `` without any interpolation
+    Simple,
+    /// `` `...{ ``
+    Begin,
+    /// `}...{`
+    Mid,
+    /// `` }...` ``
+    End,
+}
+
 /// Symbolic Tokens
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Symbol {
@@ -100,4 +187,113 @@ pub enum Symbol {
     Dot2,
     /// `...`
     Dot3,
+    /// `::`, since Lua 5.2 and for type casts in Luau
+    Colon2,
+    /// `//`, since Lua 5.3
+    Slash2,
+    /// `&`, since Lua 5.3 and in Luau types
+    Amp,
+    /// `|`, since Lua 5.3 and in Luau types
+    Pipe,
+    /// `~`, since Lua 5.3
+    Tilde,
+    /// `<<`, since Lua 5.3
+    Shl,
+    /// `>>`, since Lua 5.3
+    Shr,
+    /// `+=` (Luau)
+    PlusEq,
+    /// `-=` (Luau)
+    MinusEq,
+    /// `*=` (Luau)
+    TimesEq,
+    /// `/=` (Luau)
+    SlashEq,
+    /// `%=` (Luau)
+    PercentEq,
+    /// `^=` (Luau)
+    CaretEq,
+    /// `..=` (Luau)
+    Dot2Eq,
+    /// `->` (Luau)
+    Arrow,
+    /// `?` (Luau)
+    Question,
+}
+
+impl Symbol {
+    /// Returns the source text of the symbol
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Times => "*",
+            Self::Slash => "/",
+            Self::Percent => "%",
+            Self::Caret => "^",
+            Self::Hash => "#",
+            Self::Eq => "==",
+            Self::NotEq => "~=",
+            Self::LtEq => "<=",
+            Self::GtEq => ">=",
+            Self::Lt => "<",
+            Self::Gt => ">",
+            Self::Assign => "=",
+            Self::ParenL => "(",
+            Self::ParenR => ")",
+            Self::BraceL => "{",
+            Self::BraceR => "}",
+            Self::BracketL => "[",
+            Self::BracketR => "]",
+            Self::Semicolon => ";",
+            Self::Colon => ":",
+            Self::Comma => ",",
+            Self::Dot => ".",
+            Self::Dot2 => "..",
+            Self::Dot3 => "...",
+            Self::Colon2 => "::",
+            Self::Slash2 => "//",
+            Self::Amp => "&",
+            Self::Pipe => "|",
+            Self::Tilde => "~",
+            Self::Shl => "<<",
+            Self::Shr => ">>",
+            Self::PlusEq => "+=",
+            Self::MinusEq => "-=",
+            Self::TimesEq => "*=",
+            Self::SlashEq => "/=",
+            Self::PercentEq => "%=",
+            Self::CaretEq => "^=",
+            Self::Dot2Eq => "..=",
+            Self::Arrow => "->",
+            Self::Question => "?",
+        }
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for TokenKind {
+    /// Describes the token in the style of the messages of `llex.c`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Whitespace => f.write_str("<whitespace>"),
+            Self::Keyword(keyword) => write!(f, "'{}'", keyword),
+            Self::Name => f.write_str("<name>"),
+            Self::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Self::Comment => f.write_str("<comment>"),
+            Self::String | Self::LongString { .. } | Self::InterpString(_) => {
+                f.write_str("<string>")
+            }
+            Self::Hex(_) | Self::Integer(_) | Self::Number(_) | Self::Cdata(_) => {
+                f.write_str("<number>")
+            }
+            Self::Error => f.write_str("<error>"),
+            Self::Eof => f.write_str("<eof>"),
+        }
+    }
 }
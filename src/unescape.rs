@@ -0,0 +1,484 @@
+//! # String Values
+//!
+//! Decoding of string literals into the bytes they stand for, as in
+//! `read_string` and `read_long_string` of `llex.c`, and quoting of text
+//! as a literal.
+
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, string::String, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::fmt::Write;
+
+use crate::{encoding::ByteLen, Dialect, ErrorKind, LuaVersion, Token, TokenKind};
+
+/// The bytes that an escape sequence stands for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Escaped {
+    bytes: [u8; 6],
+    len: u8,
+}
+
+impl Escaped {
+    /// `\z`, which stands for nothing
+    const EMPTY: Self = Self {
+        bytes: [0; 6],
+        len: 0,
+    };
+
+    fn byte(byte: u8) -> Self {
+        let mut bytes = [0; 6];
+        bytes[0] = byte;
+        Self { bytes, len: 1 }
+    }
+
+    /// Encode a code point up to `0x7FFFFFFF` like `luaO_utf8esc`
+    fn utf8(mut code: u32) -> Self {
+        if code < 0x80 {
+            return Self::byte(code as u8);
+        }
+        let mut buf = [0; 6];
+        let mut n = 0;
+        // Largest value that still fits into the first byte
+        let mut mfb = 0x3F;
+        loop {
+            buf[5 - n] = 0x80 | (code & 0x3F) as u8;
+            n += 1;
+            code >>= 6;
+            mfb >>= 1;
+            if code <= mfb {
+                break;
+            }
+        }
+        buf[5 - n] = ((!mfb << 1) | code) as u8;
+        n += 1;
+        let mut bytes = [0; 6];
+        bytes[..n].copy_from_slice(&buf[6 - n..]);
+        Self {
+            bytes,
+            len: n as u8,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
+}
+
+/// Decode the escape sequence after a backslash
+///
+/// Returns the bytes it stands for and the length of the sequence, without
+/// the backslash. Escapes that `dialect` does not know are invalid.
+pub(crate) fn escape(input: &[u8], dialect: Dialect) -> Result<(Escaped, usize), ErrorKind> {
+    let version = dialect.version;
+    let (&first, rest) = input.split_first().ok_or(ErrorKind::InvalidEscape)?;
+    let byte = match first {
+        b'a' => 0x07,
+        b'b' => 0x08,
+        b'f' => 0x0C,
+        b'n' => b'\n',
+        b'r' => b'\r',
+        b't' => b'\t',
+        b'v' => 0x0B,
+        b'\\' | b'"' | b'\'' => first,
+        b'\n' | b'\r' => {
+            // `\r\n` and `\n\r` count as a single newline
+            let len = match rest.first() {
+                Some(&next @ (b'\n' | b'\r')) if next != first => 2,
+                _ => 1,
+            };
+            return Ok((Escaped::byte(b'\n'), len));
+        }
+        b'0'..=b'9' => {
+            let len = 1 + rest
+                .iter()
+                .take(2)
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            let value = input[..len]
+                .iter()
+                .fold(0u32, |acc, digit| acc * 10 + u32::from(digit - b'0'));
+            let byte = u8::try_from(value).map_err(|_| ErrorKind::EscapeTooLarge)?;
+            return Ok((Escaped::byte(byte), len));
+        }
+        b'x' if version >= LuaVersion::Lua52 || dialect.luau => {
+            let digits = rest.get(..2).ok_or(ErrorKind::InvalidEscape)?;
+            let value = hex_value(digits).ok_or(ErrorKind::InvalidEscape)?;
+            return Ok((Escaped::byte(value as u8), 3));
+        }
+        b'z' if version >= LuaVersion::Lua52 || dialect.luau => {
+            // Skips the following whitespace, including newlines
+            let len = rest
+                .iter()
+                .take_while(|&&b| matches!(b, b' ' | b'\t' | b'\n' | b'\r' | 0x0B | 0x0C))
+                .count();
+            return Ok((Escaped::EMPTY, 1 + len));
+        }
+        b'u' if version >= LuaVersion::Lua53 || dialect.luajit || dialect.luau => {
+            let digits = match rest.split_first() {
+                Some((b'{', digits)) => digits,
+                _ => return Err(ErrorKind::InvalidEscape),
+            };
+            let len = digits.iter().take_while(|b| b.is_ascii_hexdigit()).count();
+            if len == 0 || digits.get(len) != Some(&b'}') {
+                return Err(ErrorKind::InvalidEscape);
+            }
+            let max = if version >= LuaVersion::Lua54 {
+                0x7FFF_FFFF
+            } else {
+                0x10_FFFF
+            };
+            let code = hex_value(&digits[..len])
+                .filter(|&code| code <= max)
+                .ok_or(ErrorKind::EscapeTooLarge)?;
+            return Ok((Escaped::utf8(code), len + 3));
+        }
+        _ => return Err(ErrorKind::InvalidEscape),
+    };
+    Ok((Escaped::byte(byte), 1))
+}
+
+/// The value of some hexadecimal digits, if they fit into a [u32]
+fn hex_value(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0u32, |acc, &digit| {
+        let digit = char::from(digit).to_digit(16)?;
+        acc.checked_mul(16)?.checked_add(digit)
+    })
+}
+
+/// The escapes of all dialects, since they are invalid in the others
+const ALL_ESCAPES: Dialect = Dialect {
+    version: LuaVersion::Lua54,
+    luajit: true,
+    luau: true,
+};
+
+/// Iterator over the bytes of a string literal
+///
+/// In short strings, escape sequences are decoded. In long strings, every
+/// newline sequence (`\n`, `\r`, `\r\n` or `\n\r`) becomes a single `\n`.
+/// Invalid escapes, which the lexer rejects, are passed through unchanged.
+///
+/// The escapes of all [Dialect]s are decoded, since they are invalid in
+/// the dialects that don't have them.
+#[derive(Debug, Clone)]
+pub struct Unescape<'a> {
+    rest: &'a [u8],
+    long: bool,
+    /// A part of an interpolated string, which may escape `{` and `` ` ``
+    interp: bool,
+    /// The rest of a decoded escape sequence
+    pending: Escaped,
+    pending_pos: u8,
+}
+
+impl<'a> Unescape<'a> {
+    /// Decode a string literal, including its quotes or brackets
+    ///
+    /// The parts of an interpolated string include their backticks and braces.
+    pub fn new(literal: &'a [u8]) -> Option<Self> {
+        match literal {
+            [quote @ (b'"' | b'\''), inner @ .., end] if quote == end => {
+                Some(Self::from_contents(inner, false, false))
+            }
+            [b'`' | b'}', inner @ .., b'`' | b'{'] => Some(Self::from_contents(inner, false, true)),
+            [b'[', rest @ ..] => {
+                let level = rest.iter().take_while(|&&b| b == b'=').count();
+                let token = Token::new(literal, TokenKind::LongString { level });
+                Some(Self::from_contents(
+                    token.long_string_contents()?,
+                    true,
+                    false,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn from_contents(rest: &'a [u8], long: bool, interp: bool) -> Self {
+        Self {
+            rest,
+            long,
+            interp,
+            pending: Escaped::EMPTY,
+            pending_pos: 0,
+        }
+    }
+
+    /// Returns the remaining bytes, if they need no decoding
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        if self.pending_pos < self.pending.len {
+            return None;
+        }
+        let special = if self.long { b'\r' } else { b'\\' };
+        match memchr::memchr(special, self.rest) {
+            Some(_) => None,
+            None => Some(self.rest),
+        }
+    }
+
+    /// Borrows the remaining bytes if possible, and collects them otherwise
+    #[cfg(feature = "alloc")]
+    pub fn into_cow(self) -> Cow<'a, [u8]> {
+        match self.as_bytes() {
+            Some(bytes) => Cow::Borrowed(bytes),
+            None => Cow::Owned(self.collect::<Vec<u8>>()),
+        }
+    }
+}
+
+impl Iterator for Unescape<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if let Some(&byte) = self.pending.as_bytes().get(usize::from(self.pending_pos)) {
+            self.pending_pos += 1;
+            return Some(byte);
+        }
+        let (&first, rest) = self.rest.split_first()?;
+        self.rest = rest;
+        match first {
+            b'\\' if !self.long => {
+                if let Some(&next @ (b'{' | b'`')) = rest.first().filter(|_| self.interp) {
+                    self.rest = &rest[1..];
+                    return Some(next);
+                }
+                if let Ok((escaped, len)) = escape(rest, ALL_ESCAPES) {
+                    self.rest = &rest[len..];
+                    self.pending = escaped;
+                    self.pending_pos = 0;
+                    return self.next();
+                }
+            }
+            b'\n' | b'\r' if self.long => {
+                if let Some(&next @ (b'\n' | b'\r')) = rest.first() {
+                    if next != first {
+                        self.rest = &rest[1..];
+                    }
+                }
+                return Some(b'\n');
+            }
+            _ => {}
+        }
+        Some(first)
+    }
+}
+
+impl<'a, S: ?Sized + ByteLen> Token<&'a S> {
+    /// Returns the bytes of a [TokenKind::String], [TokenKind::LongString] or
+    /// [TokenKind::InterpString]
+    pub fn unescape(&self) -> Option<Unescape<'a>> {
+        match self.kind() {
+            TokenKind::String | TokenKind::LongString { .. } | TokenKind::InterpString(_) => {
+                Unescape::new(self.span().as_bytes())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a string token, borrowed from the input if possible
+    #[cfg(feature = "alloc")]
+    pub fn string_value(&self) -> Option<Cow<'a, [u8]>> {
+        self.unescape().map(Unescape::into_cow)
+    }
+}
+
+/// The level of the shortest long bracket that can enclose `text`
+#[cfg(feature = "serde")]
+fn long_bracket_level(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    // Lua 5.1 rejects a `[[` inside of `[[...]]`
+    let min = usize::from(text.contains("[["));
+    (min..)
+        .find(|&level| {
+            // A `]` and `level` times `=` at the end would close early
+            let closes = |i: usize| {
+                let rest = &bytes[i + 1..];
+                rest.len() >= level
+                    && rest[..level].iter().all(|&b| b == b'=')
+                    && rest.get(level).is_none_or(|&b| b == b']')
+            };
+            !(0..bytes.len()).any(|i| bytes[i] == b']' && closes(i))
+        })
+        .unwrap()
+}
+
+/// Write a short string escape for an ASCII character, or the character
+#[cfg(feature = "alloc")]
+pub(crate) fn escape_char(out: &mut String, c: char) {
+    match c {
+        '"' => out.push_str("\\\""),
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        // Three digits, so that a following digit isn't part of the escape
+        '\0'..='\x1f' | '\x7f' => write!(out, "\\{:03}", c as u32).unwrap(),
+        c => out.push(c),
+    }
+}
+
+/// Write a string literal for `text`, a long string if it has line breaks
+#[cfg(feature = "serde")]
+pub(crate) fn quote(out: &mut String, text: &str) {
+    let long = text.contains('\n')
+        && !text
+            .chars()
+            .any(|c| c != '\n' && c != '\t' && c.is_ascii_control());
+    if long {
+        let level = long_bracket_level(text);
+        out.push('[');
+        out.extend((0..level).map(|_| '='));
+        out.push('[');
+        // The line break after the opening bracket is skipped
+        if text.starts_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(text);
+        out.push(']');
+        out.extend((0..level).map(|_| '='));
+        out.push(']');
+    } else {
+        quote_short(out, text);
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn quote_short(out: &mut String, text: &str) {
+    out.push('"');
+    text.chars().for_each(|c| escape_char(out, c));
+    out.push('"');
+}
+
+/// Write a table key, as `["key"]` if it isn't a name
+///
+/// A long string would start with `[[`, which can't follow the bracket.
+#[cfg(feature = "alloc")]
+pub(crate) fn quote_key(out: &mut String, key: &str) {
+    if crate::keywords::is_name(key) {
+        out.push_str(key);
+    } else {
+        out.push('[');
+        quote_short(out, key);
+        out.push(']');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, Escaped, Unescape};
+    use crate::{Dialect, ErrorKind, LuaVersion, Token, TokenKind};
+
+    fn unescape(literal: &str) -> ([u8; 32], usize) {
+        let mut bytes = [0; 32];
+        let mut len = 0;
+        for byte in Unescape::new(literal.as_bytes()).unwrap() {
+            bytes[len] = byte;
+            len += 1;
+        }
+        (bytes, len)
+    }
+
+    fn assert_unescape(literal: &str, expected: &[u8]) {
+        let (bytes, len) = unescape(literal);
+        assert_eq!(&bytes[..len], expected, "{}", literal);
+    }
+
+    #[test]
+    fn test_escape() {
+        let escape = |input| escape(input, LuaVersion::Lua51.into());
+        let byte = Escaped::byte;
+        assert_eq!(escape(b"n"), Ok((byte(b'\n'), 1)));
+        assert_eq!(escape(b"\r\nx"), Ok((byte(b'\n'), 2)));
+        assert_eq!(escape(b"\n\n"), Ok((byte(b'\n'), 1)));
+        assert_eq!(escape(b"0659"), Ok((byte(65), 3)));
+        assert_eq!(escape(b"9x"), Ok((byte(9), 1)));
+        assert_eq!(escape(b"256"), Err(ErrorKind::EscapeTooLarge));
+        assert_eq!(escape(b"q"), Err(ErrorKind::InvalidEscape));
+        assert_eq!(escape(b""), Err(ErrorKind::InvalidEscape));
+        assert_eq!(escape(b"x41"), Err(ErrorKind::InvalidEscape));
+    }
+
+    #[test]
+    fn test_version_escapes() {
+        let check = |input, version: LuaVersion, expected: &[u8], expected_len| {
+            let (escaped, len) = escape(input, version.into()).unwrap();
+            assert_eq!((escaped.as_bytes(), len), (expected, expected_len));
+        };
+        check(b"x4aB", LuaVersion::Lua52, b"J", 3);
+        check(b"z \r\n\tx", LuaVersion::Lua52, b"", 5);
+        check(b"u{e9}", LuaVersion::Lua53, "\u{e9}".as_bytes(), 5);
+        check(b"u{1F600}", LuaVersion::Lua53, "\u{1F600}".as_bytes(), 8);
+        check(
+            b"u{7FFFFFFF}",
+            LuaVersion::Lua54,
+            b"\xFD\xBF\xBF\xBF\xBF\xBF",
+            11,
+        );
+        let invalid = Err(ErrorKind::InvalidEscape);
+        assert_eq!(escape(b"x4", LuaVersion::Lua52.into()), invalid);
+        assert_eq!(escape(b"xg0", LuaVersion::Lua52.into()), invalid);
+        assert_eq!(escape(b"u{41}", LuaVersion::Lua52.into()), invalid);
+        assert_eq!(escape(b"u41", LuaVersion::Lua53.into()), invalid);
+        assert_eq!(escape(b"u{}", LuaVersion::Lua53.into()), invalid);
+        assert_eq!(escape(b"u{41", LuaVersion::Lua53.into()), invalid);
+        let too_large = Err(ErrorKind::EscapeTooLarge);
+        assert_eq!(escape(b"u{110000}", LuaVersion::Lua53.into()), too_large);
+        assert_eq!(escape(b"u{80000000}", LuaVersion::Lua54.into()), too_large);
+
+        let luau = escape(b"u{e9}", Dialect::LUAU).unwrap();
+        assert_eq!(luau.0.as_bytes(), "\u{e9}".as_bytes());
+        assert_eq!(escape(b"x41", Dialect::LUAU).map(|e| e.1), Ok(3));
+        let luajit = Dialect {
+            luajit: true,
+            ..Dialect::default()
+        };
+        assert_eq!(escape(b"u{41}", luajit).map(|e| e.1), Ok(5));
+        assert_eq!(escape(b"u{110000}", luajit), too_large);
+    }
+
+    #[test]
+    fn test_short_strings() {
+        assert_unescape(r#""plain""#, b"plain");
+        assert_unescape(r#"'a\tb\\c\'d\"'"#, b"a\tb\\c'd\"");
+        assert_unescape(r#""\65\066\0677""#, b"ABC7");
+        assert_unescape("'one\\\r\ntwo'", b"one\ntwo");
+        assert_unescape(r#""\a\b\f\v\r""#, b"\x07\x08\x0C\x0B\r");
+        assert_unescape("'\\x41\\z \n B\\u{20AC}!'", "AB\u{20AC}!".as_bytes());
+        assert_unescape("`a\\{b\\`{", b"a{b`");
+        assert_unescape("}\\n`", b"\n");
+        assert!(Unescape::new(b"'unterminated").is_none());
+        assert!(Unescape::new(b"name").is_none());
+    }
+
+    #[test]
+    fn test_long_strings() {
+        assert_unescape(
+            "[[\r\nfirst\r\nsecond\n\rthird\rend]]",
+            b"first\nsecond\nthird\nend",
+        );
+        assert_unescape("[==[a\\n]]b]==]", b"a\\n]]b");
+        assert_eq!(Unescape::new(b"[[x]]").unwrap().as_bytes(), Some(&b"x"[..]));
+        assert_eq!(Unescape::new(b"[[\r]]").unwrap().as_bytes(), Some(&b""[..]));
+        assert_eq!(Unescape::new(b"[[a\rb]]").unwrap().as_bytes(), None);
+    }
+
+    #[test]
+    fn test_token() {
+        let token = Token::new("'\\104i'", TokenKind::String);
+        assert!(token.unescape().unwrap().eq(*b"hi"));
+        assert!(Token::new("x", TokenKind::Name).unescape().is_none());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_string_value() {
+        use alloc::borrow::Cow;
+
+        let token = Token::new(&b"\"caf\xe9\""[..], TokenKind::String);
+        assert_eq!(token.string_value(), Some(Cow::Borrowed(&b"caf\xe9"[..])));
+        let token = Token::new("\"a\\nb\"", TokenKind::String);
+        let value = token.string_value().unwrap();
+        assert!(matches!(value, Cow::Owned(_)));
+        assert_eq!(&*value, b"a\nb");
+    }
+}
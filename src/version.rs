@@ -0,0 +1,93 @@
+//! # Language Versions
+
+use core::fmt;
+
+/// The version of the Lua language that the [Lexer](crate::Lexer) and
+/// [Parser](crate::Parser) accept
+///
+/// Later versions compare greater than earlier ones. The default is Lua 5.1.
+///
+/// - 5.2 adds `goto` and labels, empty statements and the `\x` and `\z` escapes
+/// - 5.3 adds integers, bitwise operators, floor division and `\u{XXX}` escapes
+/// - 5.4 adds the `<const>` and `<close>` attributes of local variables
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LuaVersion {
+    /// See: <https://www.lua.org/manual/5.1/manual.html>
+    #[default]
+    Lua51,
+    /// See: <https://www.lua.org/manual/5.2/manual.html>
+    Lua52,
+    /// See: <https://www.lua.org/manual/5.3/manual.html>
+    Lua53,
+    /// See: <https://www.lua.org/manual/5.4/manual.html>
+    Lua54,
+}
+
+impl LuaVersion {
+    /// Returns the version for a string like `5.3`
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "5.1" => Some(Self::Lua51),
+            "5.2" => Some(Self::Lua52),
+            "5.3" => Some(Self::Lua53),
+            "5.4" => Some(Self::Lua54),
+            _ => None,
+        }
+    }
+
+    /// Returns the version number, e.g. `5.3`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lua51 => "5.1",
+            Self::Lua52 => "5.2",
+            Self::Lua53 => "5.3",
+            Self::Lua54 => "5.4",
+        }
+    }
+}
+
+impl fmt::Display for LuaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Lua {}", self.as_str())
+    }
+}
+
+/// The Lua version and the extensions of other implementations that the
+/// [Lexer](crate::Lexer) and [Parser](crate::Parser) accept
+///
+/// The default is plain Lua 5.1.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Dialect {
+    pub version: LuaVersion,
+    /// LuaJIT: `LL` and `ULL` integer suffixes, imaginary numbers and `\u{XXX}` escapes
+    pub luajit: bool,
+    /// Luau: compound assignments, `continue`, type annotations, string
+    /// interpolation with backticks and `if-then-else` expressions, as well
+    /// as the `\x`, `\z` and `\u{XXX}` escapes
+    pub luau: bool,
+}
+
+impl Dialect {
+    /// LuaJIT 2.1, which includes `goto` and the other syntax of Lua 5.2
+    pub const LUAJIT: Self = Self {
+        version: LuaVersion::Lua52,
+        luajit: true,
+        luau: false,
+    };
+
+    /// Luau, which is based on Lua 5.1
+    pub const LUAU: Self = Self {
+        version: LuaVersion::Lua51,
+        luajit: false,
+        luau: true,
+    };
+}
+
+impl From<LuaVersion> for Dialect {
+    fn from(version: LuaVersion) -> Self {
+        Self {
+            version,
+            ..Self::default()
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Runs the `sluap` binary on temporary files
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+/// A file in the temporary directory that is removed again
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let name = format!("sluap-{}-{}", std::process::id(), name);
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn sluap(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sluap"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+#[test]
+fn test_check() {
+    let good = TempFile::new("good.lua", "local x = 1\nreturn x\n");
+    let bad = TempFile::new("bad.lua", "x = = 1\nlocal 1\n");
+
+    let output = sluap(&["check", good.path()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "");
+
+    let output = sluap(&["check", good.path(), bad.path()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "{0}:1:5: expression expected near '='\n{0}:2:7: <name> expected near <number>\n",
+            bad.path()
+        )
+    );
+}
+
+#[test]
+fn test_shebang() {
+    let file = TempFile::new("shebang.lua", "#!/usr/bin/env lua\nx  = 1\ny = = 2\n");
+    let output = sluap(&["check", file.path()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        format!("{}:3:5: expression expected near '='\n", file.path())
+    );
+
+    let file = TempFile::new("script.lua", "#!/usr/bin/env lua\nx  = 1\n");
+    assert_eq!(sluap(&["check", file.path()]).status.code(), Some(0));
+    let output = sluap(&["fmt", file.path()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "#!/usr/bin/env lua\nx = 1\n");
+
+    let output = sluap(&["fmt", "--check", file.path()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), format!("{}: not formatted\n", file.path()));
+    let output = sluap(&["fmt", "--write", file.path()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        fs::read_to_string(&file.0).unwrap(),
+        "#!/usr/bin/env lua\nx = 1\n"
+    );
+    assert_eq!(
+        sluap(&["fmt", "--check", file.path()]).status.code(),
+        Some(0)
+    );
+}
+
+#[test]
+fn test_lex() {
+    let file = TempFile::new("lex.lua", "x = 'a'");
+    let output = sluap(&["lex", file.path()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "1:1-1:2 Name \"x\"\n1:2-1:3 Whitespace \" \"\n1:3-1:4 Symbol(Assign) \"=\"\n\
+         1:4-1:5 Whitespace \" \"\n1:5-1:8 String \"'a'\"\n"
+    );
+
+    let file = TempFile::new("unfinished.lua", "x = 'a");
+    let output = sluap(&["lex", file.path()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        format!("{}:1:5: unfinished string\n", file.path())
+    );
+}
+
+#[test]
+fn test_usage() {
+    let output = sluap(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("sluap: missing command\n"));
+
+    let output = sluap(&["lex", "a.lua", "b.lua"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("sluap: lex takes a single FILE\n"));
+
+    let output = sluap(&["check", "--lua", "6", "a.lua"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("sluap: invalid value \"6\" for --lua\n"));
+
+    let output = sluap(&["check", "missing.lua"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("missing.lua: "));
+}